@@ -9,45 +9,110 @@
 
 //! Module containing context-internal types and traits.
 
-#[cfg(feature = "dtls-pki")]
-use std::ffi::CString;
 #[cfg(dtls)]
 use std::ptr::NonNull;
-use std::{any::Any, ffi::c_void, fmt::Debug, net::SocketAddr, ops::Sub, sync::Once, time::Duration};
+use std::{
+    any::Any,
+    collections::HashMap,
+    ffi::{c_void, CString},
+    fmt::{Debug, Formatter},
+    net::{IpAddr, SocketAddr},
+    ops::Sub,
+    path::PathBuf,
+    sync::Once,
+    time::{Duration, Instant},
+};
 #[cfg(all(feature = "dtls-pki", unix))]
 use std::{os::unix::ffi::OsStrExt, path::Path};
 
 use libc::c_uint;
+#[cfg(feature = "oscore")]
+use libcoap_sys::coap_context_oscore_server;
 #[cfg(feature = "dtls-pki")]
 use libcoap_sys::coap_context_set_pki_root_cas;
 use libcoap_sys::{
     coap_add_resource, coap_can_exit, coap_context_get_csm_max_message_size, coap_context_get_csm_timeout,
     coap_context_get_max_handshake_sessions, coap_context_get_max_idle_sessions, coap_context_get_session_timeout,
     coap_context_set_block_mode, coap_context_set_csm_max_message_size, coap_context_set_csm_timeout,
-    coap_context_set_keepalive, coap_context_set_max_handshake_sessions, coap_context_set_max_idle_sessions,
-    coap_context_set_session_timeout, coap_context_t, coap_event_t, coap_free_context, coap_get_app_data,
-    coap_io_process, coap_new_context, coap_proto_t, coap_register_event_handler, coap_register_response_handler,
-    coap_set_app_data, coap_startup_with_feature_checks, COAP_BLOCK_SINGLE_BODY, COAP_BLOCK_USE_LIBCOAP, COAP_IO_WAIT,
+    coap_context_set_keepalive, coap_context_set_max_body_size, coap_context_set_max_handshake_sessions,
+    coap_context_set_max_idle_sessions, coap_context_set_session_timeout, coap_context_t, coap_dtls_set_log_level,
+    coap_event_t, coap_free_context, coap_get_app_data, coap_io_process, coap_join_mcast_group_intf, coap_log_impl,
+    coap_log_t, coap_new_context, coap_proto_t, coap_register_event_handler, coap_register_nack_handler,
+    coap_register_pong_handler, coap_register_response_handler, coap_session_t, coap_set_app_data, coap_set_log_level,
+    coap_startup_with_feature_checks, COAP_BLOCK_SINGLE_BODY, COAP_BLOCK_USE_LIBCOAP, COAP_IO_WAIT,
 };
 
 #[cfg(any(feature = "dtls-rpk", feature = "dtls-pki"))]
 use crate::crypto::pki_rpk::ServerPkiRpkCryptoContext;
 #[cfg(feature = "dtls-psk")]
 use crate::crypto::psk::ServerPskContext;
+#[cfg(dtls)]
+use crate::crypto::{ClientCryptoContext, ServerCryptoContext};
+#[cfg(feature = "metrics")]
+use crate::metrics;
+#[cfg(feature = "oscore")]
+use crate::oscore::OscoreContext;
 use crate::{
-    error::{ContextConfigurationError, EndpointCreationError, IoProcessError},
-    event::{event_handler_callback, CoapEventHandler},
-    mem::{CoapLendableFfiRcCell, CoapLendableFfiWeakCell, DropInnerExclusively},
-    resource::{CoapResource, UntypedCoapResource},
-    session::{session_response_handler, CoapServerSession, CoapSession},
-    transport::CoapEndpoint,
+    error::{
+        ContextConfigurationError, ContextCreationError, EndpointCreationError, FileResourceError, IoProcessError,
+        OutstandingSessionsError, SessionError,
+    },
+    event::{event_handler_callback, CoapEvent, CoapEventCallback, CoapEventHandler},
+    file_resource,
+    mem::{CoapFfiWeakCell, CoapLendableFfiRcCell, CoapLendableFfiWeakCell, DropInnerExclusively},
+    message::{CoapRequest, CoapResponse},
+    protocol::{CoapResponseCode, MaxAge},
+    rate_limit::{RateLimitConfig, RateLimitStats, RateLimiter},
+    resource::{CoapResource, ResourceDescription, ResourceHandle, ResourceInfo, UntypedCoapResource},
+    session::{
+        client::CoapClientSessionInner, session_nack_handler, session_pong_handler, session_response_handler,
+        CoapClientSession, CoapServerSession, CoapSession, CoapSessionCommon,
+    },
+    transport::{CoapEndpoint, CoapEndpointInfo},
+    types::{CoapLogLevel, CoapMessageId, CoapProtocol},
+    well_known::{self, WELL_KNOWN_CORE_PATH},
 };
 
 static COAP_STARTUP_ONCE: Once = Once::new();
 
+/// Default number of recently received requests per session that are remembered for message
+/// deduplication (see [CoapContext::set_deduplication_cache_size()]).
+const DEFAULT_DEDUPLICATION_CACHE_SIZE: usize = 64;
+
 #[inline(always)]
 pub(crate) fn ensure_coap_started() {
-    COAP_STARTUP_ONCE.call_once(coap_startup_with_feature_checks);
+    COAP_STARTUP_ONCE.call_once(|| {
+        coap_startup_with_feature_checks();
+        #[cfg(feature = "log_everything")]
+        {
+            set_log_level(CoapLogLevel::Debug);
+            set_dtls_log_level(CoapLogLevel::Debug);
+        }
+    });
+}
+
+/// Sets the log level of libcoap's internal logging.
+///
+/// As the log level is a global setting, this function may be called at any time, even before a
+/// [CoapContext] has been created, and affects all contexts.
+///
+/// If the `log_everything` feature is enabled, the log level is set to [CoapLogLevel::Debug] when
+/// libcoap is first initialized, overriding previous calls to this function.
+pub fn set_log_level(level: CoapLogLevel) {
+    // SAFETY: Setting the log level only modifies a global variable and has no other requirements.
+    unsafe { coap_set_log_level(level.to_raw_log_level()) }
+}
+
+/// Sets the log level of the DTLS library used by libcoap.
+///
+/// As the log level is a global setting, this function may be called at any time, even before a
+/// [CoapContext] has been created, and affects all contexts.
+///
+/// If the `log_everything` feature is enabled, the log level is set to [CoapLogLevel::Debug] when
+/// libcoap is first initialized, overriding previous calls to this function.
+pub fn set_dtls_log_level(level: CoapLogLevel) {
+    // SAFETY: Setting the log level only modifies global state and has no other requirements.
+    unsafe { coap_dtls_set_log_level(level.to_raw_log_level()) }
 }
 
 #[derive(Debug)]
@@ -60,8 +125,31 @@ struct CoapContextInner<'a> {
     resources: Vec<Box<dyn UntypedCoapResource>>,
     /// A list of server-side sessions that are currently active.
     server_sessions: Vec<CoapServerSession<'a>>,
+    /// Weak references to the client-side sessions created for this context.
+    client_sessions: Vec<CoapFfiWeakCell<CoapClientSessionInner<'a>>>,
     /// The event handler responsible for library-user side handling of events.
     event_handler: Option<Box<dyn CoapEventHandler>>,
+    /// Whether duplicate requests should be detected and answered without calling the resource
+    /// handler again.
+    deduplication: bool,
+    /// Number of recently received requests per server-side session that are remembered for
+    /// deduplication.
+    deduplication_cache_size: usize,
+    /// Whether received messages are parsed with strict parsing enabled (see
+    /// [CoapContext::set_strict_parsing()]).
+    strict_parsing: bool,
+    /// Maximum number of concurrently active server-side sessions, if limited.
+    max_sessions: Option<usize>,
+    /// Rate limit for requests received by server-side sessions, if enabled.
+    rate_limiter: Option<RateLimiter>,
+    /// Maximum size of request bodies that are passed to resource handlers, if limited.
+    max_body_size: Option<usize>,
+    /// Hook that decides whether requests may be passed to resource handlers (see
+    /// [CoapContext::set_access_control()]).
+    access_control: Option<AccessControlHook>,
+    /// Attributes advertised at `/.well-known/core` for resources added using
+    /// [CoapContext::add_resource_with_attributes()], keyed by their URI path.
+    resource_descriptions: HashMap<String, ResourceDescription>,
     /// PSK context for encrypted server-side sessions.
     #[cfg(feature = "dtls-psk")]
     psk_context: Option<ServerPskContext<'a>>,
@@ -70,6 +158,32 @@ struct CoapContextInner<'a> {
     pki_rpk_context: Option<ServerPkiRpkCryptoContext<'a>>,
 }
 
+impl CoapContextInner<'_> {
+    /// Returns the number of server-side sessions that are neither closed nor refused due to the
+    /// session limit, excluding DTLS HELLO sessions that have not become full sessions yet.
+    fn active_session_count(&self) -> usize {
+        self.server_sessions
+            .iter()
+            .filter(|v| !v.is_closed() && !v.is_refused() && !v.is_unannounced())
+            .count()
+    }
+
+    /// Returns the number of sessions bound to this context that are still referenced outside of
+    /// it.
+    fn outstanding_session_count(&self) -> usize {
+        let client_sessions = self.client_sessions.iter().filter(|v| v.strong_count() > 0).count();
+        let server_sessions = self.server_sessions.iter().filter(|v| v.reference_count() > 1).count();
+        client_sessions + server_sessions
+    }
+
+    /// Refuses the given session if the session limit has been reached.
+    fn enforce_session_limit(&self, session: &CoapServerSession) {
+        if self.max_sessions.is_some_and(|max| self.active_session_count() >= max) {
+            session.refuse();
+        }
+    }
+}
+
 /// A CoAP Context — container for general state and configuration information relating to CoAP
 ///
 /// The equivalent to the [coap_context_t] type in libcoap.
@@ -105,13 +219,24 @@ impl<'a> CoapContext<'a> {
                     .expect("coap_context_set_block_mode() flags have invalid type for function"),
             );
             coap_register_response_handler(raw_context, Some(session_response_handler));
+            coap_register_pong_handler(raw_context, Some(session_pong_handler));
+            coap_register_nack_handler(raw_context, Some(session_nack_handler));
         }
         let inner = CoapLendableFfiRcCell::new(CoapContextInner {
             raw_context,
             endpoints: Vec::new(),
             resources: Vec::new(),
             server_sessions: Vec::new(),
+            client_sessions: Vec::new(),
             event_handler: None,
+            deduplication: true,
+            deduplication_cache_size: DEFAULT_DEDUPLICATION_CACHE_SIZE,
+            strict_parsing: false,
+            max_sessions: None,
+            rate_limiter: None,
+            max_body_size: None,
+            access_control: None,
+            resource_descriptions: HashMap::new(),
             #[cfg(feature = "dtls-psk")]
             psk_context: None,
             #[cfg(any(feature = "dtls-pki", feature = "dtls-rpk"))]
@@ -129,6 +254,110 @@ impl<'a> CoapContext<'a> {
         Ok(CoapContext { inner })
     }
 
+    /// Registers a newly created client session with this context.
+    pub(crate) fn track_client_session(&mut self, session: CoapFfiWeakCell<CoapClientSessionInner<'a>>) {
+        let mut inner = self.inner.borrow_mut();
+        inner.client_sessions.retain(|v| v.strong_count() > 0);
+        inner.client_sessions.push(session);
+    }
+
+    /// Creates a new context for a DTLS server that is bound to the given address and uses the
+    /// given cryptography information provider.
+    ///
+    /// This combines [CoapContext::new()], [CoapContext::set_psk_context()] or
+    /// [CoapContext::set_pki_rpk_context()] (depending on the type of `crypto`) and
+    /// [CoapContext::add_endpoint_dtls()] into a single call.
+    /// If any of these steps fails, the partially configured context is dropped and the error is
+    /// returned, i.e., a context is only returned if it is fully set up.
+    ///
+    /// Additional endpoints or cryptography information providers of the other type may be added
+    /// to the returned context afterwards.
+    ///
+    /// This function requires at least one of the `dtls-psk`, `dtls-pki` or `dtls-rpk` features
+    /// to be enabled, which in turn require a DTLS library backend to be available (see the
+    /// `dtls_openssl`, `dtls_gnutls`, `dtls_mbedtls` and `dtls_tinydtls` features).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [ContextCreationError] that wraps the error of the step that failed.
+    #[cfg(dtls)]
+    pub fn new_dtls_server(
+        bind_addr: SocketAddr,
+        crypto: impl Into<ServerCryptoContext<'a>>,
+    ) -> Result<CoapContext<'a>, ContextCreationError> {
+        let mut context = CoapContext::new()?;
+        match crypto.into() {
+            #[cfg(feature = "dtls-psk")]
+            ServerCryptoContext::Psk(psk_context) => context.set_psk_context(psk_context)?,
+            #[cfg(any(feature = "dtls-pki", feature = "dtls-rpk"))]
+            ServerCryptoContext::PkiRpk(pki_rpk_context) => context.set_pki_rpk_context(pki_rpk_context)?,
+        }
+        context.add_endpoint_dtls(bind_addr)?;
+        Ok(context)
+    }
+
+    /// Creates a new context along with a DTLS client session to the given peer address that uses
+    /// the given cryptography information provider.
+    ///
+    /// In libcoap-rs, client-side cryptography information is configured for each session instead
+    /// of for the context, so this combines [CoapContext::new()] and
+    /// [CoapClientSession::connect_dtls()] into a single call.
+    /// If the session can not be created, the context is dropped and the error is returned.
+    ///
+    /// This function requires at least one of the `dtls-psk`, `dtls-pki` or `dtls-rpk` features
+    /// to be enabled, which in turn require a DTLS library backend to be available (see the
+    /// `dtls_openssl`, `dtls_gnutls`, `dtls_mbedtls` and `dtls_tinydtls` features).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [ContextCreationError] that wraps the error of the step that failed.
+    #[cfg(dtls)]
+    pub fn new_dtls_client(
+        peer_addr: SocketAddr,
+        crypto: impl Into<ClientCryptoContext<'a>>,
+    ) -> Result<(CoapContext<'a>, CoapClientSession<'a>), ContextCreationError> {
+        let mut context = CoapContext::new()?;
+        let session = CoapClientSession::connect_dtls(&mut context, peer_addr, crypto)?;
+        Ok((context, session))
+    }
+
+    /// Creates a new context for a server that responds to requests sent to the given multicast
+    /// `groups` (see [RFC 7252, Section 8](https://datatracker.ietf.org/doc/html/rfc7252#section-8)).
+    ///
+    /// This creates a UDP endpoint bound to the `unicast` address, joins all multicast groups on
+    /// it and makes the context serve `/.well-known/core`, so that clients can discover the
+    /// server's resources using a multicast GET request.
+    /// If `unicast` is not the unspecified address (which also receives datagrams sent to any of
+    /// the joined groups), an additional endpoint bound to each group address and the port of
+    /// `unicast` is created to receive the group requests.
+    ///
+    /// Responses to requests received via multicast are always sent as non-confirmable messages
+    /// (see [RFC 7252, Section 8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-8.2)).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [ContextCreationError] that wraps the error of the step that failed.
+    pub fn new_multicast_server(
+        unicast: SocketAddr,
+        groups: &[IpAddr],
+    ) -> Result<CoapContext<'a>, ContextCreationError> {
+        let mut context = CoapContext::new()?;
+        context.add_endpoint_udp(unicast)?;
+        // libcoap joins the groups on all existing UDP endpoints, using the address an endpoint is
+        // bound to as the interface address. Therefore, the groups have to be joined before the
+        // endpoints bound to the group addresses are created.
+        for group in groups {
+            context.join_multicast_group(*group)?;
+        }
+        if !unicast.ip().is_unspecified() {
+            for group in groups {
+                context.add_endpoint_udp(SocketAddr::new(*group, unicast.port()))?;
+            }
+        }
+        context.register_well_known_core();
+        Ok(context)
+    }
+
     /// Restores a CoapContext from its raw counterpart.
     ///
     /// # Safety
@@ -146,60 +375,42 @@ impl<'a> CoapContext<'a> {
     /// Handle an incoming event provided by libcoap.
     pub(crate) fn handle_event(&self, mut session: CoapSession<'a>, event: coap_event_t) {
         let inner_ref = &mut *self.inner.borrow_mut();
+        #[cfg(feature = "metrics")]
+        match event {
+            coap_event_t::COAP_EVENT_MSG_RETRANSMITTED => metrics::record_message_retransmitted(session.proto()),
+            coap_event_t::COAP_EVENT_DTLS_ERROR => metrics::record_dtls_handshake_failure(session.proto()),
+            _ => {},
+        }
+        // DTLS HELLO sessions might never become full sessions, so their events are not passed on
+        // to the event handler. Once the session is no longer a HELLO session, the application is
+        // notified about the new session before the actual event is handled.
+        let (is_hello, announce_session) = match &session {
+            CoapSession::Server(serv_sess) if serv_sess.is_hello() => (true, false),
+            CoapSession::Server(serv_sess) if serv_sess.is_unannounced() => {
+                inner_ref.enforce_session_limit(serv_sess);
+                serv_sess.mark_announced();
+                (false, true)
+            },
+            _ => (false, false),
+        };
         // Call event handler for event.
-        if let Some(handler) = &mut inner_ref.event_handler {
-            match event {
-                coap_event_t::COAP_EVENT_DTLS_CLOSED => handler.handle_dtls_closed(&mut session),
-                coap_event_t::COAP_EVENT_DTLS_CONNECTED => handler.handle_dtls_connected(&mut session),
-                coap_event_t::COAP_EVENT_DTLS_RENEGOTIATE => handler.handle_dtls_renegotiate(&mut session),
-                coap_event_t::COAP_EVENT_DTLS_ERROR => handler.handle_dtls_error(&mut session),
-                coap_event_t::COAP_EVENT_TCP_CONNECTED => handler.handle_tcp_connected(&mut session),
-                coap_event_t::COAP_EVENT_TCP_CLOSED => handler.handle_tcp_closed(&mut session),
-                coap_event_t::COAP_EVENT_TCP_FAILED => handler.handle_tcp_failed(&mut session),
-                coap_event_t::COAP_EVENT_SESSION_CONNECTED => handler.handle_session_connected(&mut session),
-                coap_event_t::COAP_EVENT_SESSION_CLOSED => handler.handle_session_closed(&mut session),
-                coap_event_t::COAP_EVENT_SESSION_FAILED => handler.handle_session_failed(&mut session),
-                coap_event_t::COAP_EVENT_PARTIAL_BLOCK => handler.handle_partial_block(&mut session),
-                coap_event_t::COAP_EVENT_SERVER_SESSION_NEW => {
-                    if let CoapSession::Server(server_session) = &mut session {
-                        handler.handle_server_session_new(server_session)
-                    } else {
-                        panic!("server-side session event fired for non-server-side session");
-                    }
-                },
-                coap_event_t::COAP_EVENT_SERVER_SESSION_DEL => {
-                    if let CoapSession::Server(server_session) = &mut session {
-                        handler.handle_server_session_del(server_session)
-                    } else {
-                        panic!("server-side session event fired for non-server-side session");
-                    }
-                },
-                coap_event_t::COAP_EVENT_XMIT_BLOCK_FAIL => handler.handle_xmit_block_fail(&mut session),
-                coap_event_t::COAP_EVENT_BAD_PACKET => handler.handle_bad_packet(&mut session),
-                coap_event_t::COAP_EVENT_MSG_RETRANSMITTED => handler.handle_msg_retransmitted(&mut session),
-                coap_event_t::COAP_EVENT_OSCORE_DECRYPTION_FAILURE => {
-                    handler.handle_oscore_decryption_failure(&mut session)
-                },
-                coap_event_t::COAP_EVENT_OSCORE_NOT_ENABLED => handler.handle_oscore_not_enabled(&mut session),
-                coap_event_t::COAP_EVENT_OSCORE_NO_PROTECTED_PAYLOAD => {
-                    handler.handle_oscore_no_protected_payload(&mut session)
-                },
-                coap_event_t::COAP_EVENT_OSCORE_NO_SECURITY => handler.handle_oscore_no_security(&mut session),
-                coap_event_t::COAP_EVENT_OSCORE_INTERNAL_ERROR => handler.handle_oscore_internal_error(&mut session),
-                coap_event_t::COAP_EVENT_OSCORE_DECODE_ERROR => handler.handle_oscore_decode_error(&mut session),
-                coap_event_t::COAP_EVENT_WS_PACKET_SIZE => handler.handle_ws_packet_size(&mut session),
-                coap_event_t::COAP_EVENT_WS_CONNECTED => handler.handle_ws_connected(&mut session),
-                coap_event_t::COAP_EVENT_WS_CLOSED => handler.handle_ws_closed(&mut session),
-                coap_event_t::COAP_EVENT_KEEPALIVE_FAILURE => handler.handle_keepalive_failure(&mut session),
-                _ => {
-                    // TODO probably a log message is justified here.
-                },
+        if let (Some(handler), false) = (&mut inner_ref.event_handler, is_hello) {
+            if announce_session {
+                handler.handle_event(CoapEvent::ServerSessionNew, &mut session);
+            }
+            if let Some(event) = CoapEvent::from_raw(event) {
+                handler.handle_event(event, &mut session);
             }
         }
         // For server-side sessions: Ensure that server-side session wrappers are either kept in memory or dropped when needed.
         if let CoapSession::Server(serv_sess) = session {
             match event {
-                coap_event_t::COAP_EVENT_SERVER_SESSION_NEW => inner_ref.server_sessions.push(serv_sess),
+                coap_event_t::COAP_EVENT_SERVER_SESSION_NEW => {
+                    if !is_hello {
+                        inner_ref.enforce_session_limit(&serv_sess);
+                    }
+                    inner_ref.server_sessions.push(serv_sess)
+                },
                 coap_event_t::COAP_EVENT_SERVER_SESSION_DEL => {
                     std::mem::drop(inner_ref.server_sessions.remove(
                         inner_ref.server_sessions.iter().position(|v| v.eq(&serv_sess)).expect(
@@ -213,6 +424,38 @@ impl<'a> CoapContext<'a> {
         }
     }
 
+    /// Checks whether the remote address of the given server-side session has changed and
+    /// notifies the event handler using [CoapEvent::ServerSessionAddressChanged] if it has.
+    pub(crate) fn handle_address_migration(&self, session: &CoapServerSession<'_>) {
+        // DTLS HELLO sessions are not announced to the event handler (see handle_event()).
+        if session.is_hello() || !session.detect_address_migration() {
+            return;
+        }
+        if let Some(handler) = &mut self.inner.borrow_mut().event_handler {
+            handler.handle_event(CoapEvent::ServerSessionAddressChanged, &mut session.clone().into());
+        }
+    }
+
+    /// Sets the event handler that should be notified about session events (e.g. completed or
+    /// failed DTLS handshakes), replacing any previously set event handler.
+    pub fn set_event_handler<H: CoapEventHandler + 'static>(&mut self, handler: H) {
+        self.inner.borrow_mut().event_handler = Some(Box::new(handler));
+    }
+
+    /// Sets a closure that should be called for all session events, replacing any previously set
+    /// event handler.
+    ///
+    /// This is a shorthand for [CoapContext::set_event_handler()] with an event handler that
+    /// only implements [CoapEventHandler::handle_event()].
+    pub fn set_event_callback<F: FnMut(CoapEvent, &mut CoapSession) + 'static>(&mut self, callback: F) {
+        self.set_event_handler(CoapEventCallback::new(callback))
+    }
+
+    /// Removes the currently set event handler (if any).
+    pub fn clear_event_handler(&mut self) {
+        self.inner.borrow_mut().event_handler = None;
+    }
+
     /// Sets the server-side cryptography information provider.
     ///
     /// # Errors
@@ -340,6 +583,67 @@ impl<'a> CoapContext<'a> {
             Err(ContextConfigurationError::Unknown)
         }
     }
+
+    /// Enables OSCORE ([RFC 8613](https://datatracker.ietf.org/doc/html/rfc8613)) for server-side
+    /// sessions of this context using the given security context.
+    ///
+    /// Afterwards, OSCORE-protected requests are unprotected before being passed on to the
+    /// resource handlers, and the responses to them are protected automatically.
+    ///
+    /// # Errors
+    /// Will return [`ContextConfigurationError::InvalidOscoreContext`] if libcoap rejected the
+    /// security context and [`ContextConfigurationError::Unknown`] if the call to the underlying
+    /// libcoap function fails.
+    #[cfg(feature = "oscore")]
+    pub fn enable_oscore_server(&mut self, oscore: OscoreContext) -> Result<(), ContextConfigurationError> {
+        let raw_conf = oscore
+            .create_raw_conf()
+            .ok_or(ContextConfigurationError::InvalidOscoreContext)?;
+        // SAFETY: raw context is valid, raw_conf was just created and is freed by libcoap.
+        let result = unsafe { coap_context_oscore_server(self.inner.borrow_mut().raw_context, raw_conf) };
+        if result == 1 {
+            Ok(())
+        } else {
+            Err(ContextConfigurationError::Unknown)
+        }
+    }
+
+    /// Sends the given response to the given server-side session outside of a request handler,
+    /// e.g., to push a notification to one specific observer.
+    ///
+    /// Acknowledgement responses are sent as confirmable messages for reliable delivery, see
+    /// [CoapServerSession::send_response()] for more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [SessionError] if the response could not be sent.
+    pub fn send_to(
+        &mut self,
+        session: &mut CoapServerSession<'a>,
+        response: CoapResponse,
+    ) -> Result<CoapMessageId, SessionError> {
+        session.send_response(response)
+    }
+
+    /// Sends the given response to all server-side sessions for which `filter` returns true.
+    ///
+    /// The response is sent to each session using [CoapServerSession::send_response()].
+    /// Returns the number of sessions the response was sent to successfully.
+    pub fn send_broadcast<F: Fn(&CoapServerSession<'a>) -> bool>(&self, response: CoapResponse, filter: F) -> usize {
+        // Clone the matching session wrappers so that the context is not borrowed while sending.
+        let sessions: Vec<CoapServerSession<'a>> = self
+            .inner
+            .borrow()
+            .server_sessions
+            .iter()
+            .filter(|session| filter(session))
+            .cloned()
+            .collect();
+        sessions
+            .iter()
+            .filter(|session| session.send_response(response.clone()).is_ok())
+            .count()
+    }
 }
 
 impl CoapContext<'_> {
@@ -367,7 +671,64 @@ impl CoapContext<'_> {
         Ok(())
     }
 
+    /// Joins the given multicast group on all UDP endpoints of this context, allowing them to
+    /// receive requests sent to the group (see
+    /// [RFC 7252, Section 8](https://datatracker.ietf.org/doc/html/rfc7252#section-8)).
+    ///
+    /// The operating system chooses the network interface the group is joined on based on the
+    /// addresses the endpoints are bound to.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ContextConfigurationError::MulticastGroupJoin] if `group` is not a multicast
+    /// address, this context has no UDP endpoints, or the group could not be joined on any of them.
+    pub fn join_multicast_group(&mut self, group: IpAddr) -> Result<(), ContextConfigurationError> {
+        let inner_ref = self.inner.borrow();
+        if !group.is_multicast()
+            || !inner_ref
+                .endpoints
+                .iter()
+                .any(|v| v.info().protocol() == CoapProtocol::Udp)
+        {
+            return Err(ContextConfigurationError::MulticastGroupJoin(group));
+        }
+        let group_name = CString::new(group.to_string()).expect("IP addresses never contain null bytes");
+        // SAFETY: Raw context is valid, the context has at least one endpoint (as required by
+        // libcoap) and group_name is a valid C string. Providing null as the interface name lets
+        // the operating system choose the interface.
+        if unsafe { coap_join_mcast_group_intf(inner_ref.raw_context, group_name.as_ptr(), std::ptr::null()) } != 0 {
+            return Err(ContextConfigurationError::MulticastGroupJoin(group));
+        }
+        Ok(())
+    }
+
+    /// Returns information about all endpoints of this context (i.e., their protocols and the
+    /// local addresses they are bound to), in the order they were added in.
+    ///
+    /// This can be used to determine the port that was assigned to an endpoint that was created
+    /// for port 0.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use libcoap_rs::CoapContext;
+    ///
+    /// let mut context = CoapContext::new().unwrap();
+    /// context.add_endpoint_udp("127.0.0.1:0".parse().unwrap()).unwrap();
+    /// let endpoint = context.endpoints().next().unwrap();
+    /// println!("Listening on port {}", endpoint.local_addr().port());
+    /// ```
+    pub fn endpoints(&self) -> impl Iterator<Item = CoapEndpointInfo> {
+        let infos: Vec<CoapEndpointInfo> = self.inner.borrow().endpoints.iter().map(CoapEndpoint::info).collect();
+        infos.into_iter()
+    }
+
     /// Creates a new UDP endpoint that is bound to the given address.
+    ///
+    /// Endpoints bound to the unspecified IPv6 address (`::`) always operate in dual-stack mode,
+    /// i.e., they also accept IPv4 traffic using IPv4-mapped IPv6 addresses (see
+    /// [RFC 4291, Section 2.5.5.2](https://datatracker.ietf.org/doc/html/rfc4291#section-2.5.5.2)),
+    /// as libcoap disables the `IPV6_V6ONLY` socket option regardless of the platform default.
+    /// This also applies to [CoapContext::add_endpoint_tcp()] and [CoapContext::add_endpoint_dtls()].
     pub fn add_endpoint_udp(&mut self, addr: SocketAddr) -> Result<(), EndpointCreationError> {
         self.add_endpoint(addr, coap_proto_t::COAP_PROTO_UDP)
     }
@@ -408,13 +769,252 @@ impl CoapContext<'_> {
         };
     }
 
+    /// Adds the given resource to the resource pool of this context and advertises it at
+    /// `/.well-known/core` using the attributes in `description` (see
+    /// [RFC 6690](https://datatracker.ietf.org/doc/html/rfc6690)).
+    ///
+    /// The resource is made observable if `description.observable` is set.
+    /// The returned handle can be used to update the advertised attributes later on (see
+    /// [ResourceHandle::update_attributes()]).
+    ///
+    /// # Implementation details (informative, not covered by semver guarantees)
+    ///
+    /// As libcoap does not allow changing the attributes of a resource, this function replaces
+    /// libcoap's built-in handling of `/.well-known/core` with a resource that lists all
+    /// resources of this context. Resources added without a description are listed without
+    /// attributes.
+    pub fn add_resource_with_attributes<D: Any + ?Sized + Debug>(
+        &mut self,
+        res: CoapResource<D>,
+        description: ResourceDescription,
+    ) -> ResourceHandle {
+        res.set_get_observable(description.observable);
+        let handle = ResourceHandle::new(res.uri_path());
+        self.add_resource(res);
+        self.set_resource_description(handle.uri_path(), description);
+        handle
+    }
+
+    /// Sets the attributes advertised at `/.well-known/core` for the resource with the given
+    /// `uri_path`, adding the resource discovery resource if necessary.
+    pub(crate) fn set_resource_description(&mut self, uri_path: &str, description: ResourceDescription) {
+        self.inner
+            .borrow_mut()
+            .resource_descriptions
+            .insert(uri_path.to_string(), description);
+        self.register_well_known_core();
+    }
+
+    /// Adds the resource discovery resource at `/.well-known/core` (see
+    /// [RFC 6690, Section 4](https://datatracker.ietf.org/doc/html/rfc6690#section-4)) unless it
+    /// has already been added.
+    ///
+    /// The resource lists all resources of this context (including ones added later on) along
+    /// with the attributes provided using [CoapContext::add_resource_with_attributes()], and
+    /// supports filtering them using a single query parameter (e.g., `?rt=temperature`, see
+    /// [RFC 6690, Section 4.1](https://datatracker.ietf.org/doc/html/rfc6690#section-4.1)).
+    ///
+    /// This function is called automatically when adding a resource with attributes, so it only
+    /// has to be called explicitly if all resources are added without attributes.
+    ///
+    /// # Implementation details (informative, not covered by semver guarantees)
+    ///
+    /// The added resource replaces libcoap's built-in handling of `/.well-known/core`.
+    pub fn register_well_known_core(&mut self) {
+        let has_well_known_core = self
+            .inner
+            .borrow()
+            .resources
+            .iter()
+            .any(|v| v.uri_path() == WELL_KNOWN_CORE_PATH);
+        if !has_well_known_core {
+            self.add_resource(well_known::well_known_core_resource());
+        }
+    }
+
+    /// Returns the link-format description of all resources of this context that match the given
+    /// query `filter`, which is served at `/.well-known/core`.
+    pub(crate) fn well_known_core(&self, filter: Option<&str>) -> String {
+        let inner_ref = self.inner.borrow();
+        let resources = inner_ref
+            .resources
+            .iter()
+            .map(|v| v.uri_path())
+            .filter(|v| *v != WELL_KNOWN_CORE_PATH)
+            .map(|v| (v, inner_ref.resource_descriptions.get(v)));
+        well_known::link_format(resources, filter)
+    }
+
+    /// Returns information about all resources that are currently registered with this context,
+    /// including their advertised attributes and current number of observers.
+    ///
+    /// This can be used to e.g. build a management resource that lists all resources of a server.
+    pub fn resources(&self) -> impl Iterator<Item = ResourceInfo> {
+        let inner_ref = self.inner.borrow();
+        inner_ref
+            .resources
+            .iter()
+            .map(|resource| ResourceInfo {
+                path: resource.uri_path().to_string(),
+                attributes: inner_ref
+                    .resource_descriptions
+                    .get(resource.uri_path())
+                    .cloned()
+                    .unwrap_or_default(),
+                observable: resource.is_observable(),
+                observer_count: resource.observer_count(),
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Adds a resource with the given `uri_path` that serves the contents of the file at
+    /// `file_path` in response to GET requests.
+    ///
+    /// The file is read whenever a request is received, so changes to the file are visible to
+    /// subsequent requests.
+    /// The content format of responses is derived from the file extension (`.cbor` for CBOR,
+    /// `.json` for JSON and `.txt` for `text/plain`, all other files are served as
+    /// `application/octet-stream`).
+    /// Responses contain an ETag derived from the modification time and size of the file as well
+    /// as a Max-Age option.
+    /// Files that do not fit into a single message (based on the maximum PDU size of the session)
+    /// are sent using block-wise transfers (see [RFC 7959](https://datatracker.ietf.org/doc/html/rfc7959)).
+    ///
+    /// Returns a handle to the added resource, which can be used to advertise attributes for it
+    /// (see [ResourceHandle::update_attributes()]).
+    ///
+    /// # Errors
+    /// Returns [FileResourceError::PathTraversal] if `uri_path` or `file_path` contain `..`
+    /// components, [FileResourceError::InvalidFileType] if `file_path` does not refer to a
+    /// regular file, or [FileResourceError::Io] if the file could not be accessed.
+    pub fn add_file_resource(
+        &mut self,
+        uri_path: &str,
+        file_path: PathBuf,
+    ) -> Result<ResourceHandle, FileResourceError> {
+        file_resource::check_uri_path(uri_path)?;
+        file_resource::check_file_path(&file_path)?;
+        if !std::fs::metadata(&file_path)?.is_file() {
+            return Err(FileResourceError::InvalidFileType);
+        }
+        let resource = file_resource::file_resource(uri_path, file_path);
+        let handle = ResourceHandle::new(resource.uri_path());
+        self.add_resource(resource);
+        Ok(handle)
+    }
+
+    /// Adds resources serving all files in the directory at `dir_path` (including its
+    /// subdirectories) below the URI path `uri_prefix`, returning handles to the added resources.
+    ///
+    /// For instance, if `uri_prefix` is `fw`, the file `<dir_path>/v1/image.bin` is served at
+    /// `fw/v1/image.bin`.
+    /// The directory is only scanned once when calling this function, i.e., files that are created
+    /// afterwards are not served. Symbolic links are not followed.
+    /// See [CoapContext::add_file_resource()] for a description of the responses sent by the
+    /// resources.
+    ///
+    /// # Errors
+    /// Returns [FileResourceError::PathTraversal] if `uri_prefix` or `dir_path` contain `..`
+    /// components, [FileResourceError::InvalidFileType] if `dir_path` does not refer to a
+    /// directory, [FileResourceError::InvalidFileName] if a file name is not valid UTF-8, or
+    /// [FileResourceError::Io] if the directory could not be read.
+    /// If an error is returned, no resources were added.
+    pub fn add_resource_directory(
+        &mut self,
+        uri_prefix: &str,
+        dir_path: PathBuf,
+    ) -> Result<Vec<ResourceHandle>, FileResourceError> {
+        file_resource::check_uri_path(uri_prefix)?;
+        file_resource::check_file_path(&dir_path)?;
+        if !std::fs::metadata(&dir_path)?.is_dir() {
+            return Err(FileResourceError::InvalidFileType);
+        }
+        let mut files = Vec::new();
+        file_resource::collect_files(&dir_path, uri_prefix.trim_matches('/'), &mut files)?;
+        let mut handles = Vec::with_capacity(files.len());
+        for (uri_path, file_path) in files {
+            let resource = file_resource::file_resource(&uri_path, file_path);
+            handles.push(ResourceHandle::new(resource.uri_path()));
+            self.add_resource(resource);
+        }
+        Ok(handles)
+    }
+
+    /// Removes the resource with the given `uri_path` from the resource pool of this context.
+    ///
+    /// Returns whether a resource with this path was associated with this context.
+    ///
+    /// # Panics
+    /// Panics if the resource is still in use elsewhere, e.g., if this function is called from
+    /// inside of one of the resource's request handlers.
+    #[cfg(feature = "pubsub")]
+    pub(crate) fn remove_resource(&mut self, uri_path: &str) -> bool {
+        let mut inner_ref = self.inner.borrow_mut();
+        let Some(position) = inner_ref.resources.iter().position(|v| v.uri_path() == uri_path) else {
+            return false;
+        };
+        let resource = inner_ref.resources.remove(position);
+        inner_ref.resource_descriptions.remove(uri_path);
+        std::mem::drop(inner_ref);
+        // Dropping the inner resource also removes the raw resource from the raw context.
+        resource.drop_inner_exclusive();
+        true
+    }
+
+    /// Calls `f` with a reference to the resource with the given `uri_path` and returns its
+    /// result, or returns `None` if there is no such resource with user data type `D`.
+    #[cfg(feature = "pubsub")]
+    pub(crate) fn with_resource<D: Any + ?Sized + Debug, R>(
+        &self,
+        uri_path: &str,
+        f: impl FnOnce(&CoapResource<D>) -> R,
+    ) -> Option<R> {
+        let inner_ref = self.inner.borrow();
+        inner_ref
+            .resources
+            .iter()
+            .find(|v| v.uri_path() == uri_path)
+            .and_then(|v| v.as_any().downcast_ref::<CoapResource<D>>())
+            .map(f)
+    }
+
     /// Performs currently outstanding IO operations, waiting for a maximum duration of `timeout`.
     ///
     /// This is the function where most of the IO operations made using this library are actually
     /// executed. It is recommended to call this function in a loop for as long as the CoAP context
     /// is used.
+    ///
+    /// Requests queued using [CoapClientSession::try_send()] are handed over to libcoap before
+    /// processing IO.
+    /// If notifications were held back using [CoapResource::notify_observers_with_rate_limit()],
+    /// this function returns early once the next one of them is due to be sent.
     pub fn do_io(&mut self, timeout: Option<Duration>) -> Result<Duration, IoProcessError> {
+        let client_sessions: Vec<_> = self
+            .inner
+            .borrow()
+            .client_sessions
+            .iter()
+            .filter_map(CoapFfiWeakCell::upgrade)
+            .collect();
+        for session in client_sessions {
+            CoapClientSession::from_inner(session).drain_send_queue();
+        }
         let mut inner_ref = self.inner.borrow_mut();
+        let next_notification = inner_ref
+            .resources
+            .iter()
+            .filter_map(|resource| resource.flush_pending_notifications())
+            .min();
+        // A timeout of zero would make libcoap wait indefinitely, so wait for at least 1 ms.
+        let until_next_notification = next_notification.map(|next| {
+            next.saturating_duration_since(Instant::now())
+                .max(Duration::from_millis(1))
+        });
+        let timeout = match (timeout, until_next_notification) {
+            (Some(timeout), Some(until_next)) => Some(timeout.min(until_next)),
+            (timeout, until_next) => timeout.or(until_next),
+        };
         // Round up the duration if it is not a clean number of seconds.
         let timeout = if let Some(timeout) = timeout {
             let mut temp_timeout = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
@@ -442,6 +1042,13 @@ impl CoapContext<'_> {
         if spent_time < 0 {
             return Err(IoProcessError::Unknown);
         }
+        // Messages other than requests (e.g., ACKs or RSTs for notifications) do not pass through
+        // our handlers, so check all server-side sessions for address changes after processing.
+        let server_sessions = inner_ref.server_sessions.clone();
+        drop(inner_ref);
+        for session in &server_sessions {
+            self.handle_address_migration(session);
+        }
         // Return with duration of call.
         Ok(Duration::from_millis(spent_time.unsigned_abs() as u64))
     }
@@ -514,6 +1121,216 @@ impl CoapContext<'_> {
         unsafe { coap_context_set_max_idle_sessions(self.inner.borrow().raw_context, max_idle_sessions) };
     }
 
+    /// Returns the maximum number of concurrently active server-side sessions, or `None` if the
+    /// number of sessions is not limited (see [CoapContext::set_max_sessions()]).
+    pub fn max_sessions(&self) -> Option<usize> {
+        self.inner.borrow().max_sessions
+    }
+
+    /// Limits the number of concurrently active server-side sessions to `max`.
+    ///
+    /// If a new session is created while `max` sessions are already active, the new session is
+    /// refused: Requests received using this session are not passed to the resource handlers, but
+    /// answered with a `5.03 Service Unavailable` response whose Max-Age option indicates when the
+    /// client may retry (the session timeout, after which idle sessions are freed), after which
+    /// the session is closed.
+    ///
+    /// This also sets the maximum number of idle sessions kept by libcoap (see
+    /// [CoapContext::set_max_idle_sessions()]) to `max`.
+    pub fn set_max_sessions(&mut self, max: usize) {
+        self.set_max_idle_sessions(max.try_into().unwrap_or(c_uint::MAX));
+        self.inner.borrow_mut().max_sessions = Some(max);
+    }
+
+    /// Removes the limit on the number of concurrently active server-side sessions set using
+    /// [CoapContext::set_max_sessions()].
+    ///
+    /// Note that this does not reset the maximum number of idle sessions kept by libcoap.
+    pub fn clear_max_sessions(&mut self) {
+        self.inner.borrow_mut().max_sessions = None;
+    }
+
+    /// Returns the current request rate limit, or `None` if requests are not rate limited (see
+    /// [CoapContext::set_rate_limit()]).
+    pub fn rate_limit(&self) -> Option<RateLimitConfig> {
+        self.inner.borrow().rate_limiter.as_ref().map(RateLimiter::config)
+    }
+
+    /// Limits the rate at which requests are accepted from a single source according to the given
+    /// configuration.
+    ///
+    /// Requests that exceed the rate limit are not passed to the resource handlers, but answered
+    /// with a `4.29 Too Many Requests` response whose Max-Age option indicates the number of
+    /// seconds after which the client may retry (see
+    /// [RFC 8516, Section 4](https://datatracker.ietf.org/doc/html/rfc8516#section-4)).
+    /// Retransmissions of requests that are detected by message deduplication (see
+    /// [CoapContext::set_deduplication()]) do not count towards the rate limit.
+    ///
+    /// Replacing an existing rate limit resets its state and statistics.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::{
+    ///     rate_limit::{RateLimitConfig, RateLimitKey},
+    ///     CoapContext,
+    /// };
+    ///
+    /// let mut context = CoapContext::new().unwrap();
+    /// let config = RateLimitConfig {
+    ///     max_requests_per_second: 10,
+    ///     burst_size: 20,
+    ///     by: RateLimitKey::SourceIp,
+    /// };
+    /// context.set_rate_limit(config);
+    /// assert_eq!(context.rate_limit(), Some(config));
+    /// assert!(context.rate_limit_stats().is_empty());
+    /// ```
+    pub fn set_rate_limit(&mut self, config: RateLimitConfig) {
+        self.inner.borrow_mut().rate_limiter = Some(RateLimiter::new(config));
+    }
+
+    /// Removes the request rate limit set using [CoapContext::set_rate_limit()].
+    pub fn clear_rate_limit(&mut self) {
+        self.inner.borrow_mut().rate_limiter = None;
+    }
+
+    /// Returns the number of accepted and rejected requests for each IP address that requests
+    /// were received from since the rate limit was set (see [CoapContext::set_rate_limit()]).
+    ///
+    /// To bound the memory used for rate limiting, the statistics of an IP address may be removed
+    /// once its rate limit has recovered completely (i.e., once it has been idle for long enough)
+    /// and requests from many other addresses are received.
+    ///
+    /// Returns an empty map if requests are not rate limited.
+    pub fn rate_limit_stats(&self) -> HashMap<IpAddr, RateLimitStats> {
+        self.inner
+            .borrow()
+            .rate_limiter
+            .as_ref()
+            .map(|v| v.stats().clone())
+            .unwrap_or_default()
+    }
+
+    /// Accounts for a request received from `remote_addr` in the rate limit.
+    ///
+    /// Returns `None` if the request may be handled, or the number of seconds after which the
+    /// client may retry if the request exceeds the rate limit.
+    pub(crate) fn check_rate_limit(&self, remote_addr: SocketAddr) -> Option<MaxAge> {
+        self.inner
+            .borrow_mut()
+            .rate_limiter
+            .as_mut()
+            .and_then(|v| v.check(remote_addr))
+    }
+
+    /// Returns the maximum size of request bodies in bytes, or `None` if the size of request bodies
+    /// is not limited (see [CoapContext::set_max_body_size()]).
+    pub fn max_body_size(&self) -> Option<usize> {
+        self.inner.borrow().max_body_size
+    }
+
+    /// Limits the size of request bodies that are passed to resource handlers to `max` bytes.
+    ///
+    /// Requests whose body (or whose total body size as indicated by the Size1 option, see
+    /// [RFC 7959, Section 4](https://datatracker.ietf.org/doc/html/rfc7959#section-4)) exceeds
+    /// this limit are not passed to the resource handlers, but answered with a
+    /// `4.13 Request Entity Too Large` response that indicates the limit in its Size1 option.
+    /// The size is checked before the request is converted into a
+    /// [CoapRequest](crate::message::CoapRequest), i.e., this library never copies the body of
+    /// rejected requests.
+    ///
+    /// The limit is also set as the maximum body size of libcoap, which rejects block-wise
+    /// transfers ([RFC 7959](https://datatracker.ietf.org/doc/html/rfc7959)) whose indicated or
+    /// received body size exceeds it before reassembling their body, i.e., no memory is allocated
+    /// for the body of rejected block-wise requests either.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::CoapContext;
+    ///
+    /// let mut context = CoapContext::new().unwrap();
+    /// assert_eq!(context.max_body_size(), None);
+    /// context.set_max_body_size(1024);
+    /// assert_eq!(context.max_body_size(), Some(1024));
+    /// ```
+    pub fn set_max_body_size(&mut self, max: usize) {
+        let mut inner = self.inner.borrow_mut();
+        // SAFETY: Provided context is always valid as an invariant of this struct.
+        unsafe { coap_context_set_max_body_size(inner.raw_context, u32::try_from(max).unwrap_or(u32::MAX)) };
+        inner.max_body_size = Some(max);
+    }
+
+    /// Removes the request body size limit set using [CoapContext::set_max_body_size()].
+    pub fn clear_max_body_size(&mut self) {
+        let mut inner = self.inner.borrow_mut();
+        // SAFETY: Provided context is always valid as an invariant of this struct.
+        // A maximum body size of zero disables the limit in libcoap.
+        unsafe { coap_context_set_max_body_size(inner.raw_context, 0) };
+        inner.max_body_size = None;
+    }
+
+    /// Sets a hook that authorizes requests (e.g., based on their path, method or the PSK
+    /// identity of the session) before they are passed to resource handlers, replacing any
+    /// previously set hook.
+    ///
+    /// If the hook returns [AccessDecision::Deny], the resource handler is not called and the
+    /// request is answered with the given response code instead (e.g., `4.01 Unauthorized` or
+    /// `4.03 Forbidden`). The hook is only called for requests to resources that have a handler
+    /// for the request method, other requests are still answered with `4.04 Not Found` or
+    /// `4.05 Method Not Allowed` by libcoap.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::{
+    ///     message::CoapMessageCommon,
+    ///     protocol::{CoapMessageCode, CoapRequestCode, CoapResponseCode},
+    ///     AccessDecision, CoapContext,
+    /// };
+    ///
+    /// let mut context = CoapContext::new().unwrap();
+    /// context.set_access_control(|_session, request| match request.code() {
+    ///     CoapMessageCode::Request(CoapRequestCode::Get) => AccessDecision::Allow,
+    ///     _ => AccessDecision::Deny(CoapResponseCode::Unauthorized),
+    /// });
+    /// ```
+    pub fn set_access_control<F: FnMut(&CoapServerSession, &CoapRequest) -> AccessDecision + 'static>(
+        &mut self,
+        hook: F,
+    ) {
+        self.inner.borrow_mut().access_control = Some(AccessControlHook(Box::new(hook)));
+    }
+
+    /// Removes the hook set using [CoapContext::set_access_control()], allowing all requests.
+    pub fn clear_access_control(&mut self) {
+        self.inner.borrow_mut().access_control = None;
+    }
+
+    /// Decides whether the given request received using `session` may be passed to the resource
+    /// handler using the hook set with [CoapContext::set_access_control()].
+    pub(crate) fn check_access(&self, session: &CoapServerSession, request: &CoapRequest) -> AccessDecision {
+        let Some(mut hook) = self.inner.borrow_mut().access_control.take() else {
+            return AccessDecision::Allow;
+        };
+        let decision = (hook.0)(session, request);
+        self.inner.borrow_mut().access_control.get_or_insert(hook);
+        decision
+    }
+
+    /// Removes the observer with the given session and token from the resources of this context
+    /// after libcoap ended the observation because a notification was rejected or not
+    /// acknowledged.
+    pub(crate) fn remove_failed_observer(&self, session: &CoapServerSession, token: &[u8]) {
+        for resource in &self.inner.borrow().resources {
+            resource.remove_observer(session, token);
+        }
+    }
+
+    /// Returns the number of server-side sessions that are currently active, i.e., sessions that
+    /// are known to libcoap and have neither been closed nor refused due to the session limit.
+    pub fn active_session_count(&self) -> usize {
+        self.inner.borrow().active_session_count()
+    }
+
     /// Returns the maximum size for Capabilities and Settings Messages
     ///
     /// CSMs are used in CoAP over TCP as specified in
@@ -589,6 +1406,131 @@ impl CoapContext<'_> {
         };
     }
 
+    /// Returns whether duplicate requests are detected and answered without calling the resource
+    /// handler again (see [CoapContext::set_deduplication()]).
+    pub fn deduplication(&self) -> bool {
+        self.inner.borrow().deduplication
+    }
+
+    /// Sets whether duplicate requests received by server-side sessions of this context should be
+    /// detected (enabled by default).
+    ///
+    /// [RFC 7252, Section 4.5](https://datatracker.ietf.org/doc/html/rfc7252#section-4.5) requires
+    /// servers to deduplicate retransmitted messages that are received within `EXCHANGE_LIFETIME`.
+    /// If deduplication is enabled, a request whose message ID and token match a recently received
+    /// request of the same session will not be passed to the resource handler again.
+    /// Instead, the response that was sent for the original request (if any) is retransmitted.
+    pub fn set_deduplication(&mut self, enabled: bool) {
+        self.inner.borrow_mut().deduplication = enabled;
+    }
+
+    /// Returns the number of recently received requests per server-side session that are
+    /// remembered for deduplication.
+    pub fn deduplication_cache_size(&self) -> usize {
+        self.inner.borrow().deduplication_cache_size
+    }
+
+    /// Sets the number of recently received requests per server-side session that are remembered
+    /// for deduplication (see [CoapContext::set_deduplication()]).
+    ///
+    /// If more requests than this are received within `EXCHANGE_LIFETIME`, the oldest ones are
+    /// forgotten, i.e., their duplicates will no longer be detected.
+    /// The cached responses of all remembered requests are kept in memory, so larger values
+    /// increase memory usage for each session.
+    pub fn set_deduplication_cache_size(&mut self, cache_size: usize) {
+        self.inner.borrow_mut().deduplication_cache_size = cache_size;
+    }
+
+    /// Returns whether messages received by this context are parsed with strict parsing enabled
+    /// (see [CoapContext::set_strict_parsing()]).
+    pub fn strict_parsing(&self) -> bool {
+        self.inner.borrow().strict_parsing
+    }
+
+    /// Sets whether requests received by server-side sessions and responses received by
+    /// client-side sessions of this context are parsed with strict parsing enabled (disabled by
+    /// default).
+    ///
+    /// See [CoapMessage::with_strict_parsing()](crate::message::CoapMessage::with_strict_parsing())
+    /// for more information. The setting is retained in the messages of the parsed requests and
+    /// responses, so it also applies if they are parsed again (e.g., by proxies).
+    pub fn set_strict_parsing(&mut self, enabled: bool) {
+        self.inner.borrow_mut().strict_parsing = enabled;
+    }
+
+    /// Returns the number of sessions bound to this context that are still in use outside of it.
+    ///
+    /// This includes all [CoapClientSession]s created for this context that have not been dropped
+    /// yet, as well as [CoapServerSession] instances that were kept beyond the handler call they
+    /// were provided to.
+    /// Dropping the context while this number is not zero would invalidate the underlying raw
+    /// sessions. In this case, the context leaks its underlying libcoap context (so that the
+    /// remaining sessions can still be dropped safely), poisons the remaining sessions (see
+    /// [CoapSessionCommon::is_poisoned()]) and logs a warning describing the issue.
+    /// Therefore, ensure that this function returns zero before dropping the context, or use
+    /// [CoapContext::close()] to be notified about outstanding sessions.
+    pub fn outstanding_sessions(&self) -> usize {
+        self.inner.borrow().outstanding_session_count()
+    }
+
+    /// Drops this context, returning an error if sessions bound to it were still in use (see
+    /// [CoapContext::outstanding_sessions()]).
+    ///
+    /// In contrast to simply dropping the context, this allows reacting to outstanding sessions.
+    /// In both cases, the underlying libcoap context is leaked and the remaining sessions are
+    /// poisoned if sessions are still in use.
+    ///
+    /// # Errors
+    ///
+    /// Returns [OutstandingSessionsError::SessionsInUse] with the number of outstanding sessions
+    /// if there were any.
+    pub fn close(self) -> Result<(), OutstandingSessionsError> {
+        let outstanding_sessions = self.outstanding_sessions();
+        std::mem::drop(self);
+        match outstanding_sessions {
+            0 => Ok(()),
+            count => Err(OutstandingSessionsError::SessionsInUse(count)),
+        }
+    }
+
+    /// Closes all server-side sessions that are currently associated with this context.
+    ///
+    /// See [CoapServerSession::close()] for more information.
+    pub fn close_all_sessions(&mut self) {
+        let raw_sessions: Vec<*mut coap_session_t> = self
+            .inner
+            .borrow()
+            .server_sessions
+            .iter()
+            // SAFETY: We only use the raw session pointer to re-create a session wrapper below.
+            .map(|session| unsafe { session.raw_session_mut() })
+            .collect();
+        for raw_session in raw_sessions {
+            // SAFETY: The session is still referenced by the wrapper in our server session list, so
+            // it is valid and its app data points to a valid CoapServerSessionInner.
+            // The context may not be borrowed while closing, as libcoap may call our event handler.
+            let mut session = unsafe { CoapServerSession::from_raw(raw_session) };
+            session.close();
+        }
+    }
+
+    /// Sets the log level of libcoap's internal logging.
+    ///
+    /// This is a global setting that affects all contexts, see [set_log_level()](crate::set_log_level())
+    /// for a variant of this function that can be called before a context is created.
+    pub fn set_log_level(level: CoapLogLevel) {
+        set_log_level(level)
+    }
+
+    /// Sets the log level of the DTLS library used by libcoap.
+    ///
+    /// This is a global setting that affects all contexts, see
+    /// [set_dtls_log_level()](crate::set_dtls_log_level()) for a variant of this function that can
+    /// be called before a context is created.
+    pub fn set_dtls_log_level(level: CoapLogLevel) {
+        set_dtls_log_level(level)
+    }
+
     /// Returns a reference to the raw context contained in this struct.
     ///
     /// # Safety
@@ -636,6 +1578,235 @@ impl CoapContext<'_> {
     // TODO coap_session_get_by_peer
 }
 
+/// Decision of the hook set using [CoapContext::set_access_control()] about whether a request may
+/// be passed to the resource handler.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum AccessDecision {
+    /// The request is passed to the resource handler.
+    Allow,
+    /// The request is answered with the given response code without calling the resource handler.
+    Deny(CoapResponseCode),
+}
+
+/// Wrapper around the hook set using [CoapContext::set_access_control()].
+struct AccessControlHook(Box<dyn FnMut(&CoapServerSession, &CoapRequest) -> AccessDecision>);
+
+impl Debug for AccessControlHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessControlHook").finish_non_exhaustive()
+    }
+}
+
+/// Function that adds a resource to a context, used to defer adding resources to
+/// [CoapContextBuilder::build()].
+type DeferredResource<'a> = Box<dyn FnOnce(&mut CoapContext<'a>) + 'a>;
+
+/// Builder for [CoapContext]s that sets up endpoints, cryptography information providers,
+/// resources and session limits in a single chain.
+///
+/// The configuration is only applied in [CoapContextBuilder::build()], which either returns the
+/// fully configured context or, if any step fails, drops the partially configured context and
+/// returns the error.
+///
+/// # Examples
+/// A DTLS server using pre-shared keys with two resources:
+/// ```no_run
+/// use libcoap_rs::{
+///     crypto::psk::{PskKey, ServerPskContextBuilder},
+///     message::{CoapMessageCommon, CoapRequest, CoapResponse},
+///     protocol::{CoapMessageCode, CoapRequestCode, CoapResponseCode},
+///     session::{CoapServerSession, CoapSessionCommon},
+///     CoapContextBuilder, CoapRequestHandler, CoapResource, ResourceDescription,
+/// };
+///
+/// /// Creates a resource that answers GET requests with the given text.
+/// fn text_resource(uri_path: &str, text: &'static str) -> CoapResource<()> {
+///     let resource = CoapResource::new(uri_path, (), false);
+///     resource.set_method_handler(
+///         CoapRequestCode::Get,
+///         Some(CoapRequestHandler::new(
+///             move |_: &mut (), sess: &mut CoapServerSession, _: &CoapRequest, mut rsp: CoapResponse| {
+///                 rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+///                 rsp.set_data(Some(text.as_bytes()));
+///                 sess.send(rsp).unwrap();
+///             },
+///         )),
+///     );
+///     resource
+/// }
+///
+/// let key = PskKey::new(Some("server_identity"), "server_key______");
+/// let mut context = CoapContextBuilder::new()
+///     .enable_dtls(ServerPskContextBuilder::new(key).build())
+///     .bind_dtls("[::]:5684".parse().unwrap())
+///     .add_resource(text_resource("hello", "Hello World!"))
+///     .add_resource_with_attributes(
+///         text_resource("temperature", "21.5"),
+///         ResourceDescription {
+///             title: Some("Temperature".to_string()),
+///             ..Default::default()
+///         },
+///     )
+///     .max_sessions(16)
+///     .build()
+///     .expect("unable to create CoAP context");
+/// loop {
+///     context.do_io(None).expect("error during IO");
+/// }
+/// ```
+#[derive(Default)]
+pub struct CoapContextBuilder<'a> {
+    bind_addrs: Vec<SocketAddr>,
+    #[cfg(feature = "tcp")]
+    tcp_enabled: bool,
+    #[cfg(dtls)]
+    dtls_bind_addrs: Vec<SocketAddr>,
+    #[cfg(dtls)]
+    dtls_crypto: Vec<ServerCryptoContext<'a>>,
+    resources: Vec<DeferredResource<'a>>,
+    max_sessions: Option<usize>,
+    log_level: Option<CoapLogLevel>,
+}
+
+impl<'a> CoapContextBuilder<'a> {
+    /// Creates a new builder for a context without any endpoints or resources.
+    pub fn new() -> CoapContextBuilder<'a> {
+        CoapContextBuilder::default()
+    }
+
+    /// Adds a UDP endpoint (and a TCP endpoint, if enabled using
+    /// [CoapContextBuilder::enable_tcp()]) that is bound to the given address.
+    ///
+    /// May be called multiple times to bind to multiple addresses.
+    pub fn bind(mut self, addr: SocketAddr) -> CoapContextBuilder<'a> {
+        self.bind_addrs.push(addr);
+        self
+    }
+
+    /// Additionally adds a TCP endpoint for each address provided to
+    /// [CoapContextBuilder::bind()].
+    #[cfg(feature = "tcp")]
+    pub fn enable_tcp(mut self) -> CoapContextBuilder<'a> {
+        self.tcp_enabled = true;
+        self
+    }
+
+    /// Adds a DTLS endpoint that is bound to the given address.
+    ///
+    /// DTLS endpoints require a cryptography information provider to be set using
+    /// [CoapContextBuilder::enable_dtls()].
+    #[cfg(dtls)]
+    pub fn bind_dtls(mut self, addr: SocketAddr) -> CoapContextBuilder<'a> {
+        self.dtls_bind_addrs.push(addr);
+        self
+    }
+
+    /// Sets the cryptography information provider used for DTLS endpoints (see
+    /// [CoapContext::set_psk_context()] and [CoapContext::set_pki_rpk_context()]).
+    ///
+    /// May be called once for each type of cryptography information provider, setting a second
+    /// provider of the same type causes [CoapContextBuilder::build()] to fail.
+    #[cfg(dtls)]
+    pub fn enable_dtls(mut self, crypto: impl Into<ServerCryptoContext<'a>>) -> CoapContextBuilder<'a> {
+        self.dtls_crypto.push(crypto.into());
+        self
+    }
+
+    /// Adds the given resource to the context (see [CoapContext::add_resource()]).
+    pub fn add_resource<D: Any + ?Sized + Debug>(mut self, res: CoapResource<D>) -> CoapContextBuilder<'a> {
+        self.resources
+            .push(Box::new(move |context: &mut CoapContext<'a>| context.add_resource(res)));
+        self
+    }
+
+    /// Adds the given resource to the context and advertises it using the given attributes (see
+    /// [CoapContext::add_resource_with_attributes()]).
+    pub fn add_resource_with_attributes<D: Any + ?Sized + Debug>(
+        mut self,
+        res: CoapResource<D>,
+        description: ResourceDescription,
+    ) -> CoapContextBuilder<'a> {
+        self.resources.push(Box::new(move |context: &mut CoapContext<'a>| {
+            context.add_resource_with_attributes(res, description);
+        }));
+        self
+    }
+
+    /// Limits the number of concurrently active server-side sessions (see
+    /// [CoapContext::set_max_sessions()]).
+    pub fn max_sessions(mut self, max: usize) -> CoapContextBuilder<'a> {
+        self.max_sessions = Some(max);
+        self
+    }
+
+    /// Sets the log level of libcoap once the context has been built successfully (see
+    /// [set_log_level()]).
+    ///
+    /// Note that the log level is a global setting that affects all contexts.
+    pub fn log_level(mut self, level: CoapLogLevel) -> CoapContextBuilder<'a> {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Creates the context and applies the configuration of this builder to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [ContextCreationError] that wraps the error of the step that failed, in which
+    /// case the partially configured context is dropped.
+    pub fn build(self) -> Result<CoapContext<'a>, ContextCreationError> {
+        let mut context = CoapContext::new()?;
+        #[cfg(dtls)]
+        for crypto in self.dtls_crypto {
+            match crypto {
+                #[cfg(feature = "dtls-psk")]
+                ServerCryptoContext::Psk(psk_context) => context.set_psk_context(psk_context)?,
+                #[cfg(any(feature = "dtls-pki", feature = "dtls-rpk"))]
+                ServerCryptoContext::PkiRpk(pki_rpk_context) => context.set_pki_rpk_context(pki_rpk_context)?,
+            }
+        }
+        for addr in self.bind_addrs {
+            context.add_endpoint_udp(addr)?;
+            #[cfg(feature = "tcp")]
+            if self.tcp_enabled {
+                context.add_endpoint_tcp(addr)?;
+            }
+        }
+        #[cfg(dtls)]
+        for addr in self.dtls_bind_addrs {
+            context.add_endpoint_dtls(addr)?;
+        }
+        if let Some(max) = self.max_sessions {
+            context.set_max_sessions(max);
+        }
+        for add_resource in self.resources {
+            add_resource(&mut context);
+        }
+        if let Some(level) = self.log_level {
+            set_log_level(level);
+        }
+        Ok(context)
+    }
+}
+
+impl Debug for CoapContextBuilder<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("CoapContextBuilder");
+        debug.field("bind_addrs", &self.bind_addrs);
+        #[cfg(feature = "tcp")]
+        debug.field("tcp_enabled", &self.tcp_enabled);
+        #[cfg(dtls)]
+        debug
+            .field("dtls_bind_addrs", &self.dtls_bind_addrs)
+            .field("dtls_crypto", &self.dtls_crypto);
+        debug
+            .field("resources", &self.resources.len())
+            .field("max_sessions", &self.max_sessions)
+            .field("log_level", &self.log_level)
+            .finish()
+    }
+}
+
 impl Drop for CoapContextInner<'_> {
     fn drop(&mut self) {
         // Disable event handler before dropping, as we would otherwise need to lend our reference
@@ -646,10 +1817,40 @@ impl Drop for CoapContextInner<'_> {
         // [as_mut_context()] contracts (we check validity of the pointer on construction).
         // Passing a NULL handler/None to coap_register_event_handler() is allowed as per the
         // documentation.
-        let a = 1.clone();
         unsafe {
             coap_register_event_handler(self.raw_context, None);
         }
+        // Observers refer to sessions that are freed along with the context.
+        for resource in self.resources.iter() {
+            resource.clear_observers();
+        }
+        let outstanding_sessions = self.outstanding_session_count();
+        if outstanding_sessions > 0 {
+            // Prevent the remaining sessions from being used with a context that no longer exists.
+            for session in self.client_sessions.iter().filter_map(CoapFfiWeakCell::upgrade) {
+                session.poison();
+            }
+            for session in self.server_sessions.iter() {
+                session.poison();
+            }
+            // Freeing the raw context would also free the raw sessions that are still referenced
+            // elsewhere, so we leak the raw context and everything attached to it instead.
+            std::mem::forget(std::mem::take(&mut self.server_sessions));
+            std::mem::forget(std::mem::take(&mut self.endpoints));
+            std::mem::forget(std::mem::take(&mut self.resources));
+            #[cfg(feature = "dtls-psk")]
+            std::mem::forget(self.psk_context.take());
+            #[cfg(any(feature = "dtls-pki", feature = "dtls-rpk"))]
+            std::mem::forget(self.pki_rpk_context.take());
+            let message = CString::new(format!(
+                "CoapContext was dropped while {outstanding_sessions} session(s) bound to it were still in use, \
+                 leaking the underlying libcoap context. Drop all sessions before dropping their context."
+            ))
+            .expect("log message does not contain null bytes");
+            // SAFETY: The format string and the message are valid null-terminated strings.
+            unsafe { coap_log_impl(coap_log_t::COAP_LOG_WARN, c"%s".as_ptr(), message.as_ptr()) };
+            return;
+        }
         for session in std::mem::take(&mut self.server_sessions).into_iter() {
             session.drop_exclusively();
         }
@@ -82,3 +82,22 @@ pub enum ClientCryptoContext< 'a > {
     #[cfg(feature = "dtls-rpk")]
     Rpk(pki_rpk::PkiRpkContext<'a, pki_rpk::Rpk>),
 }
+
+/// Server-side context for cryptography.
+///
+/// Can be provided to server-side context constructors for encrypted endpoints (such as
+/// [`CoapContext::new_dtls_server`](crate::CoapContext::new_dtls_server)).
+///
+/// The available enum variants depend on the enabled DTLS features (`dtls-psk`, `dtls-pki`, and/or
+/// `dtls-rpk`).
+#[cfg(dtls)]
+#[derive(Clone, Debug)]
+pub enum ServerCryptoContext<'a> {
+    /// Context for server-side DTLS sessions with pre-shared keys.
+    #[cfg(feature = "dtls-psk")]
+    Psk(psk::ServerPskContext<'a>),
+    /// Context for server-side DTLS sessions using either a public key infrastructure or raw
+    /// public keys.
+    #[cfg(any(feature = "dtls-pki", feature = "dtls-rpk"))]
+    PkiRpk(pki_rpk::ServerPkiRpkCryptoContext<'a>),
+}
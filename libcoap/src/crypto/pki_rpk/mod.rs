@@ -351,6 +351,12 @@ impl ServerPkiRpkCryptoContext<'_> {
     }
 }
 
+impl<'a> From<ServerPkiRpkCryptoContext<'a>> for crate::crypto::ServerCryptoContext<'a> {
+    fn from(value: ServerPkiRpkCryptoContext<'a>) -> Self {
+        crate::crypto::ServerCryptoContext::PkiRpk(value)
+    }
+}
+
 /// Marker indicating that a cryptographic context does not do TLS library-side certificate
 /// verification.
 ///
@@ -768,14 +774,21 @@ impl<'a, KTY: KeyType> PkiRpkContext<'a, KTY> {
         validated: bool,
     ) -> c_int {
         let inner = (*self.inner).borrow();
-        // This function is only ever called if a CN key provider is set, so it's fine to unwrap
-        // here.
-        if match inner.cn_callback.as_ref().unwrap() {
+        // For PKI contexts, this function is also called if no CN validator is set (in order to
+        // record the peer certificate), in which case we defer to the TLS library's validation.
+        let accepted = match inner.cn_callback.as_ref() {
+            None => true,
             #[cfg(feature = "dtls-pki")]
-            CnCallback::Pki(pki) => pki.validate_cn(cn, asn1_public_cert, session, depth, validated),
+            Some(CnCallback::Pki(pki)) => pki.validate_cn(cn, asn1_public_cert, session, depth, validated),
             #[cfg(feature = "dtls-rpk")]
-            CnCallback::Rpk(rpk) => rpk.validate_rpk(asn1_public_cert, session, validated),
-        } {
+            Some(CnCallback::Rpk(rpk)) => rpk.validate_rpk(asn1_public_cert, session, validated),
+        };
+        // A depth of zero indicates the peer's own certificate (as opposed to one of its CAs).
+        #[cfg(feature = "dtls-pki")]
+        if accepted && depth == 0 && inner.raw_cfg.is_rpk_not_cert == 0 {
+            session.set_peer_certificate(asn1_public_cert, cn.to_string_lossy().into_owned());
+        }
+        if accepted {
             1
         } else {
             0
@@ -875,7 +888,13 @@ unsafe extern "C" fn dtls_pki_cn_callback<KTY: KeyType>(
     arg: *mut c_void,
 ) -> c_int {
     let session = CoapSession::from_raw(session);
-    let cn = CStr::from_ptr(cn);
+    // As this callback is always set for PKI contexts, it may also be called for certificates
+    // without a common name.
+    let cn = if cn.is_null() {
+        Default::default()
+    } else {
+        CStr::from_ptr(cn)
+    };
     let asn1_public_cert = std::slice::from_raw_parts(asn1_public_cert, asn1_length);
     let validated = validated == 1;
     let context = PkiRpkContext::from_raw(arg as *const RefCell<PkiRpkContextInner<KTY>>);
@@ -14,7 +14,7 @@ use crate::crypto::pki_rpk::{
     EngineKeyComponent, KeyComponent, KeyDef, KeyDefSealed, NonCertVerifying, PemFileKeyComponent,
     PemMemoryKeyComponent, Pkcs11KeyComponent, PkiRpkContext, PkiRpkContextBuilder, ServerPkiRpkCryptoContext,
 };
-use crate::crypto::ClientCryptoContext;
+use crate::crypto::{ClientCryptoContext, ServerCryptoContext};
 use crate::session::CoapSession;
 use libcoap_sys::{
     coap_const_char_ptr_t, coap_dtls_key_t, coap_dtls_key_t__bindgen_ty_1, coap_dtls_pki_t, coap_pki_define_t,
@@ -30,6 +30,9 @@ pub struct Pki {}
 impl KeyTypeSealed for Pki {
     fn set_key_type_defaults(ctx: &mut coap_dtls_pki_t) {
         ctx.is_rpk_not_cert = 0;
+        // Always install the CN callback in order to record the peer certificate (see
+        // CoapSessionCommon::peer_certificate_der()).
+        ctx.validate_cn_call_back = Some(pki_rpk::dtls_pki_cn_callback::<Pki>);
     }
 }
 
@@ -48,6 +51,12 @@ impl<'a> From<PkiRpkContext<'a, Pki>> for ClientCryptoContext<'a> {
     }
 }
 
+impl<'a> From<PkiRpkContext<'a, Pki>> for ServerCryptoContext<'a> {
+    fn from(value: PkiRpkContext<'a, Pki>) -> Self {
+        ServerCryptoContext::PkiRpk(ServerPkiRpkCryptoContext::Pki(value))
+    }
+}
+
 impl<'a> PkiRpkContextBuilder<'a, Pki, NonCertVerifying> {
     /// Enables PKI certificate verification of the peer's certificate when using the build
     /// encryption context.
@@ -88,8 +97,9 @@ impl<'a, V: CertVerificationMode> PkiRpkContextBuilder<'a, Pki, V> {
     ///
     /// # Implementation details (informative, not covered by semver guarantees)
     ///
-    /// Setting a `cn_validator` will set the `validate_cn_call_back` of the underlying
-    /// [`coap_dtls_pki_t`] to a wrapper function, which will then call the CN validator.
+    /// The `validate_cn_call_back` of the underlying [`coap_dtls_pki_t`] is always set to a
+    /// wrapper function (which also records the peer certificate), setting a `cn_validator` will
+    /// cause this wrapper function to call the CN validator.
     pub fn cn_validator(mut self, validator: impl PkiCnValidator + 'a) -> Self {
         self.ctx.cn_callback = Some(CnCallback::Pki(Box::new(validator)));
         self.ctx.raw_cfg.validate_cn_call_back = Some(pki_rpk::dtls_pki_cn_callback::<Pki>);
@@ -13,7 +13,7 @@ use crate::crypto::pki_rpk::{
     Asn1PrivateKeyType, CnCallback, KeyComponent, KeyDef, KeyDefSealed, NonCertVerifying, PemMemoryKeyComponent,
     Pkcs11KeyComponent, PkiRpkContext, PkiRpkContextBuilder, ServerPkiRpkCryptoContext,
 };
-use crate::crypto::ClientCryptoContext;
+use crate::crypto::{ClientCryptoContext, ServerCryptoContext};
 use crate::session::CoapSession;
 use libcoap_sys::{
     coap_const_char_ptr_t, coap_dtls_key_t, coap_dtls_key_t__bindgen_ty_1, coap_dtls_pki_t, coap_pki_define_t,
@@ -47,6 +47,12 @@ impl<'a> From<PkiRpkContext<'a, Rpk>> for ServerPkiRpkCryptoContext<'a> {
     }
 }
 
+impl<'a> From<PkiRpkContext<'a, Rpk>> for ServerCryptoContext<'a> {
+    fn from(value: PkiRpkContext<'a, Rpk>) -> Self {
+        ServerCryptoContext::PkiRpk(ServerPkiRpkCryptoContext::Rpk(value))
+    }
+}
+
 impl<'a> PkiRpkContextBuilder<'a, Rpk, NonCertVerifying> {
     /// Sets the raw public key validator for this encryption context.
     ///
@@ -8,7 +8,7 @@
  */
 
 use crate::crypto::psk::key::PskKey;
-use crate::error::ContextConfigurationError;
+use crate::error::{ContextConfigurationError, PskKeyProviderError};
 use crate::session::CoapServerSession;
 use libcoap_sys::{
     coap_bin_const_t, coap_context_set_psk2, coap_context_t, coap_dtls_spsk_info_t, coap_dtls_spsk_t, coap_session_t,
@@ -23,6 +23,7 @@ use std::hash::Hash;
 use std::os::raw::c_char;
 use std::ptr::NonNull;
 use std::rc::{Rc, Weak};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// Builder for a server-side DTLS encryption context for use with pre-shared keys (PSK).
 #[derive(Debug)]
@@ -171,9 +172,18 @@ pub struct ServerPskContext<'a> {
     inner: Rc<RefCell<ServerPskContextInner<'a>>>,
 }
 
+impl<'a> From<ServerPskContext<'a>> for crate::crypto::ServerCryptoContext<'a> {
+    fn from(value: ServerPskContext<'a>) -> Self {
+        crate::crypto::ServerCryptoContext::Psk(value)
+    }
+}
+
 impl ServerPskContext<'_> {
     /// Returns a pointer to the PSK key data to use for a given `identity` and `session`, or
-    /// [`std::ptr::null()`] if the provided identity hint and/or session are unacceptable.
+    /// [`std::ptr::null()`] if the provided identity and/or session are unacceptable.
+    ///
+    /// Returning a null pointer causes the DTLS library to abort the handshake with an alert
+    /// (typically `unknown_psk_identity`).
     ///
     /// The returned pointer is guaranteed to remain valid as long as the underlying
     /// [`ServerPskContextInner`] is not dropped.
@@ -190,7 +200,7 @@ impl ServerPskContext<'_> {
             .unwrap()
             .key_for_identity(identity, session);
 
-        if let Some(key) = key {
+        if let Ok(key) = key {
             let boxed_key_info = Box::new(key.into_raw_spsk_info());
             let boxed_key_ptr = Box::into_raw(boxed_key_info);
             // TODO remove these entries prematurely if the underlying session is removed (would
@@ -277,10 +287,60 @@ impl<'a> ServerPskContext<'a> {
 
 /// Trait for types that can provide pre-shared keys for a key identity given by a client to a
 /// server.
+///
+/// Implementations may also derive keys on the fly, e.g., from a master key and the identity
+/// provided by the client.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libcoap_rs::crypto::psk::{PskKey, ServerPskContextBuilder, ServerPskIdentityKeyProvider};
+/// use libcoap_rs::error::PskKeyProviderError;
+/// use libcoap_rs::session::CoapServerSession;
+///
+/// #[derive(Debug)]
+/// struct DerivingKeyProvider {
+///     master_key: [u8; 16],
+/// }
+///
+/// impl<'a> ServerPskIdentityKeyProvider<'a> for DerivingKeyProvider {
+///     fn key_for_identity(
+///         &self,
+///         identity: &[u8],
+///         _session: &CoapServerSession<'_>,
+///     ) -> Result<PskKey<'a>, PskKeyProviderError> {
+///         if !identity.starts_with(b"device-") {
+///             return Err(PskKeyProviderError::UnknownIdentity);
+///         }
+///         // Use a proper key derivation function (such as HKDF) in practice.
+///         let key: Vec<u8> = self
+///             .master_key
+///             .iter()
+///             .zip(identity.iter().cycle())
+///             .map(|(k, i)| k ^ i)
+///             .collect();
+///         Ok(PskKey::new(Some(identity), key))
+///     }
+/// }
+///
+/// let default_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+/// let psk_context = ServerPskContextBuilder::new(default_key)
+///     .id_key_provider(DerivingKeyProvider { master_key: *b"dtls_master_key_" })
+///     .build();
+/// ```
 pub trait ServerPskIdentityKeyProvider<'a>: Debug {
     /// Provides the key for the key `identity` given by the client that is connected through
-    /// `session`, or `None` if the identity unacceptable or no key is available.
-    fn key_for_identity(&self, identity: &[u8], session: &CoapServerSession<'_>) -> Option<PskKey<'a>>;
+    /// `session`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the identity is unacceptable or no key is available.
+    /// In this case, the DTLS handshake is aborted and the client is notified using a DTLS alert.
+    fn key_for_identity(
+        &self,
+        identity: &[u8],
+        session: &CoapServerSession<'_>,
+    ) -> Result<PskKey<'a>, PskKeyProviderError>;
 }
 
 impl<'a, T: Debug> ServerPskIdentityKeyProvider<'a> for T
@@ -288,13 +348,115 @@ where
     T: AsRef<[PskKey<'a>]>,
 {
     /// Returns the first key whose identity is equal to the one requested.
-    /// If not found, returns the first key that has no key ID set.
-    fn key_for_identity(&self, identity: &[u8], _session: &CoapServerSession<'_>) -> Option<PskKey<'a>> {
+    /// If not found, returns the first key that has no key ID set, or
+    /// [PskKeyProviderError::UnknownIdentity] if there is no such key.
+    fn key_for_identity(
+        &self,
+        identity: &[u8],
+        _session: &CoapServerSession<'_>,
+    ) -> Result<PskKey<'a>, PskKeyProviderError> {
         let keys = self.as_ref();
         keys.iter()
             .find(|k| k.identity().is_some_and(|kid| kid == identity))
             .or_else(|| keys.iter().find(|k| k.identity().is_none()))
             .cloned()
+            .ok_or(PskKeyProviderError::UnknownIdentity)
+    }
+}
+
+/// Thread-safe database of pre-shared keys that maps PSK identities to keys.
+///
+/// Clones of a database share the same keys, which allows updating the keys available to a
+/// server at runtime (e.g., from a management thread) after providing the database as an
+/// identity key provider using [ServerPskContextBuilder::id_key_provider()].
+/// Changes apply to all handshakes started afterwards, established sessions are not affected.
+///
+/// While the database itself is [Send] and [Sync], the created [ServerPskContext] is not, so the
+/// database has to be cloned before creating the server context on its thread.
+///
+/// # Examples
+///
+/// ```no_run
+/// use libcoap_rs::crypto::psk::{CoapPskDatabase, PskKey, ServerPskContextBuilder};
+///
+/// let database = CoapPskDatabase::new();
+/// database.insert("device-1", "dtls_test_key__1");
+///
+/// let server_database = database.clone();
+/// let server_thread = std::thread::spawn(move || {
+///     let default_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+///     let psk_context = ServerPskContextBuilder::new(default_key)
+///         .id_key_provider(server_database)
+///         .build();
+///     // Create the context and run the server here.
+/// });
+///
+/// // Keys may be added or removed while the server is running.
+/// database.insert("device-2", "dtls_test_key__2");
+/// database.remove(b"device-1");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CoapPskDatabase {
+    keys: Arc<RwLock<HashMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl CoapPskDatabase {
+    /// Creates a new empty PSK database.
+    pub fn new() -> CoapPskDatabase {
+        CoapPskDatabase::default()
+    }
+
+    /// Adds the given `key` for the PSK `identity`, replacing the previous key for this identity
+    /// (if any).
+    pub fn insert(&self, identity: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) {
+        self.write().insert(identity.into(), key.into());
+    }
+
+    /// Removes the key for the PSK `identity` (if any).
+    pub fn remove(&self, identity: &[u8]) {
+        self.write().remove(identity);
+    }
+
+    /// Returns the number of PSK identities stored in this database.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Returns whether this database contains no keys.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Returns whether this database contains a key for the PSK `identity`.
+    pub fn contains_identity(&self, identity: &[u8]) -> bool {
+        self.read().contains_key(identity)
+    }
+
+    /// Acquires a read lock on the stored keys.
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<Vec<u8>, Vec<u8>>> {
+        // Modifications of the map cannot panic halfway through, so the map is always in a
+        // consistent state even if a thread panicked while holding the lock.
+        self.keys.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquires a write lock on the stored keys.
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<Vec<u8>, Vec<u8>>> {
+        self.keys.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<'a> ServerPskIdentityKeyProvider<'a> for CoapPskDatabase {
+    /// Returns the key stored for the given identity, or [PskKeyProviderError::UnknownIdentity]
+    /// if there is none.
+    fn key_for_identity(
+        &self,
+        identity: &[u8],
+        _session: &CoapServerSession<'_>,
+    ) -> Result<PskKey<'a>, PskKeyProviderError> {
+        self.read()
+            .get(identity)
+            .map(|key| PskKey::new(Some(identity), key.as_slice()))
+            .ok_or(PskKeyProviderError::UnknownIdentity)
     }
 }
 
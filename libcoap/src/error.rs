@@ -10,12 +10,16 @@
 //! Error types
 
 use std::ffi::NulError;
+use std::net::IpAddr;
 use std::string::FromUtf8Error;
 use std::sync::PoisonError;
+use std::time::Duration;
 
 use thiserror::Error;
 
-use crate::protocol::{CoapMessageType, CoapOptionType};
+#[cfg(feature = "senml")]
+use crate::protocol::ContentFormat;
+use crate::protocol::{CoapMessageCode, CoapMessageType, CoapOptionType, CoapResponseCode};
 
 #[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum EndpointCreationError {
@@ -33,6 +37,32 @@ pub enum ContextConfigurationError {
         "CoAP context configuration error: attempted to set encryption context while one has already been configured for this encryption variant"
     )]
     CryptoContextAlreadySet,
+    /// The given multicast group could not be joined.
+    #[error("CoAP context configuration error: unable to join multicast group {}", .0)]
+    MulticastGroupJoin(IpAddr),
+    /// The given OSCORE security context was rejected by libcoap.
+    #[error("CoAP context configuration error: invalid OSCORE security context")]
+    InvalidOscoreContext,
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OutstandingSessionsError {
+    /// The given number of sessions bound to the context were still in use when it was closed.
+    #[error("CoAP context error: {} session(s) bound to the context are still in use", .0)]
+    SessionsInUse(usize),
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ContextCreationError {
+    /// The newly created context could not be configured.
+    #[error("CoAP context creation error: unable to configure context")]
+    Configuration(#[from] ContextConfigurationError),
+    /// An endpoint for the newly created context could not be created.
+    #[error("CoAP context creation error: unable to create endpoint")]
+    Endpoint(#[from] EndpointCreationError),
+    /// A session for the newly created context could not be created.
+    #[error("CoAP context creation error: unable to create session")]
+    Session(#[from] SessionCreationError),
 }
 
 #[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
@@ -68,6 +98,16 @@ pub enum SessionCreationError {
     /// Unknown error inside of libcoap
     #[error("CoAP session creation error: unknown error in call to libcoap")]
     Unknown,
+    /// The given OSCORE security context was rejected by libcoap.
+    #[error("CoAP session creation error: invalid OSCORE security context")]
+    InvalidOscoreContext,
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AddressConversionError {
+    /// The address family of the raw address is neither `AF_INET` nor `AF_INET6`.
+    #[error("CoAP address conversion error: unsupported address family {}", .0)]
+    UnsupportedFamily(u16),
 }
 
 #[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
@@ -93,6 +133,16 @@ impl<T> From<PoisonError<T>> for RngError {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum TokenError {
+    /// Token is longer than the maximum token length of 8 bytes.
+    #[error("CoAP token error: token of length {} exceeds maximum length", .0)]
+    TooLong(usize),
+    /// Random token could not be generated.
+    #[error("CoAP token error: unable to generate random token")]
+    Rng(#[from] RngError),
+}
+
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
 pub enum OptionValueError {
     /// Provided value for option is too short.
@@ -110,6 +160,14 @@ pub enum OptionValueError {
     /// Option has an illegal value.
     #[error("CoAP option has invalid value")]
     IllegalValue,
+    /// Option value contains a character that is not allowed for this option type (e.g. a
+    /// Uri-Host value that is neither a valid hostname nor an IP address).
+    #[error("CoAP option has invalid value: invalid character")]
+    InvalidCharacter,
+    /// The provided option number belongs to a known option type, which should be constructed
+    /// using the corresponding typed [CoapOption](crate::message::CoapOption) variant instead.
+    #[error("CoAP option has invalid value: option number belongs to known option {:?}, use the typed variant instead", .0)]
+    KnownOptionNumber(CoapOptionType),
 }
 
 #[derive(Error, Debug, Clone, Eq, PartialEq)]
@@ -137,12 +195,23 @@ pub enum MessageConversionError {
     /// Non-repeatable option was repeated.
     #[error("CoAP message conversion error: non-repeatable option of type {:?} repeated", .0)]
     NonRepeatableOptionRepeated(CoapOptionType),
+    /// Multiple non-repeatable options were repeated (only returned for messages with strict
+    /// parsing enabled).
+    #[error("CoAP message conversion error: non-repeatable options of types {:?} repeated", .0)]
+    NonRepeatableOptionsRepeated(Vec<CoapOptionType>),
     /// Provided URI has invalid scheme.
     #[error("CoAP message conversion error: provided uri does not have scheme valid for CoAP")]
     NotACoapUri(UriParsingError),
     /// Invalid message code.
     #[error("CoAP message conversion error: invalid message code")]
     InvalidMessageCode(#[from] MessageCodeError),
+    /// Invalid message type for the message code.
+    #[error("CoAP message conversion error: invalid message type")]
+    InvalidMessageType(#[from] MessageTypeError),
+    /// The message type may not be combined with the message code (e.g., an acknowledgement
+    /// carrying a request, see [RFC 7252, Section 4](https://datatracker.ietf.org/doc/html/rfc7252#section-4)).
+    #[error("CoAP message conversion error: message type {:?} cannot be combined with code {:?}", .0, .1)]
+    InvalidTypeCodeCombination(CoapMessageType, CoapMessageCode),
     /// A message with code 0.00 (Empty) contains data.
     #[error("CoAP message conversion error: empty message contains data")]
     DataInEmptyMessage,
@@ -152,6 +221,12 @@ pub enum MessageConversionError {
     /// Message has no ID.
     #[error("CoAP message conversion error: message id missing")]
     MissingMessageId,
+    /// Message token is longer than the 8 bytes that can be encoded in the message header.
+    #[error("CoAP message conversion error: token too long")]
+    TokenTooLong,
+    /// The provided bytes could not be parsed as a CoAP message.
+    #[error("CoAP message conversion error: malformed message")]
+    MalformedMessage,
     /// Two (or more) options were combined which must not be combined (e.g., Proxy-Scheme and
     /// Proxy-URI).
     #[error("CoAP message conversion error: options {:?} and {:?} cannot be combined", .0, .1)]
@@ -160,6 +235,10 @@ pub enum MessageConversionError {
     /// was not recognized).
     #[error("CoAP option identified as critical but not recognized")]
     CriticalOptionUnrecognized,
+    /// Message contains more options than allowed (see
+    /// [CoapMessage::set_max_options()](crate::message::CoapMessage::set_max_options())).
+    #[error("CoAP message conversion error: message contains too many options ({})", .0)]
+    TooManyOptions(usize),
     /// Unknown error inside of libcoap.
     #[error("unknown CoAP message conversion error")]
     Unknown,
@@ -181,9 +260,229 @@ pub enum MessageCodeError {
     NotAResponseCode,
 }
 
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CodeParseError {
+    /// The provided string is not a message code in dotted "c.dd" notation.
+    #[error("CoAP message code parsing error: not in dotted notation")]
+    InvalidFormat,
+    /// The provided string is in dotted notation, but does not represent a known code of the
+    /// requested kind.
+    #[error("CoAP message code parsing error: unknown message code")]
+    UnknownCode,
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ObserveError {
+    /// The value does not fit into the 24 bits of the Observe option.
+    #[error("CoAP observe error: value out of range")]
+    OutOfRange,
+    /// Requests may only use the Observe option to register (0) or deregister (1) as an observer.
+    #[error("CoAP observe error: invalid value {} for request (must be 0 or 1)", .0)]
+    InvalidRequestValue(u32),
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HopLimitError {
+    /// The Hop-Limit of the request would reach zero, so the request must not be forwarded and
+    /// should be answered with `5.08 Hop Limit Reached` instead.
+    #[error("CoAP hop limit error: hop limit exhausted")]
+    Exhausted,
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MessageTypeParseError {
+    /// The provided string is not the abbreviation of a known message type (CON, NON, ACK or RST).
+    #[error("CoAP message type parsing error: unknown message type")]
+    UnknownType,
+}
+
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum PathMatchError {
+    /// The request path does not match the template, i.e., the number of segments differs or a
+    /// literal segment of the template is not equal to the corresponding path segment.
+    #[error("CoAP path match error: request path does not match template")]
+    NoMatch,
+    /// The template is malformed (e.g., a parameter has no name or its braces are unbalanced).
+    #[error("CoAP path match error: invalid template segment {:?}", .0)]
+    InvalidTemplate(String),
+}
+
 #[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum MessageTypeError {
     /// Message type cannot be used for this message code (e.g., ACK for request).
     #[error("message type {:?} cannot be used for this message code", .0)]
     InvalidForMessageCode(CoapMessageType),
+    /// A message of this type cannot be acknowledged or reset (e.g., an ACK for a
+    /// non-confirmable message).
+    #[error("message of type {:?} cannot be answered with this message type", .0)]
+    InvalidForReplyTo(CoapMessageType),
+}
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    /// The session has already been closed (e.g. using [CoapServerSession::close()](crate::session::CoapServerSession::close())).
+    #[error("CoAP session error: session has already been closed")]
+    Closed,
+    /// The message that should be sent could not be converted into a raw message.
+    #[error("CoAP session error: unable to convert message to send")]
+    MessageConversion(#[from] MessageConversionError),
+    /// libcoap was unable to send the message (e.g., because the session is not established).
+    #[error("CoAP session error: unable to send message")]
+    SendFailed,
+    /// Sending the non-confirmable message would exceed the probing rate of the session (see
+    /// [CoapSessionCommon::set_probing_rate()](crate::session::CoapSessionCommon::set_probing_rate())).
+    /// The message may be sent after the contained duration has passed.
+    #[error("CoAP session error: probing rate exceeded, retry after {:?}", .0)]
+    ProbingRateExceeded(Duration),
+    /// The context the session belongs to has already been dropped (see
+    /// [CoapSessionCommon::is_poisoned()](crate::session::CoapSessionCommon::is_poisoned())).
+    #[error("CoAP session error: the context of this session has already been dropped")]
+    ContextDropped,
+    /// The send queue of the session is full, so the request has to be queued again later (see
+    /// [CoapClientSession::try_send()](crate::session::CoapClientSession::try_send())).
+    #[error("CoAP session error: send queue is full")]
+    WouldBlock,
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BorrowError {
+    /// The value is no longer usable because the context it belongs to has been dropped.
+    #[error("CoAP borrow error: the context this value belongs to has already been dropped")]
+    Poisoned,
+    /// The value is already borrowed in a way that does not allow this borrow.
+    #[error("CoAP borrow error: value is already borrowed")]
+    AlreadyBorrowed,
+}
+
+#[derive(Error, Debug)]
+pub enum DownloadError {
+    /// The download was cancelled using the provided cancellation flag.
+    #[error("CoAP download error: download was cancelled")]
+    Cancelled,
+    /// The download did not complete within the provided timeout.
+    #[error("CoAP download error: download timed out")]
+    Timeout,
+    /// The peer responded with a response code that does not indicate success.
+    #[error("CoAP download error: peer responded with error code {:?}", .0)]
+    ErrorResponse(CoapResponseCode),
+    /// The request failed, e.g., because it was not acknowledged by the peer or the peer is
+    /// unreachable (see
+    /// [CoapSessionCommon::take_request_error()](crate::session::CoapSessionCommon::take_request_error())).
+    #[error("CoAP download error: request failed")]
+    Request(#[from] RequestError),
+    /// The request could not be sent.
+    #[error("CoAP download error: unable to send request")]
+    Session(#[from] SessionError),
+    /// An error occurred while performing IO operations.
+    #[error("CoAP download error: error while performing IO")]
+    IoProcess(#[from] IoProcessError),
+}
+
+#[derive(Error, Debug)]
+pub enum RequestError {
+    /// No response was received within the provided timeout (while the request may still have
+    /// been retransmitted).
+    #[error("CoAP request error: no response received before timeout")]
+    Timeout,
+    /// The confirmable request was not acknowledged by the peer even after the maximum number of
+    /// retransmissions (see
+    /// [CoapSessionCommon::set_max_retransmit()](crate::session::CoapSessionCommon::set_max_retransmit())),
+    /// i.e., the peer is most likely unreachable.
+    #[error("CoAP request error: request not acknowledged after maximum number of retransmissions")]
+    RetransmitExhausted,
+    /// An ICMP error (e.g., "port unreachable") indicated that the peer can not be reached, so the
+    /// request failed before the maximum number of retransmissions was reached.
+    #[error("CoAP request error: peer unreachable")]
+    PeerUnreachable,
+    /// The request could not be sent.
+    #[error("CoAP request error: unable to send request")]
+    Session(#[from] SessionError),
+    /// An error occurred while performing IO operations.
+    #[error("CoAP request error: error while performing IO")]
+    IoProcess(#[from] IoProcessError),
+}
+
+#[cfg(feature = "pubsub")]
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PubSubError {
+    /// The given topic handle does not refer to an existing topic of this broker.
+    #[error("CoAP pubsub error: unknown topic")]
+    UnknownTopic,
+    /// A topic with the given name already exists.
+    #[error("CoAP pubsub error: topic already exists")]
+    TopicExists,
+    /// The given topic name is empty, too long or contains characters other than unreserved URI
+    /// characters.
+    #[error("CoAP pubsub error: invalid topic name")]
+    InvalidTopicName,
+}
+
+#[derive(Error, Debug)]
+pub enum FileResourceError {
+    /// The provided URI path or file path contains a `..` (or `.`) component.
+    #[error("CoAP file resource error: path must not contain parent directory components")]
+    PathTraversal,
+    /// The provided path does not refer to a regular file (or a directory, respectively).
+    #[error("CoAP file resource error: path does not refer to a file of the expected type")]
+    InvalidFileType,
+    /// A non-UTF-8 file name was found that cannot be represented as a URI path.
+    #[error("CoAP file resource error: file name is not valid UTF-8")]
+    InvalidFileName,
+    /// The file or directory could not be accessed.
+    #[error("CoAP file resource error: unable to access file")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PskKeyProviderError {
+    /// No key is known for the PSK identity provided by the peer.
+    #[error("CoAP PSK key provider error: unknown PSK identity")]
+    UnknownIdentity,
+    /// The PSK identity is known, but the peer is not allowed to use it.
+    #[error("CoAP PSK key provider error: PSK identity may not be used by this peer")]
+    IdentityNotPermitted,
+    /// The key for the PSK identity could not be derived.
+    #[error("CoAP PSK key provider error: unable to derive key for PSK identity")]
+    KeyDerivationFailed,
+}
+
+#[cfg(feature = "http")]
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    /// The CoAP response code has no HTTP equivalent.
+    #[error("CoAP proxy error: response code {:?} has no HTTP equivalent", .0)]
+    UnsupportedResponseCode(CoapResponseCode),
+    /// The CoAP response does not have a response code (e.g., because it was changed using
+    /// [CoapMessageCommon::as_message_mut()](crate::message::CoapMessageCommon::as_message_mut())).
+    #[error("CoAP proxy error: message code {:?} is not a response code", .0)]
+    InvalidMessageCode(CoapMessageCode),
+    /// The HTTP request method has no CoAP equivalent.
+    #[error("CoAP proxy error: HTTP method {} has no CoAP equivalent", .0)]
+    UnsupportedMethod(::http::Method),
+    /// The media type of the HTTP request body has no CoAP content format equivalent.
+    #[error("CoAP proxy error: media type {} has no CoAP content format equivalent", .0)]
+    UnsupportedMediaType(String),
+    /// The value of the given HTTP header could not be converted.
+    #[error("CoAP proxy error: invalid value for HTTP header {}", .0)]
+    InvalidHeader(::http::HeaderName),
+    /// The target URI of the HTTP request could not be converted into a CoAP URI.
+    #[error("CoAP proxy error: unable to convert request URI")]
+    InvalidUri(#[from] UriParsingError),
+    /// The HTTP message could not be constructed.
+    #[error("CoAP proxy error: unable to construct HTTP message")]
+    Http(#[from] ::http::Error),
+}
+
+#[cfg(feature = "senml")]
+#[derive(Error, Debug, Clone, Eq, PartialEq)]
+pub enum SenmlError {
+    /// The message does not have a SenML content format (i.e., neither JSON nor CBOR).
+    #[error("CoAP SenML error: content format {:?} is not a supported SenML format", .0)]
+    UnsupportedContentFormat(Option<ContentFormat>),
+    /// The payload is not a valid SenML pack.
+    #[error("CoAP SenML error: malformed pack: {}", .0)]
+    Malformed(String),
+    /// A record has neither a name nor a base name applying to it.
+    #[error("CoAP SenML error: record without a name")]
+    MissingName,
 }
@@ -9,7 +9,7 @@
 
 //! Event handling-related code
 
-use std::fmt::Debug;
+use std::fmt::{Debug, Formatter};
 
 use libcoap_sys::{coap_event_t, coap_session_get_context, coap_session_t};
 use libcoap_sys::{coap_session_get_type, coap_session_type_t};
@@ -26,6 +26,67 @@ use crate::session::CoapServerSession;
 ///
 /// This is the equivalent to the [libcoap `coap_event_handler_t` type](https://libcoap.net/doc/reference/develop/group__events.html#ga5d57fba7df54eae6f8cb3a47a4cb3569).
 pub trait CoapEventHandler: Debug {
+    /// Handle an arbitrary event.
+    ///
+    /// The default implementation of this function dispatches the event to the more specific
+    /// `handle_*` functions of this trait.
+    /// Override this function if you want to handle all events in one place.
+    ///
+    /// # Panics
+    ///
+    /// The default implementation panics if a [CoapEvent::ServerSessionNew] or
+    /// [CoapEvent::ServerSessionDel] event is provided for a session that is not a server-side
+    /// session.
+    fn handle_event(&mut self, event: CoapEvent, session: &mut CoapSession) {
+        match event {
+            CoapEvent::DtlsClosed => self.handle_dtls_closed(session),
+            CoapEvent::DtlsConnected => self.handle_dtls_connected(session),
+            CoapEvent::DtlsRenegotiate => self.handle_dtls_renegotiate(session),
+            CoapEvent::DtlsError => self.handle_dtls_error(session),
+            CoapEvent::TcpConnected => self.handle_tcp_connected(session),
+            CoapEvent::TcpClosed => self.handle_tcp_closed(session),
+            CoapEvent::TcpFailed => self.handle_tcp_failed(session),
+            CoapEvent::SessionConnected => self.handle_session_connected(session),
+            CoapEvent::SessionClosed => self.handle_session_closed(session),
+            CoapEvent::SessionFailed => self.handle_session_failed(session),
+            CoapEvent::PartialBlock => self.handle_partial_block(session),
+            CoapEvent::XmitBlockFail => self.handle_xmit_block_fail(session),
+            CoapEvent::ServerSessionNew => {
+                if let CoapSession::Server(server_session) = session {
+                    self.handle_server_session_new(server_session)
+                } else {
+                    panic!("server-side session event fired for non-server-side session");
+                }
+            },
+            CoapEvent::ServerSessionDel => {
+                if let CoapSession::Server(server_session) = session {
+                    self.handle_server_session_del(server_session)
+                } else {
+                    panic!("server-side session event fired for non-server-side session");
+                }
+            },
+            CoapEvent::ServerSessionAddressChanged => {
+                if let CoapSession::Server(server_session) = session {
+                    self.handle_server_session_address_changed(server_session)
+                } else {
+                    panic!("server-side session event fired for non-server-side session");
+                }
+            },
+            CoapEvent::BadPacket => self.handle_bad_packet(session),
+            CoapEvent::MsgRetransmitted => self.handle_msg_retransmitted(session),
+            CoapEvent::OscoreDecryptionFailure => self.handle_oscore_decryption_failure(session),
+            CoapEvent::OscoreNotEnabled => self.handle_oscore_not_enabled(session),
+            CoapEvent::OscoreNoProtectedPayload => self.handle_oscore_no_protected_payload(session),
+            CoapEvent::OscoreNoSecurity => self.handle_oscore_no_security(session),
+            CoapEvent::OscoreInternalError => self.handle_oscore_internal_error(session),
+            CoapEvent::OscoreDecodeError => self.handle_oscore_decode_error(session),
+            CoapEvent::WsPacketSize => self.handle_ws_packet_size(session),
+            CoapEvent::WsConnected => self.handle_ws_connected(session),
+            CoapEvent::WsClosed => self.handle_ws_closed(session),
+            CoapEvent::KeepaliveFailure => self.handle_keepalive_failure(session),
+        }
+    }
+
     /// Handle a DTLS connected event.
     ///
     /// This event is triggered when a DTLS session switches to the connected state.
@@ -111,6 +172,15 @@ pub trait CoapEventHandler: Debug {
     #[allow(unused_variables)]
     fn handle_server_session_del(&mut self, session: &mut CoapServerSession) {}
 
+    /// Handle a server-side session address change event.
+    ///
+    /// This event is triggered if the remote address of a server-side session has changed (see
+    /// [CoapServerSession::address_migrated()]), which may happen for DTLS sessions using a
+    /// Connection ID. The new address is available using
+    /// [addr_remote()](crate::session::CoapSessionCommon::addr_remote()).
+    #[allow(unused_variables)]
+    fn handle_server_session_address_changed(&mut self, session: &mut CoapServerSession) {}
+
     /// Handle the receival of a badly formatted packet.
     ///
     /// Note that this only refers to packets that can't be parsed by libcoap, i.e. valid packets
@@ -166,6 +236,132 @@ pub trait CoapEventHandler: Debug {
     fn handle_keepalive_failure(&mut self, session: &mut CoapSession) {}
 }
 
+/// Events that may be reported by libcoap for a session.
+///
+/// This is the equivalent to the [libcoap `coap_event_t` type](https://libcoap.net/doc/reference/develop/group__events.html).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum CoapEvent {
+    /// A DTLS session was closed.
+    DtlsClosed,
+    /// A DTLS session switched to the connected state (i.e., the handshake completed successfully).
+    DtlsConnected,
+    /// A DTLS renegotiation occurred.
+    DtlsRenegotiate,
+    /// A DTLS error occurred (e.g., the handshake failed).
+    DtlsError,
+    /// A new TCP connection was established.
+    TcpConnected,
+    /// A TCP connection was closed.
+    TcpClosed,
+    /// A TCP connection failed.
+    TcpFailed,
+    /// A session was established after a CSM exchange (only for reliable protocols).
+    SessionConnected,
+    /// A session was closed (only for reliable protocols).
+    SessionClosed,
+    /// A session failed, e.g. because the CSM exchange failed (only for reliable protocols).
+    SessionFailed,
+    /// A message was only partially received.
+    PartialBlock,
+    /// A block could not be transmitted.
+    XmitBlockFail,
+    /// A new server-side session was created.
+    ServerSessionNew,
+    /// A server-side session was deleted.
+    ServerSessionDel,
+    /// The remote address of a server-side session has changed.
+    ///
+    /// Unlike the other events, this event is not reported by libcoap itself, but generated by
+    /// this wrapper whenever it detects the change (see [CoapServerSession::address_migrated()]).
+    ServerSessionAddressChanged,
+    /// A badly formatted packet was received.
+    BadPacket,
+    /// A message was retransmitted.
+    MsgRetransmitted,
+    /// OSCORE decryption failed.
+    OscoreDecryptionFailure,
+    /// OSCORE is not enabled.
+    OscoreNotEnabled,
+    /// No protected payload was provided for OSCORE.
+    OscoreNoProtectedPayload,
+    /// No OSCORE security definition was found.
+    OscoreNoSecurity,
+    /// An internal OSCORE error occurred.
+    OscoreInternalError,
+    /// OSCORE options could not be decoded.
+    OscoreDecodeError,
+    /// A WebSocket packet was oversized.
+    WsPacketSize,
+    /// A WebSocket layer is up.
+    WsConnected,
+    /// A WebSocket layer was closed.
+    WsClosed,
+    /// A keepalive packet was not responded to.
+    KeepaliveFailure,
+}
+
+impl CoapEvent {
+    /// Converts the given raw event into a [CoapEvent], returning `None` if the event type is not
+    /// known to this wrapper.
+    pub(crate) fn from_raw(raw_event: coap_event_t) -> Option<CoapEvent> {
+        match raw_event {
+            coap_event_t::COAP_EVENT_DTLS_CLOSED => Some(CoapEvent::DtlsClosed),
+            coap_event_t::COAP_EVENT_DTLS_CONNECTED => Some(CoapEvent::DtlsConnected),
+            coap_event_t::COAP_EVENT_DTLS_RENEGOTIATE => Some(CoapEvent::DtlsRenegotiate),
+            coap_event_t::COAP_EVENT_DTLS_ERROR => Some(CoapEvent::DtlsError),
+            coap_event_t::COAP_EVENT_TCP_CONNECTED => Some(CoapEvent::TcpConnected),
+            coap_event_t::COAP_EVENT_TCP_CLOSED => Some(CoapEvent::TcpClosed),
+            coap_event_t::COAP_EVENT_TCP_FAILED => Some(CoapEvent::TcpFailed),
+            coap_event_t::COAP_EVENT_SESSION_CONNECTED => Some(CoapEvent::SessionConnected),
+            coap_event_t::COAP_EVENT_SESSION_CLOSED => Some(CoapEvent::SessionClosed),
+            coap_event_t::COAP_EVENT_SESSION_FAILED => Some(CoapEvent::SessionFailed),
+            coap_event_t::COAP_EVENT_PARTIAL_BLOCK => Some(CoapEvent::PartialBlock),
+            coap_event_t::COAP_EVENT_XMIT_BLOCK_FAIL => Some(CoapEvent::XmitBlockFail),
+            coap_event_t::COAP_EVENT_SERVER_SESSION_NEW => Some(CoapEvent::ServerSessionNew),
+            coap_event_t::COAP_EVENT_SERVER_SESSION_DEL => Some(CoapEvent::ServerSessionDel),
+            coap_event_t::COAP_EVENT_BAD_PACKET => Some(CoapEvent::BadPacket),
+            coap_event_t::COAP_EVENT_MSG_RETRANSMITTED => Some(CoapEvent::MsgRetransmitted),
+            coap_event_t::COAP_EVENT_OSCORE_DECRYPTION_FAILURE => Some(CoapEvent::OscoreDecryptionFailure),
+            coap_event_t::COAP_EVENT_OSCORE_NOT_ENABLED => Some(CoapEvent::OscoreNotEnabled),
+            coap_event_t::COAP_EVENT_OSCORE_NO_PROTECTED_PAYLOAD => Some(CoapEvent::OscoreNoProtectedPayload),
+            coap_event_t::COAP_EVENT_OSCORE_NO_SECURITY => Some(CoapEvent::OscoreNoSecurity),
+            coap_event_t::COAP_EVENT_OSCORE_INTERNAL_ERROR => Some(CoapEvent::OscoreInternalError),
+            coap_event_t::COAP_EVENT_OSCORE_DECODE_ERROR => Some(CoapEvent::OscoreDecodeError),
+            coap_event_t::COAP_EVENT_WS_PACKET_SIZE => Some(CoapEvent::WsPacketSize),
+            coap_event_t::COAP_EVENT_WS_CONNECTED => Some(CoapEvent::WsConnected),
+            coap_event_t::COAP_EVENT_WS_CLOSED => Some(CoapEvent::WsClosed),
+            coap_event_t::COAP_EVENT_KEEPALIVE_FAILURE => Some(CoapEvent::KeepaliveFailure),
+            _ => None,
+        }
+    }
+}
+
+/// Event handler that forwards all events to a closure.
+///
+/// Created by [CoapContext::set_event_callback()].
+pub(crate) struct CoapEventCallback<F: FnMut(CoapEvent, &mut CoapSession)> {
+    callback: F,
+}
+
+impl<F: FnMut(CoapEvent, &mut CoapSession)> CoapEventCallback<F> {
+    pub(crate) fn new(callback: F) -> Self {
+        CoapEventCallback { callback }
+    }
+}
+
+impl<F: FnMut(CoapEvent, &mut CoapSession)> Debug for CoapEventCallback<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoapEventCallback").finish_non_exhaustive()
+    }
+}
+
+impl<F: FnMut(CoapEvent, &mut CoapSession)> CoapEventHandler for CoapEventCallback<F> {
+    fn handle_event(&mut self, event: CoapEvent, session: &mut CoapSession) {
+        (self.callback)(event, session)
+    }
+}
+
 // This should be fine as we don't provide this type to an FFI function, we only read from it.
 #[allow(improper_ctypes_definitions)]
 pub(crate) unsafe extern "C" fn event_handler_callback(raw_session: *mut coap_session_t, event: coap_event_t) -> i32 {
@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * file_resource.rs - Resources serving static files for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Resources that serve the contents of files, see [CoapContext::add_file_resource()] and
+//! [CoapContext::add_resource_directory()].
+
+use std::{
+    fs::{File, Metadata},
+    io::{Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+#[cfg(doc)]
+use crate::context::CoapContext;
+use crate::{
+    error::FileResourceError,
+    message::{response::payload_etag, CoapMessageCommon, CoapOption, CoapRequest, CoapResponse},
+    protocol::{CoapContentFormat, CoapRequestCode, CoapResponseCode, ContentFormat, ETag, MaxAge},
+    resource::{CoapRequestHandler, CoapResource},
+    session::{CoapServerSession, CoapSessionCommon},
+};
+
+/// Max-Age (in seconds) sent in responses of file resources.
+const FILE_RESOURCE_MAX_AGE: MaxAge = 60;
+
+/// Number of bytes of a PDU that are reserved for the message header, token and options when
+/// determining the block size for block-wise transfers of files.
+const BLOCK_OVERHEAD: usize = 64;
+
+/// Largest block size exponent that may be used for Block2 transfers (SZX = 7 is reserved for
+/// BERT, see [RFC 8323, Section 6](https://datatracker.ietf.org/doc/html/rfc8323#section-6)).
+const MAX_BLOCK_SZX: u32 = 6;
+
+/// Largest block number that can be encoded in a Block2 option.
+const MAX_BLOCK_NUM: usize = (1 << 20) - 1;
+
+/// User data of a resource serving a file.
+#[derive(Debug)]
+pub(crate) struct FileResourceData {
+    path: PathBuf,
+    content_format: ContentFormat,
+}
+
+/// Returns an error if the given path contains a parent directory (`..`) component.
+pub(crate) fn check_file_path(path: &Path) -> Result<(), FileResourceError> {
+    if path.components().any(|v| v == Component::ParentDir) {
+        return Err(FileResourceError::PathTraversal);
+    }
+    Ok(())
+}
+
+/// Returns an error if the given URI path contains a `.` or `..` segment.
+pub(crate) fn check_uri_path(uri_path: &str) -> Result<(), FileResourceError> {
+    if uri_path.split('/').any(|v| v == "." || v == "..") {
+        return Err(FileResourceError::PathTraversal);
+    }
+    Ok(())
+}
+
+/// Recursively collects all regular files in `dir`, along with the URI paths they should be served
+/// at (below `uri_prefix`).
+///
+/// Symbolic links are not followed.
+pub(crate) fn collect_files(
+    dir: &Path,
+    uri_prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<(), FileResourceError> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|_| FileResourceError::InvalidFileName)?;
+        let uri_path = if uri_prefix.is_empty() {
+            name
+        } else {
+            format!("{uri_prefix}/{name}")
+        };
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), &uri_path, files)?;
+        } else if file_type.is_file() {
+            files.push((uri_path, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+/// Determines the content format of a file based on its extension.
+fn content_format_for_path(path: &Path) -> ContentFormat {
+    let content_format = match path.extension().and_then(|v| v.to_str()) {
+        Some("cbor") => CoapContentFormat::Cbor,
+        Some("json") => CoapContentFormat::Json,
+        Some("txt") => CoapContentFormat::TextPlain,
+        _ => CoapContentFormat::OctetStream,
+    };
+    content_format as ContentFormat
+}
+
+/// Creates a resource with the given `uri_path` that serves the file at `path`.
+pub(crate) fn file_resource(uri_path: &str, path: PathBuf) -> CoapResource<FileResourceData> {
+    let resource = CoapResource::new(
+        uri_path,
+        FileResourceData {
+            content_format: content_format_for_path(&path),
+            path,
+        },
+        false,
+    );
+    resource.set_method_handler(CoapRequestCode::Get, Some(CoapRequestHandler::new(handle_file_get)));
+    resource
+}
+
+/// Calculates an ETag for a file based on its modification time and size.
+///
+/// Uses the same stable hash function as [CoapResponse::compute_and_set_etag()], so the ETag of an
+/// unchanged file stays the same across program runs (e.g., after a server restart).
+fn file_etag(metadata: &Metadata) -> ETag {
+    let mut input = Vec::with_capacity(20);
+    if let Some(modified) = metadata.modified().ok().and_then(|v| v.duration_since(UNIX_EPOCH).ok()) {
+        input.extend_from_slice(&modified.as_secs().to_be_bytes());
+        input.extend_from_slice(&modified.subsec_nanos().to_be_bytes());
+    }
+    input.extend_from_slice(&metadata.len().to_be_bytes());
+    payload_etag(&input)
+}
+
+/// Opens the file at `path` and returns it along with its metadata.
+fn open_file(path: &Path) -> std::io::Result<(File, Metadata)> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    Ok((file, metadata))
+}
+
+/// Reads up to `len` bytes starting at `offset` from `file` (less if the file ends before).
+fn read_file_range(file: &mut File, offset: usize, len: usize) -> std::io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let mut content = Vec::with_capacity(len);
+    file.take(len as u64).read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Determines the block size exponent (SZX) for Block2 transfers on the given session, which is
+/// limited by the maximum PDU size of the session and the block size requested by the client.
+fn block_szx(session: &CoapServerSession, requested_szx: Option<u32>) -> u32 {
+    let available = session.max_pdu_size().saturating_sub(BLOCK_OVERHEAD);
+    let mut szx = MAX_BLOCK_SZX;
+    while szx > 0 && (16 << szx) > available {
+        szx -= 1;
+    }
+    requested_szx.map_or(szx, |v| v.min(szx))
+}
+
+fn handle_file_get(
+    data: &mut FileResourceData,
+    session: &mut CoapServerSession,
+    request: &CoapRequest,
+    mut response: CoapResponse,
+) {
+    let Ok((mut file, metadata)) = open_file(&data.path) else {
+        session.respond(response, CoapResponseCode::NotFound);
+        return;
+    };
+    let file_size = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+
+    let requested_block = request.options_iter().find_map(|option| match option {
+        CoapOption::Block2(value) => Some(*value),
+        _ => None,
+    });
+    let szx = block_szx(session, requested_block.map(|v| v & 0x7));
+    let block_size = 16usize << szx;
+    // Only the requested block is read from the file, so that large files are not read completely
+    // for each block.
+    let content = if requested_block.is_some() || file_size > block_size {
+        // The client may request a larger block size than we use, so determine the offset based on
+        // the requested size first (see RFC 7959, Section 2.4).
+        let offset = match requested_block {
+            Some(value) => (value >> 4) as usize * (16 << (value & 0x7).min(MAX_BLOCK_SZX)),
+            None => 0,
+        };
+        let num = offset / block_size;
+        if (offset > 0 && offset >= file_size) || num > MAX_BLOCK_NUM {
+            session.respond(response, CoapResponseCode::BadOption);
+            return;
+        }
+        let start = num * block_size;
+        let end = (start + block_size).min(file_size);
+        let more = end < file_size;
+        response.add_option(CoapOption::Block2(((num as u32) << 4) | (u32::from(more) << 3) | szx));
+        if num == 0 {
+            // The file size always fits into the Size2 option, as larger files would exceed the
+            // maximum block number.
+            let _ = response.set_total_size(file_size);
+        }
+        read_file_range(&mut file, start, end - start)
+    } else {
+        read_file_range(&mut file, 0, file_size)
+    };
+    let Ok(content) = content else {
+        session.respond(response, CoapResponseCode::NotFound);
+        return;
+    };
+    response.set_data(Some(content));
+    response.set_content_format(Some(data.content_format));
+    response.set_etag(Some(file_etag(&metadata)));
+    response.set_max_age(Some(FILE_RESOURCE_MAX_AGE));
+    session.respond(response, CoapResponseCode::Content);
+}
@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * hex.rs - Hexadecimal encoding of byte strings for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+use std::fmt::Write;
+
+/// Encodes the given bytes as a lowercase hexadecimal string.
+pub(crate) fn encode(value: &[u8]) -> String {
+    let mut encoded = String::with_capacity(value.len() * 2);
+    for byte in value {
+        // Writing into a String cannot fail.
+        let _ = write!(encoded, "{byte:02x}");
+    }
+    encoded
+}
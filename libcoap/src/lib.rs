@@ -22,7 +22,7 @@
 //!         - [x] DTLS using PKI/RPK
 //!     - [ ] TCP
 //!     - [ ] TLS
-//!     - [ ] OSCORE
+//!     - [x] OSCORE (over UDP, requires the `oscore` feature)
 //!     - [ ] WebSockets
 //! - [ ] Blockwise Transfer
 //!     - [x] Receiving large messages
@@ -202,20 +202,37 @@
 
 extern crate core;
 
-pub use context::CoapContext;
-pub use event::CoapEventHandler;
-pub use resource::{CoapRequestHandler, CoapResource};
+pub use context::{set_dtls_log_level, set_log_level, AccessDecision, CoapContext, CoapContextBuilder};
+pub use event::{CoapEvent, CoapEventHandler};
+pub use resource::{
+    CoapObserverInfo, CoapRequestHandler, CoapResource, ObserveConfirmationPolicy, ResourceDescription, ResourceHandle,
+    ResourceInfo,
+};
 
 mod context;
 #[cfg(dtls)]
 pub mod crypto;
 pub mod error;
 mod event;
+mod file_resource;
+mod hex;
 mod mem;
 pub mod message;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "oscore")]
+pub mod oscore;
 pub mod prng;
 pub mod protocol;
+#[cfg(feature = "pubsub")]
+pub mod pubsub;
+pub mod rate_limit;
 mod resource;
+#[cfg(feature = "senml")]
+pub mod senml;
+#[cfg(feature = "serde")]
+mod serde_hex;
 pub mod session;
 pub mod transport;
 pub mod types;
+mod well_known;
@@ -14,6 +14,9 @@ use std::ffi::c_void;
 use std::fmt::{Debug, Formatter};
 use std::ops::{Deref, DerefMut};
 use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::BorrowError;
 
 /// Trait implemented by libcoap wrapper structs that contain an inner value that may be dropped
 /// exclusively, i.e., that can be dropped with the additional check that there are no further
@@ -29,23 +32,39 @@ pub(crate) trait DropInnerExclusively {
     fn drop_exclusively(self);
 }
 
+/// The value shared between all references to a [CoapFfiRcCell].
+#[derive(Debug)]
+struct CoapFfiCellContents<D> {
+    value: RefCell<D>,
+    /// Whether the value may no longer be used, e.g., because the libcoap structs it refers to
+    /// belong to a context that was dropped (see [CoapFfiRcCell::poison()]).
+    poisoned: AtomicBool,
+}
+
 /// A strong reference counted cell, created from an app data/user data pointer inside of a C
 /// library struct.
 ///
 /// This type is a wrapper around Rc<RefCell<D>> with some additional functions for creating from
 /// or converting to raw pointers.
-pub(crate) struct CoapFfiRcCell<D>(Rc<RefCell<D>>);
+///
+/// Additionally, the cell can be poisoned (see [CoapFfiRcCell::poison()]), after which
+/// [CoapFfiRcCell::try_borrow()] and [CoapFfiRcCell::try_borrow_mut()] refuse to provide access
+/// to the contained value.
+pub(crate) struct CoapFfiRcCell<D>(Rc<CoapFfiCellContents<D>>);
 
 impl<D> CoapFfiRcCell<D> {
     /// Creates a new instance of CoapFfiRcCell, containing the provided value.
     pub fn new(value: D) -> CoapFfiRcCell<D> {
-        CoapFfiRcCell(Rc::new(RefCell::new(value)))
+        CoapFfiRcCell(Rc::new(CoapFfiCellContents {
+            value: RefCell::new(value),
+            poisoned: AtomicBool::new(false),
+        }))
     }
 
     /// Converts from a raw user data/application data pointer inside of a libcoap C library struct
     /// into the appropriate reference type.
     ///
-    /// This is done by first restoring the `Rc<RefCell<D>>` using [Rc::from_raw()], then
+    /// This is done by first restoring the `Rc` using [Rc::from_raw()], then
     /// cloning and creating the [CoapFfiRcCell] from it (maintaining the original reference using
     /// [Rc::into_raw()]).
     ///
@@ -57,9 +76,10 @@ impl<D> CoapFfiRcCell<D> {
     /// used, see the struct-level documentation.
     ///
     /// To safely use this function, the following invariants must be kept:
-    /// - ptr is a valid pointer to an Rc<RefCell<D>>
+    /// - ptr is a valid pointer created using [CoapFfiRcCell::create_raw_rc()] or
+    ///   [CoapFfiRcCell::create_raw_weak()] whose value has not been dropped yet
     pub unsafe fn clone_raw_rc(ptr: *mut c_void) -> CoapFfiRcCell<D> {
-        let orig_ref = Rc::from_raw(ptr as *const RefCell<D>);
+        let orig_ref = Rc::from_raw(ptr as *const CoapFfiCellContents<D>);
         let new_ref = Rc::clone(&orig_ref);
         // Pointer should not have changed, so we don't need to use the returned value.
         let _ = Rc::into_raw(orig_ref);
@@ -69,8 +89,8 @@ impl<D> CoapFfiRcCell<D> {
     /// Converts from a raw user data/application data pointer inside of a libcoap C library struct
     /// into the appropriate reference type.
     ///
-    /// This is done by first restoring the `Weak<RefCell<D>>` using [Weak::from_raw()],
-    /// upgrading it to a `Rc<RefCell<D>>` then cloning and creating the [CoapFfiRcCell] from the
+    /// This is done by first restoring the `Weak` using [Weak::from_raw()],
+    /// upgrading it to an `Rc` then cloning and creating the [CoapFfiRcCell] from the
     /// upgraded reference (restoring the raw pointer again afterwards using [Rc::downgrade()] and
     /// [Weak::into_raw()]).
     ///
@@ -85,23 +105,23 @@ impl<D> CoapFfiRcCell<D> {
     /// used, see the struct-level documentation.
     ///
     /// To safely use this function, the following invariants must be kept:
-    /// - ptr is a valid pointer to a `Weak<RefCell<D>>`
+    /// - ptr is a valid pointer created using [CoapFfiRcCell::create_raw_weak()]
     pub unsafe fn clone_raw_weak(ptr: *mut c_void) -> CoapFfiRcCell<D> {
-        let orig_ref = Weak::from_raw(ptr as *const RefCell<D>);
+        let orig_ref = Weak::from_raw(ptr as *const CoapFfiCellContents<D>);
         let new_ref = Weak::upgrade(&orig_ref).expect("attempted to upgrade a weak reference that was orphaned");
         let _weakref = Weak::into_raw(orig_ref);
         CoapFfiRcCell(new_ref)
     }
 
     /// Converts from a raw user data/application data pointer inside of a libcoap C library struct
-    /// into the underlying `Weak<RefCell<D>>`.
+    /// into the underlying weak reference.
     ///
-    /// This is done by restoring the `Weak<RefCell<D>>` using [Weak::from_raw()],
+    /// This is done by restoring the `Weak` using [Weak::from_raw()],
     ///
     /// Note that unlike [CoapFfiRcCell::clone_raw_weak()], this does not clone the weak reference
     /// inside of the pointer and instead restores the `Weak` directly from the pointer.
-    /// This means that dropping the `Weak` returned from this function invalidates the pointer
-    /// provided to this function.
+    /// This means that dropping the [CoapFfiWeakCell] returned from this function invalidates the
+    /// pointer provided to this function.
     ///
     /// # Panics
     /// Panics if the provided Weak reference is orphaned.
@@ -111,23 +131,23 @@ impl<D> CoapFfiRcCell<D> {
     /// used, see the struct-level documentation.
     ///
     /// To safely use this function, the following invariants must be kept:
-    /// - ptr is a valid pointer to a `Weak<RefCell<D>>`
-    /// - as soon as the returned `Weak<RefCell<D>>` is dropped, the provided pointer is treated as
+    /// - ptr is a valid pointer created using [CoapFfiRcCell::create_raw_weak()]
+    /// - as soon as the returned [CoapFfiWeakCell] is dropped, the provided pointer is treated as
     ///   invalid.
-    pub unsafe fn raw_ptr_to_weak(ptr: *mut c_void) -> Weak<RefCell<D>> {
-        Weak::from_raw(ptr as *const RefCell<D>)
+    pub unsafe fn raw_ptr_to_weak(ptr: *mut c_void) -> CoapFfiWeakCell<D> {
+        CoapFfiWeakCell(Weak::from_raw(ptr as *const CoapFfiCellContents<D>))
     }
 
     /// Converts from a raw user data/application data pointer inside of a libcoap C library struct
-    /// into the underlying `Rc<RefCell<D>>`.
+    /// into the underlying `Rc`.
     ///
-    /// This is done by restoring the `Rc<RefCell<D>>` using [Rc::from_raw()],
+    /// This is done by restoring the `Rc` using [Rc::from_raw()],
     ///
     /// Note that unlike [CoapFfiRcCell::clone_raw_rc()], this does not clone the weak reference
     /// inside of the pointer and instead restores the `Rc` directly from the pointer.
     /// This means that dropping the `Rc` returned from this function invalidates the pointer
-    /// provided to this function and that the provided pointer must point to a valid
-    /// `Rc<RefCell<D>>`.
+    /// provided to this function and that the provided pointer must have been created using
+    /// [CoapFfiRcCell::create_raw_rc()].
     ///
     /// # Panics
     /// Panics if the provided Weak reference is orphaned.
@@ -137,20 +157,20 @@ impl<D> CoapFfiRcCell<D> {
     /// used, see the struct-level documentation.
     ///
     /// To safely use this function, the following invariants must be kept:
-    /// - ptr is a valid pointer to a `Rc<RefCell<D>>`
-    /// - as soon as the returned `Rc<RefCell<D>>` is dropped, the provided pointer is treated as
+    /// - ptr is a valid pointer created using [CoapFfiRcCell::create_raw_rc()]
+    /// - as soon as the returned [CoapFfiRcCell] is dropped, the provided pointer is treated as
     ///   invalid.
     // Kept for consistency
     #[allow(unused)]
-    pub unsafe fn raw_ptr_to_rc(ptr: *mut c_void) -> Rc<RefCell<D>> {
-        Rc::from_raw(ptr as *const RefCell<D>)
+    pub unsafe fn raw_ptr_to_rc(ptr: *mut c_void) -> CoapFfiRcCell<D> {
+        CoapFfiRcCell(Rc::from_raw(ptr as *const CoapFfiCellContents<D>))
     }
 
     /// Creates a raw reference, suitable for storage inside of a libcoap C library user/application
     /// data pointer.
     ///
     /// This function internally calls [Rc::clone()] and then [Rc::into_raw()] to create a pointer
-    /// referring to a clone of the `Rc<RefCell<D>>` contained in this type.
+    /// referring to a clone of the `Rc` contained in this type.
     ///
     /// Note that this increases the reference count of the Rc by one.
     // Kept for consistency
@@ -163,7 +183,7 @@ impl<D> CoapFfiRcCell<D> {
     /// data pointer.
     ///
     /// This function internally calls [Rc::downgrade()] and then [Weak::into_raw()] to create a
-    /// pointer referring to a weak reference of the `Rc<RefCell<D>>` contained in this type.
+    /// pointer referring to a weak reference of the `Rc` contained in this type.
     ///
     /// Note that this does not increase the reference count of the [Rc] by one. If you want to
     /// ensure that the underlying D is never cleaned up for as long as this pointer exists, you
@@ -172,26 +192,84 @@ impl<D> CoapFfiRcCell<D> {
         Weak::into_raw(Rc::downgrade(&self.0)) as *mut c_void
     }
 
-    /// Creates an immutable reference to the contained data type.
+    /// Returns the number of strong references to the contained value (including this one).
+    pub fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.0)
+    }
+
+    /// Returns a pointer to the contained value that may only be used for identity comparisons.
+    pub fn as_ptr(&self) -> *const c_void {
+        Rc::as_ptr(&self.0).cast()
+    }
+
+    /// Creates a new weak reference to the contained value.
+    pub fn downgrade(&self) -> CoapFfiWeakCell<D> {
+        CoapFfiWeakCell(Rc::downgrade(&self.0))
+    }
+
+    /// Marks the contained value as no longer usable for all references to this cell.
+    ///
+    /// This is used if the libcoap structs the value refers to are no longer valid, e.g., because
+    /// the context they belong to has been dropped.
+    /// Subsequent calls to [CoapFfiRcCell::try_borrow()] and [CoapFfiRcCell::try_borrow_mut()]
+    /// return [BorrowError::Poisoned], while [CoapFfiRcCell::borrow()] and
+    /// [CoapFfiRcCell::borrow_mut()] remain usable for cleaning up the value.
+    pub fn poison(&self) {
+        self.0.poisoned.store(true, Ordering::Release);
+    }
+
+    /// Returns whether this cell has been poisoned using [CoapFfiRcCell::poison()].
+    pub fn is_poisoned(&self) -> bool {
+        self.0.poisoned.load(Ordering::Acquire)
+    }
+
+    /// Creates an immutable reference to the contained data type, ignoring whether the cell has
+    /// been poisoned.
     ///
     /// # Panics
     /// Panics if borrowing here would violate Rusts aliasing rules.
     pub fn borrow(&self) -> Ref<D> {
-        RefCell::borrow(&self.0)
+        RefCell::borrow(&self.0.value)
     }
 
-    /// Creates a mutable reference to the contained data type.
+    /// Creates a mutable reference to the contained data type, ignoring whether the cell has been
+    /// poisoned.
     ///
     /// # Panics
     /// Panics if borrowing mutably here would violate Rusts aliasing rules.
     pub fn borrow_mut(&self) -> RefMut<D> {
-        RefCell::borrow_mut(&self.0)
+        RefCell::borrow_mut(&self.0.value)
+    }
+
+    /// Creates an immutable reference to the contained data type.
+    ///
+    /// # Errors
+    /// Returns [BorrowError::Poisoned] if the cell has been poisoned and
+    /// [BorrowError::AlreadyBorrowed] if borrowing here would violate Rusts aliasing rules.
+    pub fn try_borrow(&self) -> Result<Ref<D>, BorrowError> {
+        if self.is_poisoned() {
+            return Err(BorrowError::Poisoned);
+        }
+        RefCell::try_borrow(&self.0.value).map_err(|_| BorrowError::AlreadyBorrowed)
+    }
+
+    /// Creates a mutable reference to the contained data type.
+    ///
+    /// # Errors
+    /// Returns [BorrowError::Poisoned] if the cell has been poisoned and
+    /// [BorrowError::AlreadyBorrowed] if borrowing mutably here would violate Rusts aliasing
+    /// rules.
+    pub fn try_borrow_mut(&self) -> Result<RefMut<D>, BorrowError> {
+        if self.is_poisoned() {
+            return Err(BorrowError::Poisoned);
+        }
+        RefCell::try_borrow_mut(&self.0.value).map_err(|_| BorrowError::AlreadyBorrowed)
     }
 }
 
 impl<D: PartialEq> PartialEq for CoapFfiRcCell<D> {
     fn eq(&self, other: &Self) -> bool {
-        Rc::eq(&self.0, &other.0)
+        RefCell::eq(&self.0.value, &other.0.value)
     }
 }
 
@@ -205,7 +283,10 @@ impl<D> Clone for CoapFfiRcCell<D> {
 
 impl<D: Debug> Debug for CoapFfiRcCell<D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("CoapFfiRcCell").field("0", &self.0).finish()
+        f.debug_struct("CoapFfiRcCell")
+            .field("0", &self.0.value)
+            .field("poisoned", &self.is_poisoned())
+            .finish()
     }
 }
 
@@ -217,6 +298,43 @@ impl<T: Debug> DropInnerExclusively for CoapFfiRcCell<T> {
     }
 }
 
+/// The weak variant of a [CoapFfiRcCell].
+pub(crate) struct CoapFfiWeakCell<D>(Weak<CoapFfiCellContents<D>>);
+
+impl<D> CoapFfiWeakCell<D> {
+    /// Attempts to upgrade this weak cell into a full [CoapFfiRcCell], returning None if the
+    /// underlying value was already dropped.
+    pub fn upgrade(&self) -> Option<CoapFfiRcCell<D>> {
+        self.0.upgrade().map(CoapFfiRcCell)
+    }
+
+    /// Returns the number of strong references to the contained value.
+    pub fn strong_count(&self) -> usize {
+        self.0.strong_count()
+    }
+
+    /// Returns a pointer to the contained value that may only be used for identity comparisons
+    /// (see [CoapFfiRcCell::as_ptr()]).
+    ///
+    /// As the memory of the value is only released once all weak references are gone, the pointer
+    /// will not be reused for a different value while this weak cell exists.
+    pub fn as_ptr(&self) -> *const c_void {
+        self.0.as_ptr().cast()
+    }
+}
+
+impl<D> Clone for CoapFfiWeakCell<D> {
+    fn clone(&self) -> Self {
+        CoapFfiWeakCell(self.0.clone())
+    }
+}
+
+impl<D> Debug for CoapFfiWeakCell<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoapFfiWeakCell").finish_non_exhaustive()
+    }
+}
+
 /// A reference counted cell suitable for passing through the FFI barrier, with the additional
 /// possibility of passing an existing reference through this barrier.
 ///
@@ -0,0 +1,288 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * message/http.rs - Conversion of CoAP messages from and to HTTP messages.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Conversion of CoAP messages from and to HTTP messages, as required for CoAP-HTTP proxies (see
+//! [RFC 8075](https://datatracker.ietf.org/doc/html/rfc8075)).
+
+use ::http::{
+    header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH, LOCATION},
+    HeaderMap, HeaderName, Method, StatusCode,
+};
+
+use crate::{
+    error::ProxyError,
+    hex,
+    message::{CoapMessageCommon, CoapRequest, CoapResponse},
+    protocol::{
+        CoapContentFormat, CoapMatch, CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode, ETag,
+    },
+    types::CoapUri,
+};
+
+/// Maximum length of an ETag in bytes (see
+/// [RFC 7252, Section 5.10.6](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10.6)).
+const MAX_ETAG_LENGTH: usize = 8;
+
+/// Returns the HTTP status code corresponding to the given CoAP response code (see
+/// [RFC 8075, Section 7](https://datatracker.ietf.org/doc/html/rfc8075#section-7)), or `None` if
+/// there is no equivalent HTTP status code.
+fn http_status_code(code: CoapResponseCode) -> Option<StatusCode> {
+    Some(match code {
+        CoapResponseCode::Created => StatusCode::CREATED,
+        CoapResponseCode::Deleted | CoapResponseCode::Changed | CoapResponseCode::Content => StatusCode::OK,
+        CoapResponseCode::Valid => StatusCode::NOT_MODIFIED,
+        // HTTP servers must send an Allow header alongside 405 responses, for which there is no
+        // CoAP equivalent.
+        CoapResponseCode::BadRequest
+        | CoapResponseCode::BadOption
+        | CoapResponseCode::NotAllowed
+        | CoapResponseCode::Incomplete => StatusCode::BAD_REQUEST,
+        CoapResponseCode::Unauthorized | CoapResponseCode::Forbidden => StatusCode::FORBIDDEN,
+        CoapResponseCode::NotFound => StatusCode::NOT_FOUND,
+        CoapResponseCode::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+        CoapResponseCode::Conflict => StatusCode::CONFLICT,
+        CoapResponseCode::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+        CoapResponseCode::RequestTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        CoapResponseCode::UnsupportedContentFormat => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        CoapResponseCode::Unprocessable => StatusCode::UNPROCESSABLE_ENTITY,
+        CoapResponseCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+        CoapResponseCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        CoapResponseCode::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+        CoapResponseCode::BadGateway | CoapResponseCode::ProxyingNotSupported => StatusCode::BAD_GATEWAY,
+        CoapResponseCode::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        CoapResponseCode::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT,
+        // See RFC 8768, Section 4.
+        CoapResponseCode::HopLimitReached => StatusCode::LOOP_DETECTED,
+        // Only used for intermediate responses in block-wise transfers.
+        CoapResponseCode::Continue => return None,
+    })
+}
+
+/// Returns the CoAP request code corresponding to the given HTTP request method (see
+/// [RFC 8075, Section 6](https://datatracker.ietf.org/doc/html/rfc8075#section-6)), or `None` if
+/// there is no equivalent CoAP request code.
+fn coap_request_code(method: &Method) -> Option<CoapRequestCode> {
+    match *method {
+        Method::GET => Some(CoapRequestCode::Get),
+        Method::POST => Some(CoapRequestCode::Post),
+        Method::PUT => Some(CoapRequestCode::Put),
+        Method::DELETE => Some(CoapRequestCode::Delete),
+        Method::PATCH => Some(CoapRequestCode::Patch),
+        _ => None,
+    }
+}
+
+/// Encodes the given CoAP ETag as an HTTP entity tag (a quoted hexadecimal string).
+fn encode_etag(etag: &ETag) -> String {
+    format!("\"{}\"", hex::encode(etag))
+}
+
+/// Decodes an HTTP entity tag that was created by [encode_etag()].
+///
+/// Returns `None` for weak entity tags and entity tags that do not correspond to a CoAP ETag.
+fn decode_etag(entity_tag: &str) -> Option<ETag> {
+    let hex = entity_tag.strip_prefix('"')?.strip_suffix('"')?;
+    if hex.is_empty() || hex.len() % 2 != 0 || hex.len() > MAX_ETAG_LENGTH * 2 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses the comma-separated list of entity tags in all headers with the given `name`.
+///
+/// Returns `Ok(None)` if the header is not present and `Ok(Some(Vec::new()))` if its value is `*`.
+fn parse_etag_header(headers: &HeaderMap, name: HeaderName) -> Result<Option<Vec<ETag>>, ProxyError> {
+    let mut etags = None;
+    for value in headers.get_all(&name) {
+        let value = value.to_str().map_err(|_| ProxyError::InvalidHeader(name.clone()))?;
+        let list: &mut Vec<ETag> = etags.get_or_insert_with(Vec::new);
+        for entity_tag in value.split(',').map(str::trim) {
+            if entity_tag == "*" {
+                continue;
+            }
+            list.push(decode_etag(entity_tag).ok_or_else(|| ProxyError::InvalidHeader(name.clone()))?);
+        }
+    }
+    Ok(etags)
+}
+
+impl CoapResponse {
+    /// Converts this response into an HTTP response, as done by a CoAP-HTTP proxy (see
+    /// [RFC 8075, Section 7](https://datatracker.ietf.org/doc/html/rfc8075#section-7)).
+    ///
+    /// The response code is mapped to the equivalent HTTP status code, and the following options
+    /// are converted into HTTP headers:
+    /// - "Content-Format" is converted into a `Content-Type` header containing the registered
+    ///   media type (or `application/coap-payload` for unknown content formats, see
+    ///   [RFC 8075, Section 6.4](https://datatracker.ietf.org/doc/html/rfc8075#section-6.4)).
+    /// - "Max-Age" is converted into a `Cache-Control: max-age=N` header.
+    /// - "ETag" is converted into an `ETag` header containing the ETag as a quoted hexadecimal
+    ///   string.
+    /// - "Location-Path" and "Location-Query" are converted into a `Location` header.
+    ///
+    /// All other options are CoAP-specific and therefore dropped.
+    ///
+    /// Note that CoAP responses without a "Max-Age" option may be cached for 60 seconds, while HTTP
+    /// caches may use heuristic freshness lifetimes for HTTP responses without `Cache-Control`
+    /// headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ProxyError::UnsupportedResponseCode] if the response code of this response is
+    /// [CoapResponseCode::Continue] (which is only used for block-wise transfers), and
+    /// [ProxyError::InvalidMessageCode] if the message code of the underlying message is not a
+    /// response code.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapResponse};
+    /// use libcoap_rs::protocol::{CoapContentFormat, CoapMessageType, CoapResponseCode};
+    ///
+    /// let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    /// response.set_content_format(Some(CoapContentFormat::Json as u16));
+    /// response.set_max_age(Some(30));
+    /// response.set_etag(Some(vec![0x13, 0x37].into_boxed_slice()));
+    /// response.set_data(Some("{}".as_bytes()));
+    ///
+    /// let http_response = response.into_http_response().unwrap();
+    /// assert_eq!(http_response.status(), http::StatusCode::OK);
+    /// assert_eq!(http_response.headers()["content-type"], "application/json");
+    /// assert_eq!(http_response.headers()["cache-control"], "max-age=30");
+    /// assert_eq!(http_response.headers()["etag"], "\"1337\"");
+    /// assert_eq!(http_response.body(), "{}".as_bytes());
+    /// ```
+    pub fn into_http_response(self) -> Result<::http::Response<Vec<u8>>, ProxyError> {
+        let code = match self.code() {
+            CoapMessageCode::Response(code) => code,
+            code => return Err(ProxyError::InvalidMessageCode(code)),
+        };
+        let status = http_status_code(code).ok_or(ProxyError::UnsupportedResponseCode(code))?;
+        let mut builder = ::http::Response::builder().status(status);
+        if let Some(content_format) = self.content_format() {
+            builder = match CoapContentFormat::from(content_format).media_type() {
+                Some(media_type) => builder.header(CONTENT_TYPE, media_type),
+                None => builder.header(CONTENT_TYPE, format!("application/coap-payload; cf={content_format}")),
+            };
+        }
+        if let Some(max_age) = self.max_age() {
+            builder = builder.header(CACHE_CONTROL, format!("max-age={max_age}"));
+        }
+        if let Some(etag) = self.etag() {
+            builder = builder.header(ETAG, encode_etag(etag));
+        }
+        if let Some(location) = self.location() {
+            builder = builder.header(LOCATION, location.to_string());
+        }
+        let body = self.data().map(|v| v.to_vec()).unwrap_or_default();
+        Ok(builder.body(body)?)
+    }
+}
+
+impl CoapRequest {
+    /// Converts the given HTTP request into a confirmable CoAP request, as done by an HTTP-CoAP
+    /// proxy (see [RFC 8075, Section 6](https://datatracker.ietf.org/doc/html/rfc8075#section-6)).
+    ///
+    /// The path and query of the HTTP request target are used as the CoAP request URI, and the
+    /// request method is mapped to the equivalent CoAP request code.
+    /// The following headers are converted into CoAP options:
+    /// - `Content-Type` is converted into a "Content-Format" option.
+    /// - `Accept` is converted into an "Accept" option containing the content format of the first
+    ///   media type that has a CoAP equivalent (other media types are ignored).
+    /// - `If-Match` is converted into an "If-Match" option.
+    /// - `If-None-Match: *` is converted into an "If-None-Match" option, while entity tags in
+    ///   `If-None-Match` headers are converted into "ETag" options, allowing the CoAP server to
+    ///   validate cached representations.
+    ///
+    /// Entity tags are expected to be quoted hexadecimal strings, as created by
+    /// [CoapResponse::into_http_response()].
+    ///
+    /// # Errors
+    ///
+    /// Returns [ProxyError::UnsupportedMethod] if the request method has no CoAP equivalent,
+    /// [ProxyError::UnsupportedMediaType] if the `Content-Type` has no CoAP equivalent,
+    /// [ProxyError::InvalidHeader] if one of the converted headers has an invalid value, or
+    /// [ProxyError::InvalidUri] if the request target can not be converted into a CoAP URI.
+    pub fn from_http_request(request: ::http::Request<Vec<u8>>) -> Result<CoapRequest, ProxyError> {
+        let target_uri = CoapUri::try_from_str(request.uri().path_and_query().map_or("/", |v| v.as_str()))?;
+        CoapRequest::from_http_request_with_target(request, target_uri)
+    }
+
+    /// Converts the given HTTP request into a CoAP request for the given target URI.
+    ///
+    /// This works just like [CoapRequest::from_http_request()], except that the request target
+    /// of the HTTP request is ignored and `target_uri` is used as the CoAP request URI instead.
+    /// This is useful for proxies that determine the CoAP server based on the HTTP request target
+    /// (e.g., using the URI mapping described in
+    /// [RFC 8075, Section 5](https://datatracker.ietf.org/doc/html/rfc8075#section-5)).
+    ///
+    /// # Errors
+    ///
+    /// Returns [ProxyError::UnsupportedMethod] if the request method has no CoAP equivalent (e.g.,
+    /// for `HEAD`, `OPTIONS` or `TRACE` requests), [ProxyError::UnsupportedMediaType] if the
+    /// `Content-Type` has no CoAP equivalent or [ProxyError::InvalidHeader] if one of the
+    /// converted headers has an invalid value.
+    pub fn from_http_request_with_target(
+        request: ::http::Request<Vec<u8>>,
+        target_uri: CoapUri,
+    ) -> Result<CoapRequest, ProxyError> {
+        let (parts, body) = request.into_parts();
+        let code =
+            coap_request_code(&parts.method).ok_or_else(|| ProxyError::UnsupportedMethod(parts.method.clone()))?;
+        let mut coap_request =
+            CoapRequest::new(CoapMessageType::Con, code, target_uri).expect("confirmable messages are valid requests");
+
+        if let Some(content_type) = parts.headers.get(CONTENT_TYPE) {
+            let content_type = content_type
+                .to_str()
+                .map_err(|_| ProxyError::InvalidHeader(CONTENT_TYPE))?;
+            let content_format = CoapContentFormat::from_media_type(content_type)
+                .ok_or_else(|| ProxyError::UnsupportedMediaType(content_type.to_string()))?;
+            coap_request.set_content_format(Some(content_format as u16));
+        }
+        for accept in parts.headers.get_all(ACCEPT) {
+            let accept = accept.to_str().map_err(|_| ProxyError::InvalidHeader(ACCEPT))?;
+            // Quality values are not supported by CoAP, so we strip them off.
+            let content_format = accept.split(',').find_map(|media_range| {
+                let media_type: Vec<&str> = media_range
+                    .split(';')
+                    .filter(|parameter| !parameter.trim_start().starts_with("q="))
+                    .collect();
+                CoapContentFormat::from_media_type(&media_type.join(";"))
+            });
+            if let Some(content_format) = content_format {
+                coap_request.set_accept(Some(content_format as u16));
+                break;
+            }
+        }
+        if let Some(etags) = parse_etag_header(&parts.headers, IF_MATCH)? {
+            let matches = if etags.is_empty() {
+                // "If-Match: *" matches any existing representation, just like an empty If-Match
+                // option.
+                vec![CoapMatch::Empty]
+            } else {
+                etags.into_iter().map(CoapMatch::ETag).collect()
+            };
+            coap_request.set_if_match(Some(matches));
+        }
+        if let Some(etags) = parse_etag_header(&parts.headers, IF_NONE_MATCH)? {
+            if etags.is_empty() {
+                coap_request.set_if_none_match(true);
+            } else {
+                coap_request.set_etag(Some(etags));
+            }
+        }
+        if !body.is_empty() {
+            coap_request.set_data(Some(body));
+        }
+        Ok(coap_request)
+    }
+}
@@ -15,45 +15,88 @@
 //! process of creating requests and responses and setting the appropriate options ([CoapRequest]
 //! and [CoapResponse]).
 
-use std::{ffi::c_void, mem::MaybeUninit, slice::Iter};
 use std::fmt::Write;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    ffi::c_void,
+    hash::{Hash, Hasher},
+    mem::MaybeUninit,
+    slice::Iter,
+    time::Instant,
+};
 
 use num_traits::FromPrimitive;
 
 use libcoap_sys::{
     coap_add_data, coap_add_data_large_request, coap_add_optlist_pdu, coap_add_token, coap_delete_optlist,
     coap_delete_pdu, coap_get_data, coap_insert_optlist, coap_new_optlist, coap_opt_length, coap_opt_t, coap_opt_value,
-    coap_option_iterator_init, coap_option_next, coap_option_num_t, coap_optlist_t, coap_pdu_get_code,
-    coap_pdu_get_mid, coap_pdu_get_token, coap_pdu_get_type, coap_pdu_init, coap_pdu_set_code, coap_pdu_set_type,
-    coap_pdu_t, coap_session_t,
+    coap_option_iterator_init, coap_option_next, coap_option_num_t, coap_optlist_t, coap_pdu_code_t, coap_pdu_get_code,
+    coap_pdu_get_mid, coap_pdu_get_token, coap_pdu_get_type, coap_pdu_init, coap_pdu_parse, coap_pdu_set_code,
+    coap_pdu_set_type, coap_pdu_t, coap_pdu_type_t, coap_proto_t, coap_session_t,
 };
 pub use request::CoapRequest;
 pub use response::CoapResponse;
 
+use crate::context::ensure_coap_started;
+use crate::protocol::{is_critical_option, Echo, Oscore, RequestTag};
+use crate::types::{
+    decode_var_len_u16, decode_var_len_u32, decode_var_len_u8, encode_var_len_u16, encode_var_len_u32,
+    encode_var_len_u8,
+};
 use crate::{
-    error::{MessageConversionError, OptionValueError},
+    error::{MessageConversionError, MessageTypeError, OptionValueError},
     protocol::{
-        Block, CoapMatch, CoapMessageCode, CoapMessageType, CoapOptionNum, CoapOptionType, ContentFormat, ETag,
-        HopLimit, MaxAge, NoResponse, Observe, ProxyScheme, ProxyUri, Size, UriHost, UriPath, UriPort, UriQuery,
+        Block, CoapMatch, CoapMessageCode, CoapMessageType, CoapOptionNum, CoapOptionType, CoapToken, ContentFormat,
+        ETag, HopLimit, MaxAge, NoResponse, Observe, ProxyScheme, ProxyUri, Size, UriHost, UriPath, UriPort, UriQuery,
     },
     session::CoapSessionCommon,
     types::CoapMessageId,
 };
-use crate::context::ensure_coap_started;
-use crate::protocol::{Echo, Oscore, RequestTag};
-use crate::types::{
-    decode_var_len_u16, decode_var_len_u32, decode_var_len_u8, encode_var_len_u16, encode_var_len_u32,
-    encode_var_len_u8,
-};
 
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "proptest")]
+mod proptest;
 pub mod request;
 pub mod response;
 
+/// Storage type for message bodies.
+///
+/// If the `bytes` feature is enabled, message bodies are stored in a reference-counted
+/// [bytes::Bytes] buffer, which allows sharing them without copying (see
+/// [CoapMessage::data_as_bytes()]).
+#[cfg(feature = "bytes")]
+type MessageData = ::bytes::Bytes;
+#[cfg(not(feature = "bytes"))]
+type MessageData = Box<[u8]>;
+
+/// Converts a message body into the storage type used for message bodies.
+#[cfg(feature = "bytes")]
+fn into_message_data(data: Box<[u8]>) -> MessageData {
+    MessageData::from(data)
+}
+
+/// Converts a message body into the storage type used for message bodies.
+#[cfg(not(feature = "bytes"))]
+fn into_message_data(data: Box<[u8]>) -> MessageData {
+    data
+}
+
+/// Maximum length of a message token that can be encoded in the message header (see
+/// [RFC 7252, Section 3](https://datatracker.ietf.org/doc/html/rfc7252#section-3)).
+const MAX_TOKEN_LEN: usize = 8;
+
 /// Representation of a CoAP option including its value.
 ///
 /// For an enum describing the possible option types (and their associated option numbers), see
 /// [CoapOptionType], for the data type representing option numbers, see [CoapOptionNum]
+///
+/// If the `serde` feature is enabled, options can be (de)serialized as externally tagged enum
+/// values (e.g., `{"UriPath":"sensors"}`), where binary option values (such as ETags) are
+/// represented as hexadecimal strings.
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoapOption {
     IfMatch(CoapMatch),
     IfNoneMatch,
@@ -73,18 +116,61 @@ pub enum CoapOption {
     Block2(Block),
     HopLimit(HopLimit),
     NoResponse(NoResponse),
-    ETag(ETag),
+    ETag(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] ETag),
     MaxAge(MaxAge),
     Observe(Observe),
-    Oscore(Oscore),
-    Echo(Echo),
-    RTag(RequestTag),
+    Oscore(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] Oscore),
+    Echo(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] Echo),
+    RTag(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] RequestTag),
     QBlock1(Block),
     QBlock2(Block),
-    Other(CoapOptionNum, Box<[u8]>),
+    Other(
+        CoapOptionNum,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] Box<[u8]>,
+    ),
 }
 
+/// Maximum length of an option value that can be encoded in a CoAP message.
+///
+/// See [RFC 7252, Section 3.1](https://datatracker.ietf.org/doc/html/rfc7252#section-3.1) for
+/// the option length encoding (which allows for lengths of up to 65535+269 bytes).
+const MAX_OPTION_VALUE_LEN: usize = 65535 + 269;
+
 impl CoapOption {
+    /// Creates a [CoapOption::Other] instance for an option that is not known to this library.
+    ///
+    /// # Errors
+    /// Returns [OptionValueError::KnownOptionNumber] if `number` belongs to an option type that is
+    /// known (see [CoapOptionType]), in which case the typed variant should be used instead, and
+    /// [OptionValueError::TooLong] if `value` exceeds the maximum value length of a CoAP option.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::OptionValueError;
+    /// use libcoap_rs::message::CoapOption;
+    /// use libcoap_rs::protocol::CoapOptionType;
+    ///
+    /// let option = CoapOption::other(65000, b"custom".as_slice())?;
+    /// assert_eq!(option.number(), 65000);
+    ///
+    /// assert_eq!(
+    ///     CoapOption::other(CoapOptionType::UriHost as u16, b"example.com".as_slice()),
+    ///     Err(OptionValueError::KnownOptionNumber(CoapOptionType::UriHost))
+    /// );
+    ///
+    /// # Result::<(), OptionValueError>::Ok(())
+    /// ```
+    pub fn other(number: CoapOptionNum, value: impl Into<Box<[u8]>>) -> Result<CoapOption, OptionValueError> {
+        if let Ok(opt_type) = CoapOptionType::try_from(number) {
+            return Err(OptionValueError::KnownOptionNumber(opt_type));
+        }
+        let value = value.into();
+        if value.len() > MAX_OPTION_VALUE_LEN {
+            return Err(OptionValueError::TooLong);
+        }
+        Ok(CoapOption::Other(number, value))
+    }
+
     /// Create a CoAP option from its raw representation in the C library.
     ///
     /// # Safety
@@ -101,6 +187,12 @@ impl CoapOption {
         Self::from_type_value(number, value)
     }
 
+    /// Returns whether this option is a [CoapOption::Other] instance for a known option number,
+    /// i.e., an option whose value could not be parsed (see [CoapMessage::from_raw_pdu_lossy()]).
+    fn is_lossy(&self) -> bool {
+        matches!(self, CoapOption::Other(number, _) if CoapOptionType::try_from(*number).is_ok())
+    }
+
     /// Returns the option number associated with this option.
     pub fn number(&self) -> CoapOptionNum {
         match self {
@@ -137,6 +229,10 @@ impl CoapOption {
     /// Converts the option into a `Box<[u8]>` containing the value bytes.
     pub fn into_value_bytes(self) -> Result<Box<[u8]>, OptionValueError> {
         let num = self.number();
+        // RFC 8768, Section 3 restricts the Hop-Limit to values between 1 and 255.
+        if let CoapOption::HopLimit(_) = self {
+            CoapOptionType::validate_value(&self)?;
+        }
         let bytes = match self {
             CoapOption::IfMatch(val) => match val {
                 CoapMatch::ETag(tag) => tag,
@@ -161,7 +257,7 @@ impl CoapOption {
             CoapOption::NoResponse(value) => encode_var_len_u8(value),
             CoapOption::ETag(value) => value,
             CoapOption::MaxAge(value) => encode_var_len_u32(value),
-            CoapOption::Observe(value) => encode_var_len_u32(value),
+            CoapOption::Observe(value) => encode_var_len_u32(value.value()),
             CoapOption::Oscore(value) => value,
             CoapOption::Echo(value) => value,
             CoapOption::RTag(value) => value,
@@ -179,9 +275,66 @@ impl CoapOption {
         Ok(bytes)
     }
 
+    /// Returns the encoded value bytes of this option without consuming it.
+    ///
+    /// In contrast to [into_value_bytes()](CoapOption::into_value_bytes()), the value of opaque and
+    /// string options is borrowed from the option itself, so inspecting or forwarding those does
+    /// not require any allocation.
+    /// Only integer options have to be encoded into a newly allocated buffer.
+    ///
+    /// Note that this function does not check whether the value satisfies the length constraints
+    /// of the option type.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use libcoap_rs::message::CoapOption;
+    ///
+    /// let etag = CoapOption::ETag(vec![0xAB, 0xCD].into_boxed_slice());
+    /// assert!(matches!(etag.value_bytes(), Cow::Borrowed([0xAB, 0xCD])));
+    ///
+    /// let port = CoapOption::UriPort(5683);
+    /// assert_eq!(port.value_bytes().as_ref(), [0x16, 0x33]);
+    /// ```
+    pub fn value_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            CoapOption::IfMatch(CoapMatch::ETag(tag)) => Cow::Borrowed(tag),
+            CoapOption::IfMatch(CoapMatch::Empty) | CoapOption::IfNoneMatch => Cow::Borrowed(&[]),
+            CoapOption::UriHost(value)
+            | CoapOption::UriPath(value)
+            | CoapOption::UriQuery(value)
+            | CoapOption::LocationPath(value)
+            | CoapOption::LocationQuery(value)
+            | CoapOption::ProxyUri(value)
+            | CoapOption::ProxyScheme(value) => Cow::Borrowed(value.as_bytes()),
+            CoapOption::ETag(value)
+            | CoapOption::Oscore(value)
+            | CoapOption::Echo(value)
+            | CoapOption::RTag(value)
+            | CoapOption::Other(_, value) => Cow::Borrowed(value),
+            CoapOption::UriPort(value)
+            | CoapOption::ContentFormat(value)
+            | CoapOption::Accept(value)
+            | CoapOption::HopLimit(value) => Cow::Owned(encode_var_len_u16(*value).into_vec()),
+            CoapOption::Size1(value)
+            | CoapOption::Size2(value)
+            | CoapOption::Block1(value)
+            | CoapOption::Block2(value)
+            | CoapOption::MaxAge(value)
+            | CoapOption::QBlock1(value)
+            | CoapOption::QBlock2(value) => Cow::Owned(encode_var_len_u32(*value).into_vec()),
+            CoapOption::Observe(value) => Cow::Owned(encode_var_len_u32(value.value()).into_vec()),
+            CoapOption::NoResponse(value) => Cow::Owned(encode_var_len_u8(*value).into_vec()),
+        }
+    }
+
     /// Converts this option into a raw coap_optlist_t instance, suitable for addition to a raw
     /// coap_pdu_t.
+    ///
+    /// The option value is checked using [CoapOptionType::validate_value()] beforehand.
     pub(crate) fn into_optlist_entry(self) -> Result<*mut coap_optlist_t, OptionValueError> {
+        CoapOptionType::validate_value(&self)?;
         let num = self.number();
         let value = self.into_value_bytes()?;
         Ok(unsafe { coap_new_optlist(num, value.len(), value.as_ptr()) })
@@ -240,7 +393,11 @@ impl CoapOption {
                     CoapOptionType::NoResponse => {
                         Ok(CoapOption::NoResponse(decode_var_len_u8(value.as_slice()) as NoResponse))
                     },
-                    CoapOptionType::Observe => Ok(CoapOption::Observe(decode_var_len_u32(value.as_slice()))),
+                    // The length check above limits the value to 3 bytes.
+                    CoapOptionType::Observe => {
+                        let value = decode_var_len_u32(value.as_slice());
+                        Ok(CoapOption::Observe(Observe::wrapping(value)))
+                    },
                     CoapOptionType::Oscore => Ok(CoapOption::Oscore(value.into_boxed_slice())),
                     CoapOptionType::Echo => Ok(CoapOption::Echo(value.into_boxed_slice())),
                     CoapOptionType::RTag => Ok(CoapOption::RTag(value.into_boxed_slice())),
@@ -253,22 +410,99 @@ impl CoapOption {
     }
 }
 
+impl TryFrom<(CoapOptionType, &[u8])> for CoapOption {
+    type Error = OptionValueError;
+
+    /// Parses the given option value bytes (as they would appear on the wire) into a typed
+    /// [CoapOption] of the given option type.
+    ///
+    /// # Errors
+    /// Returns [OptionValueError::TooShort] or [OptionValueError::TooLong] if the value does not
+    /// satisfy the length constraints of the option type, and
+    /// [OptionValueError::StringConversion] if the value of a string option is not valid UTF-8.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::OptionValueError;
+    /// use libcoap_rs::message::CoapOption;
+    /// use libcoap_rs::protocol::CoapOptionType;
+    ///
+    /// let port = CoapOption::try_from((CoapOptionType::UriPort, [0x16, 0x33].as_slice()))?;
+    /// assert_eq!(port, CoapOption::UriPort(5683));
+    ///
+    /// assert_eq!(
+    ///     CoapOption::try_from((CoapOptionType::UriPort, [0x01, 0x16, 0x33].as_slice())),
+    ///     Err(OptionValueError::TooLong)
+    /// );
+    ///
+    /// # Result::<(), OptionValueError>::Ok(())
+    /// ```
+    fn try_from((opt_type, value): (CoapOptionType, &[u8])) -> Result<Self, Self::Error> {
+        Self::from_type_value(opt_type.to_raw_option_num(), value.to_vec())
+    }
+}
+
+impl TryFrom<(CoapOptionNum, &[u8])> for CoapOption {
+    type Error = OptionValueError;
+
+    /// Parses the given option value bytes (as they would appear on the wire) into a
+    /// [CoapOption] for the given option number.
+    ///
+    /// Values of known option types are parsed into the corresponding typed variant (with the
+    /// same constraints as the `TryFrom<(CoapOptionType, &[u8])>` implementation), while values of
+    /// unknown options are returned as [CoapOption::Other].
+    fn try_from((number, value): (CoapOptionNum, &[u8])) -> Result<Self, Self::Error> {
+        if CoapOptionType::try_from(number).is_err() && value.len() > MAX_OPTION_VALUE_LEN {
+            return Err(OptionValueError::TooLong);
+        }
+        Self::from_type_value(number, value.to_vec())
+    }
+}
+
+/// Characters that would be interpreted as delimiters if they appeared in a path component.
+const PATH_DELIMITERS: &[char] = &['/', '?', '#'];
+/// Characters that would be interpreted as delimiters if they appeared in a query component.
+const QUERY_DELIMITERS: &[char] = &['&', '#'];
+
+/// Percent-encodes the given path or query component (see
+/// [RFC 3986, Section 2.1](https://datatracker.ietf.org/doc/html/rfc3986#section-2.1)).
+///
+/// Option values are literal strings that are not percent-encoded, while libcoap decodes each
+/// component when converting a URI into options. Therefore, `%` and all characters in
+/// `delimiters` have to be encoded in order for the component to be converted back into the
+/// original option value.
+fn encode_uri_component(component: &str, delimiters: &[char]) -> String {
+    let mut out_str = String::with_capacity(component.len());
+    for c in component.chars() {
+        if c == '%' || delimiters.contains(&c) {
+            // Both '%' and the delimiters are ASCII characters, so their code point is a single
+            // byte.
+            write!(&mut out_str, "%{:02X}", c as u32).expect("unable to create URI component");
+        } else {
+            out_str.push(c);
+        }
+    }
+    out_str
+}
+
 /// Constructs a path string from a [Vec] of strings containing the separate path components.
 pub(crate) fn construct_path_string(path_components: Vec<String>) -> String {
     path_components.into_iter().fold(String::new(), |mut a: String, v| {
         // Writing to a String _shouldn't_ cause an error.
         // If it does, something is terribly wrong and we should panic.
-        write!(&mut a, "/{}", v).expect("unable to create path string");
+        write!(&mut a, "/{}", encode_uri_component(&v, PATH_DELIMITERS)).expect("unable to create path string");
         a
     })
 }
 
 /// Constructs a query string from a [Vec] of strings containing the separate query components.
 pub(crate) fn construct_query_string(query_components: Vec<String>) -> String {
-    let mut iter = query_components.iter();
+    let mut iter = query_components
+        .iter()
+        .map(|q| encode_uri_component(q, QUERY_DELIMITERS));
     let mut out_str = String::from("?");
     if let Some(q) = iter.next() {
-        out_str = out_str + q
+        out_str += &q;
     }
     for q in iter {
         out_str += format!("&{}", q).as_ref();
@@ -293,6 +527,38 @@ pub trait CoapMessageCommon {
         self.as_message().options.iter()
     }
 
+    /// Returns the raw value bytes of the first option with the given number, if there is one.
+    ///
+    /// This allows accessing options (e.g., vendor-specific ones stored as [CoapOption::Other])
+    /// without matching on the [CoapOption] variants.
+    /// Values of options that are stored in decoded form (i.e., integer options such as Uri-Port)
+    /// are encoded on demand (see [CoapOption::value_bytes()]), all other values are borrowed from
+    /// the message.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessage, CoapMessageCommon, CoapOption};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType};
+    ///
+    /// let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Empty);
+    /// message.add_option(CoapOption::Other(65000, vec![1, 2, 3].into_boxed_slice()));
+    /// assert_eq!(message.raw_option_bytes(65000).as_deref(), Some([1, 2, 3].as_slice()));
+    /// assert_eq!(message.raw_option_bytes(65001), None);
+    /// ```
+    fn raw_option_bytes(&self, num: CoapOptionNum) -> Option<Cow<'_, [u8]>> {
+        self.raw_option_bytes_all(num).next()
+    }
+
+    /// Returns an iterator over the raw value bytes of all options with the given number (in the
+    /// order in which they were added), which is mostly useful for repeatable options.
+    ///
+    /// See [raw_option_bytes()](CoapMessageCommon::raw_option_bytes()) for more information.
+    fn raw_option_bytes_all(&self, num: CoapOptionNum) -> impl Iterator<Item = Cow<'_, [u8]>> {
+        self.options_iter()
+            .filter(move |option| option.number() == num)
+            .map(CoapOption::value_bytes)
+    }
+
     /// Returns the CoAP message type (confirmable, non-confirmable, acknowledgement, rst) of this message.
     fn type_(&self) -> CoapMessageType {
         self.as_message().type_
@@ -332,20 +598,44 @@ pub trait CoapMessageCommon {
 
     /// Sets the data/body of this message.
     fn set_data<D: Into<Box<[u8]>>>(&mut self, data: Option<D>) {
-        self.as_message_mut().data = data.map(Into::into);
+        self.as_message_mut().data = data.map(|v| into_message_data(v.into()));
     }
 
     /// Returns the message token.
-    fn token(&self) -> Option<&[u8]> {
-        self.as_message().token.as_ref().map(|v| v.as_ref())
+    fn token(&self) -> Option<&CoapToken> {
+        self.as_message().token.as_ref()
     }
 
     /// Sets the message token.
     ///
+    /// Accepts a [CoapToken] as well as raw bytes. Tokens longer than [CoapToken::MAX_LEN] can be
+    /// set, but converting the message into a PDU will fail for them.
+    ///
     /// Note that [CoapSessionCommon::send_request()] will automatically set the token to a random
     /// value if you don't.
     fn set_token<D: Into<Box<[u8]>>>(&mut self, token: Option<D>) {
-        self.as_message_mut().token = token.map(Into::into);
+        self.as_message_mut().token = token.map(|v| CoapToken::from_boxed_unchecked(v.into()));
+    }
+
+    /// Returns the estimated size in bytes of this message when encoded as a PDU for transports
+    /// using the message format of [RFC 7252, Section 3](https://datatracker.ietf.org/doc/html/rfc7252#section-3)
+    /// (i.e., UDP and DTLS).
+    ///
+    /// The estimate consists of the 4 byte message header, the token, all options (including their
+    /// encoded option deltas and lengths), and the payload marker and payload (if there is one).
+    /// Option values that cannot be encoded are not included in the estimate.
+    ///
+    /// Use this function (or [fits_in_pdu()](CoapMessageCommon::fits_in_pdu())) to detect messages
+    /// that exceed the maximum PDU size of a session (see [CoapSessionCommon::max_pdu_size()])
+    /// before sending them, e.g., in order to split their payload using block-wise transfers.
+    fn estimated_pdu_size(&self) -> usize {
+        self.as_message().encoded_len()
+    }
+
+    /// Returns whether the encoded size of this message does not exceed `max_size` (see
+    /// [estimated_pdu_size()](CoapMessageCommon::estimated_pdu_size())).
+    fn fits_in_pdu(&self, max_size: usize) -> bool {
+        self.estimated_pdu_size() <= max_size
     }
 
     /// Returns a reference to this message.
@@ -354,8 +644,29 @@ pub trait CoapMessageCommon {
     fn as_message_mut(&mut self) -> &mut CoapMessage;
 }
 
+/// Result of fitting a message within a size limit using [CoapMessage::truncate_to_fit()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TruncationResult {
+    /// The message fits within the size limit after dropping the given number of elective
+    /// options (which is zero if the message already fit).
+    ///
+    /// The message header, token and all critical options are always preserved.
+    Fits { dropped: usize },
+    /// The message does not fit within the size limit even after dropping all options that may
+    /// be dropped, e.g., because of its critical options or payload.
+    ///
+    /// The message is left unchanged in this case.
+    ImpossibleWithCriticalOptions,
+}
+
 /// Representation of a CoAP message.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+///
+/// If the `serde` feature is enabled, messages can be (de)serialized (e.g., to JSON for logging
+/// or test fixtures), with the token and body represented as hexadecimal strings.
+/// This representation is independent of the wire encoding of the message. Note that messages
+/// are not validated when deserializing them, and that the time of reception is not included.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CoapMessage {
     /// CoAP message type (CON, NON, ACK, RST).
     type_: CoapMessageType,
@@ -366,9 +677,36 @@ pub struct CoapMessage {
     /// List of CoAP options associated with this message.
     options: Vec<CoapOption>,
     /// CoAP message token – used for request-response-matching.
-    token: Option<Box<[u8]>>,
+    token: Option<CoapToken>,
     /// Message body of this message.
-    data: Option<Box<[u8]>>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_hex::option"))]
+    data: Option<MessageData>,
+    /// Point in time at which this message was received (if known).
+    ///
+    /// This is not part of the message itself, so it is ignored when comparing or hashing
+    /// messages.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    received_at: Option<Instant>,
+    /// Maximum number of options this message may contain when serializing it (see
+    /// [CoapMessage::set_max_options()]).
+    ///
+    /// Like the time of reception, this is not part of the message itself.
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_max_options"))]
+    max_options: usize,
+    /// Whether all repeated non-repeatable options should be reported when parsing this message
+    /// into a request or response (see [CoapMessage::with_strict_parsing()]).
+    ///
+    /// Like the time of reception, this is not part of the message itself.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    strict_parsing: bool,
+}
+
+/// Default maximum number of options per message (see [CoapMessage::set_max_options()]).
+pub const DEFAULT_MAX_OPTIONS: usize = 256;
+
+#[cfg(feature = "serde")]
+fn default_max_options() -> usize {
+    DEFAULT_MAX_OPTIONS
 }
 
 impl CoapMessage {
@@ -382,43 +720,642 @@ impl CoapMessage {
             options: Vec::new(),
             token: None,
             data: None,
+            received_at: None,
+            max_options: DEFAULT_MAX_OPTIONS,
+            strict_parsing: false,
         }
     }
 
+    /// Returns the maximum number of options this message may contain when serializing it (see
+    /// [CoapMessage::set_max_options()]).
+    pub fn max_options(&self) -> usize {
+        self.max_options
+    }
+
+    /// Sets the maximum number of options this message may contain when serializing it (the
+    /// default is [DEFAULT_MAX_OPTIONS]).
+    ///
+    /// Messages with more options are rejected with [MessageConversionError::TooManyOptions]
+    /// instead of being encoded, which limits the work spent on messages with a pathological number
+    /// of options (e.g., when forwarding messages in a proxy).
+    pub fn set_max_options(&mut self, max_options: usize) {
+        self.max_options = max_options;
+    }
+
+    /// Enables or disables strict parsing of this message.
+    ///
+    /// By default, [CoapRequest::from_message()] and [CoapResponse::from_message()] fail with
+    /// [MessageConversionError::NonRepeatableOptionRepeated] for the first repeated non-repeatable
+    /// option they encounter. With strict parsing, the whole message is checked beforehand and all
+    /// offending option types are reported at once using
+    /// [MessageConversionError::NonRepeatableOptionsRepeated], which is useful for diagnostics.
+    ///
+    /// Use [CoapContext::set_strict_parsing()](crate::CoapContext::set_strict_parsing()) to
+    /// enable strict parsing for received messages.
+    pub fn with_strict_parsing(mut self, strict: bool) -> CoapMessage {
+        self.strict_parsing = strict;
+        self
+    }
+
+    /// Returns whether strict parsing is enabled for this message (see
+    /// [CoapMessage::with_strict_parsing()]).
+    pub fn strict_parsing(&self) -> bool {
+        self.strict_parsing
+    }
+
+    /// Returns all non-repeatable option types that occur more than once in this message.
+    ///
+    /// Each option type is returned once, in the order in which its first repetition occurs.
+    /// Options of unknown types are not checked, as their repeatability cannot be determined.
+    pub fn find_duplicate_non_repeatable_options(&self) -> Vec<CoapOptionType> {
+        let mut seen_options = HashSet::new();
+        let mut duplicates = Vec::new();
+        for option_type in self.non_repeatable_option_types() {
+            if !seen_options.insert(option_type) && !duplicates.contains(&option_type) {
+                duplicates.push(option_type);
+            }
+        }
+        duplicates
+    }
+
+    /// Returns whether any non-repeatable option occurs more than once in this message.
+    ///
+    /// Unlike [CoapMessage::find_duplicate_non_repeatable_options()], this function stops at the
+    /// first repetition.
+    pub fn has_any_duplicates(&self) -> bool {
+        self.first_duplicate_non_repeatable_option().is_some()
+    }
+
+    /// Returns the types of all known non-repeatable options in this message in order.
+    fn non_repeatable_option_types(&self) -> impl Iterator<Item = CoapOptionType> + '_ {
+        self.options
+            .iter()
+            .filter_map(|o| CoapOptionType::try_from(o.number()).ok())
+            .filter(|t| !t.is_repeatable())
+    }
+
+    /// Returns the first non-repeatable option type that occurs more than once in this message.
+    fn first_duplicate_non_repeatable_option(&self) -> Option<CoapOptionType> {
+        let mut seen_options = HashSet::new();
+        self.non_repeatable_option_types().find(|t| !seen_options.insert(*t))
+    }
+
+    /// Checks for repeated non-repeatable options if strict parsing is enabled (see
+    /// [CoapMessage::with_strict_parsing()]).
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::NonRepeatableOptionsRepeated] with all offending option
+    /// types if strict parsing is enabled and any non-repeatable option is repeated.
+    pub(crate) fn check_strict_option_repetition(&self) -> Result<(), MessageConversionError> {
+        if self.strict_parsing {
+            let duplicates = self.find_duplicate_non_repeatable_options();
+            if !duplicates.is_empty() {
+                return Err(MessageConversionError::NonRepeatableOptionsRepeated(duplicates));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates an empty acknowledgement message for the given confirmable message.
+    ///
+    /// The created message has the Empty code (0.00), an empty token and the same message ID as
+    /// `for_message` (see [RFC 7252, Section 4.2](https://datatracker.ietf.org/doc/html/rfc7252#section-4.2)).
+    ///
+    /// # Errors
+    /// Returns [MessageTypeError::InvalidForReplyTo] if `for_message` is not confirmable.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::MessageTypeError;
+    /// use libcoap_rs::message::{CoapMessage, CoapMessageCommon};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode};
+    ///
+    /// let mut request = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    /// request.set_mid(Some(42));
+    ///
+    /// let ack = CoapMessage::new_ack(&request)?;
+    /// assert_eq!(ack.type_(), CoapMessageType::Ack);
+    /// assert_eq!(ack.code(), CoapMessageCode::Empty);
+    /// assert_eq!(ack.mid(), Some(42));
+    ///
+    /// # Result::<(), MessageTypeError>::Ok(())
+    /// ```
+    pub fn new_ack(for_message: &CoapMessage) -> Result<CoapMessage, MessageTypeError> {
+        match for_message.type_ {
+            CoapMessageType::Con => Ok(CoapMessage::new_empty_reply(CoapMessageType::Ack, for_message)),
+            v => Err(MessageTypeError::InvalidForReplyTo(v)),
+        }
+    }
+
+    /// Creates an empty reset message for the given message, e.g., to reject a confirmable
+    /// message that cannot be processed or to stop receiving observe notifications.
+    ///
+    /// The created message has the Empty code (0.00), an empty token and the same message ID as
+    /// `for_message` (see [RFC 7252, Section 4.2 and 4.3](https://datatracker.ietf.org/doc/html/rfc7252#section-4.2)).
+    ///
+    /// # Errors
+    /// Returns [MessageTypeError::InvalidForReplyTo] if `for_message` is neither confirmable nor
+    /// non-confirmable (ACK and RST messages must not be reset).
+    pub fn new_rst(for_message: &CoapMessage) -> Result<CoapMessage, MessageTypeError> {
+        match for_message.type_ {
+            CoapMessageType::Con | CoapMessageType::Non => {
+                Ok(CoapMessage::new_empty_reply(CoapMessageType::Rst, for_message))
+            },
+            v => Err(MessageTypeError::InvalidForReplyTo(v)),
+        }
+    }
+
+    /// Returns whether this message is a Reset message, i.e., an empty message of type RST, which
+    /// indicates that the peer was unable to process the message with the same message ID (see
+    /// [RFC 7252, Section 4.2](https://datatracker.ietf.org/doc/html/rfc7252#section-4.2)).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::CoapMessage;
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode};
+    ///
+    /// let request = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    /// assert!(CoapMessage::new_rst(&request).unwrap().is_reset());
+    /// assert!(!CoapMessage::new_ack(&request).unwrap().is_reset());
+    /// ```
+    pub fn is_reset(&self) -> bool {
+        self.type_ == CoapMessageType::Rst && self.code == CoapMessageCode::Empty
+    }
+
+    /// Returns whether this message is an empty acknowledgement, i.e., an ACK that does not carry
+    /// a piggybacked response (see
+    /// [RFC 7252, Section 5.2.2](https://datatracker.ietf.org/doc/html/rfc7252#section-5.2.2)).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::CoapMessage;
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode};
+    ///
+    /// let request = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    /// assert!(CoapMessage::new_ack(&request).unwrap().is_empty_ack());
+    /// let response = CoapMessage::new(CoapMessageType::Ack, CoapMessageCode::Response(CoapResponseCode::Content));
+    /// assert!(!response.is_empty_ack());
+    /// ```
+    pub fn is_empty_ack(&self) -> bool {
+        self.type_ == CoapMessageType::Ack && self.code == CoapMessageCode::Empty
+    }
+
+    /// Creates an empty message of the given type with the message ID of `for_message`.
+    fn new_empty_reply(type_: CoapMessageType, for_message: &CoapMessage) -> CoapMessage {
+        let mut message = CoapMessage::new(type_, CoapMessageCode::Empty);
+        message.mid = for_message.mid;
+        message.token = Some(CoapToken::default());
+        message
+    }
+
     /// Parses the given raw coap_pdu_t into a CoapMessage.
     ///
     /// # Safety
     /// raw_pdu must point to a valid instance of coap_pdu_t.
     pub unsafe fn from_raw_pdu(raw_pdu: *const coap_pdu_t) -> Result<CoapMessage, MessageConversionError> {
+        Self::parse_raw_pdu(raw_pdu, false)
+    }
+
+    /// Parses the given raw coap_pdu_t into a CoapMessage on a best-effort basis, e.g., for
+    /// logging or diagnostics.
+    ///
+    /// In contrast to [CoapMessage::from_raw_pdu()], options whose values cannot be parsed (e.g.,
+    /// string options containing invalid UTF-8) do not cause an error, but are instead represented
+    /// as [CoapOption::Other] containing the raw option value.
+    /// The numbers of these options can be retrieved using
+    /// [CoapMessage::lossy_option_numbers()].
+    /// If the message code is unknown, it is replaced by [CoapMessageCode::Empty].
+    ///
+    /// # Safety
+    /// raw_pdu must point to a valid instance of coap_pdu_t.
+    pub unsafe fn from_raw_pdu_lossy(raw_pdu: *const coap_pdu_t) -> CoapMessage {
+        // Lossy parsing replaces all values that would cause an error, so it cannot fail.
+        Self::parse_raw_pdu(raw_pdu, true).expect("lossy parsing of raw PDU failed")
+    }
+
+    /// Returns whether this message contains options that could not be parsed and are therefore
+    /// represented as [CoapOption::Other] (see [CoapMessage::from_raw_pdu_lossy()]).
+    pub fn has_lossy_options(&self) -> bool {
+        self.options.iter().any(CoapOption::is_lossy)
+    }
+
+    /// Returns the numbers of all options of this message that could not be parsed and are
+    /// therefore represented as [CoapOption::Other] (see [CoapMessage::from_raw_pdu_lossy()]), in
+    /// the order of their first occurrence.
+    ///
+    /// As [CoapOption::other()] does not allow creating options with known option numbers, these
+    /// are identified as [CoapOption::Other] instances whose number belongs to a known
+    /// [CoapOptionType].
+    pub fn lossy_option_numbers(&self) -> Vec<CoapOptionNum> {
+        let mut numbers = Vec::new();
+        for option in self.options.iter().filter(|v| v.is_lossy()) {
+            if !numbers.contains(&option.number()) {
+                numbers.push(option.number());
+            }
+        }
+        numbers
+    }
+
+    /// Parses the given raw coap_pdu_t into a CoapMessage.
+    ///
+    /// If `lossy` is true, parsing does not fail, see [CoapMessage::from_raw_pdu_lossy()].
+    ///
+    /// # Safety
+    /// raw_pdu must point to a valid instance of coap_pdu_t.
+    unsafe fn parse_raw_pdu(raw_pdu: *const coap_pdu_t, lossy: bool) -> Result<CoapMessage, MessageConversionError> {
         ensure_coap_started();
         let mut option_iter = MaybeUninit::zeroed();
         coap_option_iterator_init(raw_pdu, option_iter.as_mut_ptr(), std::ptr::null());
         let mut option_iter = option_iter.assume_init();
         let mut options = Vec::new();
         while let Some(read_option) = coap_option_next(&mut option_iter).as_ref() {
-            options.push(CoapOption::from_raw_opt(option_iter.number, read_option).map_err(|e| {
-                MessageConversionError::InvalidOptionValue(CoapOptionType::try_from(option_iter.number).ok(), e)
-            })?);
+            let option = match CoapOption::from_raw_opt(option_iter.number, read_option) {
+                Ok(option) => option,
+                Err(_) if lossy => {
+                    let value =
+                        std::slice::from_raw_parts(coap_opt_value(read_option), coap_opt_length(read_option) as usize);
+                    CoapOption::Other(option_iter.number, Box::from(value))
+                },
+                Err(e) => {
+                    let option_type = CoapOptionType::try_from(option_iter.number).ok();
+                    return Err(MessageConversionError::InvalidOptionValue(option_type, e));
+                },
+            };
+            options.push(option);
         }
         let mut len: usize = 0;
         let mut data = std::ptr::null();
         coap_get_data(raw_pdu, &mut len, &mut data);
         let data = match len {
             0 => None,
-            len => Some(Vec::from(std::slice::from_raw_parts(data, len)).into_boxed_slice()),
+            len => Some(into_message_data(Box::from(std::slice::from_raw_parts(data, len)))),
         };
         let raw_token = coap_pdu_get_token(raw_pdu);
-        let token = Vec::from(std::slice::from_raw_parts(raw_token.s, raw_token.length));
+        let token = CoapToken::from_bytes_unchecked(std::slice::from_raw_parts(raw_token.s, raw_token.length));
         Ok(CoapMessage {
             type_: coap_pdu_get_type(raw_pdu).into(),
-            code: coap_pdu_get_code(raw_pdu).try_into().unwrap(),
+            code: match coap_pdu_get_code(raw_pdu).try_into() {
+                Ok(code) => code,
+                Err(_) if lossy => CoapMessageCode::Empty,
+                Err(e) => return Err(e.into()),
+            },
             mid: Some(coap_pdu_get_mid(raw_pdu)),
             options,
-            token: Some(token.into_boxed_slice()),
+            token: Some(token),
             data,
+            received_at: None,
+            max_options: DEFAULT_MAX_OPTIONS,
+            strict_parsing: false,
         })
     }
 
+    /// Parses the given raw coap_pdu_t into a CoapMessage, recording the current point in time as
+    /// the time at which the message was received (see [CoapMessage::received_at()]).
+    ///
+    /// The timestamp is taken before the message is parsed, so this function should be called as
+    /// early as possible after libcoap has provided the raw PDU.
+    /// As this function is intended for received messages, the combination of message type and
+    /// code is validated using [CoapMessage::validate_type_code()].
+    ///
+    /// Returns the parsed message along with the recorded timestamp.
+    ///
+    /// # Safety
+    /// raw_pdu must point to a valid instance of coap_pdu_t.
+    pub unsafe fn from_raw_pdu_with_timestamp(
+        raw_pdu: *const coap_pdu_t,
+    ) -> Result<(CoapMessage, Instant), MessageConversionError> {
+        let received_at = Instant::now();
+        let mut message = Self::from_raw_pdu(raw_pdu)?;
+        message.validate_type_code()?;
+        message.received_at = Some(received_at);
+        Ok((message, received_at))
+    }
+
+    /// Returns the point in time at which this message was received, if it was parsed using
+    /// [CoapMessage::from_raw_pdu_with_timestamp()].
+    ///
+    /// For requests provided to resource handlers, this is the time at which libcoap handed the
+    /// request to this library.
+    pub fn received_at(&self) -> Option<Instant> {
+        self.received_at
+    }
+
+    /// Checks whether the message type of this message may be combined with its message code
+    /// according to
+    /// [RFC 7252, Sections 4.1 to 4.3](https://datatracker.ietf.org/doc/html/rfc7252#section-4.1).
+    ///
+    /// Received messages are checked automatically, but as parsing messages (e.g., using
+    /// [CoapMessage::from_bytes()]) does not validate this combination, this function can be used
+    /// to check messages obtained in other ways.
+    ///
+    /// Note that whether an acknowledgement or reset refers to a message that may actually be
+    /// acknowledged or rejected (e.g., an acknowledgement for a non-confirmable message) can only
+    /// be determined by the session the message was received on.
+    ///
+    /// # Errors
+    ///
+    /// Returns [MessageConversionError::InvalidTypeCodeCombination] if
+    /// - a non-confirmable message is empty (non-confirmable messages always carry a request or
+    ///   response),
+    /// - an acknowledgement or reset carries a request, or
+    /// - a reset carries a response (resets must always be empty).
+    ///
+    /// Returns [MessageConversionError::DataInEmptyMessage] if an empty message (e.g., a reset)
+    /// contains a payload.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::MessageConversionError;
+    /// use libcoap_rs::message::CoapMessage;
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode};
+    ///
+    /// let ping = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Empty);
+    /// assert_eq!(ping.validate_type_code(), Ok(()));
+    ///
+    /// let code = CoapMessageCode::Request(CoapRequestCode::Get);
+    /// let ack = CoapMessage::new(CoapMessageType::Ack, code);
+    /// assert_eq!(
+    ///     ack.validate_type_code(),
+    ///     Err(MessageConversionError::InvalidTypeCodeCombination(CoapMessageType::Ack, code))
+    /// );
+    /// ```
+    pub fn validate_type_code(&self) -> Result<(), MessageConversionError> {
+        let valid = match self.code {
+            CoapMessageCode::Empty => self.type_ != CoapMessageType::Non,
+            CoapMessageCode::Request(_) => matches!(self.type_, CoapMessageType::Con | CoapMessageType::Non),
+            CoapMessageCode::Response(_) => self.type_ != CoapMessageType::Rst,
+        };
+        if !valid {
+            return Err(MessageConversionError::InvalidTypeCodeCombination(
+                self.type_, self.code,
+            ));
+        }
+        if self.code == CoapMessageCode::Empty && self.data.as_ref().is_some_and(|v| !v.is_empty()) {
+            return Err(MessageConversionError::DataInEmptyMessage);
+        }
+        Ok(())
+    }
+
+    /// Drops elective options from this message until its encoded size (see
+    /// [CoapMessageCommon::estimated_pdu_size()]) does not exceed `max_size`.
+    ///
+    /// This can be used on devices with small MTUs in order to send messages that would otherwise
+    /// be too large. Options are dropped in the following order, stopping as soon as the message
+    /// fits:
+    /// 1. elective options of unknown types (i.e., [CoapOption::Other] instances with an even
+    ///    option number, see [RFC 7252, Section 5.4.6](https://datatracker.ietf.org/doc/html/rfc7252#section-5.4.6)),
+    /// 2. the "Size1" and "Size2" options,
+    /// 3. the "Content-Format" option.
+    ///
+    /// The message header, token and payload as well as all critical options are never changed.
+    /// Other elective options (e.g., "ETag" or "Max-Age") are kept as well, as most of them
+    /// change the meaning of the message.
+    ///
+    /// If the message does not fit even after dropping all of these options,
+    /// [TruncationResult::ImpossibleWithCriticalOptions] is returned and the message is left
+    /// unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessage, CoapMessageCommon, CoapOption, TruncationResult};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapResponseCode};
+    ///
+    /// let mut message = CoapMessage::new(CoapMessageType::Ack, CoapResponseCode::Content.into());
+    /// message.add_option(CoapOption::Size2(1024));
+    /// message.set_data(Some(vec![0; 8]));
+    /// // Header (4) + Size2 option (1 + 1 + 2) + payload marker (1) + payload (8) = 17 bytes.
+    /// assert_eq!(message.truncate_to_fit(16), TruncationResult::Fits { dropped: 1 });
+    /// assert_eq!(message.estimated_pdu_size(), 13);
+    /// assert_eq!(message.truncate_to_fit(12), TruncationResult::ImpossibleWithCriticalOptions);
+    /// ```
+    pub fn truncate_to_fit(&mut self, max_size: usize) -> TruncationResult {
+        /// Returns the position of an option in the order in which options are dropped, or `None`
+        /// if the option must not be dropped.
+        fn drop_priority(option: &CoapOption) -> Option<u8> {
+            match option {
+                CoapOption::Other(number, _)
+                    if !is_critical_option(*number) && CoapOptionType::try_from(*number).is_err() =>
+                {
+                    Some(0)
+                },
+                CoapOption::Size1(_) | CoapOption::Size2(_) => Some(1),
+                CoapOption::ContentFormat(_) => Some(2),
+                _ => None,
+            }
+        }
+
+        let original_options = self.options.clone();
+        let mut dropped = 0;
+        for priority in 0..=2 {
+            // Options added last are dropped first.
+            while self.encoded_len() > max_size {
+                match self.options.iter().rposition(|o| drop_priority(o) == Some(priority)) {
+                    Some(i) => {
+                        self.options.remove(i);
+                        dropped += 1;
+                    },
+                    None => break,
+                }
+            }
+        }
+        if self.encoded_len() > max_size {
+            self.options = original_options;
+            return TruncationResult::ImpossibleWithCriticalOptions;
+        }
+        TruncationResult::Fits { dropped }
+    }
+
+    /// Parses the given bytes as a CoAP message in the wire format used for CoAP over UDP/DTLS
+    /// (see [RFC 7252, Section 3](https://datatracker.ietf.org/doc/html/rfc7252#section-3)).
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::MalformedMessage] if `bytes` is not a well-formed CoAP
+    /// message, and other [MessageConversionError]s if the message contents are invalid (e.g.,
+    /// unknown message codes or invalid option values).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::MessageConversionError;
+    /// use libcoap_rs::message::{CoapMessage, CoapMessageCommon};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode, CoapToken};
+    ///
+    /// // CON GET request with message ID 0x1234, token 0xAB and Uri-Path "test".
+    /// let bytes = [0x41, 0x01, 0x12, 0x34, 0xAB, 0xB4, b't', b'e', b's', b't'];
+    /// let message = CoapMessage::from_bytes(&bytes)?;
+    ///
+    /// assert_eq!(message.type_(), CoapMessageType::Con);
+    /// assert_eq!(message.code(), CoapMessageCode::Request(CoapRequestCode::Get));
+    /// assert_eq!(message.mid(), Some(0x1234));
+    /// assert_eq!(message.token().map(CoapToken::as_bytes), Some([0xAB].as_slice()));
+    /// assert_eq!(message.to_bytes()?, bytes);
+    ///
+    /// # Result::<(), MessageConversionError>::Ok(())
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<CoapMessage, MessageConversionError> {
+        ensure_coap_started();
+        // SAFETY: all values are valid, cannot cause UB.
+        let pdu = unsafe {
+            coap_pdu_init(
+                coap_pdu_type_t::COAP_MESSAGE_CON,
+                coap_pdu_code_t::COAP_EMPTY_CODE,
+                0,
+                bytes.len(),
+            )
+        };
+        if pdu.is_null() {
+            return Err(MessageConversionError::Unknown);
+        }
+        // SAFETY: We just checked that pdu is a valid pointer, data pointer and length are valid.
+        // The PDU is deleted after parsing, from_raw_pdu() copies all relevant data.
+        unsafe {
+            let result = if coap_pdu_parse(coap_proto_t::COAP_PROTO_UDP, bytes.as_ptr(), bytes.len(), pdu) == 0 {
+                Err(MessageConversionError::MalformedMessage)
+            } else {
+                CoapMessage::from_raw_pdu(pdu)
+            };
+            coap_delete_pdu(pdu);
+            result
+        }
+    }
+
+    /// Serializes this message into the wire format used for CoAP over UDP/DTLS (see
+    /// [RFC 7252, Section 3](https://datatracker.ietf.org/doc/html/rfc7252#section-3)).
+    ///
+    /// Options are sorted by their option number, with options of the same number remaining in the
+    /// order they were added in. A missing token is encoded as a zero-length token.
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::MissingMessageId] if no message ID has been set,
+    /// [MessageConversionError::TokenTooLong] if the token exceeds 8 bytes,
+    /// [MessageConversionError::DataInEmptyMessage] if a message with code 0.00 contains data,
+    /// [MessageConversionError::TooManyOptions] if the message contains more options than allowed
+    /// (see [CoapMessage::set_max_options()]), and other [MessageConversionError]s if the options
+    /// of this message are invalid.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MessageConversionError> {
+        self.check_option_count()?;
+        self.check_option_repetition()?;
+        let mid = self.mid.ok_or(MessageConversionError::MissingMessageId)?;
+        let token = self.token.as_ref().map(CoapToken::as_bytes).unwrap_or_default();
+        if token.len() > MAX_TOKEN_LEN {
+            return Err(MessageConversionError::TokenTooLong);
+        }
+        let data = self.data.as_deref().unwrap_or_default();
+        if !data.is_empty() && self.code == CoapMessageCode::Empty {
+            return Err(MessageConversionError::DataInEmptyMessage);
+        }
+
+        let mut bytes = Vec::with_capacity(4 + token.len() + data.len() + 1);
+        bytes.push(0b01 << 6 | (self.type_ as u8) << 4 | token.len() as u8);
+        bytes.push(self.code.to_raw_pdu_code() as u8);
+        bytes.extend_from_slice(&(mid as u16).to_be_bytes());
+        bytes.extend_from_slice(token);
+
+        let mut options = self.options.clone();
+        options.sort_by_key(CoapOption::number);
+        let mut last_number = 0;
+        for option in options {
+            let number = option.number();
+            let value = option
+                .into_value_bytes()
+                .map_err(|e| MessageConversionError::InvalidOptionValue(CoapOptionType::try_from(number).ok(), e))?;
+            if value.len() > MAX_OPTION_VALUE_LEN {
+                return Err(MessageConversionError::InvalidOptionValue(
+                    CoapOptionType::try_from(number).ok(),
+                    OptionValueError::TooLong,
+                ));
+            }
+            let (delta_nibble, delta_ext) = encode_option_header_value((number - last_number).into());
+            let (length_nibble, length_ext) = encode_option_header_value(value.len());
+            bytes.push(delta_nibble << 4 | length_nibble);
+            bytes.extend_from_slice(&delta_ext);
+            bytes.extend_from_slice(&length_ext);
+            bytes.extend_from_slice(&value);
+            last_number = number;
+        }
+
+        if !data.is_empty() {
+            bytes.push(0xFF);
+            bytes.extend_from_slice(data);
+        }
+        Ok(bytes)
+    }
+
+    /// Returns the options of this message as a map from option numbers to the encoded values of
+    /// all options with that number.
+    ///
+    /// Values of repeated options are stored in the order they were added in, which is also the
+    /// order in which they are encoded.
+    ///
+    /// This representation is independent of the option types known to this library, which makes
+    /// it useful for transforming messages (e.g., in proxies) or for serializing options into
+    /// other formats. Use [CoapMessage::from_options_map()] to create a message from such a map.
+    ///
+    /// # Errors
+    /// Returns an [OptionValueError] if the value of one of the options cannot be encoded (see
+    /// [CoapOption::into_value_bytes()]).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::OptionValueError;
+    /// use libcoap_rs::message::{CoapMessage, CoapMessageCommon, CoapOption};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapOptionType, CoapRequestCode};
+    ///
+    /// let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    /// message.add_option(CoapOption::UriPath("sensors".to_string()));
+    /// message.add_option(CoapOption::UriPath("temp".to_string()));
+    /// message.add_option(CoapOption::UriPort(5683));
+    ///
+    /// let map = message.options_as_map()?;
+    /// assert_eq!(
+    ///     map[&(CoapOptionType::UriPath as u16)],
+    ///     vec![b"sensors".to_vec().into_boxed_slice(), b"temp".to_vec().into_boxed_slice()]
+    /// );
+    /// assert_eq!(map[&(CoapOptionType::UriPort as u16)], vec![vec![0x16, 0x33].into_boxed_slice()]);
+    /// # Result::<(), OptionValueError>::Ok(())
+    /// ```
+    pub fn options_as_map(&self) -> Result<BTreeMap<CoapOptionNum, Vec<Box<[u8]>>>, OptionValueError> {
+        let mut map: BTreeMap<CoapOptionNum, Vec<Box<[u8]>>> = BTreeMap::new();
+        for option in &self.options {
+            map.entry(option.number())
+                .or_default()
+                .push(option.clone().into_value_bytes()?);
+        }
+        Ok(map)
+    }
+
+    /// Creates a new message with the given type and code that contains the options described by
+    /// `map` (see [CoapMessage::options_as_map()]).
+    ///
+    /// Values of known option types are parsed into their typed [CoapOption] variants, all other
+    /// values are added as [CoapOption::Other].
+    /// The message ID, token and body of the created message are not set.
+    ///
+    /// # Errors
+    /// Returns an [OptionValueError] if one of the values is not a valid value for its option
+    /// number (see the `TryFrom<(CoapOptionNum, &[u8])>` implementation of [CoapOption]).
+    pub fn from_options_map(
+        type_: CoapMessageType,
+        code: CoapMessageCode,
+        map: BTreeMap<CoapOptionNum, Vec<Box<[u8]>>>,
+    ) -> Result<CoapMessage, OptionValueError> {
+        let mut message = CoapMessage::new(type_, code);
+        for (number, values) in map {
+            for value in values {
+                message.add_option(CoapOption::try_from((number, value.as_ref()))?);
+            }
+        }
+        Ok(message)
+    }
+
+    /// Returns the body of this message as a [bytes::Bytes] buffer.
+    ///
+    /// The returned buffer shares its memory with the body stored in this message, i.e., the body
+    /// is not copied.
+    #[cfg(feature = "bytes")]
+    pub fn data_as_bytes(&self) -> Option<::bytes::Bytes> {
+        self.data.clone()
+    }
+
     /// Converts this message into a raw PDU suitable for sending using the raw [coap_send()](libcoap_sys::coap_send())
     /// function.
     ///
@@ -452,6 +1389,66 @@ impl CoapMessage {
         }
     }
 
+    /// Returns the size in bytes of this message when encoded for transports using the CoAP
+    /// message format of [RFC 7252, Section 3](https://datatracker.ietf.org/doc/html/rfc7252#section-3)
+    /// (i.e., UDP and DTLS).
+    ///
+    /// Option values that cannot be encoded are not included in the returned size.
+    pub(crate) fn encoded_len(&self) -> usize {
+        /// Number of extended bytes required for an option delta or length.
+        fn extended_len(value: usize) -> usize {
+            match value {
+                0..=12 => 0,
+                13..=268 => 1,
+                _ => 2,
+            }
+        }
+        let mut options: Vec<(CoapOptionNum, usize)> = self
+            .options
+            .iter()
+            .map(|o| (o.number(), o.clone().into_value_bytes().map_or(0, |v| v.len())))
+            .collect();
+        // Options are encoded in the order of their option numbers.
+        options.sort_by_key(|(number, _)| *number);
+        let mut previous_number = 0;
+        let mut len = 4 + self.token.as_ref().map_or(0, |v| v.len());
+        for (number, value_len) in options {
+            len += 1 + extended_len(usize::from(number - previous_number)) + extended_len(value_len) + value_len;
+            previous_number = number;
+        }
+        if let Some(data) = self.data.as_ref().filter(|v| !v.is_empty()) {
+            // Payload marker and payload.
+            len += 1 + data.len();
+        }
+        len
+    }
+
+    /// Checks that this message does not contain more options than allowed (see
+    /// [CoapMessage::set_max_options()]).
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::TooManyOptions] if the limit is exceeded.
+    fn check_option_count(&self) -> Result<(), MessageConversionError> {
+        if self.options.len() > self.max_options {
+            return Err(MessageConversionError::TooManyOptions(self.options.len()));
+        }
+        Ok(())
+    }
+
+    /// Checks that no non-repeatable option occurs more than once in this message.
+    ///
+    /// Options of unknown types are not checked, as their repeatability cannot be determined.
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::NonRepeatableOptionRepeated] for the first non-repeatable
+    /// option type that occurs multiple times.
+    fn check_option_repetition(&self) -> Result<(), MessageConversionError> {
+        match self.first_duplicate_non_repeatable_option() {
+            Some(option_type) => Err(MessageConversionError::NonRepeatableOptionRepeated(option_type)),
+            None => Ok(()),
+        }
+    }
+
     /// Applies this message to the given raw PDU.
     ///
     /// Note that existing parameters of the PDU are not explicitly removed. However, because this
@@ -472,10 +1469,16 @@ impl CoapMessage {
         session: &S,
     ) -> Result<*mut coap_pdu_t, MessageConversionError> {
         assert!(!raw_pdu.is_null(), "attempted to apply CoapMessage to null pointer");
+        self.check_option_count()?;
+        self.check_option_repetition()?;
         coap_pdu_set_type(raw_pdu, self.type_.to_raw_pdu_type());
         coap_pdu_set_code(raw_pdu, self.code.to_raw_pdu_code());
         let message = self.as_message_mut();
-        let token: &[u8] = message.token.as_ref().ok_or(MessageConversionError::MissingToken)?;
+        let token = message
+            .token
+            .as_ref()
+            .map(CoapToken::as_bytes)
+            .ok_or(MessageConversionError::MissingToken)?;
         if coap_add_token(raw_pdu, token.len(), token.as_ptr()) == 0 {
             return Err(MessageConversionError::Unknown);
         }
@@ -512,6 +1515,7 @@ impl CoapMessage {
             match message.code {
                 CoapMessageCode::Empty => return Err(MessageConversionError::DataInEmptyMessage),
                 CoapMessageCode::Request(_) => {
+                    let data = Vec::from(data).into_boxed_slice();
                     let len = data.len();
                     let box_ptr = Box::into_raw(data);
                     coap_add_data_large_request(
@@ -537,6 +1541,31 @@ impl CoapMessage {
     }
 }
 
+impl PartialEq for CoapMessage {
+    fn eq(&self, other: &Self) -> bool {
+        // The time of reception is deliberately not compared, see the field documentation.
+        self.type_ == other.type_
+            && self.code == other.code
+            && self.mid == other.mid
+            && self.options == other.options
+            && self.token == other.token
+            && self.data == other.data
+    }
+}
+
+impl Eq for CoapMessage {}
+
+impl Hash for CoapMessage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_.hash(state);
+        self.code.hash(state);
+        self.mid.hash(state);
+        self.options.hash(state);
+        self.token.hash(state);
+        self.data.hash(state);
+    }
+}
+
 impl CoapMessageCommon for CoapMessage {
     fn as_message(&self) -> &CoapMessage {
         self
@@ -559,6 +1588,47 @@ impl From<CoapResponse> for CoapMessage {
     }
 }
 
+/// Parses a CoAP message from its wire format (see [CoapMessage::from_bytes()]).
+///
+/// The body of the resulting message references the body contained in the provided buffer, i.e.,
+/// it is not copied.
+#[cfg(feature = "bytes")]
+impl TryFrom<::bytes::Bytes> for CoapMessage {
+    type Error = MessageConversionError;
+
+    fn try_from(value: ::bytes::Bytes) -> Result<Self, Self::Error> {
+        let mut message = CoapMessage::from_bytes(&value)?;
+        // The body is always located at the end of the message, so we can simply reference the
+        // corresponding part of the buffer instead of the copy created while parsing.
+        if let Some(data_len) = message.data.as_ref().map(|v| v.len()) {
+            message.data = Some(value.slice(value.len() - data_len..));
+        }
+        Ok(message)
+    }
+}
+
+/// Serializes a CoAP message into its wire format (see [CoapMessage::to_bytes()]).
+#[cfg(feature = "bytes")]
+impl TryFrom<CoapMessage> for ::bytes::Bytes {
+    type Error = MessageConversionError;
+
+    fn try_from(value: CoapMessage) -> Result<Self, Self::Error> {
+        value.to_bytes().map(::bytes::Bytes::from)
+    }
+}
+
+/// Encodes an option delta or length into the 4-bit header nibble and the extended bytes following
+/// the option header (see [RFC 7252, Section 3.1](https://datatracker.ietf.org/doc/html/rfc7252#section-3.1)).
+///
+/// `value` must not exceed [MAX_OPTION_VALUE_LEN].
+fn encode_option_header_value(value: usize) -> (u8, Vec<u8>) {
+    match value {
+        0..=12 => (value as u8, Vec::new()),
+        13..=268 => (13, vec![(value - 13) as u8]),
+        _ => (14, ((value - 269) as u16).to_be_bytes().to_vec()),
+    }
+}
+
 /// Handler provided to libcoap to cleanup large message bodies.
 unsafe extern "C" fn large_data_cleanup_handler(_session: *mut coap_session_t, app_ptr: *mut c_void) {
     std::mem::drop(Box::from_raw(app_ptr as *mut u8));
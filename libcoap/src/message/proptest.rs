@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * message/proptest.rs - Arbitrary CoAP options for property-based testing.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Generation of arbitrary CoAP options using [proptest](::proptest).
+
+use ::proptest::{
+    arbitrary::{any, Arbitrary},
+    collection::vec,
+    prop_oneof,
+    strategy::{BoxedStrategy, Just, Strategy},
+};
+
+use crate::{
+    message::CoapOption,
+    protocol::{CoapMatch, CoapOptionType, Observe},
+};
+
+/// Largest value of a "Block1", "Block2", "Q-Block1" or "Q-Block2" option, which is limited to 3
+/// bytes (see [RFC 7959, Section 2.2](https://datatracker.ietf.org/doc/html/rfc7959#section-2.2)).
+const MAX_BLOCK_VALUE: u32 = 0xFF_FFFF;
+
+/// Returns a strategy for opaque option values of the given option type.
+fn opaque(opt_type: CoapOptionType) -> impl Strategy<Value = Box<[u8]>> {
+    vec(any::<u8>(), opt_type.min_len()..=opt_type.max_len()).prop_map(Vec::into_boxed_slice)
+}
+
+/// Returns a strategy for string option values of the given option type.
+///
+/// The UTF-8 encoding of a character takes up to four bytes, so the number of characters is
+/// limited to a quarter of the maximum value length.
+fn string(opt_type: CoapOptionType) -> impl Strategy<Value = String> {
+    vec(any::<char>(), opt_type.min_len()..=opt_type.max_len() / 4).prop_map(|v| v.into_iter().collect())
+}
+
+/// Generates arbitrary options whose values satisfy the length constraints of their option type
+/// (see [CoapOptionType::min_len()] and [CoapOptionType::max_len()]) as well as the value
+/// constraints checked by [CoapOption::into_value_bytes()], i.e., options that can be encoded and
+/// decoded again without any changes.
+///
+/// Options of unknown option numbers are generated as [CoapOption::Other] instances.
+impl Arbitrary for CoapOption {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<CoapOption>;
+
+    fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+        prop_oneof![
+            prop_oneof![
+                Just(CoapMatch::Empty),
+                vec(any::<u8>(), 1..=CoapOptionType::IfMatch.max_len())
+                    .prop_map(|v| CoapMatch::ETag(v.into_boxed_slice())),
+            ]
+            .prop_map(CoapOption::IfMatch),
+            Just(CoapOption::IfNoneMatch),
+            string(CoapOptionType::UriHost).prop_map(CoapOption::UriHost),
+            any::<u16>().prop_map(CoapOption::UriPort),
+            string(CoapOptionType::UriPath).prop_map(CoapOption::UriPath),
+            string(CoapOptionType::UriQuery).prop_map(CoapOption::UriQuery),
+            string(CoapOptionType::LocationPath).prop_map(CoapOption::LocationPath),
+            string(CoapOptionType::LocationQuery).prop_map(CoapOption::LocationQuery),
+            string(CoapOptionType::ProxyUri).prop_map(CoapOption::ProxyUri),
+            string(CoapOptionType::ProxyScheme).prop_map(CoapOption::ProxyScheme),
+            any::<u16>().prop_map(CoapOption::ContentFormat),
+            any::<u16>().prop_map(CoapOption::Accept),
+            any::<u32>().prop_map(CoapOption::Size1),
+            any::<u32>().prop_map(CoapOption::Size2),
+            (0..=MAX_BLOCK_VALUE).prop_map(CoapOption::Block1),
+            (0..=MAX_BLOCK_VALUE).prop_map(CoapOption::Block2),
+            // RFC 8768, Section 3 restricts the Hop-Limit to values between 1 and 255.
+            (1..=255u16).prop_map(CoapOption::HopLimit),
+            any::<u8>().prop_map(CoapOption::NoResponse),
+            opaque(CoapOptionType::ETag).prop_map(CoapOption::ETag),
+            any::<u32>().prop_map(CoapOption::MaxAge),
+            (0..=Observe::MAX.value()).prop_map(|v| CoapOption::Observe(Observe::wrapping(v))),
+            opaque(CoapOptionType::Oscore).prop_map(CoapOption::Oscore),
+            opaque(CoapOptionType::Echo).prop_map(CoapOption::Echo),
+            opaque(CoapOptionType::RTag).prop_map(CoapOption::RTag),
+            (0..=MAX_BLOCK_VALUE).prop_map(CoapOption::QBlock1),
+            (0..=MAX_BLOCK_VALUE).prop_map(CoapOption::QBlock2),
+            (
+                // Option number 0 is reserved (RFC 7252, Section 12.2).
+                (1..=u16::MAX).prop_filter("option number is known", |v| CoapOptionType::try_from(*v).is_err()),
+                vec(any::<u8>(), 0..=64),
+            )
+                .prop_map(|(number, value)| CoapOption::Other(number, value.into_boxed_slice())),
+        ]
+        .boxed()
+    }
+}
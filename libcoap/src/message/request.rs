@@ -7,20 +7,23 @@
  * See the README as well as the LICENSE file for more information.
  */
 
+use std::collections::HashMap;
 use std::str::FromStr;
 
+use crate::error::OptionValueError;
+use crate::message::{construct_path_string, construct_query_string};
+use crate::session::CoapSessionCommon;
 use crate::{
-    error::{MessageConversionError, MessageTypeError},
+    error::{
+        HopLimitError, MessageCodeError, MessageConversionError, MessageTypeError, PathMatchError, UriParsingError,
+    },
     message::{CoapMessage, CoapMessageCommon, CoapOption},
     protocol::{
-        CoapMatch, CoapMessageCode, CoapMessageType, CoapOptionType, CoapRequestCode, ContentFormat, ETag, HopLimit,
-        NoResponse, Observe,
+        CoapMatch, CoapMessageCode, CoapMessageType, CoapOptionType, CoapRequestCode, ContentFormat, ETag, Echo,
+        HopLimit, NoResponse, ObserveAction,
     },
     types::{CoapUri, CoapUriScheme},
 };
-use crate::error::OptionValueError;
-use crate::message::{construct_path_string, construct_query_string};
-use crate::session::CoapSessionCommon;
 
 /// Representation of a CoAP request message.
 ///
@@ -37,7 +40,10 @@ pub struct CoapRequest {
     if_none_match: bool,
     hop_limit: Option<HopLimit>,
     no_response: Option<NoResponse>,
-    observe: Option<Observe>,
+    observe: Option<ObserveAction>,
+    echo: Option<Echo>,
+    /// Whether the request asks for the total size of the resource using a "Size2" option.
+    size_hint: bool,
 }
 
 impl CoapRequest {
@@ -62,9 +68,71 @@ impl CoapRequest {
             hop_limit: None,
             no_response: None,
             observe: None,
+            echo: None,
+            size_hint: false,
         })
     }
 
+    /// Creates a new confirmable GET request for the given URI.
+    ///
+    /// The URI's host, port, path and query are converted into the corresponding Uri-Host,
+    /// Uri-Port, Uri-Path and Uri-Query options once the request is converted into a message.
+    ///
+    /// Returns an error if the URI could not be parsed.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapRequest};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode};
+    ///
+    /// let request = CoapRequest::get("coap://example.com/sensors/temp")?;
+    /// assert_eq!(request.type_(), CoapMessageType::Con);
+    /// assert_eq!(request.code(), CoapMessageCode::Request(CoapRequestCode::Get));
+    /// # Ok::<(), libcoap_rs::error::UriParsingError>(())
+    /// ```
+    pub fn get(uri: &str) -> Result<CoapRequest, UriParsingError> {
+        Self::from_method_and_uri(CoapRequestCode::Get, uri)
+    }
+
+    /// Creates a new confirmable POST request for the given URI with the given payload.
+    ///
+    /// See [CoapRequest::get()] for how the URI is handled.
+    pub fn post<D: Into<Box<[u8]>>>(uri: &str, payload: D) -> Result<CoapRequest, UriParsingError> {
+        let mut request = Self::from_method_and_uri(CoapRequestCode::Post, uri)?;
+        request.set_data(Some(payload));
+        Ok(request)
+    }
+
+    /// Creates a new confirmable PUT request for the given URI with the given payload.
+    ///
+    /// See [CoapRequest::get()] for how the URI is handled.
+    pub fn put<D: Into<Box<[u8]>>>(uri: &str, payload: D) -> Result<CoapRequest, UriParsingError> {
+        let mut request = Self::from_method_and_uri(CoapRequestCode::Put, uri)?;
+        request.set_data(Some(payload));
+        Ok(request)
+    }
+
+    /// Creates a new confirmable DELETE request for the given URI.
+    ///
+    /// See [CoapRequest::get()] for how the URI is handled.
+    pub fn delete(uri: &str) -> Result<CoapRequest, UriParsingError> {
+        Self::from_method_and_uri(CoapRequestCode::Delete, uri)
+    }
+
+    /// Creates a new confirmable FETCH request for the given URI.
+    ///
+    /// The request body describing what to fetch can be set using
+    /// [CoapMessageCommon::set_data()]. See [CoapRequest::get()] for how the URI is handled.
+    pub fn fetch(uri: &str) -> Result<CoapRequest, UriParsingError> {
+        Self::from_method_and_uri(CoapRequestCode::Fetch, uri)
+    }
+
+    /// Parses the given URI and creates a new confirmable request with the given method for it.
+    fn from_method_and_uri(method: CoapRequestCode, uri: &str) -> Result<CoapRequest, UriParsingError> {
+        let uri = CoapUri::from_str(uri)?;
+        Ok(CoapRequest::new(CoapMessageType::Con, method, uri).expect("CON is a valid message type for requests"))
+    }
+
     /// Returns the "Accept" option value for this request.
     pub fn accept(&self) -> Option<ContentFormat> {
         self.accept
@@ -80,6 +148,61 @@ impl CoapRequest {
         self.accept = accept
     }
 
+    /// Returns all content formats indicated as acceptable for the response by "Accept" options of
+    /// this request, in the order of preference of the client.
+    ///
+    /// This consists of the value set using [CoapRequest::set_accept()] (or parsed from a
+    /// received request), followed by the values of "Accept" options that were added using
+    /// [CoapMessageCommon::add_option()].
+    /// Note that [RFC 7252, Section 5.10.4](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10.4)
+    /// defines the "Accept" option as non-repeatable, so requests received from peers contain at
+    /// most one value (requests with repeated "Accept" options are rejected when parsing them).
+    ///
+    /// An empty list indicates that the client accepts any content format.
+    pub fn accepted_content_formats(&self) -> Vec<ContentFormat> {
+        let additional = self.pdu.options_iter().filter_map(|option| match option {
+            CoapOption::Accept(content_format) => Some(*content_format),
+            _ => None,
+        });
+        self.accept.into_iter().chain(additional).collect()
+    }
+
+    /// Selects the content format of the response to this request from the `offered` content
+    /// formats, i.e., the formats in which the requested resource can be provided.
+    ///
+    /// Returns the first content format of [CoapRequest::accepted_content_formats()] that is also
+    /// contained in `offered`, or `None` if there is no such format, in which case the server
+    /// should respond with `4.06 Not Acceptable`.
+    /// If the request does not indicate any acceptable content format, the client accepts any
+    /// format, so the first `offered` format is returned (or `None` if `offered` is empty).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::CoapRequest;
+    /// use libcoap_rs::protocol::{CoapContentFormat, CoapMessageType, CoapRequestCode};
+    ///
+    /// const OFFERED: [u16; 2] = [CoapContentFormat::Cbor as u16, CoapContentFormat::Json as u16];
+    ///
+    /// let uri = "/sensors/temperature".parse().unwrap();
+    /// let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    /// assert_eq!(request.best_content_format(&OFFERED), Some(CoapContentFormat::Cbor as u16));
+    ///
+    /// request.set_accept(Some(CoapContentFormat::Json as u16));
+    /// assert_eq!(request.best_content_format(&OFFERED), Some(CoapContentFormat::Json as u16));
+    ///
+    /// request.set_accept(Some(CoapContentFormat::TextPlain as u16));
+    /// assert_eq!(request.best_content_format(&OFFERED), None);
+    /// ```
+    pub fn best_content_format(&self, offered: &[ContentFormat]) -> Option<ContentFormat> {
+        let accepted = self.accepted_content_formats();
+        if accepted.is_empty() {
+            return offered.first().copied();
+        }
+        accepted
+            .into_iter()
+            .find(|content_format| offered.contains(content_format))
+    }
+
     /// Returns the "ETag" option value for this request.
     pub fn etag(&self) -> Option<&Vec<ETag>> {
         self.etag.as_ref()
@@ -143,6 +266,11 @@ impl CoapRequest {
     ///
     /// It is usually nonsensical to set this value to `true` if an If-Match-Expression has been set.
     ///
+    /// Setting this option on a PUT request makes the request create-only: The server only
+    /// creates the resource if it does not exist yet, and responds with `4.12 Precondition Failed`
+    /// otherwise. The option is non-repeatable and empty, so it is added to the message exactly
+    /// once if set to `true`.
+    ///
     /// See [RFC 7252, Section 5.10.8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10.8.2)
     /// for more information.
     pub fn set_if_none_match(&mut self, if_none_match: bool) {
@@ -165,6 +293,44 @@ impl CoapRequest {
         self.hop_limit = hop_limit;
     }
 
+    /// Decrements the "Hop-Limit" option value of this request before forwarding it, as required
+    /// for proxies by [RFC 8768, Section 3](https://datatracker.ietf.org/doc/html/rfc8768#section-3).
+    ///
+    /// Requests without a Hop-Limit are left unchanged.
+    ///
+    /// # Errors
+    /// Returns [HopLimitError::Exhausted] without modifying the request if the Hop-Limit would
+    /// reach zero. In this case, the request must not be forwarded, and the proxy should respond
+    /// with `5.08 Hop Limit Reached` instead.
+    pub fn decrement_hop_limit(&mut self) -> Result<(), HopLimitError> {
+        match self.hop_limit {
+            Some(limit) if limit <= 1 => Err(HopLimitError::Exhausted),
+            Some(limit) => {
+                self.hop_limit = Some(limit - 1);
+                Ok(())
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Requests the server to indicate the total size of the resource representation using the
+    /// "Size2" option in its response.
+    ///
+    /// This adds a "Size2" option with the value 0 to the request, which allows clients to
+    /// allocate appropriately sized buffers before receiving the representation (see
+    /// [RFC 7959, Section 4](https://datatracker.ietf.org/doc/html/rfc7959#section-4)).
+    /// The indicated size can be obtained from the response using
+    /// [CoapResponse::total_size()](crate::message::CoapResponse::total_size()).
+    pub fn request_size_hint(&mut self) {
+        self.size_hint = true;
+    }
+
+    /// Returns whether this request asks for the total size of the resource representation
+    /// (see [CoapRequest::request_size_hint()]).
+    pub fn size_hint_requested(&self) -> bool {
+        self.size_hint
+    }
+
     /// Returns the "No-Response" option value for this request.
     pub fn no_response(&self) -> Option<NoResponse> {
         self.no_response
@@ -182,7 +348,7 @@ impl CoapRequest {
     }
 
     /// Returns the "Observe" option value for this request.
-    pub fn observe(&self) -> Option<Observe> {
+    pub fn observe(&self) -> Option<ObserveAction> {
         self.observe
     }
 
@@ -193,22 +359,296 @@ impl CoapRequest {
     ///
     /// This option is defined in [RFC 7641](https://datatracker.ietf.org/doc/html/rfc7641) and is
     /// not part of the main CoAP spec. Some peers may therefore not support this option.
-    pub fn set_observe(&mut self, observe: Option<Observe>) {
+    /// Requests may only use the option to register or deregister as an observer (see
+    /// [RFC 7641, Section 2](https://datatracker.ietf.org/doc/html/rfc7641#section-2)).
+    pub fn set_observe(&mut self, observe: Option<ObserveAction>) {
         self.observe = observe;
     }
 
+    /// Returns the "Echo" option value for this request.
+    pub fn echo(&self) -> Option<&Echo> {
+        self.echo.as_ref()
+    }
+
+    /// Sets the "Echo" option value for this request.
+    ///
+    /// This option returns the value of an "Echo" option received in a response (see
+    /// [CoapResponse::set_echo()](crate::message::CoapResponse::set_echo())) to the server in order
+    /// to prove that the request is recent.
+    ///
+    /// Clients usually do not need to set this option, as libcoap automatically repeats requests
+    /// that were answered with `4.01 Unauthorized` and an "Echo" option, including the received
+    /// value (see `man coap_send`).
+    /// However, libcoap only does so for the requests it sends itself: A proxy that forwards an
+    /// "Echo" challenge of an upstream server to its client (or the client's answer to the upstream
+    /// server) has to relay the option explicitly, which is possible by setting it here.
+    /// libcoap sends the value set here unchanged.
+    ///
+    /// See [RFC 9175, Section 2.2](https://datatracker.ietf.org/doc/html/rfc9175#section-2.2)
+    /// for more information.
+    pub fn set_echo(&mut self, echo: Option<Echo>) {
+        self.echo = echo;
+    }
+
+    /// Turns this request into a request that registers the client as an observer of the requested
+    /// resource by setting the "Observe" option to 0 (register).
+    ///
+    /// Note that [RFC 7641, Section 3.1](https://datatracker.ietf.org/doc/html/rfc7641#section-3.1)
+    /// requires the client to use the same token for the registration and all notifications
+    /// belonging to this subscription are sent using this token.
+    /// A deregistration request (see [CoapRequest::as_observe_deregistration()]) must therefore also
+    /// use the token of the original registration request.
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::InvalidOptionForMessageType] if the request code is not
+    /// [CoapRequestCode::Get] or [CoapRequestCode::Fetch] (see
+    /// [RFC 8132, Section 2.4](https://datatracker.ietf.org/doc/html/rfc8132#section-2.4)), as
+    /// only these requests may be used for observation.
+    pub fn as_observe_registration(&mut self) -> Result<(), MessageConversionError> {
+        self.check_observable()?;
+        self.observe = Some(ObserveAction::Register);
+        Ok(())
+    }
+
+    /// Turns this request into a request that cancels a previous registration as an observer of
+    /// the requested resource by setting the "Observe" option to 1 (deregister).
+    ///
+    /// In order to be matched to the correct subscription, the token of this request must be the
+    /// same as the one of the original registration request (see
+    /// [RFC 7641, Section 3.6](https://datatracker.ietf.org/doc/html/rfc7641#section-3.6)).
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::InvalidOptionForMessageType] if the request code is not
+    /// [CoapRequestCode::Get] or [CoapRequestCode::Fetch].
+    pub fn as_observe_deregistration(&mut self) -> Result<(), MessageConversionError> {
+        self.check_observable()?;
+        self.observe = Some(ObserveAction::Deregister);
+        Ok(())
+    }
+
+    /// Returns whether this request is a request to register as an observer of the requested
+    /// resource (i.e., a GET or FETCH request whose "Observe" option is set to 0).
+    pub fn is_observe_registration(&self) -> bool {
+        self.check_observable().is_ok() && self.observe == Some(ObserveAction::Register)
+    }
+
+    /// Returns whether this request is a request to deregister as an observer of the requested
+    /// resource (i.e., a GET or FETCH request whose "Observe" option is set to 1).
+    pub fn is_observe_deregistration(&self) -> bool {
+        self.check_observable().is_ok() && self.observe == Some(ObserveAction::Deregister)
+    }
+
+    /// Checks whether the request code of this request allows for resource observation.
+    fn check_observable(&self) -> Result<(), MessageConversionError> {
+        match self.code() {
+            CoapMessageCode::Request(CoapRequestCode::Get | CoapRequestCode::Fetch) => Ok(()),
+            _ => Err(MessageConversionError::InvalidOptionForMessageType(
+                CoapOptionType::Observe,
+            )),
+        }
+    }
+
     /// Returns the CoAP URI that is requested.
     pub fn uri(&self) -> &CoapUri {
         &self.uri
     }
 
+    /// Returns the query of this request as a sorted list of key-value pairs, e.g., for use as
+    /// part of a cache key.
+    ///
+    /// Each Uri-Query option is split at its first `=` into a key and a value. Options without a
+    /// `=` (flags) have no value. The pairs are sorted by key and then by value, so requests that
+    /// only differ in the order of their Uri-Query options have the same normalized query.
+    /// Repeated keys are preserved, as their meaning is application-specific.
+    ///
+    /// Use [CoapRequest::normalized_query_options()] to obtain the corresponding options.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::CoapRequest;
+    ///
+    /// let request = CoapRequest::get("/sensors?unit=c&raw&a=1").unwrap();
+    /// assert_eq!(
+    ///     request.normalized_query(),
+    ///     vec![
+    ///         ("a".to_string(), Some("1".to_string())),
+    ///         ("raw".to_string(), None),
+    ///         ("unit".to_string(), Some("c".to_string())),
+    ///     ]
+    /// );
+    /// ```
+    pub fn normalized_query(&self) -> Vec<(String, Option<String>)> {
+        let mut query: Vec<_> = self
+            .uri
+            .decoded_query_components()
+            .into_iter()
+            .map(|component| match component.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (component, None),
+            })
+            .collect();
+        query.sort();
+        query
+    }
+
+    /// Returns the Uri-Query options for the normalized query of this request (see
+    /// [CoapRequest::normalized_query()]) in their normalized order.
+    pub fn normalized_query_options(&self) -> Vec<CoapOption> {
+        self.normalized_query()
+            .into_iter()
+            .map(|(key, value)| match value {
+                Some(value) => CoapOption::UriQuery(format!("{key}={value}")),
+                None => CoapOption::UriQuery(key),
+            })
+            .collect()
+    }
+
+    /// Returns the request method (i.e., the request code) of this request.
+    ///
+    /// This is a shorthand for matching the [CoapMessageCode] returned by
+    /// [code()](CoapMessageCommon::code()).
+    /// Returns `None` if the message code is not a request code, which can only happen if it was
+    /// changed using [as_message_mut()](CoapMessageCommon::as_message_mut()).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapRequest};
+    /// use libcoap_rs::protocol::{CoapMessageType, CoapRequestCode};
+    ///
+    /// let uri = "/test".parse().unwrap();
+    /// let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    /// assert_eq!(request.method(), Some(CoapRequestCode::Get));
+    ///
+    /// request.set_method(CoapRequestCode::Fetch);
+    /// assert_eq!(request.method(), Some(CoapRequestCode::Fetch));
+    /// ```
+    pub fn method(&self) -> Option<CoapRequestCode> {
+        match self.code() {
+            CoapMessageCode::Request(method) => Some(method),
+            CoapMessageCode::Response(_) | CoapMessageCode::Empty => None,
+        }
+    }
+
+    /// Sets the request method (i.e., the request code) of this request.
+    pub fn set_method(&mut self, method: CoapRequestCode) {
+        self.pdu.set_code(CoapMessageCode::Request(method));
+    }
+
+    /// Returns a copy of this request that can be sent again, e.g., as an application-level
+    /// retry after a "5.03 Service Unavailable" response.
+    ///
+    /// All options and the payload are copied, while the message ID and the token are cleared, so
+    /// that [CoapSessionCommon::send_request()] assigns new ones and the server does not treat
+    /// the retry as a duplicate of the original request (see
+    /// [RFC 7252, Section 4.5](https://datatracker.ietf.org/doc/html/rfc7252#section-4.5)).
+    /// If the retry should reuse the original token, set it on the returned request using
+    /// [CoapMessageCommon::set_token()].
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapRequest};
+    ///
+    /// let mut request = CoapRequest::get("coap://example.com/sensors/temp").unwrap();
+    /// request.set_mid(Some(42));
+    /// request.set_token(Some(vec![0x13, 0x37]));
+    ///
+    /// let retry = request.clone_for_retry();
+    /// assert_eq!(retry.mid(), None);
+    /// assert_eq!(retry.token(), None);
+    /// assert_eq!(retry.uri(), request.uri());
+    /// ```
+    pub fn clone_for_retry(&self) -> CoapRequest {
+        let mut request = self.clone();
+        request.set_mid(None);
+        request.set_token(None::<Box<[u8]>>);
+        request
+    }
+
+    /// Matches the requested path against the given path template and returns the values of the
+    /// template's parameters.
+    ///
+    /// Templates consist of `/`-separated segments, each of which is either a literal that must be
+    /// equal to the corresponding path segment or a parameter in curly braces (e.g., `{id}`) that
+    /// matches any path segment. The leading `/` is optional.
+    /// Path segments are compared and returned without percent-encoding (see
+    /// [CoapUri::decoded_path_segments()]).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::CoapRequest;
+    /// use libcoap_rs::protocol::{CoapMessageType, CoapRequestCode};
+    ///
+    /// let uri = "/sensors/42/readings/temperature".parse().unwrap();
+    /// let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    /// let params = request.extract_path_params("/sensors/{id}/readings/{type}").unwrap();
+    /// assert_eq!(params["id"], "42");
+    /// assert_eq!(params["type"], "temperature");
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [PathMatchError::InvalidTemplate] if the template contains malformed or duplicate
+    /// parameters and [PathMatchError::NoMatch] if the requested path does not match the
+    /// template.
+    pub fn extract_path_params(&self, template: &str) -> Result<HashMap<String, String>, PathMatchError> {
+        let template = template.strip_prefix('/').unwrap_or(template);
+        let template_segments: Vec<&str> = if template.is_empty() {
+            Vec::new()
+        } else {
+            template.split('/').collect()
+        };
+        // Validate the entire template first, so that malformed templates are reported regardless
+        // of the requested path.
+        let mut params = HashMap::new();
+        let mut template_parsed = Vec::with_capacity(template_segments.len());
+        for segment in template_segments {
+            let param = match segment.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+                Some(name) if !name.is_empty() && !name.contains(['{', '}']) => {
+                    if params.insert(name.to_string(), String::new()).is_some() {
+                        return Err(PathMatchError::InvalidTemplate(segment.to_string()));
+                    }
+                    Some(name)
+                },
+                None if !segment.contains(['{', '}']) => None,
+                _ => return Err(PathMatchError::InvalidTemplate(segment.to_string())),
+            };
+            template_parsed.push((segment, param));
+        }
+        let path_segments = self.uri.decoded_path_segments();
+        if path_segments.len() != template_parsed.len() {
+            return Err(PathMatchError::NoMatch);
+        }
+        for ((literal, param), value) in template_parsed.into_iter().zip(path_segments) {
+            match param {
+                Some(name) => {
+                    params.insert(name.to_string(), value);
+                },
+                None if literal == value => {},
+                None => return Err(PathMatchError::NoMatch),
+            }
+        }
+        Ok(params)
+    }
+
+    /// Returns whether the requested path matches the given path template (see
+    /// [CoapRequest::extract_path_params()] for the template syntax).
+    ///
+    /// Malformed templates never match.
+    pub fn matches_template(&self, template: &str) -> bool {
+        self.extract_path_params(template).is_ok()
+    }
+
     /// Parses the given [CoapMessage] into a CoapRequest.
     ///
-    /// Returns a [MessageConversionError] if the provided PDU cannot be parsed into a request.
+    /// Returns a [MessageConversionError] if the provided PDU cannot be parsed into a request
+    /// (e.g., because its message code is not a request code).
     pub fn from_message<'a>(
         mut pdu: CoapMessage,
         session: &impl CoapSessionCommon<'a>,
     ) -> Result<CoapRequest, MessageConversionError> {
+        if !matches!(pdu.code(), CoapMessageCode::Request(_)) {
+            return Err(MessageCodeError::NotARequestCode.into());
+        }
+        pdu.check_strict_option_repetition()?;
         let mut host = None;
         let mut port = None;
         let mut path = None;
@@ -223,6 +663,8 @@ impl CoapRequest {
         let mut hop_limit = None;
         let mut no_response = None;
         let mut observe = None;
+        let mut echo = None;
+        let mut size_hint = false;
         let mut additional_opts = Vec::new();
         for option in pdu.options_iter() {
             match option {
@@ -312,10 +754,21 @@ impl CoapRequest {
                 },
                 // libcoap handles blockwise transfer for us (for now).
                 CoapOption::Size1(_) => {},
-                CoapOption::Size2(_) => {
-                    return Err(MessageConversionError::InvalidOptionForMessageType(
-                        CoapOptionType::Size2,
-                    ));
+                CoapOption::Size2(value) => {
+                    if size_hint {
+                        return Err(MessageConversionError::NonRepeatableOptionRepeated(
+                            CoapOptionType::Size2,
+                        ));
+                    }
+                    // In requests, the "Size2" option is only used to ask for the total size and
+                    // must therefore have the value 0 (RFC 7959, Section 4).
+                    if *value != 0 {
+                        return Err(MessageConversionError::InvalidOptionValue(
+                            Some(CoapOptionType::Size2),
+                            OptionValueError::IllegalValue,
+                        ));
+                    }
+                    size_hint = true;
                 },
                 // libcoap handles blockwise transfer for us (for now).
                 CoapOption::Block1(_) => {},
@@ -360,10 +813,21 @@ impl CoapRequest {
                             CoapOptionType::MaxAge,
                         ));
                     }
-                    observe = Some(*value);
+                    observe = Some(ObserveAction::try_from(*value).map_err(|_| {
+                        MessageConversionError::InvalidOptionValue(
+                            Some(CoapOptionType::Observe),
+                            OptionValueError::IllegalValue,
+                        )
+                    })?);
+                },
+                CoapOption::Echo(value) => {
+                    if echo.is_some() {
+                        return Err(MessageConversionError::NonRepeatableOptionRepeated(
+                            CoapOptionType::Echo,
+                        ));
+                    }
+                    echo = Some(value.clone());
                 },
-                // Handling of echo options is automatically done by libcoap (see man coap_send)
-                CoapOption::Echo(_) => {},
                 // Handling of request tag options is automatically done by libcoap (see man
                 // coap_send)
                 CoapOption::RTag(_) => {},
@@ -422,6 +886,8 @@ impl CoapRequest {
             hop_limit,
             no_response,
             observe,
+            echo,
+            size_hint,
         })
     }
 
@@ -459,7 +925,13 @@ impl CoapRequest {
             self.pdu.add_option(CoapOption::NoResponse(no_response));
         }
         if let Some(observe) = self.observe {
-            self.pdu.add_option(CoapOption::Observe(observe));
+            self.pdu.add_option(CoapOption::Observe(observe.into()));
+        }
+        if let Some(echo) = self.echo {
+            self.pdu.add_option(CoapOption::Echo(echo));
+        }
+        if self.size_hint {
+            self.pdu.add_option(CoapOption::Size2(0));
         }
         self.pdu
     }
@@ -479,6 +951,15 @@ impl CoapMessageCommon for CoapRequest {
         }
     }
 
+    /// Returns the estimated size in bytes of this request when encoded as a PDU.
+    ///
+    /// In addition to the options added using [add_option()](CoapMessageCommon::add_option()),
+    /// this includes the options that are generated from the request's URI and other typed
+    /// fields.
+    fn estimated_pdu_size(&self) -> usize {
+        self.clone().into_message().encoded_len()
+    }
+
     fn as_message(&self) -> &CoapMessage {
         &self.pdu
     }
@@ -7,14 +7,45 @@
  * See the README as well as the LICENSE file for more information.
  */
 
+use std::str::Utf8Error;
+use std::time::{Duration, Instant};
+
 use crate::error::{MessageConversionError, MessageTypeError, OptionValueError};
-use crate::message::{CoapMessage, CoapMessageCommon, CoapOption, construct_path_string, construct_query_string};
+use crate::message::{construct_path_string, construct_query_string, CoapMessage, CoapMessageCommon, CoapOption};
 use crate::protocol::{
-    CoapMessageCode, CoapMessageType, CoapOptionType, CoapResponseCode, ContentFormat, Echo, ETag, MaxAge, Observe,
+    Block, CoapMessageCode, CoapMessageType, CoapOptionType, CoapResponseCode, ContentFormat, ETag, Echo, MaxAge,
+    Observe, Size,
 };
 use crate::types::CoapUri;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+/// Largest value of a "Block1" or "Block2" option, which consists of a 20 bit block number, the M
+/// bit and a three bit block size exponent (see
+/// [RFC 7959, Section 2.2](https://datatracker.ietf.org/doc/html/rfc7959#section-2.2)).
+const MAX_BLOCK_VALUE: Block = 0xFF_FFFF;
+/// Bit of a "Block1" or "Block2" option value that indicates whether more blocks follow.
+const BLOCK_MORE_FLAG: Block = 0x08;
+/// Mask of the block size exponent (SZX) in a "Block1" or "Block2" option value.
+const BLOCK_SZX_MASK: Block = 0x07;
+/// Block size exponent that indicates a BERT block.
+const BLOCK_SZX_BERT: Block = 0x07;
+
+/// Offset basis of the 64-bit FNV-1a hash function used for generating ETags.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// Prime of the 64-bit FNV-1a hash function used for generating ETags.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Calculates an ETag for the given payload using the 64-bit FNV-1a hash function.
+///
+/// In contrast to the hashers of the standard library, FNV-1a is stable, so the same payload
+/// always results in the same ETag, even across different program runs and Rust versions.
+pub(crate) fn payload_etag(payload: &[u8]) -> ETag {
+    let hash = payload.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    });
+    hash.to_be_bytes().into()
+}
+
+#[derive(Debug, Clone)]
 pub struct CoapResponse {
     pdu: CoapMessage,
     content_format: Option<ContentFormat>,
@@ -23,6 +54,17 @@ pub struct CoapResponse {
     echo: Option<Echo>,
     location: Option<CoapUri>,
     observe: Option<Observe>,
+    total_size: Option<Size>,
+    /// Whether the "Size2" option is always populated from the length of the body if no total
+    /// size is set explicitly.
+    include_total_size: bool,
+    /// Whether the ETag is generated from the payload when converting this response into a
+    /// message.
+    auto_etag: bool,
+    /// Point in time at which the request this response answers was received (if known).
+    ///
+    /// Like the time of reception of messages, this is ignored when comparing responses.
+    request_received_at: Option<Instant>,
 }
 
 impl CoapResponse {
@@ -43,6 +85,10 @@ impl CoapResponse {
             echo: None,
             location: None,
             observe: None,
+            total_size: None,
+            include_total_size: false,
+            auto_etag: false,
+            request_received_at: None,
         })
     }
 
@@ -95,6 +141,42 @@ impl CoapResponse {
         self.etag = etag
     }
 
+    /// Sets the "ETag" option value for this response to a value generated from the current
+    /// payload.
+    ///
+    /// The generated ETag is an 8 byte hash of the payload (using the non-cryptographic FNV-1a hash
+    /// function), so responses with the same payload always have the same ETag. If the payload is
+    /// changed afterwards, the ETag has to be generated again (or see
+    /// [CoapResponse::set_auto_etag()]).
+    pub fn compute_and_set_etag(&mut self) {
+        self.etag = Some(payload_etag(self.pdu.data().unwrap_or_default()));
+    }
+
+    /// Returns whether the "ETag" option value of this response is the one generated from its
+    /// payload by [CoapResponse::compute_and_set_etag()].
+    ///
+    /// Returns `false` if this response does not have an ETag.
+    pub fn etag_matches_payload(&self) -> bool {
+        self.etag
+            .as_ref()
+            .is_some_and(|etag| *etag == payload_etag(self.pdu.data().unwrap_or_default()))
+    }
+
+    /// Returns whether the "ETag" option value is generated from the payload when converting this
+    /// response into a message (see [CoapResponse::set_auto_etag()]).
+    pub fn auto_etag(&self) -> bool {
+        self.auto_etag
+    }
+
+    /// Sets whether the "ETag" option value is generated from the payload when converting this
+    /// response into a message, replacing the value set using [CoapResponse::set_etag()].
+    ///
+    /// This ensures that the ETag matches the final payload of the response, even if the payload
+    /// is changed after enabling this setting.
+    pub fn set_auto_etag(&mut self, auto_etag: bool) {
+        self.auto_etag = auto_etag
+    }
+
     /// Returns the "Echo" option value for this request.
     pub fn echo(&self) -> Option<&Echo> {
         self.echo.as_ref()
@@ -106,8 +188,9 @@ impl CoapResponse {
     ///
     /// The client should include the provided option value in its next request.
     ///
-    /// As handling echo options on the client side is done automatically by libcoap, this option
-    /// is not accessible in [CoapRequest], see `man coap_send` for more information.
+    /// Handling echo options on the client side is done automatically by libcoap (see
+    /// `man coap_send`), but the option can also be set explicitly using
+    /// [CoapRequest::set_echo()](crate::message::CoapRequest::set_echo()), e.g., by proxies.
     ///
     /// See [RFC 9175, Section 2.2](https://datatracker.ietf.org/doc/html/rfc9175#section-2.2)
     /// for more information.
@@ -127,10 +210,277 @@ impl CoapResponse {
     ///
     /// This option is defined in [RFC 7641](https://datatracker.ietf.org/doc/html/rfc7641) and is
     /// not part of the main CoAP spec. Some peers may therefore not support this option.
+    /// Use [CoapResponse::set_observe_sequence()] to set wrapping sequence numbers instead.
     pub fn set_observe(&mut self, observe: Option<Observe>) {
         self.observe = observe;
     }
 
+    /// Sets the "Observe" option value for this response to the given notification sequence
+    /// number.
+    ///
+    /// As Observe option values are limited to 24 bits, only the lower 24 bits of `next` are used,
+    /// i.e., sequence numbers wrap around to zero after 2^24 - 1.
+    ///
+    /// Observe notifications sent from within the handler of a [CoapResource](crate::CoapResource)
+    /// already have their sequence number set automatically (see
+    /// [CoapResource::notify_observers()](crate::CoapResource::notify_observers())).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::CoapResponse;
+    /// use libcoap_rs::protocol::{CoapMessageType, CoapResponseCode, Observe};
+    ///
+    /// let mut response = CoapResponse::new(CoapMessageType::Non, CoapResponseCode::Content).unwrap();
+    /// response.set_observe_sequence(0x1000005);
+    /// assert_eq!(response.observe().map(Observe::value), Some(5));
+    /// ```
+    pub fn set_observe_sequence(&mut self, next: u32) {
+        // Sequence numbers wrap around as described in RFC 7641, Section 4.4.
+        self.observe = Some(Observe::wrapping(next));
+    }
+
+    /// Returns the total size of the response body as indicated by the "Size2" option.
+    ///
+    /// Returns `None` if no total size is set or if the indicated size can not be represented as
+    /// a `usize` on the current platform.
+    pub fn total_size(&self) -> Option<usize> {
+        self.total_size.and_then(|v| usize::try_from(v).ok())
+    }
+
+    /// Sets the total size of the response body (in bytes), which is sent to the client using the
+    /// "Size2" option.
+    ///
+    /// This is useful if the response body is sent using a blockwise transfer, as it allows the
+    /// client to estimate the progress of the transfer based on the first block it receives.
+    /// If no total size is set explicitly, the "Size2" option is only included if enabled using
+    /// [CoapResponse::set_include_total_size()].
+    ///
+    /// See [RFC 7959, Section 4](https://datatracker.ietf.org/doc/html/rfc7959#section-4) for more
+    /// information.
+    ///
+    /// # Errors
+    ///
+    /// Returns [OptionValueError::TooLong] if `total_bytes` can not be represented using the
+    /// 32 bits available for the "Size2" option.
+    pub fn set_total_size(&mut self, total_bytes: usize) -> Result<(), OptionValueError> {
+        self.total_size = Some(Size::try_from(total_bytes).map_err(|_| OptionValueError::TooLong)?);
+        Ok(())
+    }
+
+    /// Sets whether the "Size2" option is populated from the length of the response body (unless a
+    /// total size is set explicitly using [CoapResponse::set_total_size()]).
+    ///
+    /// For responses provided to resource handlers, this is enabled automatically if the client
+    /// asked for the total size (see
+    /// [CoapRequest::request_size_hint()](crate::message::CoapRequest::request_size_hint())).
+    pub fn set_include_total_size(&mut self, include_total_size: bool) {
+        self.include_total_size = include_total_size;
+    }
+
+    /// Returns the body of this response.
+    ///
+    /// This is an alias for [CoapMessageCommon::data()].
+    pub fn payload(&self) -> Option<&[u8]> {
+        self.data()
+    }
+
+    /// Returns the body of this response as a string slice.
+    ///
+    /// Returns `Ok(None)` if the response has no body.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [Utf8Error] if the body is not valid UTF-8.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapResponse};
+    /// use libcoap_rs::protocol::{CoapMessageType, CoapResponseCode};
+    ///
+    /// let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    /// assert_eq!(response.payload_as_str(), Ok(None));
+    ///
+    /// response.set_data(Some("22.5 °C".as_bytes()));
+    /// assert_eq!(response.payload_as_str(), Ok(Some("22.5 °C")));
+    /// ```
+    pub fn payload_as_str(&self) -> Result<Option<&str>, Utf8Error> {
+        self.payload().map(std::str::from_utf8).transpose()
+    }
+
+    /// Turns this response into a `2.31 Continue` response that acknowledges the reception of a
+    /// block of a block-wise request body transfer, where `block` is the value of the "Block1"
+    /// option of the received request.
+    ///
+    /// Sets the response code to [CoapResponseCode::Continue] and adds a "Block1" option echoing
+    /// `block` (replacing any "Block1" option that was previously added), which tells the client to
+    /// continue sending the next block using the same block size (see
+    /// [RFC 7959, Section 2.3](https://datatracker.ietf.org/doc/html/rfc7959#section-2.3)).
+    ///
+    /// # Errors
+    ///
+    /// Returns [OptionValueError::IllegalValue] if `block` describes the last block of the request
+    /// body (i.e., the M bit is not set), as the final block must be answered with the actual
+    /// response to the request, or if its block size exponent is `7` (which is reserved for BERT,
+    /// see [RFC 8323, Section 6](https://datatracker.ietf.org/doc/html/rfc8323#section-6)).
+    /// Returns [OptionValueError::TooLong] if the block number does not fit into 20 bits.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapOption, CoapResponse};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapResponseCode};
+    ///
+    /// let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Changed).unwrap();
+    /// // Block number 3, more blocks follow, block size 64 bytes (SZX 2).
+    /// response.set_block1_continue((3 << 4) | 0x08 | 2).unwrap();
+    /// assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Continue));
+    /// assert!(response.options_iter().any(|o| *o == CoapOption::Block1((3 << 4) | 0x08 | 2)));
+    /// ```
+    pub fn set_block1_continue(&mut self, block: Block) -> Result<(), OptionValueError> {
+        if block > MAX_BLOCK_VALUE {
+            return Err(OptionValueError::TooLong);
+        }
+        if block & BLOCK_MORE_FLAG == 0 || block & BLOCK_SZX_MASK == BLOCK_SZX_BERT {
+            return Err(OptionValueError::IllegalValue);
+        }
+        self.pdu
+            .options
+            .retain(|option| !matches!(option, CoapOption::Block1(_)));
+        self.pdu.options.push(CoapOption::Block1(block));
+        self.set_code(CoapResponseCode::Continue);
+        Ok(())
+    }
+
+    /// Returns whether this response has the code
+    /// [2.03 Valid](CoapResponseCode::Valid), i.e., whether the representation identified by the
+    /// ETag sent in the request is still current.
+    ///
+    /// See [RFC 7252, Section 5.9.1.3](https://datatracker.ietf.org/doc/html/rfc7252#section-5.9.1.3)
+    /// for more information.
+    pub fn is_not_modified(&self) -> bool {
+        self.code() == CoapMessageCode::Response(CoapResponseCode::Valid)
+    }
+
+    /// Creates a piggybacked [2.03 Valid](CoapResponseCode::Valid) response indicating that the
+    /// representation identified by `etag` is still current.
+    ///
+    /// A client that has cached the representation with the given ETag may continue to use it and
+    /// should update the freshness of its cached copy using the "Max-Age" option of this response
+    /// (which defaults to 60 seconds if not set, see [CoapResponse::set_max_age()]).
+    /// The response has no payload, as the client already has the representation.
+    ///
+    /// Inside of request handlers, use [CoapResponse::set_valid()] on the provided response
+    /// instead, as that one already has the token and message ID of the request set.
+    ///
+    /// See [RFC 7252, Section 5.9.1.3](https://datatracker.ietf.org/doc/html/rfc7252#section-5.9.1.3)
+    /// and [RFC 7252, Section 5.10.6.2](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10.6.2)
+    /// for more information.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapResponse};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapResponseCode};
+    ///
+    /// let response = CoapResponse::new_valid(vec![0x13, 0x37].into_boxed_slice());
+    /// assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Valid));
+    /// assert_eq!(response.etag().map(|v| v.as_ref()), Some([0x13, 0x37].as_slice()));
+    /// assert_eq!(response.data(), None);
+    /// ```
+    pub fn new_valid(etag: ETag) -> CoapResponse {
+        let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Valid)
+            .expect("acknowledgements are always allowed for responses");
+        response.set_valid(etag);
+        response
+    }
+
+    /// Turns this response into a [2.03 Valid](CoapResponseCode::Valid) response indicating that
+    /// the representation identified by `etag` is still current (see [CoapResponse::new_valid()]).
+    ///
+    /// Removes the payload and "Content-Format" option of this response and disables the
+    /// automatic generation of the ETag (see [CoapResponse::set_auto_etag()]).
+    pub fn set_valid(&mut self, etag: ETag) {
+        self.set_code(CoapResponseCode::Valid);
+        self.set_data(None::<Box<[u8]>>);
+        self.content_format = None;
+        self.auto_etag = false;
+        self.etag = Some(etag);
+    }
+
+    /// Returns whether this response is piggybacked on the acknowledgement of the request it
+    /// answers (see [RFC 7252, Section 5.2.1](https://datatracker.ietf.org/doc/html/rfc7252#section-5.2.1)).
+    pub fn is_piggybacked(&self) -> bool {
+        self.pdu.type_() == CoapMessageType::Ack
+    }
+
+    /// Turns this response into a separate response, i.e., a confirmable message that is sent
+    /// independently of the acknowledgement of the request (see
+    /// [RFC 7252, Section 5.2.2](https://datatracker.ietf.org/doc/html/rfc7252#section-5.2.2)).
+    ///
+    /// Responses to confirmable requests provided to request handlers are piggybacked by default.
+    /// Separate responses are retransmitted until the client acknowledges them, which may be
+    /// preferable for large responses, as a lost piggybacked response is only retransmitted once
+    /// the client retransmits its request. libcoap acknowledges the request itself using an empty
+    /// ACK once the request handler returns.
+    ///
+    /// Calling this function on a response that is already separate has no effect.
+    ///
+    /// # Errors
+    /// Returns [MessageTypeError::InvalidForReplyTo] if this response is non-confirmable, i.e., it
+    /// answers a non-confirmable request, which should not be answered using a confirmable
+    /// response (see [RFC 7252, Section 5.2.3](https://datatracker.ietf.org/doc/html/rfc7252#section-5.2.3)).
+    pub fn set_separate(&mut self) -> Result<(), MessageTypeError> {
+        match self.pdu.type_() {
+            CoapMessageType::Ack => {
+                self.pdu.set_type_(CoapMessageType::Con);
+                // The message ID of the request is used by its acknowledgement, the separate
+                // response is assigned a new one when it is sent.
+                self.pdu.set_mid(None);
+                Ok(())
+            },
+            CoapMessageType::Con => Ok(()),
+            v => Err(MessageTypeError::InvalidForReplyTo(v)),
+        }
+    }
+
+    /// Returns the point in time at which the request this response answers was received, if
+    /// known.
+    pub fn request_received_at(&self) -> Option<Instant> {
+        self.request_received_at
+    }
+
+    /// Sets the point in time at which the request this response answers was received.
+    ///
+    /// For responses provided to resource handlers, this is set automatically to the time at which
+    /// the request was received (see [CoapMessage::received_at()]).
+    pub fn set_request_received_at(&mut self, received_at: Instant) {
+        self.request_received_at = Some(received_at);
+    }
+
+    /// Returns the time that has passed since the request this response answers was received,
+    /// i.e., the time spent processing the request so far (e.g., when called right before sending
+    /// the response from a resource handler).
+    ///
+    /// Returns `None` if the point in time at which the request was received is unknown (see
+    /// [CoapResponse::set_request_received_at()]).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use libcoap_rs::message::CoapResponse;
+    /// use libcoap_rs::protocol::{CoapMessageType, CoapResponseCode};
+    ///
+    /// let received_at = Instant::now();
+    /// std::thread::sleep(Duration::from_millis(5));
+    /// let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    /// assert_eq!(response.processing_latency(), None);
+    ///
+    /// response.set_request_received_at(received_at);
+    /// assert!(response.processing_latency().unwrap() >= Duration::from_millis(5));
+    /// ```
+    pub fn processing_latency(&self) -> Option<Duration> {
+        self.request_received_at.map(|received_at| received_at.elapsed())
+    }
+
     /// Returns the "Location" option value for this request.
     pub fn location(&self) -> Option<&CoapUri> {
         self.location.as_ref()
@@ -151,15 +501,65 @@ impl CoapResponse {
     pub fn set_location<U: Into<CoapUri>>(&mut self, uri: Option<U>) -> Result<(), OptionValueError> {
         let uri = uri.map(Into::into);
         if let Some(uri) = uri {
+            if uri.host().is_some() || uri.port().is_some() || uri.is_proxy() {
+                return Err(OptionValueError::IllegalValue);
+            }
+            if uri
+                .decoded_path_segments()
+                .iter()
+                .any(|v| v.len() > CoapOptionType::LocationPath.max_len())
+            {
+                return Err(OptionValueError::TooLong);
+            }
             self.location = Some(uri)
         }
         Ok(())
     }
 
+    /// Creates a `2.01 Created` response with the given message type that points to the created
+    /// resource at `location` using the "Location-Path" and "Location-Query" options (see
+    /// [CoapResponse::set_location()]).
+    ///
+    /// # Errors
+    /// Returns [MessageConversionError::InvalidMessageType] if `type_` cannot be used for responses
+    /// and [MessageConversionError::InvalidOptionValue] if `location` is not a valid location URI.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::MessageConversionError;
+    /// use libcoap_rs::message::{CoapMessageCommon, CoapResponse};
+    /// use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapResponseCode};
+    /// use libcoap_rs::types::CoapUri;
+    ///
+    /// let location: CoapUri = "/sensors/42".parse().unwrap();
+    /// let response = CoapResponse::created_at(location, CoapMessageType::Ack)?;
+    /// assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Created));
+    /// assert_eq!(response.location().and_then(|v| v.path()), Some("sensors/42".as_bytes()));
+    /// # Result::<(), MessageConversionError>::Ok(())
+    /// ```
+    pub fn created_at(location: CoapUri, type_: CoapMessageType) -> Result<CoapResponse, MessageConversionError> {
+        let mut response = CoapResponse::new(type_, CoapResponseCode::Created)?;
+        response
+            .set_location(Some(location))
+            .map_err(|e| MessageConversionError::InvalidOptionValue(Some(CoapOptionType::LocationPath), e))?;
+        Ok(response)
+    }
+
     /// Converts this request into a [CoapMessage] that can be sent over a [CoapSession](crate::session::CoapSession).
     pub fn into_message(mut self) -> CoapMessage {
+        if self.auto_etag {
+            self.compute_and_set_etag();
+        }
         if let Some(loc) = self.location {
-            loc.into_options().into_iter().for_each(|v| self.pdu.add_option(v));
+            // The location is a relative URI, so it is encoded using the path and query options
+            // of requests, which correspond to the location options of responses.
+            for option in loc.into_options() {
+                match option {
+                    CoapOption::UriPath(v) => self.pdu.add_option(CoapOption::LocationPath(v)),
+                    CoapOption::UriQuery(v) => self.pdu.add_option(CoapOption::LocationQuery(v)),
+                    _ => {},
+                }
+            }
         }
         if let Some(max_age) = self.max_age {
             self.pdu.add_option(CoapOption::MaxAge(max_age));
@@ -173,13 +573,28 @@ impl CoapResponse {
         if let Some(observe) = self.observe {
             self.pdu.add_option(CoapOption::Observe(observe));
         }
+        let body_len = self.pdu.data().map_or(0, |v| v.len());
+        let total_size = self
+            .total_size
+            .or_else(|| Size::try_from(body_len).ok().filter(|_| self.include_total_size));
+        if let Some(total_size) = total_size {
+            self.pdu.add_option(CoapOption::Size2(total_size));
+        }
         self.pdu
     }
 
+    /// Converts a copy of this response into a [CoapMessage], leaving the response itself intact.
+    ///
+    /// The resulting message is equal to the one returned by [CoapResponse::into_message()].
+    pub fn to_message(&self) -> CoapMessage {
+        self.clone().into_message()
+    }
+
     /// Parses the given [CoapMessage] into a CoapResponse.
     ///
     /// Returns a [MessageConversionError] if the provided PDU cannot be parsed into a response.
     pub fn from_message(pdu: CoapMessage) -> Result<CoapResponse, MessageConversionError> {
+        pdu.check_strict_option_repetition()?;
         let mut location_path = None;
         let mut location_query = None;
         let mut max_age = None;
@@ -187,6 +602,7 @@ impl CoapResponse {
         let mut echo = None;
         let mut observe = None;
         let mut content_format = None;
+        let mut total_size = None;
         let mut additional_opts = Vec::new();
         for option in pdu.options_iter() {
             match option {
@@ -284,15 +700,18 @@ impl CoapResponse {
                         CoapOptionType::Size1,
                     ));
                 },
-                CoapOption::Size2(_) => {},
-                CoapOption::Block1(_) => {
-                    return Err(MessageConversionError::InvalidOptionForMessageType(
-                        CoapOptionType::Block1,
-                    ));
+                CoapOption::Size2(value) => {
+                    if total_size.is_some() {
+                        return Err(MessageConversionError::NonRepeatableOptionRepeated(
+                            CoapOptionType::Size2,
+                        ));
+                    }
+                    total_size = Some(*value)
                 },
-                CoapOption::Block2(_) => {},
-                CoapOption::QBlock1(_) => {},
-                CoapOption::QBlock2(_) => {},
+                // Block options are handled by libcoap.
+                // Responses to block-wise requests carry a "Block1" option that acknowledges the
+                // received block (see RFC 7959, Section 2.3).
+                CoapOption::Block1(_) | CoapOption::Block2(_) | CoapOption::QBlock1(_) | CoapOption::QBlock2(_) => {},
                 CoapOption::HopLimit(_) => {
                     return Err(MessageConversionError::InvalidOptionForMessageType(
                         CoapOptionType::HopLimit,
@@ -340,10 +759,33 @@ impl CoapResponse {
             echo,
             location,
             observe,
+            total_size,
+            include_total_size: false,
+            auto_etag: false,
+            request_received_at: None,
         })
     }
 }
 
+impl PartialEq for CoapResponse {
+    fn eq(&self, other: &Self) -> bool {
+        // The time of reception of the request is not part of the response itself and therefore
+        // not compared.
+        self.pdu == other.pdu
+            && self.content_format == other.content_format
+            && self.max_age == other.max_age
+            && self.etag == other.etag
+            && self.echo == other.echo
+            && self.location == other.location
+            && self.observe == other.observe
+            && self.total_size == other.total_size
+            && self.include_total_size == other.include_total_size
+            && self.auto_etag == other.auto_etag
+    }
+}
+
+impl Eq for CoapResponse {}
+
 impl CoapMessageCommon for CoapResponse {
     /// Sets the message code of this response.
     ///
@@ -359,6 +801,14 @@ impl CoapMessageCommon for CoapResponse {
         }
     }
 
+    /// Returns the estimated size in bytes of this response when encoded as a PDU.
+    ///
+    /// In addition to the options added using [add_option()](CoapMessageCommon::add_option()),
+    /// this includes the options that are generated from the response's typed fields.
+    fn estimated_pdu_size(&self) -> usize {
+        self.clone().into_message().encoded_len()
+    }
+
     fn as_message(&self) -> &CoapMessage {
         &self.pdu
     }
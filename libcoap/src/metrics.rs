@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * metrics.rs - Metric collection using the metrics crate for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Collection of metrics using the [metrics](https://docs.rs/metrics) crate.
+//!
+//! If the `metrics` feature is enabled, all contexts and sessions automatically record the
+//! metrics listed below in the recorder installed by the application (e.g., using
+//! `metrics::set_global_recorder()`).
+//! If no recorder is installed, recording metrics has no effect.
+//!
+//! All metrics have a `protocol` label that contains the transport protocol of the session the
+//! metric was recorded for (`udp`, `dtls`, `tcp` or `tls`).
+
+use std::time::Duration;
+
+use crate::types::CoapProtocol;
+
+/// Counter of the messages sent by sessions (including requests, responses and pings).
+pub const MESSAGES_SENT: &str = "coap.messages.sent";
+
+/// Counter of the requests and responses received by sessions.
+pub const MESSAGES_RECEIVED: &str = "coap.messages.received";
+
+/// Counter of the retransmissions of confirmable messages performed by libcoap.
+pub const MESSAGES_RETRANSMITTED: &str = "coap.messages.retransmitted";
+
+/// Gauge of the number of client- and server-side sessions that currently exist.
+pub const SESSIONS_ACTIVE: &str = "coap.sessions.active";
+
+/// Counter of the failed DTLS handshakes (i.e., DTLS error events).
+pub const SESSIONS_DTLS_HANDSHAKE_FAILURES: &str = "coap.sessions.dtls_handshake_failures";
+
+/// Histogram of the time (in milliseconds) between sending a request using
+/// [CoapSessionCommon::send_request()](crate::session::CoapSessionCommon::send_request()) and
+/// receiving the first response to it.
+pub const REQUESTS_DURATION_MS: &str = "coap.requests.duration_ms";
+
+/// Name of the label that contains the transport protocol.
+const PROTOCOL_LABEL: &str = "protocol";
+
+/// Returns the value of the protocol label for the given protocol.
+fn protocol_label(proto: CoapProtocol) -> &'static str {
+    match proto {
+        CoapProtocol::None => "none",
+        CoapProtocol::Udp => "udp",
+        CoapProtocol::Dtls => "dtls",
+        CoapProtocol::Tcp => "tcp",
+        CoapProtocol::Tls => "tls",
+    }
+}
+
+/// Records a message sent on a session using the given protocol.
+pub(crate) fn record_message_sent(proto: CoapProtocol) {
+    ::metrics::counter!(MESSAGES_SENT, PROTOCOL_LABEL => protocol_label(proto)).increment(1);
+}
+
+/// Records a message received on a session using the given protocol.
+pub(crate) fn record_message_received(proto: CoapProtocol) {
+    ::metrics::counter!(MESSAGES_RECEIVED, PROTOCOL_LABEL => protocol_label(proto)).increment(1);
+}
+
+/// Records a retransmission on a session using the given protocol.
+pub(crate) fn record_message_retransmitted(proto: CoapProtocol) {
+    ::metrics::counter!(MESSAGES_RETRANSMITTED, PROTOCOL_LABEL => protocol_label(proto)).increment(1);
+}
+
+/// Records a failed DTLS handshake on a session using the given protocol.
+pub(crate) fn record_dtls_handshake_failure(proto: CoapProtocol) {
+    ::metrics::counter!(SESSIONS_DTLS_HANDSHAKE_FAILURES, PROTOCOL_LABEL => protocol_label(proto)).increment(1);
+}
+
+/// Records the time it took to receive the response to a request sent on a session using the
+/// given protocol.
+pub(crate) fn record_request_duration(proto: CoapProtocol, duration: Duration) {
+    ::metrics::histogram!(REQUESTS_DURATION_MS, PROTOCOL_LABEL => protocol_label(proto))
+        .record(duration.as_secs_f64() * 1000.0);
+}
+
+/// Accounts for a single session in the [SESSIONS_ACTIVE] gauge for as long as it exists.
+#[derive(Debug)]
+pub(crate) struct ActiveSession {
+    protocol: &'static str,
+}
+
+impl ActiveSession {
+    /// Increments the [SESSIONS_ACTIVE] gauge for the given protocol until the returned value is
+    /// dropped.
+    pub(crate) fn new(proto: CoapProtocol) -> ActiveSession {
+        let protocol = protocol_label(proto);
+        ::metrics::gauge!(SESSIONS_ACTIVE, PROTOCOL_LABEL => protocol).increment(1.0);
+        ActiveSession { protocol }
+    }
+}
+
+impl Drop for ActiveSession {
+    fn drop(&mut self) {
+        ::metrics::gauge!(SESSIONS_ACTIVE, PROTOCOL_LABEL => self.protocol).decrement(1.0);
+    }
+}
@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * oscore.rs - OSCORE security contexts for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Object Security for Constrained RESTful Environments (OSCORE,
+//! [RFC 8613](https://datatracker.ietf.org/doc/html/rfc8613)).
+//!
+//! OSCORE protects CoAP messages end-to-end based on a security context that is shared between
+//! the client and the server (see [OscoreContext]).
+//! Client sessions are protected using
+//! [CoapClientSession::connect_udp_oscore()](crate::session::CoapClientSession::connect_udp_oscore),
+//! a server accepts OSCORE-protected requests once
+//! [CoapContext::enable_oscore_server()](crate::CoapContext::enable_oscore_server) has been called.
+//! Outgoing messages of such sessions are then protected and incoming messages unprotected by
+//! libcoap automatically.
+
+use std::fmt::Write;
+
+use libcoap_sys::{coap_new_oscore_conf, coap_oscore_conf_t, coap_str_const_t};
+
+use crate::hex;
+
+/// AEAD algorithms that can be used to protect OSCORE messages (see
+/// [RFC 8613, Section 3.2.1](https://datatracker.ietf.org/doc/html/rfc8613#section-3.2.1)).
+///
+/// The values correspond to the COSE algorithm identifiers of the algorithms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum OscoreAeadAlgorithm {
+    /// AES-CCM with a 128 bit key, 64 bit tag and 13 byte nonce (mandatory to implement).
+    #[default]
+    AesCcm16_64_128 = 10,
+    /// AES-CCM with a 256 bit key, 64 bit tag and 13 byte nonce.
+    AesCcm16_64_256 = 11,
+    /// AES-CCM with a 128 bit key, 64 bit tag and 7 byte nonce.
+    AesCcm64_64_128 = 12,
+    /// AES-CCM with a 256 bit key, 64 bit tag and 7 byte nonce.
+    AesCcm64_64_256 = 13,
+    /// AES-GCM with a 128 bit key.
+    A128Gcm = 1,
+    /// AES-GCM with a 192 bit key.
+    A192Gcm = 2,
+    /// AES-GCM with a 256 bit key.
+    A256Gcm = 3,
+    /// ChaCha20 with Poly1305 authentication.
+    ChaCha20Poly1305 = 24,
+}
+
+/// HKDF algorithms that can be used to derive the OSCORE keys from the master secret (see
+/// [RFC 8613, Section 3.2.1](https://datatracker.ietf.org/doc/html/rfc8613#section-3.2.1)).
+///
+/// The values correspond to the COSE algorithm identifiers of the algorithms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum OscoreHkdfAlgorithm {
+    /// HKDF using SHA-256 (mandatory to implement).
+    #[default]
+    HkdfSha256 = -10,
+    /// HKDF using SHA-512.
+    HkdfSha512 = -11,
+}
+
+/// OSCORE security context shared between a client and a server (see
+/// [RFC 8613, Section 3](https://datatracker.ietf.org/doc/html/rfc8613#section-3)).
+///
+/// The sender ID of one endpoint is the recipient ID of the other one, all other values have to
+/// be identical on both endpoints.
+///
+/// # Examples
+/// ```no_run
+/// use libcoap_rs::oscore::{OscoreAeadAlgorithm, OscoreContext};
+///
+/// let client_context = OscoreContext::new(b"0123456789abcdef", b"\x01", b"\x02", 0)
+///     .master_salt(b"salt")
+///     .aead_algorithm(OscoreAeadAlgorithm::AesCcm16_64_128);
+/// let server_context = OscoreContext::new(b"0123456789abcdef", b"\x02", b"\x01", 0).master_salt(b"salt");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OscoreContext {
+    master_secret: Vec<u8>,
+    master_salt: Option<Vec<u8>>,
+    id_context: Option<Vec<u8>>,
+    sender_id: Vec<u8>,
+    recipient_id: Vec<u8>,
+    aead_algorithm: OscoreAeadAlgorithm,
+    hkdf_algorithm: OscoreHkdfAlgorithm,
+    replay_window: Option<u32>,
+    sender_sequence_number: u64,
+}
+
+impl OscoreContext {
+    /// Creates a new security context with the given master secret, sender ID and recipient ID
+    /// that uses the default algorithms and no master salt or ID context.
+    ///
+    /// `sender_sequence_number` is the sender sequence number that is used for the first
+    /// protected message.
+    /// Sequence numbers must never be reused with the same keys, so if a security context is
+    /// reused after a restart, this has to be set to a value higher than any previously used one
+    /// (see [RFC 8613, Section 7.5](https://datatracker.ietf.org/doc/html/rfc8613#section-7.5)).
+    pub fn new(
+        master_secret: &[u8],
+        sender_id: &[u8],
+        recipient_id: &[u8],
+        sender_sequence_number: u64,
+    ) -> OscoreContext {
+        OscoreContext {
+            master_secret: master_secret.to_vec(),
+            master_salt: None,
+            id_context: None,
+            sender_id: sender_id.to_vec(),
+            recipient_id: recipient_id.to_vec(),
+            aead_algorithm: OscoreAeadAlgorithm::default(),
+            hkdf_algorithm: OscoreHkdfAlgorithm::default(),
+            replay_window: None,
+            sender_sequence_number,
+        }
+    }
+
+    /// Sets the master salt that is used for key derivation.
+    pub fn master_salt(mut self, master_salt: &[u8]) -> OscoreContext {
+        self.master_salt = Some(master_salt.to_vec());
+        self
+    }
+
+    /// Sets the ID context that is used for key derivation and identifies the security context.
+    pub fn id_context(mut self, id_context: &[u8]) -> OscoreContext {
+        self.id_context = Some(id_context.to_vec());
+        self
+    }
+
+    /// Sets the AEAD algorithm that is used to protect messages.
+    pub fn aead_algorithm(mut self, algorithm: OscoreAeadAlgorithm) -> OscoreContext {
+        self.aead_algorithm = algorithm;
+        self
+    }
+
+    /// Sets the HKDF algorithm that is used to derive the keys from the master secret.
+    pub fn hkdf_algorithm(mut self, algorithm: OscoreHkdfAlgorithm) -> OscoreContext {
+        self.hkdf_algorithm = algorithm;
+        self
+    }
+
+    /// Sets the size of the window that is used to detect replayed messages (libcoap uses a
+    /// window of 32 messages by default).
+    pub fn replay_window(mut self, replay_window: u32) -> OscoreContext {
+        self.replay_window = Some(replay_window);
+        self
+    }
+
+    /// Returns the sender ID of this security context.
+    pub fn sender_id(&self) -> &[u8] {
+        &self.sender_id
+    }
+
+    /// Returns the recipient ID of this security context.
+    pub fn recipient_id(&self) -> &[u8] {
+        &self.recipient_id
+    }
+
+    /// Returns this security context in libcoap's textual OSCORE configuration format.
+    fn to_config_string(&self) -> String {
+        fn hex_entry(config: &mut String, keyword: &str, value: &[u8]) {
+            let _ = writeln!(config, "{},hex,\"{}\"", keyword, hex::encode(value));
+        }
+
+        let mut config = String::new();
+        hex_entry(&mut config, "master_secret", &self.master_secret);
+        if let Some(master_salt) = &self.master_salt {
+            hex_entry(&mut config, "master_salt", master_salt);
+        }
+        if let Some(id_context) = &self.id_context {
+            hex_entry(&mut config, "id_context", id_context);
+        }
+        hex_entry(&mut config, "sender_id", &self.sender_id);
+        hex_entry(&mut config, "recipient_id", &self.recipient_id);
+        let _ = writeln!(config, "aead_alg,integer,{}", self.aead_algorithm as i32);
+        let _ = writeln!(config, "hkdf_alg,integer,{}", self.hkdf_algorithm as i32);
+        if let Some(replay_window) = self.replay_window {
+            let _ = writeln!(config, "replay_window,integer,{}", replay_window);
+        }
+        config
+    }
+
+    /// Creates the raw libcoap OSCORE configuration for this security context.
+    ///
+    /// Returns `None` if libcoap rejected the configuration (e.g., because the sender or
+    /// recipient ID is too long for the selected AEAD algorithm).
+    ///
+    /// The returned configuration has to be passed on to a libcoap function that takes ownership
+    /// of it or freed using `coap_delete_oscore_conf()`.
+    pub(crate) fn create_raw_conf(&self) -> Option<*mut coap_oscore_conf_t> {
+        let config = self.to_config_string();
+        let conf_mem = coap_str_const_t {
+            length: config.len(),
+            s: config.as_ptr(),
+        };
+        // SAFETY: conf_mem points to valid memory for the duration of this call (libcoap copies
+        // all values it needs), no sequence number saving function is provided.
+        let raw_conf =
+            unsafe { coap_new_oscore_conf(conf_mem, None, std::ptr::null_mut(), self.sender_sequence_number) };
+        (!raw_conf.is_null()).then_some(raw_conf)
+    }
+}
@@ -10,37 +10,47 @@
 //! Various types that are specified and defined in the CoAP standard and its extensions.
 
 use std::{
-    ffi::CStr,
+    borrow::Borrow,
     fmt::{Display, Formatter},
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
 };
 
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
 use libcoap_sys::{
-    COAP_MEDIATYPE_APPLICATION_ACE_CBOR, COAP_MEDIATYPE_APPLICATION_CBOR, COAP_MEDIATYPE_APPLICATION_COAP_GROUP_JSON,
-    COAP_MEDIATYPE_APPLICATION_COSE_ENCRYPT,
-    COAP_MEDIATYPE_APPLICATION_COSE_ENCRYPT0, COAP_MEDIATYPE_APPLICATION_COSE_KEY, COAP_MEDIATYPE_APPLICATION_COSE_KEY_SET, COAP_MEDIATYPE_APPLICATION_COSE_MAC,
-    COAP_MEDIATYPE_APPLICATION_COSE_MAC0, COAP_MEDIATYPE_APPLICATION_COSE_SIGN,
-    COAP_MEDIATYPE_APPLICATION_COSE_SIGN1, COAP_MEDIATYPE_APPLICATION_CWT,
+    coap_option_num_t, coap_pdu_code_t, coap_pdu_type_t,
+    coap_pdu_type_t::{COAP_MESSAGE_ACK, COAP_MESSAGE_CON, COAP_MESSAGE_NON, COAP_MESSAGE_RST},
+    coap_request_t, COAP_MEDIATYPE_APPLICATION_ACE_CBOR, COAP_MEDIATYPE_APPLICATION_CBOR,
+    COAP_MEDIATYPE_APPLICATION_COAP_GROUP_JSON, COAP_MEDIATYPE_APPLICATION_COSE_ENCRYPT,
+    COAP_MEDIATYPE_APPLICATION_COSE_ENCRYPT0, COAP_MEDIATYPE_APPLICATION_COSE_KEY,
+    COAP_MEDIATYPE_APPLICATION_COSE_KEY_SET, COAP_MEDIATYPE_APPLICATION_COSE_MAC, COAP_MEDIATYPE_APPLICATION_COSE_MAC0,
+    COAP_MEDIATYPE_APPLICATION_COSE_SIGN, COAP_MEDIATYPE_APPLICATION_COSE_SIGN1, COAP_MEDIATYPE_APPLICATION_CWT,
     COAP_MEDIATYPE_APPLICATION_DOTS_CBOR, COAP_MEDIATYPE_APPLICATION_EXI, COAP_MEDIATYPE_APPLICATION_JSON,
-    COAP_MEDIATYPE_APPLICATION_LINK_FORMAT, COAP_MEDIATYPE_APPLICATION_MB_CBOR_SEQ, COAP_MEDIATYPE_APPLICATION_OCTET_STREAM,
-    COAP_MEDIATYPE_APPLICATION_OSCORE, COAP_MEDIATYPE_APPLICATION_RDF_XML, COAP_MEDIATYPE_APPLICATION_SENML_CBOR,
-    COAP_MEDIATYPE_APPLICATION_SENML_EXI, COAP_MEDIATYPE_APPLICATION_SENML_JSON,
-    COAP_MEDIATYPE_APPLICATION_SENML_XML, COAP_MEDIATYPE_APPLICATION_SENSML_CBOR, COAP_MEDIATYPE_APPLICATION_SENSML_EXI,
-    COAP_MEDIATYPE_APPLICATION_SENSML_JSON, COAP_MEDIATYPE_APPLICATION_SENSML_XML, COAP_MEDIATYPE_APPLICATION_XML,
-    COAP_MEDIATYPE_TEXT_PLAIN, COAP_OPTION_ACCEPT,
-    COAP_OPTION_BLOCK1, COAP_OPTION_BLOCK2,
-    COAP_OPTION_CONTENT_FORMAT, COAP_OPTION_ECHO, COAP_OPTION_ETAG,
-    COAP_OPTION_HOP_LIMIT, COAP_OPTION_IF_MATCH, COAP_OPTION_IF_NONE_MATCH, COAP_OPTION_LOCATION_PATH, COAP_OPTION_LOCATION_QUERY,
-    COAP_OPTION_MAXAGE, COAP_OPTION_NORESPONSE, coap_option_num_t, COAP_OPTION_OBSERVE,
-    COAP_OPTION_OSCORE, COAP_OPTION_PROXY_SCHEME, COAP_OPTION_PROXY_URI, COAP_OPTION_Q_BLOCK1,
+    COAP_MEDIATYPE_APPLICATION_LINK_FORMAT, COAP_MEDIATYPE_APPLICATION_MB_CBOR_SEQ,
+    COAP_MEDIATYPE_APPLICATION_OCTET_STREAM, COAP_MEDIATYPE_APPLICATION_OSCORE, COAP_MEDIATYPE_APPLICATION_RDF_XML,
+    COAP_MEDIATYPE_APPLICATION_SENML_CBOR, COAP_MEDIATYPE_APPLICATION_SENML_EXI, COAP_MEDIATYPE_APPLICATION_SENML_JSON,
+    COAP_MEDIATYPE_APPLICATION_SENML_XML, COAP_MEDIATYPE_APPLICATION_SENSML_CBOR,
+    COAP_MEDIATYPE_APPLICATION_SENSML_EXI, COAP_MEDIATYPE_APPLICATION_SENSML_JSON,
+    COAP_MEDIATYPE_APPLICATION_SENSML_XML, COAP_MEDIATYPE_APPLICATION_XML, COAP_MEDIATYPE_TEXT_PLAIN,
+    COAP_OPTION_ACCEPT, COAP_OPTION_BLOCK1, COAP_OPTION_BLOCK2, COAP_OPTION_CONTENT_FORMAT, COAP_OPTION_ECHO,
+    COAP_OPTION_ETAG, COAP_OPTION_HOP_LIMIT, COAP_OPTION_IF_MATCH, COAP_OPTION_IF_NONE_MATCH,
+    COAP_OPTION_LOCATION_PATH, COAP_OPTION_LOCATION_QUERY, COAP_OPTION_MAXAGE, COAP_OPTION_NORESPONSE,
+    COAP_OPTION_OBSERVE, COAP_OPTION_OSCORE, COAP_OPTION_PROXY_SCHEME, COAP_OPTION_PROXY_URI, COAP_OPTION_Q_BLOCK1,
     COAP_OPTION_Q_BLOCK2, COAP_OPTION_RTAG, COAP_OPTION_SIZE1, COAP_OPTION_SIZE2, COAP_OPTION_URI_HOST,
-    COAP_OPTION_URI_PATH, COAP_OPTION_URI_PORT, COAP_OPTION_URI_QUERY, coap_pdu_code_t, coap_pdu_type_t,
-    coap_pdu_type_t::{COAP_MESSAGE_ACK, COAP_MESSAGE_CON, COAP_MESSAGE_NON, COAP_MESSAGE_RST}, coap_request_t, coap_response_phrase,
+    COAP_OPTION_URI_PATH, COAP_OPTION_URI_PORT, COAP_OPTION_URI_QUERY,
 };
 
-use crate::error::{MessageCodeError, UnknownOptionError};
+use crate::{
+    error::{
+        CodeParseError, MessageCodeError, MessageTypeParseError, ObserveError, OptionValueError, TokenError,
+        UnknownOptionError,
+    },
+    hex,
+    message::CoapOption,
+    prng::coap_prng_try_fill,
+};
 
 pub type ETag = Box<[u8]>;
 pub type MaxAge = u32;
@@ -57,20 +67,254 @@ pub type Size = u32;
 pub type Block = u32;
 pub type HopLimit = u16;
 pub type NoResponse = u8;
-pub type Observe = u32;
 // TODO actually parse this option (for OSCORE support)
 pub type Oscore = Box<[u8]>;
 pub type Echo = Box<[u8]>;
 pub type RequestTag = Box<[u8]>;
 
 pub type CoapOptionNum = coap_option_num_t;
-pub type CoapToken = Box<[u8]>;
+
+/// Value of the Observe option (see
+/// [RFC 7641, Section 2](https://datatracker.ietf.org/doc/html/rfc7641#section-2)).
+///
+/// In requests, the value is either [Observe::REGISTER] or [Observe::DEREGISTER] (see
+/// [ObserveAction]), while in notifications it is a sequence number that is limited to 24 bits.
+///
+/// # Examples
+/// ```
+/// use libcoap_rs::error::ObserveError;
+/// use libcoap_rs::protocol::Observe;
+///
+/// assert_eq!(Observe::sequence(5)?.value(), 5);
+/// assert_eq!(Observe::sequence(0x1000000), Err(ObserveError::OutOfRange));
+/// # Result::<(), ObserveError>::Ok(())
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Observe(u32);
+
+impl Observe {
+    /// Observe option value of a request that registers the client as an observer.
+    pub const REGISTER: Observe = Observe(0);
+    /// Observe option value of a request that deregisters the client as an observer.
+    pub const DEREGISTER: Observe = Observe(1);
+    /// Largest Observe option value, as the option is limited to 3 bytes.
+    pub const MAX: Observe = Observe(0xFF_FFFF);
+
+    /// Creates an Observe option value for a notification with the given sequence number.
+    ///
+    /// # Errors
+    /// Returns [ObserveError::OutOfRange] if `n` does not fit into the 24 bits of the Observe
+    /// option (i.e., is larger than [Observe::MAX]).
+    pub fn sequence(n: u32) -> Result<Observe, ObserveError> {
+        if n > Observe::MAX.0 {
+            return Err(ObserveError::OutOfRange);
+        }
+        Ok(Observe(n))
+    }
+
+    /// Creates an Observe option value from the lower 24 bits of `n`, i.e., sequence numbers wrap
+    /// around to zero after [Observe::MAX] (see
+    /// [RFC 7641, Section 4.4](https://datatracker.ietf.org/doc/html/rfc7641#section-4.4)).
+    pub(crate) fn wrapping(n: u32) -> Observe {
+        Observe(n & Observe::MAX.0)
+    }
+
+    /// Returns the numeric value of this Observe option value.
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<Observe> for u32 {
+    fn from(value: Observe) -> Self {
+        value.0
+    }
+}
+
+impl From<ObserveAction> for Observe {
+    fn from(value: ObserveAction) -> Self {
+        match value {
+            ObserveAction::Register => Observe::REGISTER,
+            ObserveAction::Deregister => Observe::DEREGISTER,
+        }
+    }
+}
+
+/// Observe option values are (de)serialized as integers.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Observe {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Observe {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+        Observe::sequence(value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Observe option values that may be used in requests, i.e., registering or deregistering the
+/// client as an observer (see
+/// [RFC 7641, Section 2](https://datatracker.ietf.org/doc/html/rfc7641#section-2)).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ObserveAction {
+    /// Register as an observer ([Observe::REGISTER]).
+    Register,
+    /// Deregister as an observer ([Observe::DEREGISTER]).
+    Deregister,
+}
+
+impl TryFrom<Observe> for ObserveAction {
+    type Error = ObserveError;
+
+    fn try_from(value: Observe) -> Result<Self, Self::Error> {
+        match value {
+            Observe::REGISTER => Ok(ObserveAction::Register),
+            Observe::DEREGISTER => Ok(ObserveAction::Deregister),
+            Observe(value) => Err(ObserveError::InvalidRequestValue(value)),
+        }
+    }
+}
+
+/// A CoAP token used to correlate requests and responses, see
+/// [RFC 7252, Section 5.3.1](https://datatracker.ietf.org/doc/html/rfc7252#section-5.3.1).
+///
+/// Tokens created using [CoapToken::random()] or [CoapToken::from_bytes()] are at most
+/// [CoapToken::MAX_LEN] bytes long. Tokens are displayed as lowercase hexadecimal strings.
+///
+/// # Examples
+/// ```
+/// use libcoap_rs::error::TokenError;
+/// use libcoap_rs::protocol::CoapToken;
+///
+/// let token = CoapToken::from_bytes(&[0xde, 0xad, 0xbe, 0xef])?;
+/// assert_eq!(token.to_string(), "deadbeef");
+/// assert!(CoapToken::from_bytes(&[0; 9]).is_err());
+///
+/// let random = CoapToken::random(8)?;
+/// assert_eq!(random.len(), 8);
+/// # Result::<(), TokenError>::Ok(())
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct CoapToken(Box<[u8]>);
+
+impl CoapToken {
+    /// Maximum length of a token in bytes.
+    pub const MAX_LEN: usize = 8;
+
+    /// Creates a token of `len` random bytes using the libcoap PRNG (which can be seeded using
+    /// [seed_coap_prng()](crate::prng::seed_coap_prng())).
+    ///
+    /// # Errors
+    /// Returns [TokenError::TooLong] if `len` exceeds [CoapToken::MAX_LEN] and [TokenError::Rng] if
+    /// the random bytes could not be generated.
+    pub fn random(len: u8) -> Result<CoapToken, TokenError> {
+        let len = usize::from(len);
+        if len > Self::MAX_LEN {
+            return Err(TokenError::TooLong(len));
+        }
+        let mut token = vec![0; len];
+        coap_prng_try_fill(&mut token)?;
+        Ok(CoapToken(token.into_boxed_slice()))
+    }
+
+    /// Creates a token from the given bytes.
+    ///
+    /// # Errors
+    /// Returns [TokenError::TooLong] if `bytes` is longer than [CoapToken::MAX_LEN].
+    pub fn from_bytes(bytes: &[u8]) -> Result<CoapToken, TokenError> {
+        if bytes.len() > Self::MAX_LEN {
+            return Err(TokenError::TooLong(bytes.len()));
+        }
+        Ok(CoapToken(Box::from(bytes)))
+    }
+
+    /// Creates a token from the given bytes without checking their length.
+    ///
+    /// Used for tokens that are set directly on messages or received from peers, which are not
+    /// validated by this library.
+    pub(crate) fn from_bytes_unchecked(bytes: &[u8]) -> CoapToken {
+        CoapToken(Box::from(bytes))
+    }
+
+    /// Creates a token from the given boxed bytes without checking their length, see
+    /// [CoapToken::from_bytes_unchecked()].
+    pub(crate) fn from_boxed_unchecked(bytes: Box<[u8]>) -> CoapToken {
+        CoapToken(bytes)
+    }
+
+    /// Returns the bytes of this token.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the length of this token in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether this token is empty (i.e., has a length of zero).
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl AsRef<[u8]> for CoapToken {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<[u8]> for CoapToken {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<CoapToken> for Box<[u8]> {
+    fn from(value: CoapToken) -> Self {
+        value.0
+    }
+}
+
+impl TryFrom<&[u8]> for CoapToken {
+    type Error = TokenError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        CoapToken::from_bytes(value)
+    }
+}
+
+impl Display for CoapToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hex::encode(&self.0))
+    }
+}
+
+/// Tokens are (de)serialized as lowercase hexadecimal strings.
+#[cfg(feature = "serde")]
+impl serde::Serialize for CoapToken {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::serde_hex::serialize(&self.0, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CoapToken {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::serde_hex::deserialize::<Box<[u8]>, D>(deserializer).map(CoapToken)
+    }
+}
 
 /// Representation of a CoAP match expression supplied in the If-Match option, see
 /// [RFC 7252, Section 5.10.8.1](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10.8.1).
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoapMatch {
-    ETag(ETag),
+    ETag(#[cfg_attr(feature = "serde", serde(with = "crate::serde_hex"))] ETag),
     Empty,
 }
 
@@ -209,6 +453,99 @@ impl CoapOptionType {
             CoapOptionType::QBlock2 => 0,
         }
     }
+
+    /// Returns whether this option type may occur more than once in a single message.
+    ///
+    /// See the option tables in [RFC 7252, Section 5.10](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10)
+    /// and the respective specifications of later CoAP extensions.
+    pub fn is_repeatable(&self) -> bool {
+        matches!(
+            self,
+            CoapOptionType::IfMatch
+                | CoapOptionType::ETag
+                | CoapOptionType::LocationPath
+                | CoapOptionType::UriPath
+                | CoapOptionType::UriQuery
+                | CoapOptionType::LocationQuery
+                | CoapOptionType::RTag
+        )
+    }
+
+    /// Performs type-specific validation of the value of the given option.
+    ///
+    /// In addition to the length bounds returned by [min_len()](CoapOptionType::min_len()) and
+    /// [max_len()](CoapOptionType::max_len()), this checks the following constraints:
+    /// - Uri-Host values must be a valid hostname, an IPv4 address or an IPv6 address (optionally
+    ///   enclosed in square brackets), see [RFC 7252, Section 6.4](https://datatracker.ietf.org/doc/html/rfc7252#section-6.4).
+    /// - Uri-Port values must not be zero.
+    /// - Hop-Limit values must be in the range 1-255, see [RFC 8768, Section 3](https://datatracker.ietf.org/doc/html/rfc8768#section-3).
+    /// - Uri-Path, Uri-Query, Location-Path and Location-Query components must not be longer than
+    ///   255 bytes, see [RFC 7252, Section 5.10](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10).
+    ///
+    /// Options that are not subject to any of these constraints are always considered valid.
+    ///
+    /// # Errors
+    ///
+    /// Returns [OptionValueError::InvalidCharacter] if a Uri-Host value contains characters that
+    /// are not allowed in hostnames, [OptionValueError::TooLong] if a path or query component
+    /// exceeds the maximum length, and [OptionValueError::IllegalValue] for all other violations.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::OptionValueError;
+    /// use libcoap_rs::message::CoapOption;
+    /// use libcoap_rs::protocol::CoapOptionType;
+    ///
+    /// assert!(CoapOptionType::validate_value(&CoapOption::UriHost("example.com".to_string())).is_ok());
+    /// assert_eq!(
+    ///     CoapOptionType::validate_value(&CoapOption::UriHost("exa mple.com".to_string())),
+    ///     Err(OptionValueError::InvalidCharacter)
+    /// );
+    /// assert_eq!(CoapOptionType::validate_value(&CoapOption::UriPort(0)), Err(OptionValueError::IllegalValue));
+    /// ```
+    pub fn validate_value(value: &CoapOption) -> Result<(), OptionValueError> {
+        match value {
+            CoapOption::UriHost(host) => validate_uri_host(host),
+            CoapOption::UriPort(0) => Err(OptionValueError::IllegalValue),
+            CoapOption::HopLimit(limit) if !(1..=255).contains(limit) => Err(OptionValueError::IllegalValue),
+            CoapOption::UriPath(component)
+            | CoapOption::UriQuery(component)
+            | CoapOption::LocationPath(component)
+            | CoapOption::LocationQuery(component)
+                if component.len() > 255 =>
+            {
+                Err(OptionValueError::TooLong)
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Checks whether the given Uri-Host option value is a valid hostname or IP address literal.
+fn validate_uri_host(host: &str) -> Result<(), OptionValueError> {
+    if host.is_empty() || host.len() > 255 {
+        return Err(OptionValueError::IllegalValue);
+    }
+    if let Some(literal) = host.strip_prefix('[') {
+        return match literal.strip_suffix(']').map(Ipv6Addr::from_str) {
+            Some(Ok(_)) => Ok(()),
+            _ => Err(OptionValueError::IllegalValue),
+        };
+    }
+    if Ipv4Addr::from_str(host).is_ok() || Ipv6Addr::from_str(host).is_ok() {
+        return Ok(());
+    }
+    // Hostnames consist of labels separated by dots (RFC 1123, Section 2.1), each of which may
+    // contain only letters, digits and hyphens, but must not start or end with a hyphen.
+    for label in host.split('.') {
+        if label.bytes().any(|c| !c.is_ascii_alphanumeric() && c != b'-') {
+            return Err(OptionValueError::InvalidCharacter);
+        }
+        if label.is_empty() || label.len() > 63 || label.starts_with('-') || label.ends_with('-') {
+            return Err(OptionValueError::IllegalValue);
+        }
+    }
+    Ok(())
 }
 
 impl TryFrom<coap_option_num_t> for CoapOptionType {
@@ -219,6 +556,92 @@ impl TryFrom<coap_option_num_t> for CoapOptionType {
     }
 }
 
+/// Option numbers of all known [CoapOptionType]s in ascending numeric order.
+///
+/// As CoAP options must be encoded in the order of their option numbers (see
+/// [RFC 7252, Section 3.1](https://datatracker.ietf.org/doc/html/rfc7252#section-3.1)), this array
+/// also describes the order in which known options appear in a message.
+pub const COAP_OPTION_NUMBERS_SORTED: [CoapOptionNum; 26] = [
+    CoapOptionType::IfMatch as CoapOptionNum,
+    CoapOptionType::UriHost as CoapOptionNum,
+    CoapOptionType::ETag as CoapOptionNum,
+    CoapOptionType::IfNoneMatch as CoapOptionNum,
+    CoapOptionType::Observe as CoapOptionNum,
+    CoapOptionType::UriPort as CoapOptionNum,
+    CoapOptionType::LocationPath as CoapOptionNum,
+    CoapOptionType::Oscore as CoapOptionNum,
+    CoapOptionType::UriPath as CoapOptionNum,
+    CoapOptionType::ContentFormat as CoapOptionNum,
+    CoapOptionType::MaxAge as CoapOptionNum,
+    CoapOptionType::UriQuery as CoapOptionNum,
+    CoapOptionType::HopLimit as CoapOptionNum,
+    CoapOptionType::Accept as CoapOptionNum,
+    CoapOptionType::QBlock1 as CoapOptionNum,
+    CoapOptionType::LocationQuery as CoapOptionNum,
+    CoapOptionType::Block2 as CoapOptionNum,
+    CoapOptionType::Block1 as CoapOptionNum,
+    CoapOptionType::Size2 as CoapOptionNum,
+    CoapOptionType::QBlock2 as CoapOptionNum,
+    CoapOptionType::ProxyUri as CoapOptionNum,
+    CoapOptionType::ProxyScheme as CoapOptionNum,
+    CoapOptionType::Size1 as CoapOptionNum,
+    CoapOptionType::Echo as CoapOptionNum,
+    CoapOptionType::NoResponse as CoapOptionNum,
+    CoapOptionType::RTag as CoapOptionNum,
+];
+
+// Ensure at compile time that the array above is actually sorted.
+const _: () = {
+    let mut i = 1;
+    while i < COAP_OPTION_NUMBERS_SORTED.len() {
+        assert!(
+            COAP_OPTION_NUMBERS_SORTED[i - 1] < COAP_OPTION_NUMBERS_SORTED[i],
+            "COAP_OPTION_NUMBERS_SORTED is not sorted"
+        );
+        i += 1;
+    }
+};
+
+/// Returns whether `number` is the option number of a known [CoapOptionType].
+///
+/// In contrast to [CoapOptionType::try_from()], this function can be used in constant expressions.
+///
+/// # Examples
+/// ```
+/// use libcoap_rs::protocol::{is_valid_option_number, CoapOptionType};
+///
+/// const URI_PATH_KNOWN: bool = is_valid_option_number(CoapOptionType::UriPath as u16);
+/// assert!(URI_PATH_KNOWN);
+/// assert!(!is_valid_option_number(2));
+/// ```
+pub const fn is_valid_option_number(number: CoapOptionNum) -> bool {
+    let mut i = 0;
+    while i < COAP_OPTION_NUMBERS_SORTED.len() {
+        if COAP_OPTION_NUMBERS_SORTED[i] == number {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Returns whether options with the given option number are critical, i.e., whether a recipient
+/// that does not recognize the option must reject the message.
+///
+/// Critical options are those with an odd option number, see
+/// [RFC 7252, Section 5.4.6](https://datatracker.ietf.org/doc/html/rfc7252#section-5.4.6).
+///
+/// # Examples
+/// ```
+/// use libcoap_rs::protocol::{is_critical_option, CoapOptionType};
+///
+/// assert!(is_critical_option(CoapOptionType::UriPath as u16));
+/// assert!(!is_critical_option(CoapOptionType::ContentFormat as u16));
+/// ```
+pub const fn is_critical_option(number: CoapOptionNum) -> bool {
+    number & 1 == 1
+}
+
 /// Various content formats that can be used for CoAP requests.
 ///
 /// To get the corresponding numeric value, use `[value] as u16`.
@@ -262,6 +685,173 @@ pub enum CoapContentFormat {
     Other,
 }
 
+impl CoapContentFormat {
+    /// Returns whether payloads of this content format are textual (i.e., human-readable text
+    /// encoded as UTF-8).
+    ///
+    /// Returns `false` for binary formats as well as for [CoapContentFormat::Other], as nothing is
+    /// known about unregistered content formats.
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self,
+            CoapContentFormat::TextPlain
+                | CoapContentFormat::LinkFormat
+                | CoapContentFormat::Json
+                | CoapContentFormat::ApplicationXml
+                | CoapContentFormat::RdfXml
+                | CoapContentFormat::SenMlJson
+                | CoapContentFormat::SenMlXml
+                | CoapContentFormat::SensMlJson
+                | CoapContentFormat::SensMlXml
+                | CoapContentFormat::CoapGroupJson
+        )
+    }
+
+    /// Returns the value of the `charset` parameter that is part of the media type registered for
+    /// this content format, if any.
+    ///
+    /// Note that other textual formats (e.g., JSON) are also encoded as UTF-8 but do not have a
+    /// `charset` parameter, see [CoapContentFormat::is_text()].
+    pub fn charset(&self) -> Option<&'static str> {
+        match self {
+            CoapContentFormat::TextPlain => Some("utf-8"),
+            _ => None,
+        }
+    }
+
+    /// Returns the structured syntax suffix (see
+    /// [RFC 6839](https://datatracker.ietf.org/doc/html/rfc6839)) of the media type registered for
+    /// this content format, if any.
+    ///
+    /// For instance, this returns `Some("cbor")` for `application/senml+cbor`.
+    pub fn structured_suffix(&self) -> Option<&'static str> {
+        match self {
+            CoapContentFormat::DotsCbor
+            | CoapContentFormat::SenMlCbor
+            | CoapContentFormat::SensMlCbor
+            | CoapContentFormat::AceCbor => Some("cbor"),
+            CoapContentFormat::SenMlJson | CoapContentFormat::SensMlJson | CoapContentFormat::CoapGroupJson => {
+                Some("json")
+            },
+            CoapContentFormat::RdfXml | CoapContentFormat::SenMlXml | CoapContentFormat::SensMlXml => Some("xml"),
+            CoapContentFormat::MbCborSeq => Some("cbor-seq"),
+            _ => None,
+        }
+    }
+
+    /// Returns the media type (including parameters) registered for this content format, e.g.,
+    /// `text/plain; charset=utf-8` for [CoapContentFormat::TextPlain].
+    ///
+    /// Returns `None` for [CoapContentFormat::Other].
+    ///
+    /// See <https://www.iana.org/assignments/core-parameters/core-parameters.xhtml#content-formats>
+    /// for the registered media types.
+    pub fn media_type(&self) -> Option<&'static str> {
+        Some(match self {
+            CoapContentFormat::Cbor => "application/cbor",
+            CoapContentFormat::DotsCbor => "application/dots+cbor",
+            CoapContentFormat::SenMlCbor => "application/senml+cbor",
+            CoapContentFormat::SenMlExi => "application/senml-exi",
+            CoapContentFormat::CoseEncrypt => "application/cose; cose-type=\"cose-encrypt\"",
+            CoapContentFormat::CoseEncrypt0 => "application/cose; cose-type=\"cose-encrypt0\"",
+            CoapContentFormat::CoseKey => "application/cose-key",
+            CoapContentFormat::CoseKeySet => "application/cose-key-set",
+            CoapContentFormat::CoseMac => "application/cose; cose-type=\"cose-mac\"",
+            CoapContentFormat::CoseMac0 => "application/cose; cose-type=\"cose-mac0\"",
+            CoapContentFormat::CoseSign => "application/cose; cose-type=\"cose-sign\"",
+            CoapContentFormat::CoseSign1 => "application/cose; cose-type=\"cose-sign1\"",
+            CoapContentFormat::Cwt => "application/cwt",
+            CoapContentFormat::Exi => "application/exi",
+            CoapContentFormat::Json => "application/json",
+            CoapContentFormat::LinkFormat => "application/link-format",
+            CoapContentFormat::OctetStream => "application/octet-stream",
+            CoapContentFormat::RdfXml => "application/rdf+xml",
+            CoapContentFormat::SenMlJson => "application/senml+json",
+            CoapContentFormat::SenMlXml => "application/senml+xml",
+            CoapContentFormat::SensMlCbor => "application/sensml+cbor",
+            CoapContentFormat::SensMlExi => "application/sensml-exi",
+            CoapContentFormat::SensMlJson => "application/sensml+json",
+            CoapContentFormat::SensMlXml => "application/sensml+xml",
+            CoapContentFormat::ApplicationXml => "application/xml",
+            CoapContentFormat::TextPlain => "text/plain; charset=utf-8",
+            CoapContentFormat::AceCbor => "application/ace+cbor",
+            CoapContentFormat::CoapGroupJson => "application/coap-group+json",
+            CoapContentFormat::MbCborSeq => "application/missing-blocks+cbor-seq",
+            CoapContentFormat::Oscore => "application/oscore",
+            CoapContentFormat::Other => return None,
+        })
+    }
+
+    /// Returns the content format registered for the given `media_type` (including parameters),
+    /// or `None` if no content format is known for it.
+    ///
+    /// Media type names and parameter names are compared case-insensitively and whitespace is
+    /// ignored.
+    /// As `text/plain` defaults to the US-ASCII charset (a subset of UTF-8), it is also accepted as
+    /// [CoapContentFormat::TextPlain] if no charset is specified.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::protocol::CoapContentFormat;
+    ///
+    /// assert_eq!(CoapContentFormat::from_media_type("application/json"), Some(CoapContentFormat::Json));
+    /// assert_eq!(
+    ///     CoapContentFormat::from_media_type("Text/Plain;Charset=UTF-8"),
+    ///     Some(CoapContentFormat::TextPlain)
+    /// );
+    /// assert_eq!(CoapContentFormat::from_media_type("image/png"), None);
+    /// ```
+    pub fn from_media_type(media_type: &str) -> Option<CoapContentFormat> {
+        let normalized: String = media_type
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if normalized == "text/plain" {
+            return Some(CoapContentFormat::TextPlain);
+        }
+        REGISTERED_CONTENT_FORMATS.iter().copied().find(|format| {
+            format
+                .media_type()
+                .is_some_and(|v| v.replace(' ', "").eq_ignore_ascii_case(&normalized))
+        })
+    }
+}
+
+/// All content formats except [CoapContentFormat::Other].
+const REGISTERED_CONTENT_FORMATS: [CoapContentFormat; 30] = [
+    CoapContentFormat::Cbor,
+    CoapContentFormat::DotsCbor,
+    CoapContentFormat::SenMlCbor,
+    CoapContentFormat::SenMlExi,
+    CoapContentFormat::CoseEncrypt,
+    CoapContentFormat::CoseEncrypt0,
+    CoapContentFormat::CoseKey,
+    CoapContentFormat::CoseKeySet,
+    CoapContentFormat::CoseMac,
+    CoapContentFormat::CoseMac0,
+    CoapContentFormat::CoseSign,
+    CoapContentFormat::CoseSign1,
+    CoapContentFormat::Cwt,
+    CoapContentFormat::Exi,
+    CoapContentFormat::Json,
+    CoapContentFormat::LinkFormat,
+    CoapContentFormat::OctetStream,
+    CoapContentFormat::RdfXml,
+    CoapContentFormat::SenMlJson,
+    CoapContentFormat::SenMlXml,
+    CoapContentFormat::SensMlCbor,
+    CoapContentFormat::SensMlExi,
+    CoapContentFormat::SensMlJson,
+    CoapContentFormat::SensMlXml,
+    CoapContentFormat::ApplicationXml,
+    CoapContentFormat::TextPlain,
+    CoapContentFormat::AceCbor,
+    CoapContentFormat::CoapGroupJson,
+    CoapContentFormat::MbCborSeq,
+    CoapContentFormat::Oscore,
+];
+
 impl From<ContentFormat> for CoapContentFormat {
     fn from(value: u16) -> Self {
         <CoapContentFormat as FromPrimitive>::from_u16(value).unwrap_or(CoapContentFormat::Other)
@@ -274,6 +864,7 @@ impl From<ContentFormat> for CoapContentFormat {
 /// The numeric value (that can also be obtained with [to_raw_request()](CoapRequestCode::to_raw_pdu_code()))
 /// corresponds to the values defined in <https://www.iana.org/assignments/core-parameters/core-parameters.xhtml#codes>.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum CoapMessageCode {
     Empty,
@@ -291,6 +882,24 @@ impl CoapMessageCode {
             CoapMessageCode::Response(rsp) => rsp.to_raw_pdu_code(),
         }
     }
+
+    /// Returns the method name of this code if it is a request code (see
+    /// [CoapRequestCode::method_str()]).
+    pub fn to_method_str(&self) -> Option<&'static str> {
+        match self {
+            CoapMessageCode::Request(req) => Some(req.method_str()),
+            CoapMessageCode::Empty | CoapMessageCode::Response(_) => None,
+        }
+    }
+
+    /// Returns whether this code is the code of an idempotent request method (see
+    /// [CoapRequestCode::is_idempotent()]).
+    pub fn is_idempotent(&self) -> bool {
+        match self {
+            CoapMessageCode::Request(req) => req.is_idempotent(),
+            CoapMessageCode::Empty | CoapMessageCode::Response(_) => false,
+        }
+    }
 }
 
 impl From<CoapRequestCode> for CoapMessageCode {
@@ -325,17 +934,56 @@ impl TryFrom<coap_pdu_code_t> for CoapMessageCode {
 #[repr(u8)]
 #[non_exhaustive]
 #[derive(FromPrimitive, Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoapRequestCode {
     Get = coap_pdu_code_t::COAP_REQUEST_CODE_GET as u8,
     Put = coap_pdu_code_t::COAP_REQUEST_CODE_PUT as u8,
     Delete = coap_pdu_code_t::COAP_REQUEST_CODE_DELETE as u8,
     Post = coap_pdu_code_t::COAP_REQUEST_CODE_POST as u8,
+    /// FETCH method, see [RFC 8132, Section 2](https://datatracker.ietf.org/doc/html/rfc8132#section-2).
     Fetch = coap_pdu_code_t::COAP_REQUEST_CODE_FETCH as u8,
+    /// iPATCH method, see [RFC 8132, Section 3](https://datatracker.ietf.org/doc/html/rfc8132#section-3).
     IPatch = coap_pdu_code_t::COAP_REQUEST_CODE_IPATCH as u8,
+    /// PATCH method, see [RFC 8132, Section 3](https://datatracker.ietf.org/doc/html/rfc8132#section-3).
     Patch = coap_pdu_code_t::COAP_REQUEST_CODE_PATCH as u8,
 }
 
 impl CoapRequestCode {
+    /// Returns whether this method is safe, i.e., only retrieves a representation without changing
+    /// the state of the resource (see
+    /// [RFC 7252, Section 5.1](https://datatracker.ietf.org/doc/html/rfc7252#section-5.1) and
+    /// [RFC 8132, Section 2](https://datatracker.ietf.org/doc/html/rfc8132#section-2)).
+    ///
+    /// Responses to safe methods may be cached.
+    pub fn is_safe(self) -> bool {
+        matches!(self, CoapRequestCode::Get | CoapRequestCode::Fetch)
+    }
+
+    /// Returns whether this method is idempotent, i.e., performing the same request multiple times
+    /// has the same effect as performing it once (see
+    /// [RFC 7252, Section 5.1](https://datatracker.ietf.org/doc/html/rfc7252#section-5.1) and
+    /// [RFC 8132, Section 3](https://datatracker.ietf.org/doc/html/rfc8132#section-3)).
+    ///
+    /// Idempotent requests can safely be repeated if no response was received.
+    pub fn is_idempotent(self) -> bool {
+        !matches!(self, CoapRequestCode::Post | CoapRequestCode::Patch)
+    }
+
+    /// Returns the name of this method as used in its specification (e.g., `"GET"`, or `"iPATCH"`
+    /// for [CoapRequestCode::IPatch], see
+    /// [RFC 8132, Section 3](https://datatracker.ietf.org/doc/html/rfc8132#section-3)).
+    pub fn method_str(self) -> &'static str {
+        match self {
+            CoapRequestCode::Get => "GET",
+            CoapRequestCode::Put => "PUT",
+            CoapRequestCode::Delete => "DELETE",
+            CoapRequestCode::Post => "POST",
+            CoapRequestCode::Fetch => "FETCH",
+            CoapRequestCode::IPatch => "iPATCH",
+            CoapRequestCode::Patch => "PATCH",
+        }
+    }
+
     /// Returns the [coap_request_t](coap_request_t) corresponding to this request code.
     ///
     /// Note that this is *not* the code that should be set inside of a [coap_pdu_t](libcoap_sys::coap_pdu_t),
@@ -345,7 +993,7 @@ impl CoapRequestCode {
         match self {
             CoapRequestCode::Get => coap_request_t::COAP_REQUEST_GET,
             CoapRequestCode::Put => coap_request_t::COAP_REQUEST_PUT,
-            CoapRequestCode::Delete => coap_request_t::COAP_REQUEST_FETCH,
+            CoapRequestCode::Delete => coap_request_t::COAP_REQUEST_DELETE,
             CoapRequestCode::Post => coap_request_t::COAP_REQUEST_POST,
             CoapRequestCode::Fetch => coap_request_t::COAP_REQUEST_FETCH,
             CoapRequestCode::IPatch => coap_request_t::COAP_REQUEST_IPATCH,
@@ -359,7 +1007,7 @@ impl CoapRequestCode {
         match self {
             CoapRequestCode::Get => coap_pdu_code_t::COAP_REQUEST_CODE_GET,
             CoapRequestCode::Put => coap_pdu_code_t::COAP_REQUEST_CODE_PUT,
-            CoapRequestCode::Delete => coap_pdu_code_t::COAP_REQUEST_CODE_FETCH,
+            CoapRequestCode::Delete => coap_pdu_code_t::COAP_REQUEST_CODE_DELETE,
             CoapRequestCode::Post => coap_pdu_code_t::COAP_REQUEST_CODE_POST,
             CoapRequestCode::Fetch => coap_pdu_code_t::COAP_REQUEST_CODE_FETCH,
             CoapRequestCode::IPatch => coap_pdu_code_t::COAP_REQUEST_CODE_IPATCH,
@@ -368,6 +1016,17 @@ impl CoapRequestCode {
     }
 }
 
+/// All request codes that have a method name.
+const METHOD_CODES: [CoapRequestCode; 7] = [
+    CoapRequestCode::Get,
+    CoapRequestCode::Post,
+    CoapRequestCode::Put,
+    CoapRequestCode::Delete,
+    CoapRequestCode::Fetch,
+    CoapRequestCode::Patch,
+    CoapRequestCode::IPatch,
+];
+
 impl From<coap_request_t> for CoapRequestCode {
     fn from(req: coap_request_t) -> Self {
         match req {
@@ -383,6 +1042,26 @@ impl From<coap_request_t> for CoapRequestCode {
     }
 }
 
+impl Display for CoapRequestCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fmt_dotted_code(*self as u8, f)
+    }
+}
+
+impl FromStr for CoapRequestCode {
+    type Err = CodeParseError;
+
+    /// Parses a request code given either as its method name (e.g., `"GET"` or `"iPATCH"`, see
+    /// [CoapRequestCode::method_str()]) or in the dotted "c.dd" notation (e.g., `"0.01"` for
+    /// [CoapRequestCode::Get]).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(code) = METHOD_CODES.iter().copied().find(|v| v.method_str() == s) {
+            return Ok(code);
+        }
+        <CoapRequestCode as FromPrimitive>::from_u8(parse_dotted_code(s)?).ok_or(CodeParseError::UnknownCode)
+    }
+}
+
 impl TryFrom<coap_pdu_code_t> for CoapRequestCode {
     type Error = MessageCodeError;
 
@@ -398,6 +1077,7 @@ impl TryFrom<coap_pdu_code_t> for CoapRequestCode {
 #[repr(u8)]
 #[non_exhaustive]
 #[derive(Clone, Copy, FromPrimitive, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoapResponseCode {
     Content = coap_pdu_code_t::COAP_RESPONSE_CODE_CONTENT as u8,
     BadGateway = coap_pdu_code_t::COAP_RESPONSE_CODE_BAD_GATEWAY as u8,
@@ -466,20 +1146,100 @@ impl CoapResponseCode {
     }
 }
 
+impl CoapResponseCode {
+    /// Returns this response code in the dotted "c.dd" notation used in
+    /// [RFC 7252, Section 12.1](https://datatracker.ietf.org/doc/html/rfc7252#section-12.1) (e.g.,
+    /// `"2.05"` for [CoapResponseCode::Content]).
+    ///
+    /// This is equivalent to the [Display] implementation of this type.
+    pub fn display_dotted(&self) -> String {
+        self.to_string()
+    }
+
+    /// Parses a response code given in the dotted "c.dd" notation (e.g., `"4.04"`).
+    ///
+    /// # Errors
+    /// Returns [CodeParseError::InvalidFormat] if the string is not in dotted notation and
+    /// [CodeParseError::UnknownCode] if it does not represent a known response code.
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::protocol::CoapResponseCode;
+    ///
+    /// assert_eq!(CoapResponseCode::from_dotted("4.04"), Ok(CoapResponseCode::NotFound));
+    /// assert_eq!(CoapResponseCode::NotFound.to_string(), "4.04");
+    /// assert_eq!(CoapResponseCode::NotFound.reason_phrase(), "Not Found");
+    /// ```
+    pub fn from_dotted(s: &str) -> Result<CoapResponseCode, CodeParseError> {
+        <CoapResponseCode as FromPrimitive>::from_u8(parse_dotted_code(s)?).ok_or(CodeParseError::UnknownCode)
+    }
+
+    /// Returns the human-readable reason phrase for this response code as registered in
+    /// [RFC 7252, Section 12.1.2](https://datatracker.ietf.org/doc/html/rfc7252#section-12.1.2) and
+    /// the RFCs that introduced the remaining codes.
+    pub fn reason_phrase(&self) -> &'static str {
+        match self {
+            CoapResponseCode::Created => "Created",
+            CoapResponseCode::Deleted => "Deleted",
+            CoapResponseCode::Valid => "Valid",
+            CoapResponseCode::Changed => "Changed",
+            CoapResponseCode::Content => "Content",
+            CoapResponseCode::Continue => "Continue",
+            CoapResponseCode::BadRequest => "Bad Request",
+            CoapResponseCode::Unauthorized => "Unauthorized",
+            CoapResponseCode::BadOption => "Bad Option",
+            CoapResponseCode::Forbidden => "Forbidden",
+            CoapResponseCode::NotFound => "Not Found",
+            CoapResponseCode::NotAllowed => "Method Not Allowed",
+            CoapResponseCode::NotAcceptable => "Not Acceptable",
+            CoapResponseCode::Incomplete => "Request Entity Incomplete",
+            CoapResponseCode::Conflict => "Conflict",
+            CoapResponseCode::PreconditionFailed => "Precondition Failed",
+            CoapResponseCode::RequestTooLarge => "Request Entity Too Large",
+            CoapResponseCode::UnsupportedContentFormat => "Unsupported Content-Format",
+            CoapResponseCode::Unprocessable => "Unprocessable Entity",
+            CoapResponseCode::TooManyRequests => "Too Many Requests",
+            CoapResponseCode::InternalError => "Internal Server Error",
+            CoapResponseCode::NotImplemented => "Not Implemented",
+            CoapResponseCode::BadGateway => "Bad Gateway",
+            CoapResponseCode::ServiceUnavailable => "Service Unavailable",
+            CoapResponseCode::GatewayTimeout => "Gateway Timeout",
+            CoapResponseCode::ProxyingNotSupported => "Proxying Not Supported",
+            CoapResponseCode::HopLimitReached => "Hop Limit Reached",
+        }
+    }
+}
+
+/// Formats a raw message code in the dotted "c.dd" notation.
+fn fmt_dotted_code(code: u8, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}.{:02}", code >> 5, code & 0x1f)
+}
+
+/// Parses a message code given in the dotted "c.dd" notation into its raw numeric value.
+fn parse_dotted_code(s: &str) -> Result<u8, CodeParseError> {
+    let (class, detail) = s.split_once('.').ok_or(CodeParseError::InvalidFormat)?;
+    if class.len() != 1 || detail.len() != 2 || !class.bytes().chain(detail.bytes()).all(|c| c.is_ascii_digit()) {
+        return Err(CodeParseError::InvalidFormat);
+    }
+    let class: u8 = class.parse().map_err(|_| CodeParseError::InvalidFormat)?;
+    let detail: u8 = detail.parse().map_err(|_| CodeParseError::InvalidFormat)?;
+    if class > 7 || detail > 31 {
+        return Err(CodeParseError::InvalidFormat);
+    }
+    Ok(class << 5 | detail)
+}
+
 impl Display for CoapResponseCode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let response_phrase = unsafe {
-            let raw_phrase = coap_response_phrase(*self as u8);
-            if raw_phrase.is_null() {
-                "unknown response code"
-            } else {
-                CStr::from_ptr(raw_phrase)
-                    .to_str()
-                    .unwrap_or("unable to retrieve phrase for response code")
-            }
-        };
+        fmt_dotted_code(*self as u8, f)
+    }
+}
+
+impl FromStr for CoapResponseCode {
+    type Err = CodeParseError;
 
-        write!(f, "{}", response_phrase)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CoapResponseCode::from_dotted(s)
     }
 }
 
@@ -495,6 +1255,7 @@ impl TryFrom<coap_pdu_code_t> for CoapResponseCode {
 /// and described in [RFC 7252, Section 4.2 and 4.3](https://datatracker.ietf.org/doc/html/rfc7252#section-4.2).
 #[repr(u8)]
 #[derive(Copy, Clone, Hash, Eq, PartialEq, FromPrimitive, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoapMessageType {
     /// Confirmable message, i.e. a message whose reception should be confirmed by the peer.
     Con = COAP_MESSAGE_CON as u8,
@@ -519,6 +1280,34 @@ impl CoapMessageType {
     }
 }
 
+impl Display for CoapMessageType {
+    /// Formats this message type using its abbreviation from RFC 7252 (e.g., `"CON"`).
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CoapMessageType::Con => "CON",
+            CoapMessageType::Non => "NON",
+            CoapMessageType::Ack => "ACK",
+            CoapMessageType::Rst => "RST",
+        })
+    }
+}
+
+impl FromStr for CoapMessageType {
+    type Err = MessageTypeParseError;
+
+    /// Parses a message type from its abbreviation (e.g., `"CON"` for [CoapMessageType::Con]),
+    /// ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "CON" => Ok(CoapMessageType::Con),
+            "NON" => Ok(CoapMessageType::Non),
+            "ACK" => Ok(CoapMessageType::Ack),
+            "RST" => Ok(CoapMessageType::Rst),
+            _ => Err(MessageTypeParseError::UnknownType),
+        }
+    }
+}
+
 impl From<coap_pdu_type_t> for CoapMessageType {
     fn from(raw_type: coap_pdu_type_t) -> Self {
         FromPrimitive::from_u32(raw_type as u32).expect("unknown PDU type")
@@ -0,0 +1,458 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * pubsub.rs - Publish-subscribe broker for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Publish-subscribe broker based on
+//! [draft-ietf-core-coap-pubsub](https://datatracker.ietf.org/doc/html/draft-ietf-core-coap-pubsub).
+//!
+//! A [CoapPubSubBroker] wraps a [CoapContext] and manages a set of topics, which are provided as
+//! resources below the topic collection resource `/ps`. Clients may interact with the broker using
+//! the following requests:
+//! - `GET /ps`: Discover the available topics (as a list in link format).
+//! - `POST /ps`: Create a new topic. The payload must be a link in link format describing the new
+//!   topic, e.g. `<temperature>;ct=0` (the content format defaults to `text/plain` if omitted).
+//! - `GET /ps/<topic>`: Read the latest value published to a topic. If the request contains an
+//!   Observe option, the client is subscribed to (or unsubscribed from) the topic.
+//! - `PUT /ps/<topic>`: Publish a new value to a topic, which is then sent to all subscribers.
+//! - `DELETE /ps/<topic>`: Remove a topic.
+//!
+//! Topics can also be created, published to and removed by the application itself using the
+//! methods of [CoapPubSubBroker].
+//!
+//! # Examples
+//! ```
+//! use libcoap_rs::{protocol::CoapContentFormat, pubsub::CoapPubSubBroker, CoapContext};
+//!
+//! let mut broker = CoapPubSubBroker::new(CoapContext::new().unwrap());
+//! let topic = broker.create_topic("temperature", CoapContentFormat::TextPlain as u16).unwrap();
+//! broker.publish(topic, "21.5".as_bytes().into()).unwrap();
+//!
+//! assert_eq!(broker.latest_value(topic).as_deref(), Some("21.5".as_bytes()));
+//! assert_eq!(broker.subscriber_count(topic), 0);
+//! ```
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use libcoap_sys::coap_session_get_context;
+
+use crate::{
+    context::CoapContext,
+    error::{IoProcessError, PubSubError},
+    event::CoapEvent,
+    message::{CoapMessageCommon, CoapOption, CoapRequest, CoapResponse},
+    protocol::{CoapContentFormat, CoapRequestCode, CoapResponseCode, ContentFormat},
+    resource::{CoapRequestHandler, CoapResource},
+    session::{CoapServerSession, CoapSession, CoapSessionCommon},
+};
+
+/// URI path of the collection resource that contains all topics of a broker.
+pub const PUBSUB_COLLECTION_PATH: &str = "ps";
+
+/// Maximum length of a topic name (the maximum length of a single Uri-Path option value).
+const MAX_TOPIC_NAME_LENGTH: usize = 255;
+
+/// Handle referring to a topic of a [CoapPubSubBroker].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TopicHandle(u64);
+
+/// A topic of a broker.
+#[derive(Debug)]
+struct Topic {
+    name: String,
+    content_format: ContentFormat,
+    /// Latest representation published to this topic.
+    latest: Option<Box<[u8]>>,
+}
+
+impl Topic {
+    fn uri_path(&self) -> String {
+        topic_uri_path(&self.name)
+    }
+}
+
+/// State of a broker that is shared between the broker and its resource handlers.
+#[derive(Debug, Default)]
+struct BrokerState {
+    topics: HashMap<TopicHandle, Topic>,
+    /// Server-side sessions that are subscribed to each topic.
+    ///
+    /// The sessions stored here do not increase the libcoap-internal reference counter and are
+    /// removed as soon as the session is deleted, as the context has to have exclusive ownership
+    /// over the session at that point.
+    subscriptions: HashMap<TopicHandle, Vec<CoapServerSession<'static>>>,
+    next_topic_id: u64,
+    /// URI paths of topic resources that were deleted by a client and still have to be removed
+    /// from the context (which is not possible while the resource handler is running).
+    pending_removals: Vec<String>,
+}
+
+type SharedBrokerState = Rc<RefCell<BrokerState>>;
+
+impl BrokerState {
+    fn topic_by_name(&self, name: &str) -> Option<TopicHandle> {
+        self.topics.iter().find(|(_, v)| v.name == name).map(|(k, _)| *k)
+    }
+
+    fn insert_topic(&mut self, name: &str, content_format: ContentFormat) -> Result<TopicHandle, PubSubError> {
+        validate_topic_name(name)?;
+        // A topic whose resource has not been removed yet may not be recreated, as libcoap would
+        // otherwise free the old resource while it is still in use.
+        if self.topic_by_name(name).is_some() || self.pending_removals.contains(&topic_uri_path(name)) {
+            return Err(PubSubError::TopicExists);
+        }
+        let handle = TopicHandle(self.next_topic_id);
+        self.next_topic_id += 1;
+        self.topics.insert(
+            handle,
+            Topic {
+                name: name.to_string(),
+                content_format,
+                latest: None,
+            },
+        );
+        Ok(handle)
+    }
+
+    fn remove_topic(&mut self, topic: TopicHandle) -> Option<Topic> {
+        self.subscriptions.remove(&topic);
+        self.topics.remove(&topic)
+    }
+
+    fn remove_subscriber(&mut self, session: &CoapServerSession) {
+        for subscribers in self.subscriptions.values_mut() {
+            subscribers.retain(|v| !v.eq(session));
+        }
+    }
+
+    /// Returns the list of topics in link format (see [RFC 6690](https://datatracker.ietf.org/doc/html/rfc6690)).
+    fn link_format(&self) -> String {
+        let mut topics: Vec<&Topic> = self.topics.values().collect();
+        topics.sort_by(|a, b| a.name.cmp(&b.name));
+        topics
+            .iter()
+            .map(|v| format!("</{}>;rt=\"core.ps.data\";ct={};obs", v.uri_path(), v.content_format))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+/// User data of a topic resource.
+#[derive(Debug)]
+struct TopicResourceData {
+    state: SharedBrokerState,
+    topic: TopicHandle,
+}
+
+/// Simple CoAP publish-subscribe broker.
+///
+/// See the [module level documentation](crate::pubsub) for the requests that are handled by the
+/// broker.
+///
+/// The broker takes ownership over the [CoapContext] it is created with. Endpoints and additional
+/// resources can be added to the context using [CoapPubSubBroker::context_mut()].
+/// Note that the broker uses the event handler of the context to keep track of subscribed
+/// sessions, which must therefore not be replaced. Additionally, IO has to be performed using
+/// [CoapPubSubBroker::do_io()] instead of [CoapContext::do_io()], as the former also cleans up
+/// topics deleted by clients.
+#[derive(Debug)]
+pub struct CoapPubSubBroker<'a> {
+    context: CoapContext<'a>,
+    state: SharedBrokerState,
+}
+
+impl<'a> CoapPubSubBroker<'a> {
+    /// Creates a new broker without any topics, using the given `context`.
+    pub fn new(mut context: CoapContext<'a>) -> CoapPubSubBroker<'a> {
+        let state = SharedBrokerState::default();
+        let collection = CoapResource::<SharedBrokerState>::new(PUBSUB_COLLECTION_PATH, Rc::clone(&state), false);
+        collection.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(handle_collection_get)),
+        );
+        collection.set_method_handler(
+            CoapRequestCode::Post,
+            Some(CoapRequestHandler::new(handle_collection_post)),
+        );
+        context.add_resource(collection);
+        let event_state = Rc::clone(&state);
+        context.set_event_callback(move |event, session| {
+            if let (CoapEvent::ServerSessionDel, CoapSession::Server(session)) = (event, session) {
+                event_state.borrow_mut().remove_subscriber(session);
+            }
+        });
+        CoapPubSubBroker { context, state }
+    }
+
+    /// Returns a reference to the context used by this broker.
+    pub fn context(&self) -> &CoapContext<'a> {
+        &self.context
+    }
+
+    /// Returns a mutable reference to the context used by this broker.
+    ///
+    /// Do not replace the event handler of the context, see the struct level documentation.
+    pub fn context_mut(&mut self) -> &mut CoapContext<'a> {
+        &mut self.context
+    }
+
+    /// Creates a new topic with the given `name` whose representations have the given
+    /// `content_format`.
+    ///
+    /// The topic is made available at the path `/ps/<name>`.
+    ///
+    /// # Errors
+    /// Returns [PubSubError::InvalidTopicName] if the name is empty, longer than 255 bytes or does not
+    /// consist only of unreserved URI characters, and [PubSubError::TopicExists] if a topic with the
+    /// same name already exists.
+    pub fn create_topic(&mut self, name: &str, content_format: ContentFormat) -> Result<TopicHandle, PubSubError> {
+        let topic = self.state.borrow_mut().insert_topic(name, content_format)?;
+        add_topic_resource(&mut self.context, &self.state, topic, name);
+        Ok(topic)
+    }
+
+    /// Returns the handle of the topic with the given `name`, if it exists.
+    pub fn topic(&self, name: &str) -> Option<TopicHandle> {
+        self.state.borrow().topic_by_name(name)
+    }
+
+    /// Publishes a new representation to the given `topic` and notifies all subscribers.
+    ///
+    /// Notifications are sent during the next call to [CoapPubSubBroker::do_io()].
+    pub fn publish(&mut self, topic: TopicHandle, payload: Box<[u8]>) -> Result<(), PubSubError> {
+        let uri_path = {
+            let mut state = self.state.borrow_mut();
+            let topic = state.topics.get_mut(&topic).ok_or(PubSubError::UnknownTopic)?;
+            topic.latest = Some(payload);
+            topic.uri_path()
+        };
+        self.context
+            .with_resource(&uri_path, |resource: &CoapResource<TopicResourceData>| {
+                resource.notify_observers()
+            });
+        Ok(())
+    }
+
+    /// Returns the latest representation published to the given `topic`.
+    pub fn latest_value(&self, topic: TopicHandle) -> Option<Box<[u8]>> {
+        self.state.borrow().topics.get(&topic).and_then(|v| v.latest.clone())
+    }
+
+    /// Returns the number of sessions that are currently subscribed to the given `topic`.
+    pub fn subscriber_count(&self, topic: TopicHandle) -> usize {
+        self.state.borrow().subscriptions.get(&topic).map_or(0, Vec::len)
+    }
+
+    /// Removes the given `topic` and its associated resource.
+    pub fn delete_topic(&mut self, topic: TopicHandle) -> Result<(), PubSubError> {
+        let topic = self
+            .state
+            .borrow_mut()
+            .remove_topic(topic)
+            .ok_or(PubSubError::UnknownTopic)?;
+        self.context.remove_resource(&topic.uri_path());
+        Ok(())
+    }
+
+    /// Performs currently outstanding IO operations, waiting for a maximum duration of `timeout`.
+    ///
+    /// See [CoapContext::do_io()] for more information.
+    pub fn do_io(&mut self, timeout: Option<Duration>) -> Result<Duration, IoProcessError> {
+        let spent_time = self.context.do_io(timeout)?;
+        let pending_removals = std::mem::take(&mut self.state.borrow_mut().pending_removals);
+        for uri_path in pending_removals {
+            self.context.remove_resource(&uri_path);
+        }
+        Ok(spent_time)
+    }
+}
+
+impl Drop for CoapPubSubBroker<'_> {
+    fn drop(&mut self) {
+        // The context requires exclusive ownership over its server-side sessions once it is
+        // dropped, so we have to drop our references to subscribed sessions beforehand.
+        self.state.borrow_mut().subscriptions.clear();
+    }
+}
+
+fn topic_uri_path(name: &str) -> String {
+    format!("{}/{}", PUBSUB_COLLECTION_PATH, name)
+}
+
+/// Checks whether the given topic name can be used as a single path segment without any escaping.
+fn validate_topic_name(name: &str) -> Result<(), PubSubError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_TOPIC_NAME_LENGTH
+        && name != "."
+        && name != ".."
+        && name.bytes().all(|c| c.is_ascii_alphanumeric() || b"-._~".contains(&c));
+    if valid {
+        Ok(())
+    } else {
+        Err(PubSubError::InvalidTopicName)
+    }
+}
+
+/// Parses a link in link format describing a new topic, e.g. `<temperature>;ct=0`.
+///
+/// Returns the link target and the value of the `ct` attribute, if present.
+fn parse_topic_link(link: &str) -> Option<(&str, Option<ContentFormat>)> {
+    let (target, attributes) = link.trim().strip_prefix('<')?.split_once('>')?;
+    let mut content_format = None;
+    for attribute in attributes.split(';').map(str::trim).filter(|v| !v.is_empty()) {
+        if let Some(value) = attribute.strip_prefix("ct=") {
+            content_format = Some(value.trim_matches('"').parse().ok()?);
+        }
+    }
+    Some((target, content_format))
+}
+
+fn add_topic_resource(context: &mut CoapContext, state: &SharedBrokerState, topic: TopicHandle, name: &str) {
+    let resource = CoapResource::<TopicResourceData>::new(
+        &topic_uri_path(name),
+        TopicResourceData {
+            state: Rc::clone(state),
+            topic,
+        },
+        false,
+    );
+    resource.set_get_observable(true);
+    resource.set_method_handler(CoapRequestCode::Get, Some(CoapRequestHandler::new(handle_topic_get)));
+    resource.set_method_handler(
+        CoapRequestCode::Put,
+        Some(CoapRequestHandler::new_resource_ref(handle_topic_put)),
+    );
+    resource.set_method_handler(
+        CoapRequestCode::Delete,
+        Some(CoapRequestHandler::new(handle_topic_delete)),
+    );
+    context.add_resource(resource);
+}
+
+fn handle_collection_get(
+    state: &mut SharedBrokerState,
+    session: &mut CoapServerSession,
+    _request: &CoapRequest,
+    mut response: CoapResponse,
+) {
+    let topic_list = state.borrow().link_format();
+    response.set_content_format(Some(CoapContentFormat::LinkFormat as ContentFormat));
+    response.set_data(Some(topic_list.into_bytes()));
+    session.respond(response, CoapResponseCode::Content);
+}
+
+fn handle_collection_post(
+    state: &mut SharedBrokerState,
+    session: &mut CoapServerSession,
+    request: &CoapRequest,
+    mut response: CoapResponse,
+) {
+    let Some((name, content_format)) = request
+        .data()
+        .and_then(|v| std::str::from_utf8(v).ok())
+        .and_then(parse_topic_link)
+    else {
+        session.respond(response, CoapResponseCode::BadRequest);
+        return;
+    };
+    let content_format = content_format.unwrap_or(CoapContentFormat::TextPlain as ContentFormat);
+    let result = state.borrow_mut().insert_topic(name, content_format);
+    let code = match result {
+        Ok(topic) => {
+            // SAFETY: The session is valid while the handler is running and belongs to a context
+            // created by this library.
+            let mut context = unsafe { CoapContext::from_raw(coap_session_get_context(session.raw_session_mut())) };
+            add_topic_resource(&mut context, state, topic, name);
+            response.add_option(CoapOption::LocationPath(PUBSUB_COLLECTION_PATH.to_string()));
+            response.add_option(CoapOption::LocationPath(name.to_string()));
+            CoapResponseCode::Created
+        },
+        Err(PubSubError::TopicExists) => CoapResponseCode::Forbidden,
+        Err(_) => CoapResponseCode::BadRequest,
+    };
+    session.respond(response, code);
+}
+
+fn handle_topic_get(
+    data: &mut TopicResourceData,
+    session: &mut CoapServerSession,
+    request: &CoapRequest,
+    mut response: CoapResponse,
+) {
+    let mut state_ref = data.state.borrow_mut();
+    let state = &mut *state_ref;
+    let Some(topic) = state.topics.get(&data.topic) else {
+        session.respond(response, CoapResponseCode::NotFound);
+        return;
+    };
+    // The handler is also called with the registration request for each notification, so we have
+    // to check whether the session is already subscribed.
+    if request.is_observe_registration() {
+        let subscribers = state.subscriptions.entry(data.topic).or_default();
+        if !subscribers.iter().any(|v| v.eq(&*session)) {
+            // SAFETY: The raw session is valid and has its app data set. The subscriber list does
+            // not increase the reference counter, but is cleaned up once the session is deleted.
+            subscribers.push(unsafe { CoapServerSession::from_raw_without_refcount(session.raw_session_mut()) });
+        }
+    } else if request.is_observe_deregistration() {
+        if let Some(subscribers) = state.subscriptions.get_mut(&data.topic) {
+            subscribers.retain(|v| !v.eq(&*session));
+        }
+    }
+    response.set_content_format(Some(topic.content_format));
+    if let Some(latest) = &topic.latest {
+        response.set_data(Some(latest.clone()));
+    }
+    session.respond(response, CoapResponseCode::Content);
+}
+
+fn handle_topic_put(
+    resource: &CoapResource<TopicResourceData>,
+    session: &mut CoapServerSession,
+    request: &CoapRequest,
+    response: CoapResponse,
+) {
+    let (state, topic) = {
+        let data = resource.user_data();
+        (Rc::clone(&data.state), data.topic)
+    };
+    let result = match state.borrow_mut().topics.get_mut(&topic) {
+        None => Err(CoapResponseCode::NotFound),
+        Some(topic) if request.content_format().is_some_and(|v| v != topic.content_format) => {
+            Err(CoapResponseCode::UnsupportedContentFormat)
+        },
+        Some(topic) => {
+            topic.latest = Some(request.data().unwrap_or_default().into());
+            Ok(())
+        },
+    };
+    match result {
+        Ok(()) => {
+            session.respond(response, CoapResponseCode::Changed);
+            resource.notify_observers();
+        },
+        Err(code) => session.respond(response, code),
+    }
+}
+
+fn handle_topic_delete(
+    data: &mut TopicResourceData,
+    session: &mut CoapServerSession,
+    _request: &CoapRequest,
+    response: CoapResponse,
+) {
+    let mut state = data.state.borrow_mut();
+    let code = match state.remove_topic(data.topic) {
+        Some(topic) => {
+            state.pending_removals.push(topic.uri_path());
+            CoapResponseCode::Deleted
+        },
+        None => CoapResponseCode::NotFound,
+    };
+    std::mem::drop(state);
+    session.respond(response, code);
+}
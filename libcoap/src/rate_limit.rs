@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * rate_limit.rs - Request rate limiting for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Rate limiting of requests received by server-side sessions, see
+//! [CoapContext::set_rate_limit()].
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    time::Instant,
+};
+
+#[cfg(doc)]
+use crate::context::CoapContext;
+use crate::protocol::MaxAge;
+
+/// Minimum number of token buckets that are kept before idle buckets (and the statistics of IP
+/// addresses without any remaining buckets) are removed.
+const MIN_PRUNE_THRESHOLD: usize = 64;
+
+/// Determines which requests share a rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// All requests from the same IP address share a rate limit, regardless of the session (i.e.,
+    /// the source port) they were received on.
+    SourceIp,
+    /// Each session (i.e., each combination of remote IP address and port) has its own rate limit.
+    SourceSession,
+}
+
+/// Configuration of the request rate limit of a [CoapContext] (see
+/// [CoapContext::set_rate_limit()]).
+///
+/// Rate limiting uses a token bucket algorithm: Each source of requests (determined by `by`) has
+/// a bucket of `burst_size` tokens, which is refilled at `max_requests_per_second` tokens per
+/// second. Each request takes a token from the bucket, and requests that arrive while the bucket
+/// is empty are rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    /// Number of requests per second that are accepted from a single source in the long term.
+    ///
+    /// If this is zero, the buckets are never refilled, i.e., each source may only send
+    /// `burst_size` requests in total.
+    pub max_requests_per_second: u32,
+    /// Number of requests that may be received from a single source in a burst before its rate is
+    /// limited to `max_requests_per_second`.
+    ///
+    /// A burst size of zero is treated like a burst size of one.
+    pub burst_size: u32,
+    /// Determines which requests share a rate limit.
+    pub by: RateLimitKey,
+}
+
+/// Statistics about the requests received from a single IP address while rate limiting was
+/// enabled (see [CoapContext::rate_limit_stats()]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitStats {
+    /// Number of requests that were passed on to the resource handlers.
+    pub accepted: u64,
+    /// Number of requests that were answered with `4.29 Too Many Requests`.
+    pub rejected: u64,
+}
+
+/// Key identifying a token bucket, depending on the configured [RateLimitKey].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BucketKey {
+    Ip(IpAddr),
+    Session(SocketAddr),
+}
+
+/// Token bucket of a single source of requests.
+#[derive(Debug)]
+struct TokenBucket {
+    /// Number of tokens currently in the bucket.
+    tokens: f64,
+    /// Time at which `tokens` was last updated.
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Returns the number of tokens the bucket contains at `now` if it is refilled at `rate`
+    /// tokens per second.
+    fn tokens_at(&self, now: Instant, rate: f64, capacity: f64) -> f64 {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        (self.tokens + elapsed * rate).min(capacity)
+    }
+}
+
+/// State of the rate limit of a context.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: HashMap<BucketKey, TokenBucket>,
+    stats: HashMap<IpAddr, RateLimitStats>,
+    /// Number of buckets at which idle buckets are removed the next time.
+    prune_threshold: usize,
+}
+
+impl RateLimiter {
+    /// Creates a new rate limiter with the given configuration.
+    pub(crate) fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            config,
+            buckets: HashMap::new(),
+            stats: HashMap::new(),
+            prune_threshold: MIN_PRUNE_THRESHOLD,
+        }
+    }
+
+    /// Returns the configuration of this rate limiter.
+    pub(crate) fn config(&self) -> RateLimitConfig {
+        self.config
+    }
+
+    /// Returns the statistics of all IP addresses that requests were received from.
+    pub(crate) fn stats(&self) -> &HashMap<IpAddr, RateLimitStats> {
+        &self.stats
+    }
+
+    /// Number of tokens a bucket can hold.
+    fn capacity(&self) -> f64 {
+        f64::from(self.config.burst_size.max(1))
+    }
+
+    /// Accounts for a request received from `remote_addr`.
+    ///
+    /// Returns `None` if the request may be handled, or the number of seconds after which the
+    /// source may retry if the request exceeds the rate limit.
+    pub(crate) fn check(&mut self, remote_addr: SocketAddr) -> Option<MaxAge> {
+        let now = Instant::now();
+        let key = match self.config.by {
+            RateLimitKey::SourceIp => BucketKey::Ip(remote_addr.ip()),
+            RateLimitKey::SourceSession => BucketKey::Session(remote_addr),
+        };
+        if self.buckets.len() >= self.prune_threshold {
+            self.prune(now);
+        }
+        let capacity = self.capacity();
+        let rate = f64::from(self.config.max_requests_per_second);
+        let bucket = self.buckets.entry(key).or_insert(TokenBucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+        bucket.tokens = bucket.tokens_at(now, rate, capacity);
+        bucket.last_refill = now;
+
+        let stats = self.stats.entry(remote_addr.ip()).or_default();
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            stats.accepted += 1;
+            return None;
+        }
+        stats.rejected += 1;
+        if self.config.max_requests_per_second == 0 {
+            return Some(MaxAge::MAX);
+        }
+        // Float to integer casts saturate, so this cannot overflow.
+        Some(((1.0 - bucket.tokens) / rate).ceil() as MaxAge)
+    }
+
+    /// Removes all buckets that would have been refilled completely by now, as these behave just
+    /// like newly created ones, as well as the statistics of IP addresses that no longer have any
+    /// buckets.
+    fn prune(&mut self, now: Instant) {
+        let capacity = self.capacity();
+        let rate = f64::from(self.config.max_requests_per_second);
+        self.buckets
+            .retain(|_, bucket| bucket.tokens_at(now, rate, capacity) < capacity);
+        let active_ips: HashSet<IpAddr> = self
+            .buckets
+            .keys()
+            .map(|key| match key {
+                BucketKey::Ip(ip) => *ip,
+                BucketKey::Session(addr) => addr.ip(),
+            })
+            .collect();
+        self.stats.retain(|ip, _| active_ips.contains(ip));
+        self.prune_threshold = (self.buckets.len() * 2).max(MIN_PRUNE_THRESHOLD);
+    }
+}
@@ -11,31 +11,48 @@
 
 use std::{
     any::Any,
+    cell::Cell,
     cell::Ref,
     cell::RefMut,
     fmt::{Debug, Formatter},
     marker::PhantomData,
+    mem::MaybeUninit,
+    rc::Rc,
+    time::{Duration, Instant},
 };
 
 use libc::c_int;
 
+#[cfg(feature = "metrics")]
+use libcoap_sys::coap_session_get_proto;
 use libcoap_sys::{
-    coap_delete_resource, coap_new_str_const, coap_pdu_t, coap_register_request_handler, COAP_RESOURCE_FLAGS_NOTIFY_CON,
-    COAP_RESOURCE_FLAGS_NOTIFY_NON, COAP_RESOURCE_FLAGS_RELEASE_URI, coap_resource_get_uri_path, coap_resource_get_userdata,
-    coap_resource_init, coap_resource_notify_observers, coap_resource_set_get_observable, coap_resource_set_mode, coap_resource_set_userdata, coap_resource_t,
-    coap_send_rst, coap_session_t, coap_string_t,
+    coap_check_option, coap_decode_var_bytes8, coap_delete_resource, coap_get_data_large, coap_new_str_const,
+    coap_opt_length, coap_opt_value, coap_option_num_t, coap_pdu_t, coap_register_request_handler,
+    coap_resource_get_uri_path, coap_resource_get_userdata, coap_resource_init, coap_resource_notify_observers,
+    coap_resource_set_get_observable, coap_resource_set_mode, coap_resource_set_userdata, coap_resource_t,
+    coap_send_rst, coap_session_get_context, coap_session_t, coap_string_t, COAP_RESOURCE_FLAGS_NOTIFY_CON,
+    COAP_RESOURCE_FLAGS_NOTIFY_NON, COAP_RESOURCE_FLAGS_RELEASE_URI,
 };
 
-use crate::{error::MessageConversionError, message::CoapMessage, protocol::CoapRequestCode};
 use crate::context::ensure_coap_started;
 use crate::mem::{CoapFfiRcCell, DropInnerExclusively};
-use crate::message::CoapMessageCommon;
 use crate::message::request::CoapRequest;
 use crate::message::response::CoapResponse;
-use crate::protocol::CoapMessageCode;
-use crate::protocol::CoapMessageType;
+use crate::message::CoapMessageCommon;
+#[cfg(feature = "metrics")]
+use crate::metrics;
+use crate::protocol::{CoapMessageCode, Observe};
+use crate::protocol::{CoapMessageType, CoapOptionType, CoapToken};
+use crate::session::server::{CoapServerSessionWeak, RequestDeduplication};
 use crate::session::CoapServerSession;
 use crate::session::CoapSessionCommon;
+use crate::session::SentResponse;
+use crate::{
+    error::MessageConversionError,
+    message::{CoapMessage, CoapOption},
+    protocol::{CoapRequestCode, CoapResponseCode, ContentFormat, MaxAge},
+};
+use crate::{AccessDecision, CoapContext};
 
 // Trait aliases are experimental
 //trait CoapMethodHandlerFn<D> = FnMut(&D, &mut CoapSession, &CoapRequestMessage, &mut CoapResponseMessage);
@@ -59,8 +76,9 @@ macro_rules! resource_handler {
         ) {
             let handler_data =
                 prepare_resource_handler_data::<$t>(resource, session, incoming_pdu, query, response_pdu);
-            if let Ok((mut resource, mut session, incoming_pdu, outgoing_pdu)) = handler_data {
-                ($f::<D>)(&mut resource, &mut session, &incoming_pdu, outgoing_pdu)
+            if let Ok(Some((mut resource, mut session, incoming_pdu, outgoing_pdu))) = handler_data {
+                ($f::<D>)(&mut resource, &mut session, &incoming_pdu, outgoing_pdu);
+                finish_resource_handler(&resource, &session, &incoming_pdu);
             }
         }
         unsafe { CoapRequestHandler::<$t>::from_raw_handler(_coap_method_handler_wrapper::<$t>) }
@@ -72,6 +90,20 @@ macro_rules! resource_handler {
 /// If an error occurs while parsing the resource data, this function will send an RST message to the
 /// client and return a [MessageConversionError].
 ///
+/// If the request is a duplicate of a recently received request and deduplication is enabled (see
+/// [CoapContext::set_deduplication()]), this function will retransmit the response to the original
+/// request (if there is one) and return `Ok(None)`, in which case the handler must not be called.
+/// The same applies to requests received using sessions that were refused due to the session limit
+/// (see [CoapContext::set_max_sessions()]), which are answered with `5.03 Service Unavailable`,
+/// to requests received using DTLS HELLO sessions (see [CoapServerSession::is_hello()]), which
+/// are ignored, to requests whose body exceeds the maximum body size (see
+/// [CoapContext::set_max_body_size()]), which are answered with `4.13 Request Entity Too Large`,
+/// to requests exceeding the rate limit (see [CoapContext::set_rate_limit()]), which are
+/// answered with `4.29 Too Many Requests`, to requests denied by the access control hook (see
+/// [CoapContext::set_access_control()]), which are answered with the response code provided by
+/// the hook, and to notifications for observations that were cancelled (see
+/// [CoapObserverInfo::cancel()]), which are replaced by `5.03 Service Unavailable`.
+///
 /// This function is not intended for public use, the only reason it is public is that the
 /// [resource_handler!] macro requires this function.
 ///
@@ -85,14 +117,95 @@ pub unsafe fn prepare_resource_handler_data<'a, D: Any + ?Sized + Debug>(
     raw_incoming_pdu: *const coap_pdu_t,
     _raw_query: *const coap_string_t,
     raw_response_pdu: *mut coap_pdu_t,
-) -> Result<(CoapResource<D>, CoapServerSession<'a>, CoapRequest, CoapResponse), MessageConversionError> {
+) -> Result<Option<(CoapResource<D>, CoapServerSession<'a>, CoapRequest, CoapResponse)>, MessageConversionError> {
+    #[cfg(feature = "metrics")]
+    metrics::record_message_received(coap_session_get_proto(raw_session).into());
+    // Oversized requests are rejected before parsing them in order to avoid copying their body.
+    let context = CoapContext::from_raw(coap_session_get_context(raw_session));
+    if let Some(max_body_size) = context
+        .max_body_size()
+        .filter(|max| raw_request_body_size(raw_incoming_pdu) > *max)
+    {
+        let session = CoapServerSession::from_raw(raw_session);
+        let response = CoapMessage::from_raw_pdu(raw_response_pdu).and_then(CoapResponse::from_message);
+        if let (false, Ok(mut response)) = (session.is_hello(), response) {
+            // Indicate the maximum body size the server is able to handle (see RFC 7959,
+            // Section 4).
+            response.add_option(CoapOption::Size1(u32::try_from(max_body_size).unwrap_or(u32::MAX)));
+            session.respond(response, CoapResponseCode::RequestTooLarge);
+        }
+        return Ok(None);
+    }
+    // Parse the request first in order to record the time of its reception as early as possible.
+    let request = CoapMessage::from_raw_pdu_with_timestamp(raw_incoming_pdu);
     let resource_tmp = CoapFfiRcCell::clone_raw_weak(coap_resource_get_userdata(raw_resource));
     let resource = CoapResource::from(resource_tmp);
-    let session = CoapServerSession::from_raw(raw_session);
-    let request = CoapMessage::from_raw_pdu(raw_incoming_pdu).and_then(|v| CoapRequest::from_message(v, &session));
+    let mut session = CoapServerSession::from_raw(raw_session);
+    context.handle_address_migration(&session);
+    let request =
+        request.and_then(|(v, _)| CoapRequest::from_message(v.with_strict_parsing(context.strict_parsing()), &session));
     let response = CoapMessage::from_raw_pdu(raw_response_pdu).and_then(CoapResponse::from_message);
     match (request, response) {
-        (Ok(request), Ok(response)) => Ok((resource, session, request, response)),
+        // DTLS HELLO sessions are not established yet, so their requests must not be handled.
+        (Ok(_), Ok(_)) if session.is_hello() => Ok(None),
+        (Ok(request), Ok(mut response)) => {
+            if let Some(received_at) = request.as_message().received_at() {
+                response.set_request_received_at(received_at);
+            }
+            if request.size_hint_requested() {
+                response.set_include_total_size(true);
+            }
+            // libcoap adds an Observe option to responses if an observation was established or
+            // this is a notification, replace its value with the resource's sequence number.
+            if response.observe().is_some() {
+                response.set_observe(Some(resource.observe_sequence()));
+            }
+            if session.is_refused() {
+                // The session limit was reached when this session was created, tell the client to
+                // retry once idle sessions may have been freed.
+                response.set_max_age(Some(
+                    context.session_timeout().as_secs().try_into().unwrap_or(MaxAge::MAX),
+                ));
+                session.respond(response, CoapResponseCode::ServiceUnavailable);
+                session.close();
+                return Ok(None);
+            }
+            if context.deduplication() {
+                let deduplication = session.deduplicate_request(&request, context.deduplication_cache_size());
+                if let RequestDeduplication::Duplicate(cached_response) = deduplication {
+                    if let Some(cached_response) = cached_response {
+                        // Retransmission of the original response may only fail if the session has
+                        // been closed in the meantime, in which case there is nothing left to do.
+                        let _ = session.send(cached_response);
+                    }
+                    return Ok(None);
+                }
+            }
+            if let Some(retry_after) = context.check_rate_limit(session.addr_remote()) {
+                response.set_max_age(Some(retry_after));
+                session.respond(response, CoapResponseCode::TooManyRequests);
+                return Ok(None);
+            }
+            if let AccessDecision::Deny(code) = context.check_access(&session, &request) {
+                session.respond(response, code);
+                return Ok(None);
+            }
+            match resource.prepare_notification(&session, &request, &mut response) {
+                NotificationDisposition::Send => {},
+                // The notification is sent once the observer's rate limit allows it (see
+                // CoapResource::notify_observers_with_rate_limit()).
+                NotificationDisposition::Hold => return Ok(None),
+                NotificationDisposition::Cancel => {
+                    // The observation was cancelled, sending an error response instead of the
+                    // notification causes libcoap to remove the observer and informs the client.
+                    session.respond(response, CoapResponseCode::ServiceUnavailable);
+                    return Ok(None);
+                },
+            }
+            // Only the response sent by the handler is relevant for finish_resource_handler().
+            session.take_sent_response();
+            Ok(Some((resource, session, request, response)))
+        },
         (v1, v2) => {
             coap_send_rst(raw_session, raw_incoming_pdu);
             Err(v1.and(v2).err().unwrap())
@@ -100,6 +213,48 @@ pub unsafe fn prepare_resource_handler_data<'a, D: Any + ?Sized + Debug>(
     }
 }
 
+/// Updates the observers of the given resource according to the response the request handler sent
+/// for `request` (see [CoapResource::observers()]).
+///
+/// This function is not intended for public use, the only reason it is public is that the
+/// [resource_handler!] macro requires this function.
+#[inline]
+#[doc(hidden)]
+pub fn finish_resource_handler<D: Any + ?Sized + Debug>(
+    resource: &CoapResource<D>,
+    session: &CoapServerSession,
+    request: &CoapRequest,
+) {
+    resource.track_observer(session, request, session.take_sent_response());
+}
+
+/// Returns the size of the body of the given raw request, or the total body size indicated by its
+/// Size1 option if it is larger.
+///
+/// # Safety
+/// raw_pdu must point to a valid instance of coap_pdu_t.
+unsafe fn raw_request_body_size(raw_pdu: *const coap_pdu_t) -> usize {
+    let mut length = 0;
+    let mut data = std::ptr::null();
+    let mut offset = 0;
+    let mut total = 0;
+    coap_get_data_large(raw_pdu, &mut length, &mut data, &mut offset, &mut total);
+    let mut option_iter = MaybeUninit::zeroed();
+    let size1 = coap_check_option(
+        raw_pdu,
+        CoapOptionType::Size1 as coap_option_num_t,
+        option_iter.as_mut_ptr(),
+    );
+    let indicated_total = if size1.is_null() {
+        0
+    } else {
+        coap_decode_var_bytes8(coap_opt_value(size1), coap_opt_length(size1) as usize)
+    };
+    total
+        .max(length)
+        .max(usize::try_from(indicated_total).unwrap_or(usize::MAX))
+}
+
 /// Trait with functions relating to [CoapResource]s with an unknown data type.
 pub trait UntypedCoapResource: Any + Debug {
     /// Returns the uri_path this resource responds to.
@@ -121,6 +276,34 @@ pub trait UntypedCoapResource: Any + Debug {
     /// dropped, i.e. because the underlying [Rc] is used elsewhere.
     #[doc(hidden)]
     fn drop_inner_exclusive(self: Box<Self>);
+    /// Removes all observers of this resource from its list of observers (see
+    /// [CoapResource::observers()]).
+    ///
+    /// This function is used by the [CoapContext](crate::context::CoapContext) on cleanup, as the
+    /// sessions referenced by observers are freed along with the context.
+    #[doc(hidden)]
+    fn clear_observers(&self);
+    /// Returns whether this resource can currently be observed by clients (see
+    /// [CoapResource::set_get_observable()]).
+    fn is_observable(&self) -> bool;
+    /// Returns the number of clients currently observing this resource (see
+    /// [CoapResource::observers()]).
+    fn observer_count(&self) -> usize;
+    /// Triggers the notifications that were held back by
+    /// [CoapResource::notify_observers_with_rate_limit()] and whose rate limit has expired.
+    ///
+    /// Returns the time at which the rate limit of the next remaining held back notification
+    /// expires, if there is one.
+    ///
+    /// This function is used by [CoapContext::do_io()] to send trailing notifications.
+    #[doc(hidden)]
+    fn flush_pending_notifications(&self) -> Option<Instant>;
+    /// Removes the observer with the given session and token from this resource's list of
+    /// observers (see [CoapResource::observers()]), returning whether such an observer existed.
+    ///
+    /// This is used to keep the list in sync with libcoap, which ends observations whose
+    /// notifications were rejected by the client or never acknowledged.
+    fn remove_observer(&self, session: &CoapServerSession, token: &[u8]) -> bool;
     /// Returns the raw resource associated with this CoapResource.
     ///
     /// # Safety
@@ -133,6 +316,206 @@ pub trait UntypedCoapResource: Any + Debug {
     unsafe fn raw_resource(&mut self) -> *mut coap_resource_t;
 }
 
+/// Attributes of a resource that are advertised in its link-format description at
+/// `/.well-known/core` (see [RFC 6690, Section 3](https://datatracker.ietf.org/doc/html/rfc6690#section-3)
+/// and [CoapContext::add_resource_with_attributes()]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceDescription {
+    /// Human-readable name of the resource (`title` attribute).
+    pub title: Option<String>,
+    /// Resource types of the resource (`rt` attribute).
+    pub resource_type: Vec<String>,
+    /// Interfaces the resource can be accessed with (`if` attribute).
+    pub interface: Vec<String>,
+    /// Content formats the resource can be represented in (`ct` attribute, see
+    /// [RFC 7252, Section 7.2.1](https://datatracker.ietf.org/doc/html/rfc7252#section-7.2.1)).
+    pub content_formats: Vec<ContentFormat>,
+    /// Whether the resource can be observed (`obs` attribute, see
+    /// [RFC 7641, Section 6](https://datatracker.ietf.org/doc/html/rfc7641#section-6)).
+    pub observable: bool,
+    /// Estimated size of the resource's representation in bytes (`sz` attribute).
+    pub size_estimate: Option<u64>,
+}
+
+/// Runtime information about a resource registered with a [CoapContext] (see
+/// [CoapContext::resources()]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceInfo {
+    /// URI path the resource responds to.
+    pub path: String,
+    /// Attributes advertised for the resource at `/.well-known/core` (default attributes if none
+    /// were provided).
+    pub attributes: ResourceDescription,
+    /// Whether the resource can currently be observed by clients.
+    pub observable: bool,
+    /// Number of clients currently observing the resource.
+    pub observer_count: usize,
+}
+
+/// Handle to a resource that was added using [CoapContext::add_resource_with_attributes()].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceHandle {
+    uri_path: String,
+}
+
+impl ResourceHandle {
+    pub(crate) fn new(uri_path: &str) -> ResourceHandle {
+        ResourceHandle {
+            uri_path: uri_path.to_string(),
+        }
+    }
+
+    /// Returns the URI path of the referenced resource.
+    pub fn uri_path(&self) -> &str {
+        &self.uri_path
+    }
+
+    /// Replaces the attributes advertised for the referenced resource in `context` (e.g., to
+    /// change its content formats after a software update).
+    ///
+    /// Note that this only changes the advertised attributes, use
+    /// [CoapResource::set_get_observable()] to change whether the resource can actually be
+    /// observed.
+    pub fn update_attributes(&self, context: &mut CoapContext, description: ResourceDescription) {
+        context.set_resource_description(&self.uri_path, description);
+    }
+}
+
+/// Policy that determines which observe notifications of a resource are sent as confirmable
+/// messages even though they would otherwise be non-confirmable (see
+/// [CoapResource::set_observe_confirmation_policy()]).
+///
+/// Non-confirmable notifications do not tell the server whether the client is still interested
+/// in the resource, so a confirmable notification should be sent at least every 24 hours (see
+/// [RFC 7641, Section 4.5](https://datatracker.ietf.org/doc/html/rfc7641#section-4.5)).
+/// Observers that reject a confirmable notification or fail to acknowledge it are removed.
+///
+/// The default policy sends a confirmable notification if the last one (or the registration) was
+/// at least 24 hours ago.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObserveConfirmationPolicy {
+    /// Send every n-th notification to an observer as a confirmable message.
+    ///
+    /// A value of zero is treated like a value of one, i.e., all notifications are confirmable.
+    pub every_nth: Option<u32>,
+    /// Send a notification as a confirmable message if the last confirmable message was sent to
+    /// the observer at least this long ago.
+    pub max_interval: Option<Duration>,
+}
+
+impl ObserveConfirmationPolicy {
+    /// Returns whether the notification with the given (1-based) number has to be confirmable, if
+    /// the last confirmable notification was sent `since_confirmable` ago.
+    fn requires_confirmation(&self, notification_count: u32, since_confirmable: Duration) -> bool {
+        self.every_nth.is_some_and(|n| notification_count % n.max(1) == 0)
+            || self.max_interval.is_some_and(|interval| since_confirmable >= interval)
+    }
+}
+
+impl Default for ObserveConfirmationPolicy {
+    fn default() -> Self {
+        ObserveConfirmationPolicy {
+            every_nth: None,
+            max_interval: Some(Duration::from_secs(24 * 60 * 60)),
+        }
+    }
+}
+
+/// Information about a client observing a [CoapResource] according to
+/// [RFC 7641](https://datatracker.ietf.org/doc/html/rfc7641) (see [CoapResource::observers()]).
+#[derive(Debug, Clone)]
+pub struct CoapObserverInfo {
+    /// Weak reference to the session, so that observers do not keep sessions alive.
+    session: CoapServerSessionWeak<'static>,
+    token: CoapToken,
+    registered_at: Instant,
+    /// Time at which a notification was last sent to this observer.
+    last_notified: Option<Instant>,
+    /// Whether the observer has not been notified about the latest change of the resource yet.
+    notification_pending: bool,
+    /// Time until which notifications to this observer are held back (see
+    /// [CoapResource::notify_observers_with_rate_limit()]).
+    throttled_until: Option<Instant>,
+    last_confirmable: Instant,
+    notification_count: u32,
+    transport_type: CoapMessageType,
+    cancelled: Rc<Cell<bool>>,
+}
+
+impl CoapObserverInfo {
+    /// Returns the server-side session with the observing client, or `None` if the session has
+    /// already been freed.
+    pub fn session(&self) -> Option<CoapServerSession<'static>> {
+        self.session.upgrade()
+    }
+
+    /// Returns the token of the observation, which is used for all notifications sent to the
+    /// client.
+    pub fn token(&self) -> &CoapToken {
+        &self.token
+    }
+
+    /// Returns the time at which the client registered as an observer.
+    pub fn registered_at(&self) -> Instant {
+        self.registered_at
+    }
+
+    /// Returns the time at which a notification was last sent to this observer (or the time of
+    /// registration if no notification has been sent yet).
+    pub fn last_notified(&self) -> Instant {
+        self.last_notified.unwrap_or(self.registered_at)
+    }
+
+    /// Returns the number of successful notifications that were sent to this observer (not
+    /// including the response to the registration request).
+    pub fn notification_count(&self) -> u32 {
+        self.notification_count
+    }
+
+    /// Returns the message type of the request the client registered with.
+    pub fn transport_type(&self) -> CoapMessageType {
+        self.transport_type
+    }
+
+    /// Cancels this observation.
+    ///
+    /// Instead of the next notification, the client is sent a `5.03 Service Unavailable` response,
+    /// which ends the observation (see
+    /// [RFC 7641, Section 3.2](https://datatracker.ietf.org/doc/html/rfc7641#section-3.2)).
+    /// The observer is removed from the resource's list of observers afterwards.
+    pub fn cancel(&self) {
+        self.cancelled.set(true);
+    }
+
+    /// Returns whether this observation has been cancelled using [CoapObserverInfo::cancel()].
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+
+    /// Returns whether the session of this observer still exists and has not been closed.
+    fn is_active(&self) -> bool {
+        self.session.upgrade().is_some_and(|session| !session.is_closed())
+    }
+
+    /// Returns whether a notification is pending for this observer whose rate limit has expired
+    /// at `now`.
+    fn is_trailing_notification_due(&self, now: Instant) -> bool {
+        self.notification_pending && self.throttled_until.is_some_and(|until| until <= now)
+    }
+}
+
+/// Decision on how to handle a notification that libcoap asked a resource handler to generate
+/// (see [CoapResource::prepare_notification()]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NotificationDisposition {
+    /// Call the handler to generate the notification (or response).
+    Send,
+    /// Do not notify the observer yet, as it was notified too recently.
+    Hold,
+    /// Inform the observer that its observation was cancelled.
+    Cancel,
+}
+
 /// Representation of a CoapResource that can be requested from a server.
 #[derive(Debug)]
 pub struct CoapResource<D: Any + ?Sized + Debug> {
@@ -230,6 +613,14 @@ pub(crate) struct CoapResourceInner<D: Any + ?Sized + Debug> {
     raw_resource: *mut coap_resource_t,
     user_data: Box<D>,
     handlers: CoapResourceHandlers<D>,
+    /// Sequence number to use as the Observe option value of notifications.
+    observe_sequence: Observe,
+    /// Clients that are currently observing this resource.
+    observers: Vec<CoapObserverInfo>,
+    /// Whether GET requests to this resource can be observed.
+    get_observable: bool,
+    /// Policy for sending non-confirmable notifications as confirmable messages.
+    observe_confirmation_policy: Option<ObserveConfirmationPolicy>,
 }
 
 impl<D: Any + ?Sized + Debug> CoapResource<D> {
@@ -257,6 +648,10 @@ impl<D: Any + ?Sized + Debug> CoapResource<D> {
                 raw_resource,
                 user_data: user_data.into(),
                 handlers: CoapResourceHandlers::default(),
+                observe_sequence: Observe::default(),
+                observers: Vec::new(),
+                get_observable: false,
+                observe_confirmation_policy: None,
             });
             coap_resource_set_userdata(raw_resource, inner.create_raw_weak());
             inner
@@ -265,16 +660,209 @@ impl<D: Any + ?Sized + Debug> CoapResource<D> {
     }
 
     /// Notify any observers about changes to this resource.
+    ///
+    /// If there are any observers, this increments the resource's Observe sequence number (see
+    /// [CoapResource::observe_sequence()]), which is then used as the Observe option value of the
+    /// notifications sent to the observers.
+    /// Notifications held back by [CoapResource::notify_observers_with_rate_limit()] are sent
+    /// along with the other ones.
+    ///
+    /// Returns whether notifications will be sent, i.e., whether this resource is observable and
+    /// currently has at least one observer.
     pub fn notify_observers(&self) -> bool {
-        // SAFETY: Resource is valid as long as CoapResourceInner exists, query is currently unused.
-        unsafe { coap_resource_notify_observers(self.inner.borrow_mut().raw_resource, std::ptr::null_mut()) != 0 }
+        let mut inner = self.inner.borrow_mut();
+        inner.observers.retain(CoapObserverInfo::is_active);
+        for observer in inner.observers.iter_mut() {
+            observer.notification_pending = true;
+            observer.throttled_until = None;
+        }
+        inner.trigger_notifications()
+    }
+
+    /// Notify any observers about changes to this resource, but at most once per `rate` and
+    /// observer.
+    ///
+    /// Observers that were sent a notification less than `rate` ago (see
+    /// [CoapObserverInfo::last_notified()]) are not notified immediately. Instead, they are sent
+    /// a single trailing notification containing the then-current state of the resource once
+    /// `rate` has passed since their last notification, i.e., intermediate states of rapidly
+    /// changing resources are skipped, but the latest state is always published eventually.
+    /// Trailing notifications are sent by [CoapContext::do_io()], which returns early if one of
+    /// them becomes due.
+    ///
+    /// Returns whether notifications will be sent immediately, i.e., whether this resource is
+    /// observable and has at least one observer that is not subject to the rate limit.
+    pub fn notify_observers_with_rate_limit(&self, rate: Duration) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        inner.observers.retain(CoapObserverInfo::is_active);
+        let now = Instant::now();
+        let mut notify_now = false;
+        for observer in inner.observers.iter_mut() {
+            observer.notification_pending = true;
+            observer.throttled_until = observer
+                .last_notified
+                .map(|last| last + rate)
+                .filter(|until| *until > now);
+            notify_now |= observer.throttled_until.is_none();
+        }
+        notify_now && inner.trigger_notifications()
+    }
+
+    /// Returns the clients that are currently observing this resource.
+    ///
+    /// Observers are added once a successful response to their registration request has been
+    /// sent and are removed once they deregister, a notification indicates an error, their
+    /// session is closed, or their observation was cancelled using [CoapObserverInfo::cancel()]
+    /// and the client has been informed about it.
+    /// Observers whose session was closed are removed the next time observers are notified or a
+    /// request for this resource has been handled.
+    ///
+    /// Note that libcoap may also end observations on its own (e.g., if the client rejects a
+    /// notification with an RST message), which is only detected once the session is closed.
+    pub fn observers(&self) -> Ref<'_, [CoapObserverInfo]> {
+        Ref::map(self.inner.borrow(), |v| v.observers.as_slice())
+    }
+
+    /// Adjusts the response prepared by libcoap for the given request if it is a notification for
+    /// one of the observers of this resource.
+    ///
+    /// If the notification has to be confirmable according to the resource's
+    /// [ObserveConfirmationPolicy], its message type is changed accordingly.
+    ///
+    /// Returns [NotificationDisposition::Hold] if the observer is not supposed to be notified
+    /// yet (see [CoapResource::notify_observers_with_rate_limit()]), and
+    /// [NotificationDisposition::Cancel] if the request belongs to an observation that was
+    /// cancelled using [CoapObserverInfo::cancel()], in which case the observer is removed from
+    /// the list and the client has to be informed using an error response.
+    fn prepare_notification(
+        &self,
+        session: &CoapServerSession,
+        request: &CoapRequest,
+        response: &mut CoapResponse,
+    ) -> NotificationDisposition {
+        // libcoap only adds an Observe option to the response if an observation was established or
+        // the response is a notification.
+        if response.observe().is_none() {
+            return NotificationDisposition::Send;
+        }
+        let token = request.token().cloned().unwrap_or_default();
+        let mut inner = self.inner.borrow_mut();
+        // Notifications are generated by calling the handler with the registration request.
+        let Some(position) = inner
+            .observers
+            .iter()
+            .position(|observer| observer.session.refers_to(session) && observer.token == token)
+        else {
+            return NotificationDisposition::Send;
+        };
+        if inner.observers[position].is_cancelled() {
+            inner.observers.remove(position);
+            return NotificationDisposition::Cancel;
+        }
+        let policy = inner.observe_confirmation_policy;
+        let observer = &mut inner.observers[position];
+        // For notifications, libcoap assigns the same new message ID to the stored registration
+        // request and the notification, which is never piggybacked. Note that this also applies to
+        // responses to non-confirmable requests repeated by the observer using the same token,
+        // which are therefore treated like notifications.
+        if response.type_() != CoapMessageType::Ack && response.mid() == request.mid() {
+            let now = Instant::now();
+            if !observer.notification_pending || observer.throttled_until.is_some_and(|until| until > now) {
+                return NotificationDisposition::Hold;
+            }
+            observer.notification_pending = false;
+            observer.throttled_until = None;
+            observer.last_notified = Some(now);
+        }
+        // The notification is only counted once it has been sent, see track_observer().
+        let notification_count = observer.notification_count.saturating_add(1);
+        if response.type_() == CoapMessageType::Non
+            && policy.is_some_and(|v| v.requires_confirmation(notification_count, observer.last_confirmable.elapsed()))
+        {
+            response.set_type_(CoapMessageType::Con);
+        }
+        NotificationDisposition::Send
+    }
+
+    /// Updates the list of observers according to the given request and the response that its
+    /// handler sent (if any).
+    ///
+    /// Observers are only added and their notifications are only counted if a successful
+    /// response was sent, as libcoap ends observations whose registration or notification is
+    /// answered with an error (see
+    /// [RFC 7641, Section 4.2](https://datatracker.ietf.org/doc/html/rfc7641#section-4.2)).
+    fn track_observer(&self, session: &CoapServerSession, request: &CoapRequest, sent_response: Option<SentResponse>) {
+        let token = request.token().cloned().unwrap_or_default();
+        let mut inner = self.inner.borrow_mut();
+        inner.observers.retain(CoapObserverInfo::is_active);
+        let position = inner
+            .observers
+            .iter()
+            .position(|observer| observer.session.refers_to(session) && observer.token == token);
+        if request.is_observe_deregistration() {
+            if let Some(position) = position {
+                inner.observers.remove(position);
+            }
+            return;
+        }
+        let Some(sent_response) = sent_response.filter(|v| v.token == token) else {
+            return;
+        };
+        let successful = sent_response.code as u8 >> 5 == 2;
+        match position {
+            Some(position) if !successful => {
+                inner.observers.remove(position);
+            },
+            Some(position) => {
+                let observer = &mut inner.observers[position];
+                observer.notification_count = observer.notification_count.saturating_add(1);
+                if sent_response.type_ == CoapMessageType::Con {
+                    observer.last_confirmable = Instant::now();
+                }
+            },
+            // libcoap only adds an Observe option to the response if an observation was
+            // established.
+            None if successful && sent_response.observe => {
+                let now = Instant::now();
+                inner.observers.push(CoapObserverInfo {
+                    // SAFETY: The raw session is valid, as we are currently handling a request
+                    // received using it. The weak reference does not access the session once it
+                    // has been freed, and observers are cleared before the context is dropped.
+                    session: unsafe { CoapServerSession::from_raw(session.raw_session_mut()) }.downgrade(),
+                    token,
+                    registered_at: now,
+                    last_notified: None,
+                    // Changes that were notified while the registration was handled are sent to
+                    // the new observer as well.
+                    notification_pending: true,
+                    throttled_until: None,
+                    last_confirmable: now,
+                    notification_count: 0,
+                    transport_type: request.type_(),
+                    cancelled: Rc::new(Cell::new(false)),
+                });
+            },
+            None => {},
+        }
+    }
+
+    /// Returns the sequence number that is used as the Observe option value for the next
+    /// notifications sent for this resource.
+    ///
+    /// The sequence number starts at zero, is incremented by every successful call to
+    /// [CoapResource::notify_observers()] and wraps around after 2^24 - 1, as Observe option values
+    /// are limited to 24 bits (see [RFC 7641, Section 4.4](https://datatracker.ietf.org/doc/html/rfc7641#section-4.4)).
+    pub fn observe_sequence(&self) -> Observe {
+        self.inner.borrow().observe_sequence
     }
 
     /// Sets whether this resource can be observed by clients according to
     /// [RFC 7641](https://datatracker.ietf.org/doc/html/rfc7641).
     pub fn set_get_observable(&self, observable: bool) {
+        let mut inner = self.inner.borrow_mut();
+        inner.get_observable = observable;
         // SAFETY: Resource is valid as long as CoapResourceInner exists, query is currently unused.
-        unsafe { coap_resource_set_get_observable(self.inner.borrow_mut().raw_resource, observable as c_int) }
+        unsafe { coap_resource_set_get_observable(inner.raw_resource, observable as c_int) }
     }
 
     /// Sets whether observe notifications for this resource should be sent as confirmable or
@@ -284,6 +872,23 @@ impl<D: Any + ?Sized + Debug> CoapResource<D> {
         unsafe { coap_resource_set_mode(self.inner.borrow_mut().raw_resource, confirmable as c_int) }
     }
 
+    /// Sets the policy that determines which non-confirmable observe notifications of this
+    /// resource are sent as confirmable messages instead, or `None` to leave the message type of
+    /// notifications up to libcoap.
+    ///
+    /// Note that libcoap also sends a confirmable notification on its own after a number of
+    /// non-confirmable ones (five by default).
+    pub fn set_observe_confirmation_policy(&self, policy: Option<ObserveConfirmationPolicy>) {
+        self.inner.borrow_mut().observe_confirmation_policy = policy;
+    }
+
+    /// Returns the policy that determines which non-confirmable observe notifications of this
+    /// resource are sent as confirmable messages instead (see
+    /// [CoapResource::set_observe_confirmation_policy()]).
+    pub fn observe_confirmation_policy(&self) -> Option<ObserveConfirmationPolicy> {
+        self.inner.borrow().observe_confirmation_policy
+    }
+
     /// Returns the user data associated with this resource.
     pub fn user_data(&self) -> Ref<D> {
         Ref::map(self.inner.borrow(), |v| v.user_data.as_ref())
@@ -371,13 +976,55 @@ impl<D: Any + ?Sized + Debug> UntypedCoapResource for CoapResource<D> {
     }
 
     fn as_any(&self) -> &dyn Any {
-        self as &(dyn Any)
+        self as &dyn Any
     }
 
     fn drop_inner_exclusive(self: Box<Self>) {
         self.inner.drop_exclusively();
     }
 
+    fn clear_observers(&self) {
+        self.inner.borrow_mut().observers.clear();
+    }
+
+    fn is_observable(&self) -> bool {
+        self.inner.borrow().get_observable
+    }
+
+    fn observer_count(&self) -> usize {
+        self.inner.borrow().observers.iter().filter(|v| v.is_active()).count()
+    }
+
+    fn flush_pending_notifications(&self) -> Option<Instant> {
+        let mut inner = self.inner.borrow_mut();
+        let now = Instant::now();
+        let mut due = false;
+        for observer in inner.observers.iter_mut() {
+            if observer.is_trailing_notification_due(now) {
+                observer.throttled_until = None;
+                due = true;
+            }
+        }
+        if due {
+            inner.trigger_notifications();
+        }
+        inner
+            .observers
+            .iter()
+            .filter(|v| v.notification_pending)
+            .filter_map(|v| v.throttled_until)
+            .min()
+    }
+
+    fn remove_observer(&self, session: &CoapServerSession, token: &[u8]) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        let observer_count = inner.observers.len();
+        inner
+            .observers
+            .retain(|observer| !(observer.session.refers_to(session) && observer.token.as_bytes() == token));
+        inner.observers.len() != observer_count
+    }
+
     unsafe fn raw_resource(&mut self) -> *mut coap_resource_t {
         self.inner.borrow_mut().raw_resource
     }
@@ -390,6 +1037,26 @@ impl<D: Any + ?Sized + Debug> From<CoapFfiRcCell<CoapResourceInner<D>>> for Coap
     }
 }
 
+impl<D: Any + ?Sized + Debug> CoapResourceInner<D> {
+    /// Asks libcoap to generate notifications for all observers of this resource, incrementing
+    /// the Observe sequence number if it does.
+    ///
+    /// Held back notifications are discarded if libcoap does not generate notifications.
+    fn trigger_notifications(&mut self) -> bool {
+        // SAFETY: Resource is valid as long as CoapResourceInner exists, query is currently unused.
+        let notified = unsafe { coap_resource_notify_observers(self.raw_resource, std::ptr::null_mut()) != 0 };
+        if notified {
+            self.observe_sequence = Observe::wrapping(self.observe_sequence.value().wrapping_add(1));
+        } else {
+            for observer in self.observers.iter_mut() {
+                observer.notification_pending = false;
+                observer.throttled_until = None;
+            }
+        }
+        notified
+    }
+}
+
 impl<D: Any + ?Sized + Debug> Drop for CoapResourceInner<D> {
     fn drop(&mut self) {
         // SAFETY: We set the user data on creation of the inner resource, so it cannot be invalid.
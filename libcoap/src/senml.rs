@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * senml.rs - SenML payload handling for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! (De)serialization of Sensor Measurement Lists (SenML, see
+//! [RFC 8428](https://datatracker.ietf.org/doc/html/rfc8428)) in the JSON and CBOR content
+//! formats.
+//!
+//! Parsed packs are resolved (see
+//! [RFC 8428, Section 4.6](https://datatracker.ietf.org/doc/html/rfc8428#section-4.6)), i.e., base
+//! names and base times are applied to the records they refer to, so each [SenmlRecord] contains
+//! its full name and time.
+//! Serialized packs always consist of resolved records.
+//!
+//! Currently, only the `bn`, `bt`, `n`, `u`, `v` and `t` fields are supported, other fields are
+//! ignored when parsing (unless they must be understood, see
+//! [RFC 8428, Section 4.4](https://datatracker.ietf.org/doc/html/rfc8428#section-4.4)).
+//!
+//! # Examples
+//! ```
+//! use libcoap_rs::{
+//!     message::CoapResponse,
+//!     protocol::{CoapMessageType, CoapResponseCode},
+//!     senml::{SenmlContentFormat, SenmlRecord},
+//! };
+//!
+//! let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+//! let records = vec![SenmlRecord {
+//!     name: "urn:dev:ow:10e2073a01080063:temp".to_string(),
+//!     unit: Some("Cel".to_string()),
+//!     value: Some(23.1),
+//!     time: 1276020076.0,
+//! }];
+//! response.set_senml(&records, SenmlContentFormat::Cbor);
+//! assert_eq!(response.senml().unwrap(), records);
+//! ```
+
+use ciborium::value::{Integer, Value as CborValue};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::{
+    error::SenmlError,
+    message::{CoapMessageCommon, CoapRequest, CoapResponse},
+    protocol::{CoapContentFormat, ContentFormat},
+};
+
+/// CBOR labels of the supported fields, see
+/// [RFC 8428, Section 6](https://datatracker.ietf.org/doc/html/rfc8428#section-6).
+const CBOR_BASE_NAME: i128 = -2;
+const CBOR_BASE_TIME: i128 = -3;
+const CBOR_NAME: i128 = 0;
+const CBOR_UNIT: i128 = 1;
+const CBOR_VALUE: i128 = 2;
+const CBOR_TIME: i128 = 6;
+
+/// Content formats that SenML packs can be encoded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SenmlContentFormat {
+    /// `application/senml+json` (content format 110).
+    Json,
+    /// `application/senml+cbor` (content format 112).
+    Cbor,
+}
+
+impl SenmlContentFormat {
+    /// Returns the CoAP content format number of this format.
+    pub fn content_format(&self) -> ContentFormat {
+        match self {
+            SenmlContentFormat::Json => CoapContentFormat::SenMlJson as ContentFormat,
+            SenmlContentFormat::Cbor => CoapContentFormat::SenMlCbor as ContentFormat,
+        }
+    }
+
+    /// Returns the SenML format for the given CoAP content format number, if it is one.
+    pub fn from_content_format(content_format: ContentFormat) -> Option<SenmlContentFormat> {
+        [SenmlContentFormat::Json, SenmlContentFormat::Cbor]
+            .into_iter()
+            .find(|v| v.content_format() == content_format)
+    }
+}
+
+/// A single resolved SenML record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenmlRecord {
+    /// Name of the sensor or parameter this record refers to (including the base name).
+    pub name: String,
+    /// Unit of the value (e.g., `Cel` for degrees Celsius), if any.
+    pub unit: Option<String>,
+    /// Numeric value of the record, if any.
+    pub value: Option<f64>,
+    /// Time of the measurement in seconds (including the base time).
+    ///
+    /// Values below 2^28 are relative to the current time, larger values are seconds since the
+    /// UNIX epoch (see [RFC 8428, Section 4.5.3](https://datatracker.ietf.org/doc/html/rfc8428#section-4.5.3)).
+    pub time: f64,
+}
+
+/// Fields of a record as they appear in the encoded pack, i.e., before resolving it.
+#[derive(Debug, Default)]
+struct RawRecord {
+    base_name: Option<String>,
+    base_time: Option<f64>,
+    name: Option<String>,
+    unit: Option<String>,
+    value: Option<f64>,
+    time: Option<f64>,
+}
+
+/// Resolves the given raw records into records containing their full names and times.
+fn resolve(raw_records: Vec<RawRecord>) -> Result<Vec<SenmlRecord>, SenmlError> {
+    let mut base_name = String::new();
+    let mut base_time = 0.0;
+    let mut records = Vec::with_capacity(raw_records.len());
+    for raw in raw_records {
+        // Base fields apply to the record they are contained in and all subsequent records.
+        if let Some(name) = raw.base_name {
+            base_name = name;
+        }
+        if let Some(time) = raw.base_time {
+            base_time = time;
+        }
+        let name = format!("{base_name}{}", raw.name.unwrap_or_default());
+        if name.is_empty() {
+            return Err(SenmlError::MissingName);
+        }
+        records.push(SenmlRecord {
+            name,
+            unit: raw.unit,
+            value: raw.value,
+            time: base_time + raw.time.unwrap_or(0.0),
+        });
+    }
+    Ok(records)
+}
+
+/// Returns an error if the given field label must be understood but is not supported (see
+/// [RFC 8428, Section 4.4](https://datatracker.ietf.org/doc/html/rfc8428#section-4.4)).
+fn check_must_understand(label: &str) -> Result<(), SenmlError> {
+    if label.ends_with('_') {
+        return Err(SenmlError::Malformed(format!("unsupported field {label}")));
+    }
+    Ok(())
+}
+
+/// Parses a SenML pack in the JSON format.
+pub fn from_json(bytes: &[u8]) -> Result<Vec<SenmlRecord>, SenmlError> {
+    let pack: JsonValue = serde_json::from_slice(bytes).map_err(|e| SenmlError::Malformed(e.to_string()))?;
+    let JsonValue::Array(pack) = pack else {
+        return Err(SenmlError::Malformed("pack is not an array".to_string()));
+    };
+    let mut raw_records = Vec::with_capacity(pack.len());
+    for record in pack {
+        let JsonValue::Object(fields) = record else {
+            return Err(SenmlError::Malformed("record is not an object".to_string()));
+        };
+        let mut raw = RawRecord::default();
+        for (label, value) in fields {
+            let invalid = || SenmlError::Malformed(format!("invalid value for field {label}"));
+            match label.as_str() {
+                "bn" => raw.base_name = Some(value.as_str().ok_or_else(invalid)?.to_string()),
+                "bt" => raw.base_time = Some(value.as_f64().ok_or_else(invalid)?),
+                "n" => raw.name = Some(value.as_str().ok_or_else(invalid)?.to_string()),
+                "u" => raw.unit = Some(value.as_str().ok_or_else(invalid)?.to_string()),
+                "v" => raw.value = Some(value.as_f64().ok_or_else(invalid)?),
+                "t" => raw.time = Some(value.as_f64().ok_or_else(invalid)?),
+                label => check_must_understand(label)?,
+            }
+        }
+        raw_records.push(raw);
+    }
+    resolve(raw_records)
+}
+
+/// Serializes the given records into a SenML pack in the JSON format.
+pub fn to_json(records: &[SenmlRecord]) -> Vec<u8> {
+    let pack: Vec<JsonValue> = records
+        .iter()
+        .map(|record| {
+            let mut fields = JsonMap::new();
+            fields.insert("n".to_string(), JsonValue::from(record.name.as_str()));
+            if let Some(unit) = &record.unit {
+                fields.insert("u".to_string(), JsonValue::from(unit.as_str()));
+            }
+            if let Some(value) = record.value {
+                fields.insert("v".to_string(), JsonValue::from(value));
+            }
+            if record.time != 0.0 {
+                fields.insert("t".to_string(), JsonValue::from(record.time));
+            }
+            JsonValue::Object(fields)
+        })
+        .collect();
+    // Serializing a JSON value into a vector cannot fail.
+    serde_json::to_vec(&pack).expect("unable to serialize SenML pack")
+}
+
+/// Converts the given CBOR value into a number, if it is one.
+fn cbor_number(value: &CborValue) -> Option<f64> {
+    match value {
+        CborValue::Integer(v) => Some(i128::from(*v) as f64),
+        CborValue::Float(v) => Some(*v),
+        _ => None,
+    }
+}
+
+/// Parses a SenML pack in the CBOR format.
+pub fn from_cbor(bytes: &[u8]) -> Result<Vec<SenmlRecord>, SenmlError> {
+    let pack: CborValue = ciborium::de::from_reader(bytes).map_err(|e| SenmlError::Malformed(e.to_string()))?;
+    let CborValue::Array(pack) = pack else {
+        return Err(SenmlError::Malformed("pack is not an array".to_string()));
+    };
+    let mut raw_records = Vec::with_capacity(pack.len());
+    for record in pack {
+        let CborValue::Map(fields) = record else {
+            return Err(SenmlError::Malformed("record is not a map".to_string()));
+        };
+        let mut raw = RawRecord::default();
+        for (label, value) in fields {
+            let label = match label {
+                CborValue::Integer(label) => i128::from(label),
+                // Labels of fields that must be understood are text strings in CBOR as well.
+                CborValue::Text(label) => {
+                    check_must_understand(&label)?;
+                    continue;
+                },
+                _ => return Err(SenmlError::Malformed("invalid field label".to_string())),
+            };
+            let invalid = || SenmlError::Malformed(format!("invalid value for field {label}"));
+            match label {
+                CBOR_BASE_NAME => raw.base_name = Some(value.into_text().map_err(|_| invalid())?),
+                CBOR_BASE_TIME => raw.base_time = Some(cbor_number(&value).ok_or_else(invalid)?),
+                CBOR_NAME => raw.name = Some(value.into_text().map_err(|_| invalid())?),
+                CBOR_UNIT => raw.unit = Some(value.into_text().map_err(|_| invalid())?),
+                CBOR_VALUE => raw.value = Some(cbor_number(&value).ok_or_else(invalid)?),
+                CBOR_TIME => raw.time = Some(cbor_number(&value).ok_or_else(invalid)?),
+                _ => {},
+            }
+        }
+        raw_records.push(raw);
+    }
+    resolve(raw_records)
+}
+
+/// Serializes the given records into a SenML pack in the CBOR format.
+pub fn to_cbor(records: &[SenmlRecord]) -> Vec<u8> {
+    let label = |label: i128| CborValue::Integer(Integer::try_from(label).expect("invalid CBOR label"));
+    let pack = CborValue::Array(
+        records
+            .iter()
+            .map(|record| {
+                let mut fields = vec![(label(CBOR_NAME), CborValue::Text(record.name.clone()))];
+                if let Some(unit) = &record.unit {
+                    fields.push((label(CBOR_UNIT), CborValue::Text(unit.clone())));
+                }
+                if let Some(value) = record.value {
+                    fields.push((label(CBOR_VALUE), CborValue::Float(value)));
+                }
+                if record.time != 0.0 {
+                    fields.push((label(CBOR_TIME), CborValue::Float(record.time)));
+                }
+                CborValue::Map(fields)
+            })
+            .collect(),
+    );
+    let mut bytes = Vec::new();
+    // Writing into a vector cannot fail.
+    ciborium::ser::into_writer(&pack, &mut bytes).expect("unable to serialize SenML pack");
+    bytes
+}
+
+/// Parses the given payload as a SenML pack in the given content format.
+fn decode(content_format: Option<ContentFormat>, data: Option<&[u8]>) -> Result<Vec<SenmlRecord>, SenmlError> {
+    let format = content_format
+        .and_then(SenmlContentFormat::from_content_format)
+        .ok_or(SenmlError::UnsupportedContentFormat(content_format))?;
+    let data = data.unwrap_or_default();
+    match format {
+        SenmlContentFormat::Json => from_json(data),
+        SenmlContentFormat::Cbor => from_cbor(data),
+    }
+}
+
+/// Serializes the given records into a SenML pack in the given content format.
+fn encode(records: &[SenmlRecord], format: SenmlContentFormat) -> Vec<u8> {
+    match format {
+        SenmlContentFormat::Json => to_json(records),
+        SenmlContentFormat::Cbor => to_cbor(records),
+    }
+}
+
+impl CoapRequest {
+    /// Sets the body of this request to the given SenML records, encoded in the given format,
+    /// and sets the "Content-Format" option accordingly.
+    pub fn set_senml(&mut self, records: &[SenmlRecord], format: SenmlContentFormat) {
+        self.set_content_format(Some(format.content_format()));
+        self.set_data(Some(encode(records, format)));
+    }
+
+    /// Parses the body of this request as a SenML pack, using the content format indicated by
+    /// the "Content-Format" option.
+    ///
+    /// # Errors
+    /// Returns [SenmlError::UnsupportedContentFormat] if the request does not have a SenML
+    /// content format, and other [SenmlError]s if the body is not a valid SenML pack.
+    pub fn senml(&self) -> Result<Vec<SenmlRecord>, SenmlError> {
+        decode(self.content_format(), self.data())
+    }
+}
+
+impl CoapResponse {
+    /// Sets the body of this response to the given SenML records, encoded in the given format,
+    /// and sets the "Content-Format" option accordingly.
+    pub fn set_senml(&mut self, records: &[SenmlRecord], format: SenmlContentFormat) {
+        self.set_content_format(Some(format.content_format()));
+        self.set_data(Some(encode(records, format)));
+    }
+
+    /// Parses the body of this response as a SenML pack, using the content format indicated by
+    /// the "Content-Format" option.
+    ///
+    /// # Errors
+    /// Returns [SenmlError::UnsupportedContentFormat] if the response does not have a SenML
+    /// content format, and other [SenmlError]s if the body is not a valid SenML pack.
+    pub fn senml(&self) -> Result<Vec<SenmlRecord>, SenmlError> {
+        decode(self.content_format(), self.data())
+    }
+}
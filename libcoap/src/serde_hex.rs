@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * serde_hex.rs - Hexadecimal (de)serialization of byte fields for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Functions for use with `#[serde(with = "...")]` that represent byte fields (e.g., tokens,
+//! ETags and message bodies) as lowercase hexadecimal strings.
+
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+use crate::hex::encode;
+
+/// Decodes a hexadecimal string (in upper- or lowercase) into bytes.
+fn decode<E: Error>(value: &str) -> Result<Vec<u8>, E> {
+    if value.len() % 2 != 0 {
+        return Err(E::custom("hexadecimal string has an odd number of digits"));
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            value
+                .get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| E::custom("invalid hexadecimal digit"))
+        })
+        .collect()
+}
+
+pub(crate) fn serialize<T: AsRef<[u8]>, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode(value.as_ref()))
+}
+
+pub(crate) fn deserialize<'de, T: From<Vec<u8>>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    decode(&value).map(T::from)
+}
+
+/// Variants of the functions above for optional byte fields.
+pub(crate) mod option {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<T: AsRef<[u8]>, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&crate::hex::encode(value.as_ref())),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, T: From<Vec<u8>>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<T>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| super::decode(&value).map(T::from))
+            .transpose()
+    }
+}
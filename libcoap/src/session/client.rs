@@ -8,26 +8,52 @@
  */
 
 use std::cell::{Ref, RefMut};
+use std::collections::VecDeque;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "oscore")]
+use libcoap_sys::coap_new_client_session_oscore;
 use libcoap_sys::{
     coap_new_client_session, coap_proto_t, coap_register_event_handler, coap_session_get_app_data,
-    coap_session_get_context, coap_session_get_type, coap_session_init_token, coap_session_release,
-    coap_session_set_app_data, coap_session_t, coap_session_type_t, COAP_TOKEN_DEFAULT_MAX,
+    coap_session_get_context, coap_session_get_nstart, coap_session_get_type, coap_session_init_token,
+    coap_session_release, coap_session_set_app_data, coap_session_t, coap_session_type_t, COAP_TOKEN_DEFAULT_MAX,
 };
 
-use super::{CoapSessionCommon, CoapSessionInner, CoapSessionInnerProvider};
+use super::{CoapRequestHandle, CoapSessionCommon, CoapSessionInner, CoapSessionInnerProvider};
 use crate::event::event_handler_callback;
 use crate::mem::{CoapFfiRcCell, DropInnerExclusively};
+#[cfg(feature = "oscore")]
+use crate::oscore::OscoreContext;
 use crate::prng::coap_prng_try_fill;
-use crate::{context::CoapContext, error::SessionCreationError, types::CoapAddress};
+use crate::{
+    context::CoapContext,
+    error::{DownloadError, RequestError, SessionCreationError, SessionError},
+    message::{CoapMessageCommon, CoapOption, CoapRequest, CoapResponse},
+    protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode, CoapToken, ContentFormat, ETag},
+    types::{CoapAddress, CoapMessageId, CoapUri},
+};
 
 #[cfg(dtls)]
 use crate::crypto::ClientCryptoContext;
 
+/// Maximum amount of time [CoapClientSession::download()] waits for IO before checking the
+/// cancellation flag again.
+const DOWNLOAD_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Default capacity of the send queue of client sessions (see
+/// [CoapClientSession::set_send_queue_capacity()]).
+pub const DEFAULT_SEND_QUEUE_CAPACITY: usize = 32;
+
 #[derive(Debug)]
-struct CoapClientSessionInner<'a> {
+pub(crate) struct CoapClientSessionInner<'a> {
     inner: CoapSessionInner<'a>,
+    /// Requests queued using [CoapClientSession::try_send()] that have not been handed over to
+    /// libcoap yet.
+    send_queue: VecDeque<CoapRequest>,
+    /// Maximum number of requests in `send_queue`.
+    send_queue_capacity: usize,
     #[cfg(dtls)]
     // This field is actually referred to be libcoap, so it isn't actually unused.
     #[allow(unused)]
@@ -53,6 +79,8 @@ impl<'a> CoapClientSessionInner<'a> {
 
         let inner_session = CoapFfiRcCell::new(CoapClientSessionInner {
             inner: CoapSessionInner::new(raw_session),
+            send_queue: VecDeque::new(),
+            send_queue_capacity: DEFAULT_SEND_QUEUE_CAPACITY,
             #[cfg(dtls)]
             crypto_ctx: None,
         });
@@ -77,6 +105,8 @@ impl<'a> CoapClientSessionInner<'a> {
     ) -> CoapFfiRcCell<CoapClientSessionInner<'a>> {
         let inner_session = CoapFfiRcCell::new(CoapClientSessionInner {
             inner: CoapSessionInner::new(raw_session),
+            send_queue: VecDeque::new(),
+            send_queue_capacity: DEFAULT_SEND_QUEUE_CAPACITY,
             crypto_ctx: Some(crypto_ctx),
         });
 
@@ -95,6 +125,19 @@ pub struct CoapClientSession<'a> {
 }
 
 impl CoapClientSession<'_> {
+    /// Wraps the given inner session and registers it with the provided context, allowing the
+    /// context to detect client sessions that outlive it.
+    fn track<'a>(ctx: &mut CoapContext<'a>, inner: CoapFfiRcCell<CoapClientSessionInner<'a>>) -> CoapClientSession<'a> {
+        ctx.track_client_session(inner.downgrade());
+        CoapClientSession { inner }
+    }
+
+    /// Creates a CoapClientSession from its inner counterpart (e.g., one tracked by a
+    /// [CoapContext]).
+    pub(crate) fn from_inner(inner: CoapFfiRcCell<CoapClientSessionInner<'_>>) -> CoapClientSession<'_> {
+        CoapClientSession { inner }
+    }
+
     /// Create a new DTLS encrypted session with the given peer `addr` using the given `crypto_ctx`.
     ///
     /// # Errors
@@ -129,9 +172,9 @@ impl CoapClientSession<'_> {
         };
 
         // SAFETY: raw_session was just checked to be valid pointer.
-        Ok(CoapClientSession {
-            inner: unsafe { CoapClientSessionInner::new_with_crypto_ctx(raw_session.as_ptr(), crypto_ctx) },
-        })
+        Ok(CoapClientSession::track(ctx, unsafe {
+            CoapClientSessionInner::new_with_crypto_ctx(raw_session.as_ptr(), crypto_ctx)
+        }))
     }
 
     /// Create a new unencrypted session with the given peer over UDP.
@@ -156,9 +199,51 @@ impl CoapClientSession<'_> {
             return Err(SessionCreationError::Unknown);
         }
         // SAFETY: Session was just checked for validity.
-        Ok(CoapClientSession {
-            inner: unsafe { CoapClientSessionInner::new(session) },
-        })
+        Ok(CoapClientSession::track(ctx, unsafe {
+            CoapClientSessionInner::new(session)
+        }))
+    }
+
+    /// Create a new OSCORE-protected session with the given peer over UDP (see
+    /// [RFC 8613](https://datatracker.ietf.org/doc/html/rfc8613)).
+    ///
+    /// All requests sent using this session are protected using the given security context, and
+    /// the responses to them are unprotected automatically.
+    ///
+    /// libcoap only supports enabling OSCORE on session creation, so it is not possible to add
+    /// OSCORE protection to an existing session.
+    ///
+    /// # Errors
+    /// Will return [SessionCreationError::InvalidOscoreContext] if libcoap rejected the security
+    /// context and [SessionCreationError::Unknown] if libcoap was unable to create a session
+    /// (most likely because it was not possible to bind to a port).
+    #[cfg(feature = "oscore")]
+    pub fn connect_udp_oscore<'a>(
+        ctx: &mut CoapContext<'a>,
+        addr: SocketAddr,
+        oscore: OscoreContext,
+    ) -> Result<CoapClientSession<'a>, SessionCreationError> {
+        let raw_conf = oscore
+            .create_raw_conf()
+            .ok_or(SessionCreationError::InvalidOscoreContext)?;
+        // SAFETY: self.raw_context is guaranteed to be valid, local_if can be null, raw_conf was
+        // just created and is freed by libcoap.
+        let session = unsafe {
+            coap_new_client_session_oscore(
+                ctx.as_mut_raw_context(),
+                std::ptr::null(),
+                CoapAddress::from(addr).as_raw_address(),
+                coap_proto_t::COAP_PROTO_UDP,
+                raw_conf,
+            )
+        };
+        if session.is_null() {
+            return Err(SessionCreationError::Unknown);
+        }
+        // SAFETY: Session was just checked for validity.
+        Ok(CoapClientSession::track(ctx, unsafe {
+            CoapClientSessionInner::new(session)
+        }))
     }
 
     /// Create a new unencrypted session with the given peer over TCP.
@@ -183,9 +268,9 @@ impl CoapClientSession<'_> {
             return Err(SessionCreationError::Unknown);
         }
         // SAFETY: Session was just checked for validity.
-        Ok(CoapClientSession {
-            inner: unsafe { CoapClientSessionInner::new(session) },
-        })
+        Ok(CoapClientSession::track(ctx, unsafe {
+            CoapClientSessionInner::new(session)
+        }))
     }
 
     /// Restores a [CoapClientSession] from its raw counterpart.
@@ -194,8 +279,8 @@ impl CoapClientSession<'_> {
     /// the raw pointer, i.e., the session will be created with an arbitrary lifetime.
     /// Therefore, callers of this function should ensure that the created session instance does not
     /// outlive the context it is bound to.
-    /// Failing to do so will result in a panic/abort in the context destructor as it is unable to
-    /// claim exclusive ownership of the client session.
+    /// Failing to do so will result in the context being leaked by its destructor, see
+    /// [CoapContext::outstanding_sessions()].
     ///
     /// # Panics
     ///
@@ -224,6 +309,350 @@ impl CoapClientSession<'_> {
     }
 }
 
+impl<'a> CoapClientSession<'a> {
+    /// Sends the given `request` and drives the IO loop of `context` until the response body has
+    /// been received completely, returning the response body.
+    ///
+    /// Block-wise transfers ([RFC 7959](https://datatracker.ietf.org/doc/html/rfc7959)) are
+    /// handled by libcoap.
+    /// Whenever new parts of the response body are received, `on_progress` is called with the
+    /// total number of bytes received so far and the total size of the response body (if known
+    /// from a Size2 option sent by the peer).
+    /// Note that libcoap usually reassembles block-wise transfers before handing them to this
+    /// library, so `on_progress` may be called only once after the full body was received.
+    ///
+    /// The download is aborted if it does not complete within `timeout` (if set) or if `cancel`
+    /// (if set) is set to `true`.
+    ///
+    /// # Errors
+    /// Returns [DownloadError::Timeout] or [DownloadError::Cancelled] if the download was aborted,
+    /// [DownloadError::ErrorResponse] if the peer responds with a non-success response code,
+    /// [DownloadError::Request] if the request failed (e.g., because the peer did not acknowledge
+    /// it even after the maximum number of retransmissions), or the respective error if sending
+    /// the request or performing IO fails.
+    pub fn download<F: FnMut(usize, Option<usize>)>(
+        &self,
+        context: &mut CoapContext<'a>,
+        request: CoapRequest,
+        timeout: Option<Duration>,
+        cancel: Option<&AtomicBool>,
+        mut on_progress: F,
+    ) -> Result<Vec<u8>, DownloadError> {
+        let start_time = Instant::now();
+        let handle = self.send_request(request)?;
+        let mut body = Vec::new();
+        loop {
+            if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                self.remove_handle(handle);
+                return Err(DownloadError::Cancelled);
+            }
+            let remaining = timeout.map(|timeout| timeout.saturating_sub(start_time.elapsed()));
+            if remaining.is_some_and(|remaining| remaining.is_zero()) {
+                self.remove_handle(handle);
+                return Err(DownloadError::Timeout);
+            }
+            // Wake up periodically to check the cancellation flag.
+            let io_timeout = remaining.map_or(DOWNLOAD_POLL_INTERVAL, |v| v.min(DOWNLOAD_POLL_INTERVAL));
+            if let Err(e) = context.do_io(Some(io_timeout)) {
+                self.remove_handle(handle);
+                return Err(e.into());
+            }
+            for response in self.poll_handle(&handle) {
+                if let CoapMessageCode::Response(code) = response.code() {
+                    if (code.to_raw_pdu_code() as u32) >> 5 != 2 {
+                        self.remove_handle(handle);
+                        return Err(DownloadError::ErrorResponse(code));
+                    }
+                }
+                let mut total_size = None;
+                let mut more_blocks = false;
+                for option in response.options_iter() {
+                    match option {
+                        CoapOption::Size2(size) => total_size = Some(*size as usize),
+                        // The M bit of the Block2 option indicates whether more blocks will follow,
+                        // see RFC 7959, Section 2.2.
+                        CoapOption::Block2(block) => more_blocks = block & 0x08 != 0,
+                        _ => {},
+                    }
+                }
+                if let Some(data) = response.data() {
+                    body.extend_from_slice(data);
+                }
+                on_progress(body.len(), total_size);
+                if !more_blocks {
+                    self.remove_handle(handle);
+                    return Ok(body);
+                }
+            }
+            if let Some(error) = self.take_request_error(&handle) {
+                self.remove_handle(handle);
+                return Err(error.into());
+            }
+        }
+    }
+
+    /// Sends a GET request for `uri` that asks the server to validate the cached representation
+    /// identified by `etag` and drives the IO loop of `context` until the response was received.
+    ///
+    /// If the cached representation is still current, the server responds with
+    /// [CoapResponseCode::Valid](crate::protocol::CoapResponseCode::Valid) (see
+    /// [CoapResponse::is_not_modified()]), otherwise it sends the current representation (see
+    /// [RFC 7252, Section 5.10.6.2](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10.6.2)).
+    ///
+    /// Unlike [CoapClientSession::download()], responses with error codes are returned as is.
+    ///
+    /// # Errors
+    /// Returns [RequestError::Timeout] if no response was received within `timeout` (if set),
+    /// [RequestError::RetransmitExhausted] if the request was not acknowledged after the maximum
+    /// number of retransmissions, [RequestError::PeerUnreachable] if an ICMP error indicated that
+    /// the peer is unreachable (see
+    /// [CoapNackReason::IcmpIssue](super::CoapNackReason::IcmpIssue)), or the respective error if
+    /// sending the request or performing IO fails.
+    pub fn get_with_etag(
+        &self,
+        context: &mut CoapContext<'a>,
+        uri: CoapUri,
+        etag: &ETag,
+        timeout: Option<Duration>,
+    ) -> Result<CoapResponse, RequestError> {
+        let mut request = Self::get_request(uri);
+        request.set_etag(Some(vec![etag.clone()]));
+        self.send_and_await_response(context, request, timeout)
+    }
+
+    /// Sends a GET request for `uri` that asks for a representation in the content format
+    /// `accept` and drives the IO loop of `context` until the response was received.
+    ///
+    /// If the resource is not available in the requested content format, the server usually
+    /// responds with
+    /// [CoapResponseCode::NotAcceptable](crate::protocol::CoapResponseCode::NotAcceptable) (see
+    /// [RFC 7252, Section 5.10.4](https://datatracker.ietf.org/doc/html/rfc7252#section-5.10.4)).
+    ///
+    /// # Errors
+    /// See [CoapClientSession::get_with_etag()].
+    pub fn get_with_accept(
+        &self,
+        context: &mut CoapContext<'a>,
+        uri: CoapUri,
+        accept: ContentFormat,
+        timeout: Option<Duration>,
+    ) -> Result<CoapResponse, RequestError> {
+        let mut request = Self::get_request(uri);
+        request.set_accept(Some(accept));
+        self.send_and_await_response(context, request, timeout)
+    }
+
+    /// Creates a confirmable GET request for the given `uri`.
+    fn get_request(uri: CoapUri) -> CoapRequest {
+        CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri)
+            .expect("confirmable messages are valid requests")
+    }
+
+    /// Sends the given `request` and drives the IO loop of `context` until the first response to
+    /// it was received or `timeout` (if set) expired.
+    fn send_and_await_response(
+        &self,
+        context: &mut CoapContext<'a>,
+        request: CoapRequest,
+        timeout: Option<Duration>,
+    ) -> Result<CoapResponse, RequestError> {
+        let start_time = Instant::now();
+        let handle = self.send_request(request)?;
+        loop {
+            let remaining = timeout.map(|timeout| timeout.saturating_sub(start_time.elapsed()));
+            if remaining.is_some_and(|remaining| remaining.is_zero()) {
+                self.remove_handle(handle);
+                return Err(RequestError::Timeout);
+            }
+            if let Err(e) = context.do_io(remaining) {
+                self.remove_handle(handle);
+                return Err(e.into());
+            }
+            if let Some(response) = self.poll_handle(&handle).next() {
+                self.remove_handle(handle);
+                return Ok(response);
+            }
+            if let Some(error) = self.take_request_error(&handle) {
+                self.remove_handle(handle);
+                return Err(error);
+            }
+        }
+    }
+
+    /// Cancels the pending request with the given message ID that was sent using
+    /// [CoapSessionCommon::send_request()].
+    ///
+    /// After cancellation, responses to the request are no longer stored and are instead rejected
+    /// with an RST message (see [CoapSessionCommon::remove_handle()]), which also causes the peer
+    /// to cancel an observation established by the request.
+    ///
+    /// Requests that were queued using [CoapClientSession::try_send()] and have not been handed
+    /// over to libcoap yet are removed from the send queue, so they are never transmitted.
+    /// However, libcoap does not provide a public API for removing individual messages from its
+    /// retransmission queue, so a confirmable request that was already sent and has not been
+    /// acknowledged yet may still be retransmitted until it is acknowledged or the maximum number
+    /// of retransmissions is reached.
+    ///
+    /// Returns whether a pending request with the given message ID was found.
+    pub fn cancel_request(&self, id: CoapMessageId) -> bool {
+        let token = self.inner_mut().request_tokens.remove(&id);
+        token.is_some_and(|token| self.cancel_by_token(token.as_bytes()))
+    }
+
+    /// Sets the handler that is called whenever the peer answers a confirmable message sent using
+    /// this session (e.g., a request) with a Reset message, indicating that it was unable to
+    /// process the message (see
+    /// [RFC 7252, Section 4.2](https://datatracker.ietf.org/doc/html/rfc7252#section-4.2)).
+    ///
+    /// The handler is provided with the message ID of the rejected message.
+    /// As no response will be received for a rejected request, the handler may want to remove it
+    /// from the pending requests using [CoapClientSession::cancel_request()].
+    /// Reset messages that answer pings sent using [CoapSessionCommon::send_ping()] are reported
+    /// to the ping handler instead, see [CoapSessionCommon::set_ping_handler()].
+    ///
+    /// Setting a new handler replaces the previous one.
+    pub fn set_reset_handler(&mut self, handler: impl Fn(CoapMessageId) + Send + 'static) {
+        self.inner_mut().reset_handler = Some(Box::new(handler));
+    }
+
+    /// Cancels all pending requests with the given `token` that were sent using
+    /// [CoapSessionCommon::send_request()].
+    ///
+    /// See [CoapClientSession::cancel_request()] for more information.
+    ///
+    /// Returns whether a pending request with the given token was found.
+    pub fn cancel_by_token(&self, token: &[u8]) -> bool {
+        // Requests that were queued using try_send() but not handed over to libcoap yet are never
+        // transmitted.
+        self.inner
+            .borrow_mut()
+            .send_queue
+            .retain(|request| request.token().map(CoapToken::as_bytes) != Some(token));
+        let mut inner = self.inner_mut();
+        inner.request_tokens.retain(|_, v| v.as_ref() != token);
+        inner.failed_requests.remove(token);
+        #[cfg(feature = "metrics")]
+        inner.request_sent_at.remove(token);
+        inner.received_responses.remove(token).is_some()
+    }
+
+    /// Sends all of the given `requests` using [CoapSessionCommon::send()].
+    ///
+    /// Unlike [CoapSessionCommon::send_request()], the sent requests are not tracked, so responses
+    /// to them are rejected. This is mostly useful for non-confirmable requests.
+    /// As for [CoapSessionCommon::send()], retransmissions and responses are only handled during
+    /// subsequent calls to [CoapContext::do_io()].
+    ///
+    /// Returns the message IDs of all requests in the order they were provided.
+    ///
+    /// # Errors
+    /// If sending any of the requests fails, returns the indices of all failed requests in
+    /// `requests` together with the respective [SessionError]. The remaining requests are still
+    /// sent.
+    pub fn send_batch(&self, requests: Vec<CoapRequest>) -> Result<Vec<CoapMessageId>, Vec<(usize, SessionError)>> {
+        let mut message_ids = Vec::with_capacity(requests.len());
+        let mut errors = Vec::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            match self.send(request.into_message()) {
+                Ok(mid) => message_ids.push(mid),
+                Err(e) => errors.push((index, e)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(message_ids)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Queues the given `request` for transmission without blocking, returning a handle that can
+    /// be used to poll the request for completion (see [CoapSessionCommon::poll_handle()]).
+    ///
+    /// Queued requests are handed over to libcoap (using [CoapSessionCommon::send_request()])
+    /// during calls to [CoapContext::do_io()], but only as long as fewer than `NSTART` (see
+    /// [RFC 7252, Section 4.7](https://datatracker.ietf.org/doc/html/rfc7252#section-4.7))
+    /// requests sent using [CoapSessionCommon::send_request()] are still awaiting a response.
+    /// Requests stop awaiting a response once it was received, they failed (see
+    /// [CoapSessionCommon::take_request_error()]) or they were cancelled (see
+    /// [CoapClientSession::cancel_by_token()]).
+    /// As the queue is bounded (see [CoapClientSession::set_send_queue_capacity()]), applications
+    /// that generate requests faster than they are sent are notified instead of consuming an
+    /// unbounded amount of memory.
+    /// Requests that can not be sent once they are handed over to libcoap (e.g., because the
+    /// session has been closed in the meantime) fail with [RequestError::Session], which is
+    /// reported using [CoapSessionCommon::take_request_error()].
+    ///
+    /// # Errors
+    /// Returns [SessionError::WouldBlock] if the send queue is full, [SessionError::Closed] if the
+    /// session has been closed and [SessionError::ContextDropped] if the session's context has
+    /// been dropped.
+    pub fn try_send(&self, mut request: CoapRequest) -> Result<CoapRequestHandle, SessionError> {
+        if self.is_poisoned() {
+            return Err(SessionError::ContextDropped);
+        }
+        if self.inner_ref().closed {
+            return Err(SessionError::Closed);
+        }
+        if self.send_queue_len() >= self.send_queue_capacity() {
+            return Err(SessionError::WouldBlock);
+        }
+        if request.token().is_none() {
+            let token = self.inner_mut().generate_request_token();
+            request.set_token(Some(token));
+        }
+        if request.mid().is_none() {
+            request.set_mid(Some(self.next_message_id()));
+        }
+        let handle = CoapRequestHandle::new(request.mid().unwrap(), request.token().cloned().unwrap());
+        // Allow polling the handle before the request is actually sent.
+        self.inner_mut()
+            .received_responses
+            .insert(handle.token.clone(), VecDeque::new());
+        self.inner.borrow_mut().send_queue.push_back(request);
+        Ok(handle)
+    }
+
+    /// Returns the maximum number of requests that can be queued using
+    /// [CoapClientSession::try_send()] (defaults to [DEFAULT_SEND_QUEUE_CAPACITY]).
+    pub fn send_queue_capacity(&self) -> usize {
+        self.inner.borrow().send_queue_capacity
+    }
+
+    /// Sets the maximum number of requests that can be queued using
+    /// [CoapClientSession::try_send()].
+    ///
+    /// Requests that are already queued are not discarded if the queue currently holds more than
+    /// `capacity` requests.
+    pub fn set_send_queue_capacity(&self, capacity: usize) {
+        self.inner.borrow_mut().send_queue_capacity = capacity;
+    }
+
+    /// Returns the number of requests that were queued using [CoapClientSession::try_send()] but
+    /// have not been handed over to libcoap yet.
+    pub fn send_queue_len(&self) -> usize {
+        self.inner.borrow().send_queue.len()
+    }
+
+    /// Hands requests queued using [CoapClientSession::try_send()] over to libcoap, as long as the
+    /// number of requests awaiting a response does not exceed `NSTART` (see
+    /// [RFC 7252, Section 4.7](https://datatracker.ietf.org/doc/html/rfc7252#section-4.7)).
+    pub(crate) fn drain_send_queue(&self) {
+        // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner.
+        let nstart = usize::from(unsafe { coap_session_get_nstart(self.inner_ref().raw_session) }).max(1);
+        while self.inner_ref().request_tokens.len() < nstart {
+            let Some(request) = self.inner.borrow_mut().send_queue.pop_front() else {
+                break;
+            };
+            let token = request.token().cloned().expect("queued requests are assigned a token");
+            // Send failures are reported through the request's handle, see try_send().
+            if let Err(e) = self.send_request(request) {
+                self.inner_mut()
+                    .fail_request_with_token(token, RequestError::Session(e));
+            }
+        }
+    }
+}
+
 impl DropInnerExclusively for CoapClientSession<'_> {
     fn drop_exclusively(self) {
         self.inner.drop_exclusively();
@@ -265,10 +694,21 @@ impl Drop for CoapClientSessionInner<'_> {
 
 impl<'a> CoapSessionInnerProvider<'a> for CoapClientSession<'a> {
     fn inner_ref<'b>(&'b self) -> Ref<'b, CoapSessionInner<'a>> {
-        Ref::map(self.inner.borrow(), |v| &v.inner)
+        let inner = self
+            .inner
+            .try_borrow()
+            .unwrap_or_else(|e| panic!("unable to access session: {e}"));
+        Ref::map(inner, |v| &v.inner)
     }
     fn inner_mut<'b>(&'b self) -> RefMut<'b, CoapSessionInner<'a>> {
-        RefMut::map(self.inner.borrow_mut(), |v| &mut v.inner)
+        let inner = self
+            .inner
+            .try_borrow_mut()
+            .unwrap_or_else(|e| panic!("unable to access session: {e}"));
+        RefMut::map(inner, |v| &mut v.inner)
+    }
+    fn inner_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
     }
 }
 
@@ -11,37 +11,56 @@ use std::{
     any::Any,
     borrow::BorrowMut,
     cell::{Ref, RefMut},
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::{Debug, Formatter},
     marker::PhantomData,
-    net::{SocketAddr, ToSocketAddrs},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
     rc::Rc,
+    time::{Duration, Instant},
 };
 
 use libcoap_sys::{
-    coap_context_t, coap_fixed_point_t, coap_mid_t, coap_new_message_id, coap_pdu_get_token, coap_pdu_t,
-    coap_response_t, coap_send, coap_session_get_ack_random_factor, coap_session_get_ack_timeout,
-    coap_session_get_addr_local, coap_session_get_addr_remote, coap_session_get_ifindex,
-    coap_session_get_max_retransmit, coap_session_get_proto, coap_session_get_state, coap_session_get_type,
-    coap_session_init_token, coap_session_max_pdu_size, coap_session_new_token, coap_session_send_ping,
-    coap_session_set_ack_random_factor, coap_session_set_ack_timeout, coap_session_set_max_retransmit,
-    coap_session_set_mtu, coap_session_state_t, coap_session_t, coap_session_type_t,
+    coap_context_t, coap_fixed_point_t, coap_mid_t, coap_nack_reason_t, coap_new_message_id, coap_pdu_get_token,
+    coap_pdu_t, coap_response_t, coap_send, coap_session_get_ack_random_factor, coap_session_get_ack_timeout,
+    coap_session_get_addr_local, coap_session_get_addr_remote, coap_session_get_app_data, coap_session_get_context,
+    coap_session_get_ifindex, coap_session_get_max_retransmit, coap_session_get_probing_rate, coap_session_get_proto,
+    coap_session_get_state, coap_session_get_type, coap_session_init_token, coap_session_max_pdu_size,
+    coap_session_new_token, coap_session_send_ping, coap_session_set_ack_random_factor, coap_session_set_ack_timeout,
+    coap_session_set_max_retransmit, coap_session_set_mtu, coap_session_set_probing_rate, coap_session_state_t,
+    coap_session_t, coap_session_type_t, COAP_INVALID_MID,
 };
 #[cfg(feature = "dtls-psk")]
 use libcoap_sys::{coap_session_get_psk_hint, coap_session_get_psk_identity, coap_session_get_psk_key};
 
 use self::sealed::{CoapSessionCommonInternal, CoapSessionInnerProvider};
 pub use self::{client::CoapClientSession, server::CoapServerSession};
+#[cfg(feature = "metrics")]
+use crate::metrics;
 use crate::{
-    error::{MessageConversionError, SessionGetAppDataError},
-    message::{request::CoapRequest, response::CoapResponse, CoapMessage, CoapMessageCommon},
-    protocol::CoapToken,
+    error::{RequestError, SessionError, SessionGetAppDataError},
+    message::{request::CoapRequest, response::CoapResponse, CoapMessage, CoapMessageCommon, CoapOption},
+    protocol::{CoapMessageCode, CoapMessageType, CoapResponseCode, CoapToken},
     types::{CoapAddress, CoapMessageId, CoapProtocol, IfIndex, MaxRetransmit},
+    CoapContext,
 };
 
 pub mod client;
 
 pub mod server;
 
+/// Time span in which retransmissions of a message may be received (`EXCHANGE_LIFETIME` in
+/// [RFC 7252, Section 4.8.2](https://datatracker.ietf.org/doc/html/rfc7252#section-4.8.2), using
+/// the default transmission parameters).
+const EXCHANGE_LIFETIME: Duration = Duration::from_secs(247);
+
+/// Combined size of the IPv4 and UDP headers, which have to be subtracted from the path MTU to get
+/// the maximum size of a CoAP message sent over IPv4.
+const IPV4_UDP_HEADER_SIZE: u16 = 28;
+
+/// Combined size of the IPv6 and UDP headers, which have to be subtracted from the path MTU to get
+/// the maximum size of a CoAP message sent over IPv6.
+const IPV6_UDP_HEADER_SIZE: u16 = 48;
+
 /// Representation of the states that a session can be in.
 #[repr(u32)]
 pub enum CoapSessionState {
@@ -65,6 +84,58 @@ impl From<coap_session_state_t> for CoapSessionState {
     }
 }
 
+/// Reasons for which libcoap may report that a message could not be delivered to the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CoapNackReason {
+    /// A confirmable message was not acknowledged, even after the maximum number of
+    /// retransmissions (see [CoapSessionCommon::set_max_retransmit()]).
+    TooManyRetries,
+    /// The message could not be delivered, e.g., because the session was closed before it could be
+    /// sent.
+    NotDeliverable,
+    /// The peer answered the message with a Reset message.
+    Rst,
+    /// The (D)TLS handshake required for sending the message failed.
+    TlsFailed,
+    /// An ICMP error (e.g., "destination unreachable") was received while sending the message.
+    ///
+    /// On platforms that report ICMP errors for UDP sockets, this usually happens shortly after
+    /// sending a message to a port nobody is listening on. libcoap continues retransmitting
+    /// confirmable messages afterwards, as the error may be transient.
+    IcmpIssue,
+    /// The response received for the message was invalid.
+    BadResponse,
+    /// The TLS layer of a CoAP over WebSockets session failed.
+    TlsLayerFailed,
+    /// The WebSockets layer failed to establish the connection.
+    WsLayerFailed,
+    /// The WebSockets connection failed after it was established.
+    WsFailed,
+    /// A NACK reason that is not known to this wrapper (e.g., because it was added in a newer
+    /// version of libcoap), given as its raw value.
+    Other(u32),
+}
+
+impl From<coap_nack_reason_t> for CoapNackReason {
+    fn from(raw_reason: coap_nack_reason_t) -> Self {
+        match raw_reason {
+            coap_nack_reason_t::COAP_NACK_TOO_MANY_RETRIES => CoapNackReason::TooManyRetries,
+            coap_nack_reason_t::COAP_NACK_NOT_DELIVERABLE => CoapNackReason::NotDeliverable,
+            coap_nack_reason_t::COAP_NACK_RST => CoapNackReason::Rst,
+            coap_nack_reason_t::COAP_NACK_TLS_FAILED => CoapNackReason::TlsFailed,
+            coap_nack_reason_t::COAP_NACK_ICMP_ISSUE => CoapNackReason::IcmpIssue,
+            coap_nack_reason_t::COAP_NACK_BAD_RESPONSE => CoapNackReason::BadResponse,
+            coap_nack_reason_t::COAP_NACK_TLS_LAYER_FAILED => CoapNackReason::TlsLayerFailed,
+            coap_nack_reason_t::COAP_NACK_WS_LAYER_FAILED => CoapNackReason::WsLayerFailed,
+            coap_nack_reason_t::COAP_NACK_WS_FAILED => CoapNackReason::WsFailed,
+            // This conversion is used in the NACK handler called by libcoap, where panicking is
+            // not an option.
+            other => CoapNackReason::Other(other as u32),
+        }
+    }
+}
+
 mod sealed {
     use super::*;
 
@@ -76,6 +147,10 @@ mod sealed {
 
         /// Provide a RefMut to the instance of [CoapSessionInner] contained in this type.
         fn inner_mut<'b>(&'b self) -> RefMut<'b, CoapSessionInner<'a>>;
+
+        /// Returns whether the instance of [CoapSessionInner] contained in this type has been
+        /// poisoned because its context was dropped.
+        fn inner_poisoned(&self) -> bool;
     }
 
     /// Functions common between all sessions that should not be public.
@@ -124,6 +199,18 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
         inner.app_data = None;
     }
 
+    /// Returns whether this session can no longer be used because the
+    /// [CoapContext](crate::CoapContext) it belongs to has been dropped.
+    ///
+    /// Dropping a context while sessions bound to it are still in use leaks the underlying libcoap
+    /// context and poisons these sessions (see
+    /// [CoapContext::outstanding_sessions()](crate::CoapContext::outstanding_sessions())).
+    /// Sending messages using a poisoned session fails with [SessionError::ContextDropped], while
+    /// all other functions that access the session panic. Poisoned sessions may still be dropped.
+    fn is_poisoned(&self) -> bool {
+        self.inner_poisoned()
+    }
+
     /// Returns the Ack-Random-Factor used by libcoap.
     ///
     /// The returned value is a tuple consisting of an integer and a fractional part, where the
@@ -220,6 +307,33 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
         unsafe { coap_session_set_max_retransmit(self.inner_ref().raw_session, value) }
     }
 
+    /// Returns the probing rate of this session in bytes per second, i.e., the maximum average
+    /// data rate at which non-confirmable messages are sent to the peer (`PROBING_RATE` in
+    /// [RFC 7252, Section 4.7](https://datatracker.ietf.org/doc/html/rfc7252#section-4.7)).
+    fn probing_rate(&self) -> u32 {
+        // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner
+        unsafe { coap_session_get_probing_rate(self.inner_ref().raw_session) }
+    }
+
+    /// Sets the probing rate of this session in bytes per second and enables its enforcement for
+    /// non-confirmable messages sent using [CoapSessionCommon::send()] (or any function based
+    /// on it) on unreliable transports (UDP and DTLS).
+    ///
+    /// libcoap itself only uses the probing rate for some block-wise transfers, so it is not
+    /// enforced for other non-confirmable messages unless this function is called.
+    /// Once enforced, the average data rate of non-confirmable messages may not exceed the
+    /// probing rate, i.e., after sending a non-confirmable message of `n` bytes, the next one may
+    /// only be sent `n / bytes_per_second` seconds later. Attempting to send it earlier fails with
+    /// [SessionError::ProbingRateExceeded].
+    ///
+    /// A value of zero is ignored by libcoap, in which case the previous probing rate (by default,
+    /// one byte per second) is kept.
+    fn set_probing_rate(&mut self, bytes_per_second: u32) {
+        // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner
+        unsafe { coap_session_set_probing_rate(self.inner_ref().raw_session, bytes_per_second) }
+        self.inner_mut().enforce_probing_rate = true;
+    }
+
     /// Returns the underlying transport protocol used for this session.
     fn proto(&self) -> CoapProtocol {
         // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner
@@ -238,6 +352,10 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
     }
 
     /// Returns the current PSK identity for this session.
+    ///
+    /// For server-side sessions, this is the identity the client authenticated with during the
+    /// DTLS handshake (see also [CoapServerSession::dtls_peer_identity()]).
+    /// Returns `None` for sessions that do not use pre-shared keys.
     #[cfg(feature = "dtls-psk")]
     fn psk_identity(&self) -> Option<Box<[u8]>> {
         // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner
@@ -259,6 +377,16 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
         }
     }
 
+    /// Returns the DER-encoded certificate the peer presented during the (D)TLS handshake.
+    ///
+    /// Returns `None` if the session does not use PKI based encryption (e.g., for PSK or RPK
+    /// sessions), if the handshake has not been completed yet or if the peer did not present a
+    /// certificate (e.g., because the server did not request a client certificate).
+    #[cfg(feature = "dtls-pki")]
+    fn peer_certificate_der(&self) -> Option<Vec<u8>> {
+        self.inner_ref().peer_certificate.clone()
+    }
+
     /// Returns the current state of this session.
     #[must_use = "getting the current session state without using it is a no-op"]
     fn state(&self) -> CoapSessionState {
@@ -287,6 +415,37 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
         unsafe { coap_session_set_mtu(self.inner_mut().raw_session, mtu) }
     }
 
+    /// Updates the maximum size of a PDU for this session to the given path MTU that was
+    /// discovered using Path MTU Discovery (e.g., after an ICMP "Fragmentation Needed" or "Packet
+    /// Too Big" message has been received for the path to the peer).
+    ///
+    /// This sets the MTU of the session to the path MTU minus the size of the IP and UDP headers
+    /// (see [CoapSessionCommon::set_mtu()]) and records the path MTU (see
+    /// [CoapSessionCommon::pmtu_discovered()]). Block-wise transfers started afterwards use a
+    /// block size that fits into the new MTU, as libcoap derives the block size from the maximum
+    /// PDU size of the session.
+    ///
+    /// This function is called automatically if libcoap reports an ICMP error for a message sent
+    /// using this session and the operating system (currently only on Linux) has lowered the path
+    /// MTU to the peer in response to it. Applications that learn about path MTU changes in other
+    /// ways may call this function themselves.
+    /// Note that libcoap raises MTUs below 64 bytes to 64 bytes.
+    fn on_pmtu_discovery(&mut self, new_pmtu: u16) {
+        let header_size = if self.addr_remote().is_ipv4() {
+            IPV4_UDP_HEADER_SIZE
+        } else {
+            IPV6_UDP_HEADER_SIZE
+        };
+        self.set_mtu(new_pmtu.saturating_sub(header_size).into());
+        self.inner_mut().discovered_pmtu = Some(new_pmtu);
+    }
+
+    /// Returns the path MTU most recently provided to [CoapSessionCommon::on_pmtu_discovery()], or
+    /// `None` if no path MTU has been discovered for this session.
+    fn pmtu_discovered(&self) -> Option<u16> {
+        self.inner_ref().discovered_pmtu
+    }
+
     /// Returns the next message ID that should be used for this session.
     fn next_message_id(&self) -> CoapMessageId {
         // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner
@@ -302,20 +461,129 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
     }
 
     /// Send a ping message to the remote peer.
-    fn send_ping(&mut self) -> CoapMessageId {
+    ///
+    /// For UDP and DTLS sessions, this sends a CoAP Ping, i.e., an empty confirmable message
+    /// (code 0.00), which the peer answers with a Reset message (see
+    /// [RFC 7252, Section 4.3](https://datatracker.ietf.org/doc/html/rfc7252#section-4.3)).
+    /// For reliable transports (TCP/TLS), a 7.02 Ping signaling message is sent, which is answered
+    /// with a 7.03 Pong (see [RFC 8323, Section 5.4](https://datatracker.ietf.org/doc/html/rfc8323#section-5.4)).
+    ///
+    /// Once the answer is received (during a call to [CoapContext::do_io()](crate::CoapContext::do_io())),
+    /// the handler set using [CoapSessionCommon::set_ping_handler()] is called with the message ID returned
+    /// by this function.
+    ///
+    /// # Errors
+    /// Returns [SessionError::Closed] if the session has been closed, [SessionError::ContextDropped]
+    /// if the session's context has been dropped and [SessionError::SendFailed] if libcoap was
+    /// unable to send the ping (e.g., because the session is not established yet).
+    fn send_ping(&mut self) -> Result<CoapMessageId, SessionError> {
+        if self.is_poisoned() {
+            return Err(SessionError::ContextDropped);
+        }
+        if self.inner_ref().closed {
+            return Err(SessionError::Closed);
+        }
+        let proto = self.proto();
+        let mut inner = self.inner_mut();
         // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner
-        unsafe { coap_session_send_ping(self.inner_mut().raw_session) }
+        let mid = unsafe { coap_session_send_ping(inner.raw_session) };
+        if mid == COAP_INVALID_MID {
+            return Err(SessionError::SendFailed);
+        }
+        #[cfg(feature = "metrics")]
+        metrics::record_message_sent(proto);
+        // libcoap only reports Reset messages for pings as pongs if keepalive is enabled, so we
+        // have to keep track of pings on unreliable transports ourselves.
+        if matches!(proto, CoapProtocol::Udp | CoapProtocol::Dtls) {
+            inner.pending_pings.insert(mid);
+        }
+        Ok(mid)
+    }
+
+    /// Sets the handler that is called whenever the answer to a ping sent using
+    /// [CoapSessionCommon::send_ping()] (or an automatically sent keepalive ping, see
+    /// [CoapContext::set_keepalive()](crate::CoapContext::set_keepalive())) is received.
+    ///
+    /// The handler is provided with the message ID of the answered ping.
+    /// Setting a new handler replaces the previous one.
+    fn set_ping_handler(&mut self, handler: impl Fn(CoapMessageId) + Send + 'static) {
+        self.inner_mut().pong_handler = Some(Box::new(handler));
+    }
+
+    /// Sets the handler that is called whenever libcoap reports that a message sent using this
+    /// session could not be delivered.
+    ///
+    /// The handler is provided with the message ID of the affected message and the reason for the
+    /// failure. Reset messages sent in response to pings are answers to these pings (see
+    /// [CoapSessionCommon::set_ping_handler()]) and are therefore not reported to this handler.
+    /// Setting a new handler replaces the previous one.
+    fn set_nack_handler(&mut self, handler: impl Fn(CoapMessageId, CoapNackReason) + Send + 'static) {
+        self.inner_mut().nack_handler = Some(Box::new(handler));
     }
 
     /// Send the given message-like object to the peer.
     ///
     /// # Errors
-    /// Returns a [MessageConversionError] if the supplied object cannot be converted to a message.
-    fn send<P: Into<CoapMessage>>(&self, pdu: P) -> Result<CoapMessageId, MessageConversionError> {
-        let raw_pdu = pdu.into().into_raw_pdu(self)?;
+    /// Returns [SessionError::Closed] if the session has been closed,
+    /// [SessionError::ContextDropped] if the session's context has been dropped,
+    /// [SessionError::MessageConversion] if the supplied object cannot be converted to a message
+    /// and [SessionError::ProbingRateExceeded] if the message is non-confirmable and sending it
+    /// would exceed the probing rate (see [CoapSessionCommon::set_probing_rate()]).
+    ///
+    /// Confirmable and non-confirmable messages without a message ID are assigned the next
+    /// message ID of this session (see [CoapSessionCommon::next_message_id()]).
+    /// Responses to requests that were received via multicast are always sent as non-confirmable
+    /// messages.
+    fn send<P: Into<CoapMessage>>(&self, pdu: P) -> Result<CoapMessageId, SessionError> {
+        if self.is_poisoned() {
+            return Err(SessionError::ContextDropped);
+        }
+        if self.inner_ref().closed {
+            return Err(SessionError::Closed);
+        }
+        let mut message = pdu.into();
+        // Responses to requests received via multicast must be non-confirmable (RFC 7252,
+        // Section 8.2), and as these requests are never confirmable, there is nothing to
+        // acknowledge either.
+        if matches!(message.code(), CoapMessageCode::Response(_))
+            && message.type_() != CoapMessageType::Non
+            && self.addr_local().ip().is_multicast()
+        {
+            message.set_type_(CoapMessageType::Non);
+            message.set_mid(None);
+        }
+        // Acknowledgements and resets have to echo the message ID of the message they refer to,
+        // so we can only assign new message IDs to other messages.
+        if message.mid().is_none() && matches!(message.type_(), CoapMessageType::Con | CoapMessageType::Non) {
+            message.set_mid(Some(self.next_message_id()));
+        }
+        if message.type_() == CoapMessageType::Non && matches!(self.proto(), CoapProtocol::Udp | CoapProtocol::Dtls) {
+            let probing_rate = self.probing_rate();
+            self.inner_mut().throttle_non_confirmable(&message, probing_rate)?;
+        }
+        // Only piggybacked and non-confirmable responses can be replayed for duplicate requests,
+        // separate confirmable responses are retransmitted by libcoap itself until acknowledged.
+        if matches!(message.code(), CoapMessageCode::Response(_))
+            && matches!(message.type_(), CoapMessageType::Ack | CoapMessageType::Non)
+        {
+            self.inner_mut().cache_response(&message);
+        }
+        if let CoapMessageCode::Response(code) = message.code() {
+            self.inner_mut().last_sent_response = Some(SentResponse {
+                token: message.token().cloned().unwrap_or_default(),
+                code,
+                type_: message.type_(),
+                observe: message.options_iter().any(|v| matches!(v, CoapOption::Observe(_))),
+            });
+        }
+        let raw_pdu = message.into_raw_pdu(self)?;
         // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner,
         // raw pdu should be valid as we got it from `into_raw_pdu()`.
         let mid = unsafe { coap_send(self.inner_mut().raw_session, raw_pdu) };
+        #[cfg(feature = "metrics")]
+        if mid != COAP_INVALID_MID {
+            metrics::record_message_sent(self.proto());
+        }
         Ok(mid)
     }
 
@@ -323,44 +591,71 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
     /// request for completion.
     ///
     /// # Errors
-    /// Returns a [MessageConversionError] if the given Request could not be converted into a raw
+    /// Returns [SessionError::Closed] if the session has been closed,
+    /// [SessionError::ContextDropped] if the session's context has been dropped and
+    /// [SessionError::MessageConversion] if the given Request could not be converted into a raw
     /// message.
-    fn send_request(&self, mut req: CoapRequest) -> Result<CoapRequestHandle, MessageConversionError> {
+    fn send_request(&self, mut req: CoapRequest) -> Result<CoapRequestHandle, SessionError> {
+        if self.is_poisoned() {
+            return Err(SessionError::ContextDropped);
+        }
         if req.token().is_none() {
-            let mut token_len = libcoap_sys::COAP_TOKEN_DEFAULT_MAX as usize;
-            let mut token_tmp: Vec<u8> = vec![0; token_len];
-            // SAFETY: Provided pointer is valid, length matches.
-            unsafe {
-                coap_session_new_token(self.inner_mut().raw_session, &mut token_len, token_tmp.as_mut_ptr());
-            }
-            req.set_token(Some(Vec::from(&token_tmp[0..token_len])))
+            let token = self.inner_mut().generate_request_token();
+            req.set_token(Some(token))
         }
-        let token: Box<[u8]> = Box::from(req.token().unwrap());
+        let token = req.token().cloned().unwrap();
         if req.mid().is_none() {
             req.set_mid(Some(self.next_message_id()))
         }
-        self.inner_mut()
-            .received_responses
-            .insert(token.clone(), VecDeque::new());
+        let mut inner = self.inner_mut();
+        inner.received_responses.insert(token.clone(), VecDeque::new());
+        inner.request_tokens.insert(req.mid().unwrap(), token.clone());
+        inner.failed_requests.remove(&token);
+        #[cfg(feature = "metrics")]
+        inner.request_sent_at.insert(token.clone(), Instant::now());
+        std::mem::drop(inner);
         self.send(req.into_message()).map(|v| CoapRequestHandle::new(v, token))
     }
 
     /// Polls whether the request for the given handle already has pending responses.
     ///
     /// Returns an iterator over all responses associated with the request.
+    /// If the request failed (see [CoapSessionCommon::take_request_error()]), no responses are
+    /// returned.
     ///
     /// # Panics
     ///
     /// Panics if the provided handle does not refer to a valid token, i.e., because it belongs to
     /// a different session.
     fn poll_handle(&self, handle: &CoapRequestHandle) -> std::collections::vec_deque::IntoIter<CoapResponse> {
-        self.inner_mut()
+        let mut inner = self.inner_mut();
+        if inner.failed_requests.contains_key(&handle.token) {
+            return VecDeque::new().into_iter();
+        }
+        inner
             .received_responses
             .insert(handle.token.clone(), VecDeque::new())
             .expect("Attempted to poll handle that does not refer to a valid token")
             .into_iter()
     }
 
+    /// Returns the error that caused the request for the given handle to fail before a response
+    /// was received, if any.
+    ///
+    /// Requests sent using [CoapSessionCommon::send_request()] fail with
+    /// [RequestError::RetransmitExhausted] if libcoap gave up retransmitting them and with
+    /// [RequestError::PeerUnreachable] if an ICMP error indicated that the peer can not be reached.
+    /// Failed requests are no longer tracked, i.e., responses that are received for them later on
+    /// are rejected (see [CoapSessionCommon::remove_handle()]).
+    ///
+    /// The error is only returned once, subsequent calls return `None`.
+    fn take_request_error(&self, handle: &CoapRequestHandle) -> Option<RequestError> {
+        self.inner_mut()
+            .failed_requests
+            .get_mut(&handle.token)
+            .and_then(Option::take)
+    }
+
     /// Returns whether this session waits for the provided token.
     fn is_waiting_for_token(&self, token: &CoapToken) -> bool {
         self.inner_ref().received_responses.contains_key(token)
@@ -371,7 +666,12 @@ pub trait CoapSessionCommon<'a>: CoapSessionCommonInternal<'a> {
     /// Any future responses to the request associated with this handle will be responded to with an
     /// RST message.
     fn remove_handle(&self, handle: CoapRequestHandle) {
-        self.inner_mut().received_responses.remove(&handle.token);
+        let mut inner = self.inner_mut();
+        inner.received_responses.remove(&handle.token);
+        inner.request_tokens.retain(|_, v| *v != handle.token);
+        inner.failed_requests.remove(&handle.token);
+        #[cfg(feature = "metrics")]
+        inner.request_sent_at.remove(&handle.token);
     }
 
     /// Returns a mutable reference to the underlying raw session.
@@ -423,6 +723,14 @@ impl<'a> CoapSessionInnerProvider<'a> for CoapSession<'a> {
             CoapSession::Server(sess) => sess.inner_mut(),
         }
     }
+
+    fn inner_poisoned(&self) -> bool {
+        match self {
+            CoapSession::Client(sess) => sess.inner_poisoned(),
+
+            CoapSession::Server(sess) => sess.inner_poisoned(),
+        }
+    }
 }
 
 impl<'a> CoapSession<'a> {
@@ -432,7 +740,7 @@ impl<'a> CoapSession<'a> {
     /// the raw pointer, i.e., the session will be created with an arbitrary lifetime.
     /// Therefore, callers of this function should ensure that the created session instance does not
     /// outlive the context it is bound to.
-    /// Failing to do so will result in a panic/abort in the context destructor as it is unable to
+    /// Failing to do so will result in the context being leaked by its destructor as it is unable to
     /// claim exclusive ownership of the session.
     ///
     /// # Panics
@@ -458,6 +766,15 @@ impl<'a> CoapSession<'a> {
             _ => unreachable!("unknown session type"),
         }
     }
+
+    /// Stores the DER-encoded certificate presented by the peer during the (D)TLS handshake along
+    /// with its Common Name.
+    #[cfg(feature = "dtls-pki")]
+    pub(crate) fn set_peer_certificate(&self, certificate: &[u8], common_name: String) {
+        let mut inner = self.inner_mut();
+        inner.peer_certificate = Some(certificate.to_vec());
+        inner.peer_common_name = Some(common_name);
+    }
 }
 
 impl<'a> From<CoapClientSession<'a>> for CoapSession<'a> {
@@ -488,15 +805,82 @@ impl Eq for CoapSession<'_> {}
 ///
 /// For internal use only, this is only public because of some limitations in Rusts type system
 /// (as we would leak a private type).
-#[derive(Debug)]
 #[doc(hidden)]
 pub struct CoapSessionInner<'a> {
     raw_session: *mut coap_session_t,
     app_data: Option<Rc<dyn Any>>,
+    /// Responses received for pending requests, keyed by the token of the request.
+    /// Block-wise transfers are reassembled by libcoap (separately for each token) before being
+    /// stored here, so concurrent transfers on the same session do not interfere.
     received_responses: HashMap<CoapToken, VecDeque<CoapResponse>>,
+    /// Tokens of the requests sent using [CoapSessionCommon::send_request()], keyed by their
+    /// message ID.
+    request_tokens: HashMap<CoapMessageId, CoapToken>,
+    exchange_cache: VecDeque<CachedExchange>,
+    /// Message IDs of pings sent on unreliable transports that have not been answered yet.
+    pending_pings: HashSet<CoapMessageId>,
+    pong_handler: Option<Box<dyn Fn(CoapMessageId) + Send>>,
+    /// Handler that is called if a confirmable message sent using this session is answered with
+    /// a Reset message (see [CoapClientSession::set_reset_handler()]).
+    reset_handler: Option<Box<dyn Fn(CoapMessageId) + Send>>,
+    /// Handler that is called if libcoap reports that a message could not be delivered (see
+    /// [CoapSessionCommon::set_nack_handler()]).
+    nack_handler: Option<Box<dyn Fn(CoapMessageId, CoapNackReason) + Send>>,
+    /// Whether the probing rate is enforced for non-confirmable messages (see
+    /// [CoapSessionCommon::set_probing_rate()]).
+    enforce_probing_rate: bool,
+    /// Earliest point in time at which the next non-confirmable message may be sent if the probing
+    /// rate is enforced.
+    next_non_confirmable: Option<Instant>,
+    /// Path MTU discovered for this session (see [CoapSessionCommon::on_pmtu_discovery()]).
+    discovered_pmtu: Option<u16>,
+    /// The last response sent using this session (see [CoapServerSession::take_sent_response()]).
+    last_sent_response: Option<SentResponse>,
+    /// Errors of the requests sent using [CoapSessionCommon::send_request()] that failed before a
+    /// response was received, keyed by their token (see
+    /// [CoapSessionCommon::take_request_error()]).
+    /// The error is replaced with `None` once it was taken, the request remains failed until its
+    /// handle is removed.
+    failed_requests: HashMap<CoapToken, Option<RequestError>>,
+    /// DER-encoded certificate presented by the peer during the DTLS handshake (see
+    /// [CoapSessionCommon::peer_certificate_der()]).
+    #[cfg(feature = "dtls-pki")]
+    peer_certificate: Option<Vec<u8>>,
+    /// Common Name of the certificate presented by the peer (see
+    /// [CoapServerSession::dtls_peer_cn()]).
+    #[cfg(feature = "dtls-pki")]
+    peer_common_name: Option<String>,
+    /// Points in time at which the requests sent using [CoapSessionCommon::send_request()] that
+    /// have not been responded to yet were sent, keyed by their token.
+    #[cfg(feature = "metrics")]
+    request_sent_at: HashMap<CoapToken, Instant>,
+    /// Accounts for this session in the active sessions gauge.
+    #[cfg(feature = "metrics")]
+    _active_session: metrics::ActiveSession,
+    closed: bool,
     _context_lifetime_marker: PhantomData<&'a coap_context_t>,
 }
 
+impl Debug for CoapSessionInner<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // The handler functions cannot be printed, so they are omitted.
+        f.debug_struct("CoapSessionInner")
+            .field("raw_session", &self.raw_session)
+            .field("app_data", &self.app_data)
+            .field("received_responses", &self.received_responses)
+            .field("request_tokens", &self.request_tokens)
+            .field("exchange_cache", &self.exchange_cache)
+            .field("pending_pings", &self.pending_pings)
+            .field("enforce_probing_rate", &self.enforce_probing_rate)
+            .field("next_non_confirmable", &self.next_non_confirmable)
+            .field("discovered_pmtu", &self.discovered_pmtu)
+            .field("last_sent_response", &self.last_sent_response)
+            .field("failed_requests", &self.failed_requests)
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
 impl CoapSessionInner<'_> {
     /// Initializes a new session from its raw counterpart.
     ///
@@ -517,9 +901,112 @@ impl CoapSessionInner<'_> {
             raw_session,
             app_data: None,
             received_responses: HashMap::new(),
+            request_tokens: HashMap::new(),
+            exchange_cache: VecDeque::new(),
+            pending_pings: HashSet::new(),
+            pong_handler: None,
+            reset_handler: None,
+            nack_handler: None,
+            enforce_probing_rate: false,
+            next_non_confirmable: None,
+            discovered_pmtu: None,
+            last_sent_response: None,
+            failed_requests: HashMap::new(),
+            #[cfg(feature = "dtls-pki")]
+            peer_certificate: None,
+            #[cfg(feature = "dtls-pki")]
+            peer_common_name: None,
+            #[cfg(feature = "metrics")]
+            request_sent_at: HashMap::new(),
+            #[cfg(feature = "metrics")]
+            _active_session: metrics::ActiveSession::new(coap_session_get_proto(raw_session).into()),
+            closed: false,
             _context_lifetime_marker: Default::default(),
         }
     }
+
+    /// Generates a new token for a request sent using this session.
+    fn generate_request_token(&mut self) -> Vec<u8> {
+        let mut token_len = libcoap_sys::COAP_TOKEN_DEFAULT_MAX as usize;
+        let mut token_tmp: Vec<u8> = vec![0; token_len];
+        // SAFETY: Provided session pointer being valid is an invariant of CoapSessionInner,
+        // provided buffer is valid and its length matches.
+        unsafe {
+            coap_session_new_token(self.raw_session, &mut token_len, token_tmp.as_mut_ptr());
+        }
+        token_tmp.truncate(token_len);
+        token_tmp
+    }
+
+    /// Records that the request with the given message ID sent using
+    /// [CoapSessionCommon::send_request()] failed with `error` and stops tracking it (see
+    /// [CoapSessionCommon::take_request_error()]).
+    fn fail_request(&mut self, mid: CoapMessageId, error: RequestError) {
+        if let Some(token) = self.request_tokens.remove(&mid) {
+            self.fail_request_with_token(token, error);
+        }
+    }
+
+    /// Records that the request with the given token failed with `error` and stops tracking it
+    /// (see [CoapSessionInner::fail_request()]).
+    fn fail_request_with_token(&mut self, token: CoapToken, error: RequestError) {
+        self.request_tokens.retain(|_, v| *v != token);
+        self.received_responses.remove(&token);
+        self.failed_requests.insert(token, Some(error));
+    }
+
+    /// Accounts for the given non-confirmable message in the probing rate of this session, if it
+    /// is enforced (see [CoapSessionCommon::set_probing_rate()]).
+    ///
+    /// # Errors
+    /// Returns [SessionError::ProbingRateExceeded] if the message may not be sent yet.
+    fn throttle_non_confirmable(&mut self, message: &CoapMessage, probing_rate: u32) -> Result<(), SessionError> {
+        if !self.enforce_probing_rate {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if let Some(delay) = self.next_non_confirmable.and_then(|v| v.checked_duration_since(now)) {
+            if !delay.is_zero() {
+                return Err(SessionError::ProbingRateExceeded(delay));
+            }
+        }
+        let bytes = message.encoded_len() as f64;
+        self.next_non_confirmable = Some(now + Duration::from_secs_f64(bytes / f64::from(probing_rate.max(1))));
+        Ok(())
+    }
+
+    /// Stores the given response message in the deduplication cache entry of the request it
+    /// responds to (if the request is cached and has not been responded to yet).
+    fn cache_response(&mut self, message: &CoapMessage) {
+        if let Some(exchange) = self
+            .exchange_cache
+            .iter_mut()
+            .rev()
+            .find(|v| v.response.is_none() && Some(&v.token) == message.token())
+        {
+            exchange.response = Some(message.clone());
+        }
+    }
+}
+
+/// Information about a response sent using a session, which is used to update the observers of a
+/// resource once the request handler that sent it has returned.
+#[derive(Debug, Clone)]
+pub(crate) struct SentResponse {
+    pub(crate) token: CoapToken,
+    pub(crate) code: CoapResponseCode,
+    pub(crate) type_: CoapMessageType,
+    /// Whether the response contained an Observe option.
+    pub(crate) observe: bool,
+}
+
+/// A recently received request that is remembered for message deduplication.
+#[derive(Debug)]
+struct CachedExchange {
+    mid: CoapMessageId,
+    token: CoapToken,
+    received_at: Instant,
+    response: Option<CoapMessage>,
 }
 
 /// A handle returned by CoAP sessions upon sending a request.
@@ -533,12 +1020,180 @@ pub struct CoapRequestHandle {
 }
 
 impl CoapRequestHandle {
-    fn new<T: Into<Box<[u8]>>>(mid: CoapMessageId, token: T) -> CoapRequestHandle {
-        CoapRequestHandle {
-            _mid: mid,
-            token: token.into(),
+    fn new(mid: CoapMessageId, token: CoapToken) -> CoapRequestHandle {
+        CoapRequestHandle { _mid: mid, token }
+    }
+
+    /// Returns the token of the request this handle refers to.
+    pub fn token(&self) -> &CoapToken {
+        &self.token
+    }
+}
+
+/// Calls the pong handler of the given session (if one is set).
+fn handle_pong(session: &CoapSession, mid: CoapMessageId) {
+    let mut inner = session.inner_mut();
+    inner.pending_pings.remove(&mid);
+    if let Some(handler) = inner.pong_handler.as_ref() {
+        handler(mid)
+    }
+}
+
+pub(crate) unsafe extern "C" fn session_pong_handler(
+    session: *mut coap_session_t,
+    _received: *const coap_pdu_t,
+    id: coap_mid_t,
+) {
+    handle_pong(&CoapSession::from_raw(session), id)
+}
+
+pub(crate) unsafe extern "C" fn session_nack_handler(
+    session: *mut coap_session_t,
+    sent: *const coap_pdu_t,
+    reason: coap_nack_reason_t,
+    id: coap_mid_t,
+) {
+    // Sessions that were never handed to the application (e.g., DTLS HELLO sessions whose
+    // handshake failed) have no app data we could report the failure to.
+    if coap_session_get_app_data(session).is_null() {
+        return;
+    }
+    let raw_session = session;
+    let mut session = CoapSession::from_raw(session);
+    // libcoap ends observations whose notifications are rejected or never acknowledged, so the
+    // observer has to be removed from the resource's list of observers as well.
+    if let (CoapSession::Server(server_session), false) = (&session, sent.is_null()) {
+        if matches!(
+            reason,
+            coap_nack_reason_t::COAP_NACK_RST | coap_nack_reason_t::COAP_NACK_TOO_MANY_RETRIES
+        ) {
+            let raw_token = coap_pdu_get_token(sent);
+            let token = std::slice::from_raw_parts(raw_token.s, raw_token.length);
+            CoapContext::from_raw(coap_session_get_context(raw_session)).remove_failed_observer(server_session, token);
+        }
+    }
+    // No response will be received for the message, so its request duration will never be
+    // recorded.
+    #[cfg(feature = "metrics")]
+    {
+        let mut inner = session.inner_mut();
+        if let Some(token) = inner.request_tokens.get(&id).cloned() {
+            inner.request_sent_at.remove(&token);
         }
     }
+    match reason {
+        // A Reset message in response to one of our pings is the expected answer (RFC 7252,
+        // Section 4.3), but libcoap only reports it as a pong if keepalive is enabled.
+        coap_nack_reason_t::COAP_NACK_RST => {
+            let is_ping = session.inner_ref().pending_pings.contains(&id);
+            if is_ping {
+                handle_pong(&session, id);
+                return;
+            }
+            handle_reset(&session, id)
+        },
+        coap_nack_reason_t::COAP_NACK_TOO_MANY_RETRIES => {
+            session.inner_mut().fail_request(id, RequestError::RetransmitExhausted);
+        },
+        // libcoap keeps retransmitting confirmable messages after ICMP errors (as they may be
+        // transient), but requests sent by this library should fail fast, unless the error
+        // indicated that the message was too large for the path to the peer, in which case the
+        // MTU of the session is lowered instead.
+        // As libcoap does not provide a public API for removing individual messages from its
+        // retransmission queue, the request is cancelled by no longer tracking it, so responses
+        // to retransmissions are rejected.
+        coap_nack_reason_t::COAP_NACK_ICMP_ISSUE if !update_path_mtu(&mut session) => {
+            session.inner_mut().fail_request(id, RequestError::PeerUnreachable);
+        },
+        _ => {},
+    }
+    handle_nack(&session, id, reason.into());
+}
+
+/// Checks whether the operating system lowered the path MTU to the peer of the given session below
+/// the MTU of the session (e.g., after receiving an ICMP "Fragmentation Needed" or "Packet Too
+/// Big" message) and calls [CoapSessionCommon::on_pmtu_discovery()] if it did.
+///
+/// Returns whether the MTU of the session was lowered.
+fn update_path_mtu(session: &mut CoapSession) -> bool {
+    let peer = session.addr_remote();
+    let Some(path_mtu) = route_path_mtu(peer) else {
+        return false;
+    };
+    let header_size = if peer.is_ipv4() {
+        IPV4_UDP_HEADER_SIZE
+    } else {
+        IPV6_UDP_HEADER_SIZE
+    };
+    if usize::from(path_mtu.saturating_sub(header_size)) >= session.max_pdu_size() {
+        return false;
+    }
+    session.on_pmtu_discovery(path_mtu);
+    true
+}
+
+/// Returns the path MTU that the operating system currently assumes for the path to `peer`.
+///
+/// Linux caches path MTUs learned from ICMP messages per destination and reports them for any
+/// UDP socket connected to it.
+#[cfg(target_os = "linux")]
+fn route_path_mtu(peer: SocketAddr) -> Option<u16> {
+    use std::os::fd::AsRawFd;
+    let local_address = match peer {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(local_address).ok()?;
+    socket.connect(peer).ok()?;
+    let (level, name) = match peer {
+        SocketAddr::V4(_) => (libc::IPPROTO_IP, libc::IP_MTU),
+        SocketAddr::V6(_) => (libc::IPPROTO_IPV6, libc::IPV6_MTU),
+    };
+    let mut path_mtu: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: The socket is valid as long as it is not dropped, path_mtu and len are valid for
+    // writes and len contains the size of path_mtu.
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            (&mut path_mtu as *mut libc::c_int).cast(),
+            &mut len,
+        )
+    };
+    (result == 0).then(|| u16::try_from(path_mtu).unwrap_or(u16::MAX))
+}
+
+/// Returns the path MTU that the operating system currently assumes for the path to `peer`.
+///
+/// Querying path MTUs is currently only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+fn route_path_mtu(_peer: SocketAddr) -> Option<u16> {
+    None
+}
+
+/// Calls the NACK handler of the given session (if one is set).
+fn handle_nack(session: &CoapSession, mid: CoapMessageId, reason: CoapNackReason) {
+    // Take the handler out of the session while calling it, so that it may use the session itself.
+    let Some(handler) = session.inner_mut().nack_handler.take() else {
+        return;
+    };
+    handler(mid, reason);
+    // Put the handler back into the session, unless it was replaced in the meantime.
+    session.inner_mut().nack_handler.get_or_insert(handler);
+}
+
+/// Calls the reset handler of the given session (if one is set).
+fn handle_reset(session: &CoapSession, mid: CoapMessageId) {
+    // Take the handler out of the session while calling it, so that it may use the session itself
+    // (e.g., to cancel the rejected request).
+    let Some(handler) = session.inner_mut().reset_handler.take() else {
+        return;
+    };
+    handler(mid);
+    // Put the handler back into the session, unless it was replaced in the meantime.
+    session.inner_mut().reset_handler.get_or_insert(handler);
 }
 
 // This is fine, we don't read the C-type struct, we return it.
@@ -549,15 +1204,31 @@ pub(crate) unsafe extern "C" fn session_response_handler(
     received: *const coap_pdu_t,
     _id: coap_mid_t,
 ) -> coap_response_t {
+    let strict_parsing = CoapContext::from_raw(coap_session_get_context(session)).strict_parsing();
     let mut session = CoapSession::from_raw(session);
     let client = session.borrow_mut();
+    #[cfg(feature = "metrics")]
+    metrics::record_message_received(client.proto());
     // First check if the token is actually one we are currently waiting for.
     let raw_token = coap_pdu_get_token(received);
-    let token: CoapToken = CoapToken::from(std::slice::from_raw_parts(raw_token.s, raw_token.length));
+    let token = CoapToken::from_bytes_unchecked(std::slice::from_raw_parts(raw_token.s, raw_token.length));
     if !client.is_waiting_for_token(&token) {
         return coap_response_t::COAP_RESPONSE_FAIL;
     }
-    if let Ok(message) = CoapMessage::from_raw_pdu(received).and_then(CoapResponse::from_message) {
+    let message = CoapMessage::from_raw_pdu(received)
+        .and_then(|v| v.validate_type_code().map(|_| v))
+        .and_then(|v| CoapResponse::from_message(v.with_strict_parsing(strict_parsing)));
+    if let Ok(message) = message {
+        #[cfg(feature = "metrics")]
+        {
+            let sent_at = client.inner_mut().request_sent_at.remove(&token);
+            if let Some(sent_at) = sent_at {
+                metrics::record_request_duration(client.proto(), sent_at.elapsed());
+            }
+        }
+        // The request has been answered, so it can no longer be cancelled by its message ID or be
+        // reported as undeliverable.
+        client.inner_mut().request_tokens.retain(|_, v| *v != token);
         client.add_response(message);
         coap_response_t::COAP_RESPONSE_OK
     } else {
@@ -7,15 +7,28 @@
  * See the README as well as the LICENSE file for more information.
  */
 
-use std::cell::{Ref, RefMut};
+use std::{
+    cell::{Ref, RefMut},
+    net::SocketAddr,
+    time::Instant,
+};
 
 use libcoap_sys::{
-    coap_session_get_app_data, coap_session_get_type, coap_session_reference, coap_session_release,
-    coap_session_set_app_data, coap_session_t, coap_session_type_t,
+    coap_nack_reason_t, coap_session_disconnected, coap_session_get_app_data, coap_session_get_type,
+    coap_session_reference, coap_session_release, coap_session_set_app_data, coap_session_t, coap_session_type_t,
 };
 
-use super::{CoapSessionCommon, CoapSessionInner, CoapSessionInnerProvider};
-use crate::mem::{CoapFfiRcCell, DropInnerExclusively};
+use super::{
+    CachedExchange, CoapSessionCommon, CoapSessionInner, CoapSessionInnerProvider, CoapSessionState, SentResponse,
+    EXCHANGE_LIFETIME,
+};
+use crate::{
+    error::SessionError,
+    mem::{CoapFfiRcCell, CoapFfiWeakCell, DropInnerExclusively},
+    message::{CoapMessage, CoapMessageCommon, CoapRequest, CoapResponse},
+    protocol::{CoapMessageType, CoapResponseCode},
+    types::CoapMessageId,
+};
 
 impl DropInnerExclusively for CoapServerSession<'_> {
     fn drop_exclusively(self) {
@@ -35,10 +48,49 @@ pub struct CoapServerSession<'a> {
     ref_counted: bool,
 }
 
+/// Weak reference to a server-side CoAP session, which does not prevent the session from being
+/// freed once libcoap no longer uses it.
+#[derive(Debug, Clone)]
+pub(crate) struct CoapServerSessionWeak<'a> {
+    inner: CoapFfiWeakCell<CoapServerSessionInner<'a>>,
+}
+
+impl<'a> CoapServerSessionWeak<'a> {
+    /// Attempts to restore the session this weak reference refers to, returning `None` if it has
+    /// already been freed.
+    pub(crate) fn upgrade(&self) -> Option<CoapServerSession<'a>> {
+        let inner = self.inner.upgrade()?;
+        // SAFETY: The raw session is valid for as long as its inner session exists, as the inner
+        // session is dropped before libcoap frees the raw session (see the Drop implementation of
+        // CoapServerSessionInner). Incrementing its reference count keeps it valid for the
+        // lifetime of the returned session.
+        unsafe { coap_session_reference(inner.borrow().inner.raw_session) };
+        Some(CoapServerSession {
+            inner,
+            ref_counted: true,
+        })
+    }
+
+    /// Returns whether this weak reference refers to the given session.
+    pub(crate) fn refers_to(&self, session: &CoapServerSession<'_>) -> bool {
+        self.inner.as_ptr() == session.inner.as_ptr()
+    }
+}
+
 #[derive(Debug)]
 /// Inner part of a server-side CoAP session.
 struct CoapServerSessionInner<'a> {
     inner: CoapSessionInner<'a>,
+    /// Whether this session was refused because the context's session limit was reached.
+    refused: bool,
+    /// Whether this session was created as a DTLS HELLO session and has not been announced to the
+    /// application as a new session yet.
+    unannounced: bool,
+    /// Remote address of this session the last time it was checked for changes.
+    known_addr_remote: Option<SocketAddr>,
+    /// Whether the remote address of this session has changed since it was created (or since the
+    /// last call to [CoapServerSession::clear_address_migrated()]).
+    address_migrated: bool,
 }
 
 impl CoapServerSession<'_> {
@@ -53,8 +105,8 @@ impl CoapServerSession<'_> {
     ///
     /// # Safety
     /// The provided pointer must be valid for the entire (here arbitrarily chosen) lifetime of the
-    /// CoapServerSession<'a>, most notably the program will abort if the [CoapContext] is dropped
-    /// before this session is.
+    /// CoapServerSession<'a>, most notably dropping the [CoapContext] before this session will
+    /// leak the context (see [CoapContext::outstanding_sessions()](crate::CoapContext::outstanding_sessions())).
     /// The existing value in the `app_data` field of the raw session will be overridden.
     /// Make sure that this is actually okay to do so — most importantly, no other [CoapSession] may
     /// already be stored there.
@@ -70,8 +122,22 @@ impl CoapServerSession<'_> {
             coap_session_type_t::COAP_SESSION_TYPE_CLIENT => {
                 panic!("attempted to create server session from raw client session")
             },
-            coap_session_type_t::COAP_SESSION_TYPE_SERVER => CoapServerSessionInner { inner },
-            coap_session_type_t::COAP_SESSION_TYPE_HELLO => CoapServerSessionInner { inner },
+            coap_session_type_t::COAP_SESSION_TYPE_SERVER => CoapServerSessionInner {
+                inner,
+                refused: false,
+                unannounced: false,
+                known_addr_remote: None,
+                address_migrated: false,
+            },
+            // HELLO sessions are only used for the DTLS cookie exchange and might never become
+            // full sessions, so they are only announced to the application once they do.
+            coap_session_type_t::COAP_SESSION_TYPE_HELLO => CoapServerSessionInner {
+                inner,
+                refused: false,
+                unannounced: true,
+                known_addr_remote: None,
+                address_migrated: false,
+            },
             _ => unreachable!("unknown session type"),
         };
         let session_ref = CoapFfiRcCell::new(session_inner);
@@ -79,17 +145,21 @@ impl CoapServerSession<'_> {
         // Increase libcoap-internal reference counter for raw session so that it doesn't get freed
         // as long as this CoapServerSession instance exists.
         coap_session_reference(raw_session);
-        CoapServerSession {
+        let session = CoapServerSession {
             inner: session_ref,
             ref_counted: true,
-        }
+        };
+        let addr_remote = session.addr_remote();
+        session.inner.borrow_mut().known_addr_remote = Some(addr_remote);
+        session
     }
 
     /// Restores a [CoapServerSession] from its raw counterpart.
     ///
     /// Make sure that this struct cannot outlive the [CoapContext] its session originates from, as
-    /// the lifetime cannot be inferred by the compiler and dropping the context will panic/abort if
-    /// the inner session is still referenced anywhere else.
+    /// the lifetime cannot be inferred by the compiler and dropping the context will leak it if the
+    /// inner session is still referenced anywhere else (see
+    /// [CoapContext::outstanding_sessions()](crate::CoapContext::outstanding_sessions())).
     ///
     /// This function will increment the libcoap-internal reference counter for the session by one.
     /// Dropping the CoapServerSession will then decrement it again.
@@ -113,8 +183,9 @@ impl CoapServerSession<'_> {
     /// counter (useful if acquiring libcoap's global lock is undesired).
     ///
     /// Make sure that this struct cannot outlive the [CoapContext] its session originates from, as
-    /// the lifetime cannot be inferred by the compiler and dropping the context will panic/abort if
-    /// the inner session is still referenced anywhere else.
+    /// the lifetime cannot be inferred by the compiler and dropping the context will leak it if the
+    /// inner session is still referenced anywhere else (see
+    /// [CoapContext::outstanding_sessions()](crate::CoapContext::outstanding_sessions())).
     ///
     /// In addition to the above, you must also ensure that the session will not be cleaned up by
     /// libcoap in the meantime, as the reference counter is not increased while constructing the
@@ -157,6 +228,262 @@ impl CoapServerSession<'_> {
     }
 }
 
+impl<'a> CoapServerSession<'a> {
+    /// Creates a weak reference to this session.
+    pub(crate) fn downgrade(&self) -> CoapServerSessionWeak<'a> {
+        CoapServerSessionWeak {
+            inner: self.inner.downgrade(),
+        }
+    }
+}
+
+impl CoapServerSession<'_> {
+    /// Sends the given response to this session outside of a request handler, e.g., to push a
+    /// notification to one specific observer.
+    ///
+    /// As there is no request that could be acknowledged, acknowledgement responses are sent as
+    /// confirmable messages instead so that their delivery is still reliable.
+    /// Non-confirmable responses are sent as-is.
+    /// The token of the response has to be set to the token of the request it belongs to (e.g.,
+    /// the observe registration), otherwise the client will reject it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [SessionError] if the response could not be sent (see
+    /// [CoapSessionCommon::send()]).
+    pub fn send_response(&self, mut response: CoapResponse) -> Result<CoapMessageId, SessionError> {
+        if response.type_() == CoapMessageType::Ack {
+            response.set_type_(CoapMessageType::Con);
+            response.set_mid(None);
+        }
+        self.send(response)
+    }
+
+    /// Closes this session, e.g. in order to disconnect a misbehaving client.
+    ///
+    /// For encrypted sessions, this will also cause the (D)TLS library to notify the peer about the
+    /// closed connection (i.e., send a `close_notify` alert).
+    /// Pending messages to the peer are dropped, and any subsequent attempts to send messages using
+    /// this session will fail with [SessionError::Closed](crate::error::SessionError::Closed).
+    ///
+    /// Calling this function on an already closed session has no effect.
+    ///
+    /// Note that the session object itself is only freed by libcoap once it is no longer used
+    /// (i.e., after it has been idle for the configured session timeout).
+    pub fn close(&mut self) {
+        let raw_session = {
+            let mut inner = self.inner_mut();
+            if inner.closed {
+                return;
+            }
+            inner.closed = true;
+            inner.received_responses.clear();
+            inner.request_tokens.clear();
+            inner.raw_session
+        };
+        // SAFETY: raw_session is always valid for the lifetime of this object. We must not hold a
+        // reference to the inner session here, as libcoap may call our event handler, which in
+        // turn may access this session.
+        unsafe { coap_session_disconnected(raw_session, coap_nack_reason_t::COAP_NACK_NOT_DELIVERABLE) }
+    }
+
+    /// Marks this session as refused because the context's session limit has been reached (see
+    /// [CoapContext::set_max_sessions()](crate::CoapContext::set_max_sessions())).
+    pub(crate) fn refuse(&self) {
+        self.inner.borrow_mut().refused = true;
+    }
+
+    /// Returns whether this session has been refused because the context's session limit had been
+    /// reached when it was created.
+    ///
+    /// Requests received using a refused session are answered with `5.03 Service Unavailable`
+    /// instead of being passed to the resource handlers.
+    pub fn is_refused(&self) -> bool {
+        self.inner.borrow().refused
+    }
+
+    /// Returns whether this session is a DTLS HELLO session.
+    ///
+    /// HELLO sessions are created by libcoap for incoming DTLS connection attempts, and are only
+    /// used for the stateless cookie exchange that precedes the actual DTLS handshake (see
+    /// [RFC 6347, Section 4.2.1](https://datatracker.ietf.org/doc/html/rfc6347#section-4.2.1)).
+    /// As the peer might never complete the cookie exchange, a HELLO session should not be treated
+    /// as an established session.
+    /// Once the cookie exchange was successful, the session is turned into a regular server-side
+    /// session, at which point this function returns `false`.
+    ///
+    /// Events for HELLO sessions are not passed to the context's event handler, the
+    /// [CoapEvent::ServerSessionNew](crate::CoapEvent::ServerSessionNew) event for such a session
+    /// is instead delivered once it is no longer a HELLO session.
+    /// Similarly, requests are never passed to resource handlers for HELLO sessions.
+    pub fn is_hello(&self) -> bool {
+        // SAFETY: The raw session is always valid for the lifetime of this object.
+        let raw_session_type = unsafe { coap_session_get_type(self.inner_ref().raw_session) };
+        raw_session_type == coap_session_type_t::COAP_SESSION_TYPE_HELLO
+    }
+
+    /// Returns whether this session has not been announced to the application as a new session yet
+    /// because it was created as a DTLS HELLO session.
+    pub(crate) fn is_unannounced(&self) -> bool {
+        self.inner.borrow().unannounced
+    }
+
+    /// Marks this session as announced to the application.
+    pub(crate) fn mark_announced(&self) {
+        self.inner.borrow_mut().unannounced = false;
+    }
+
+    /// Returns whether the remote address of this session has changed since the session was
+    /// created (or since the last call to [CoapServerSession::clear_address_migrated()]).
+    ///
+    /// With DTLS Connection IDs ([RFC 9146](https://datatracker.ietf.org/doc/html/rfc9146)), a
+    /// peer may continue using an established session after its address has changed (e.g., due to
+    /// NAT rebinding). Applications that store state based on
+    /// [addr_remote()](CoapSessionCommon::addr_remote()) can use this function to detect that
+    /// this state has to be updated.
+    ///
+    /// The remote address is checked for changes whenever a request is received using this
+    /// session (i.e., this function may be called in resource handlers) and after each call to
+    /// [CoapContext::do_io()](crate::CoapContext::do_io()), which also covers other messages
+    /// such as ACKs for notifications. Once a change is detected, the event handler of the
+    /// context is notified using [CoapEvent::ServerSessionAddressChanged](crate::CoapEvent::ServerSessionAddressChanged).
+    /// Note that versions of libcoap without support for Connection IDs identify sessions by
+    /// their remote address, so a peer that changes its address is assigned a new session instead.
+    pub fn address_migrated(&self) -> bool {
+        self.inner.borrow().address_migrated
+    }
+
+    /// Resets the flag returned by [CoapServerSession::address_migrated()], e.g., after the
+    /// application has updated its state to refer to the new remote address.
+    pub fn clear_address_migrated(&mut self) {
+        self.inner.borrow_mut().address_migrated = false;
+    }
+
+    /// Checks whether the remote address of this session has changed since the last check and
+    /// marks the session as migrated if it has (see [CoapServerSession::address_migrated()]).
+    ///
+    /// Returns whether the address has changed.
+    pub(crate) fn detect_address_migration(&self) -> bool {
+        let addr_remote = self.addr_remote();
+        let mut inner = self.inner.borrow_mut();
+        let changed = inner.known_addr_remote.is_some_and(|known| known != addr_remote);
+        inner.known_addr_remote = Some(addr_remote);
+        inner.address_migrated |= changed;
+        changed
+    }
+
+    /// Returns whether this session has been closed, either explicitly (using
+    /// [close()](CoapServerSession::close)) or because libcoap considers the session to no longer
+    /// be connected.
+    pub fn is_closed(&self) -> bool {
+        self.inner_ref().closed || matches!(self.state(), CoapSessionState::None)
+    }
+
+    /// Returns the PSK identity the client authenticated with during the DTLS handshake, e.g., for
+    /// authorization checks in resource handlers.
+    ///
+    /// Returns `None` if the session is not encrypted or was not established using a pre-shared
+    /// key.
+    #[cfg(feature = "dtls-psk")]
+    pub fn dtls_peer_identity(&self) -> Option<Box<[u8]>> {
+        if !self.proto().is_secure() {
+            return None;
+        }
+        self.psk_identity()
+    }
+
+    /// Returns the Common Name of the certificate the client presented during the DTLS handshake,
+    /// e.g., for authorization checks in resource handlers.
+    ///
+    /// Returns `None` if the session does not use PKI based encryption or if the client did not
+    /// present a certificate.
+    /// Note that clients are only asked for a certificate if peer certificate verification is
+    /// enabled for the server (see
+    /// [PkiRpkContextBuilder::verify_peer_cert()](crate::crypto::pki_rpk::PkiRpkContextBuilder::verify_peer_cert())).
+    #[cfg(feature = "dtls-pki")]
+    pub fn dtls_peer_cn(&self) -> Option<String> {
+        self.inner_ref().peer_common_name.clone()
+    }
+}
+
+/// Result of checking an incoming request for duplicates, see
+/// [CoapServerSession::deduplicate_request()].
+#[derive(Debug)]
+pub(crate) enum RequestDeduplication {
+    /// The request has not been received before.
+    New,
+    /// The request is a duplicate of a recently received one, contains the response that was sent
+    /// for the original request (if any).
+    Duplicate(Option<CoapMessage>),
+}
+
+impl CoapServerSession<'_> {
+    /// Checks whether the given request is a duplicate of a request that was received within
+    /// `EXCHANGE_LIFETIME` (i.e., has the same message ID and token).
+    ///
+    /// If it is not, the request is remembered for future checks, keeping at most `cache_size`
+    /// requests.
+    /// Responses sent using this session are then associated with the remembered request, so that
+    /// they can be retransmitted if a duplicate arrives.
+    pub(crate) fn deduplicate_request(&self, request: &CoapRequest, cache_size: usize) -> RequestDeduplication {
+        let Some(mid) = request.mid() else {
+            return RequestDeduplication::New;
+        };
+        let token = request.token().cloned().unwrap_or_default();
+        let mut inner = self.inner_mut();
+        let cache = &mut inner.exchange_cache;
+        while cache
+            .front()
+            .is_some_and(|v| v.received_at.elapsed() > EXCHANGE_LIFETIME)
+        {
+            cache.pop_front();
+        }
+        if let Some(exchange) = cache.iter().find(|v| v.mid == mid && v.token == token) {
+            return RequestDeduplication::Duplicate(exchange.response.clone());
+        }
+        if cache_size == 0 {
+            return RequestDeduplication::New;
+        }
+        while cache.len() >= cache_size {
+            cache.pop_front();
+        }
+        cache.push_back(CachedExchange {
+            mid,
+            token,
+            received_at: Instant::now(),
+            response: None,
+        });
+        RequestDeduplication::New
+    }
+
+    /// Sets the code of the given response and sends it, ignoring errors.
+    ///
+    /// Used to answer requests from request handlers implemented by this library.
+    pub(crate) fn respond(&self, mut response: CoapResponse, code: CoapResponseCode) {
+        response.set_code(code);
+        // Sending may only fail if the session has already been closed, in which case there is
+        // nothing left to do.
+        let _ = self.send(response);
+    }
+
+    /// Returns the last response sent using this session since the previous call to this function
+    /// (if any).
+    pub(crate) fn take_sent_response(&self) -> Option<SentResponse> {
+        self.inner_mut().last_sent_response.take()
+    }
+
+    /// Returns the number of [CoapServerSession] instances referring to the same inner session.
+    pub(crate) fn reference_count(&self) -> usize {
+        self.inner.strong_count()
+    }
+
+    /// Marks this session (and all other references to it) as unusable because its context is
+    /// being dropped (see [CoapSessionCommon::is_poisoned()]).
+    pub(crate) fn poison(&self) {
+        self.inner.poison()
+    }
+}
+
 impl<'a> Drop for CoapServerSession<'a> {
     fn drop(&mut self) {
         let raw_session = self.inner.borrow_mut().inner.raw_session;
@@ -173,11 +500,23 @@ impl<'a> Drop for CoapServerSession<'a> {
 
 impl<'a> CoapSessionInnerProvider<'a> for CoapServerSession<'a> {
     fn inner_ref<'b>(&'b self) -> Ref<'b, CoapSessionInner<'a>> {
-        Ref::map(self.inner.borrow(), |v| &v.inner)
+        let inner = self
+            .inner
+            .try_borrow()
+            .unwrap_or_else(|e| panic!("unable to access session: {e}"));
+        Ref::map(inner, |v| &v.inner)
     }
 
     fn inner_mut<'b>(&'b self) -> RefMut<'b, CoapSessionInner<'a>> {
-        RefMut::map(self.inner.borrow_mut(), |v| &mut v.inner)
+        let inner = self
+            .inner
+            .try_borrow_mut()
+            .unwrap_or_else(|e| panic!("unable to access session: {e}"));
+        RefMut::map(inner, |v| &mut v.inner)
+    }
+
+    fn inner_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
     }
 }
 
@@ -193,12 +532,78 @@ impl<'a, T: CoapSessionCommon<'a>> PartialEq<T> for CoapServerSession<'_> {
 
 impl Eq for CoapServerSession<'_> {}
 
+// The weak reference stored in the raw session's app data is owned by the (single)
+// CoapServerSessionInner that created it and is released here.
+// For sessions that are no longer referenced by libcoap (i.e., on COAP_EVENT_SERVER_SESSION_DEL),
+// the context calls CoapFfiRcCell::drop_exclusively() first, which ensures that this is the last
+// strong reference and the inner value is dropped while the raw session is still valid.
 impl Drop for CoapServerSessionInner<'_> {
     fn drop(&mut self) {
         unsafe {
             let app_data = coap_session_get_app_data(self.inner.raw_session);
-            assert!(!app_data.is_null());
+            // The app data pointer is cleared below, so if it is already null here, the weak
+            // reference was released before and releasing it again would be a double free.
+            assert!(
+                !app_data.is_null(),
+                "attempted to drop inner server session whose app data was already released"
+            );
             std::mem::drop(CoapFfiRcCell::<CoapServerSessionInner>::raw_ptr_to_weak(app_data));
+            coap_session_set_app_data(self.inner.raw_session, std::ptr::null_mut());
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{SocketAddr, UdpSocket};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use libcoap_sys::{coap_new_client_session, coap_proto_t};
+
+    use super::*;
+    use crate::{types::CoapAddress, CoapContext};
+
+    fn new_inner<'a>(raw_session: *mut coap_session_t) -> CoapServerSessionInner<'a> {
+        CoapServerSessionInner {
+            // SAFETY: The raw session outlives the inner session in the test below.
+            inner: unsafe { CoapSessionInner::new(raw_session) },
+            refused: false,
+            unannounced: false,
+            known_addr_remote: None,
+            address_migrated: false,
+        }
+    }
+
+    #[test]
+    fn releasing_app_data_twice_panics() {
+        let mut context = CoapContext::new().unwrap();
+        let peer: SocketAddr = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        // SAFETY: The context is valid, and the raw session is only used within this test and
+        // released before the context is dropped.
+        let raw_session = unsafe {
+            coap_new_client_session(
+                context.as_mut_raw_context(),
+                std::ptr::null(),
+                CoapAddress::from(peer).as_raw_address(),
+                coap_proto_t::COAP_PROTO_UDP,
+            )
+        };
+        assert!(!raw_session.is_null());
+
+        let session_inner = CoapFfiRcCell::new(new_inner(raw_session));
+        // SAFETY: The raw session is valid and its app data pointer is not used otherwise.
+        unsafe { coap_session_set_app_data(raw_session, session_inner.create_raw_weak()) };
+        // A second inner session for the same raw session would release the app data weak
+        // reference again when dropped.
+        let duplicate_inner = new_inner(raw_session);
+
+        std::mem::drop(session_inner);
+        assert!(unsafe { coap_session_get_app_data(raw_session) }.is_null());
+        let result = catch_unwind(AssertUnwindSafe(|| std::mem::drop(duplicate_inner)));
+        assert!(result.is_err());
+        assert!(unsafe { coap_session_get_app_data(raw_session) }.is_null());
+
+        // SAFETY: The raw session was created above and is no longer referenced.
+        unsafe { coap_session_release(raw_session) };
+    }
+}
@@ -7,19 +7,47 @@
  * See the README as well as the LICENSE file for more information.
  */
 
-use std::{net::SocketAddr, os::raw::c_uint};
+use std::{ffi::CStr, net::SocketAddr, os::raw::c_uint};
 
 use libcoap_sys::{
-    coap_endpoint_set_default_mtu, coap_endpoint_t, coap_free_endpoint, coap_new_endpoint, coap_proto_t,
+    coap_endpoint_set_default_mtu, coap_endpoint_str, coap_endpoint_t, coap_free_endpoint, coap_new_endpoint,
+    coap_proto_t,
 };
 
-use crate::{error::EndpointCreationError, types::CoapAddress, CoapContext};
+use crate::{
+    error::EndpointCreationError,
+    types::{CoapAddress, CoapProtocol},
+    CoapContext,
+};
 
 pub type EndpointMtu = c_uint;
 
+/// Information about an endpoint of a [CoapContext] (see [CoapContext::endpoints()]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoapEndpointInfo {
+    protocol: CoapProtocol,
+    local_addr: SocketAddr,
+}
+
+impl CoapEndpointInfo {
+    /// Returns the transport protocol of the endpoint.
+    pub fn protocol(&self) -> CoapProtocol {
+        self.protocol
+    }
+
+    /// Returns the local address the endpoint is bound to.
+    ///
+    /// If the endpoint was created for an address with port 0, this contains the port that was
+    /// actually assigned by the operating system.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
 #[derive(Debug)]
 pub struct CoapEndpoint {
     raw_endpoint: *mut coap_endpoint_t,
+    info: CoapEndpointInfo,
 }
 
 /// Trait for functions common between all types of endpoints.
@@ -34,6 +62,11 @@ impl CoapEndpoint {
         }
     }
 
+    /// Returns information about this endpoint.
+    pub fn info(&self) -> CoapEndpointInfo {
+        self.info
+    }
+
     /// Method utilized by transport protocol specific constructors to actually create the endpoint in libcoap
     pub(crate) fn new_endpoint(
         context: &mut CoapContext,
@@ -51,10 +84,37 @@ impl CoapEndpoint {
         };
 
         if endpoint.is_null() {
-            Err(EndpointCreationError::Unknown)
-        } else {
-            Ok(Self { raw_endpoint: endpoint })
+            return Err(EndpointCreationError::Unknown);
         }
+        // SAFETY: The endpoint was just checked to be valid.
+        let local_addr = unsafe { Self::bound_addr(endpoint) }.unwrap_or(addr);
+        Ok(Self {
+            raw_endpoint: endpoint,
+            info: CoapEndpointInfo {
+                protocol: proto.into(),
+                local_addr,
+            },
+        })
+    }
+
+    /// Determines the address the given raw endpoint is actually bound to (which differs from the
+    /// requested one if port 0 was requested).
+    ///
+    /// libcoap does not provide direct access to the bound address, so it is parsed from the
+    /// textual representation of the endpoint, which has the form `<address> <protocol>`.
+    /// Returns `None` if this representation could not be parsed.
+    ///
+    /// # Safety
+    /// `raw_endpoint` must point to a valid endpoint.
+    unsafe fn bound_addr(raw_endpoint: *const coap_endpoint_t) -> Option<SocketAddr> {
+        // coap_endpoint_str() returns a pointer to a static buffer, so its contents have to be
+        // copied immediately.
+        let description = CStr::from_ptr(coap_endpoint_str(raw_endpoint))
+            .to_str()
+            .ok()?
+            .to_owned();
+        let (address, _protocol) = description.rsplit_once(' ')?;
+        address.parse().ok()
     }
 }
 
@@ -11,6 +11,7 @@
 
 use std::ffi::CString;
 use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomPinned;
 use std::pin::Pin;
 use std::{
@@ -19,6 +20,7 @@ use std::{
     net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs},
     os::raw::c_int,
     str::FromStr,
+    sync::atomic::{AtomicU16, Ordering},
 };
 
 use libc::{c_ushort, in6_addr, in_addr, sa_family_t, sockaddr_in, sockaddr_in6, socklen_t, AF_INET, AF_INET6};
@@ -29,10 +31,9 @@ use url::Url;
 
 use libcoap_sys::coap_uri_scheme_t::{COAP_URI_SCHEME_COAPS_WS, COAP_URI_SCHEME_COAP_WS};
 use libcoap_sys::{
-    coap_address_t, coap_delete_optlist, coap_mid_t, coap_proto_t,
+    coap_address_t, coap_delete_optlist, coap_log_t, coap_mid_t, coap_proto_t,
     coap_proto_t::{COAP_PROTO_DTLS, COAP_PROTO_NONE, COAP_PROTO_TCP, COAP_PROTO_TLS, COAP_PROTO_UDP},
-    coap_split_proxy_uri, coap_split_uri, coap_str_const_t, coap_string_equal, coap_uri_into_options,
-    coap_uri_scheme_t,
+    coap_split_proxy_uri, coap_split_uri, coap_str_const_t, coap_uri_into_options, coap_uri_scheme_t,
     coap_uri_scheme_t::{
         COAP_URI_SCHEME_COAP, COAP_URI_SCHEME_COAPS, COAP_URI_SCHEME_COAPS_TCP, COAP_URI_SCHEME_COAP_TCP,
         COAP_URI_SCHEME_HTTP, COAP_URI_SCHEME_HTTPS,
@@ -41,8 +42,9 @@ use libcoap_sys::{
 };
 
 use crate::context::ensure_coap_started;
-use crate::error::UriParsingError;
+use crate::error::{AddressConversionError, UriParsingError};
 use crate::message::CoapOption;
+use crate::prng::coap_prng_try_fill;
 use crate::protocol::UriPort;
 
 /// Interface index used internally by libcoap to refer to an endpoint.
@@ -52,6 +54,46 @@ pub type MaxRetransmit = c_ushort;
 /// Identifier for a CoAP message.
 pub type CoapMessageId = coap_mid_t;
 
+/// Thread-safe counter for generating message IDs.
+///
+/// Sessions use libcoap's own per-session counter (see
+/// [CoapSessionCommon::next_message_id()](crate::session::CoapSessionCommon::next_message_id())),
+/// this counter can be used to generate message IDs for messages that are not sent using a
+/// session or that are constructed on other threads.
+///
+/// Message IDs are generated sequentially and wrap around after 65535, i.e., a message ID is only
+/// repeated after all other 65535 message IDs have been generated.
+#[derive(Debug)]
+pub struct CoapMessageIdCounter(AtomicU16);
+
+impl CoapMessageIdCounter {
+    /// Creates a new counter starting at a random message ID (see
+    /// [RFC 7252, Section 4.4](https://datatracker.ietf.org/doc/html/rfc7252#section-4.4)).
+    pub fn new() -> CoapMessageIdCounter {
+        let mut initial = [0u8; 2];
+        // libcoap's PRNG should not fail, and even if it does, starting at zero is still valid.
+        let _ = coap_prng_try_fill(&mut initial);
+        Self::with_initial_message_id(u16::from_ne_bytes(initial))
+    }
+
+    /// Creates a new counter whose first generated message ID is `initial`.
+    pub fn with_initial_message_id(initial: u16) -> CoapMessageIdCounter {
+        CoapMessageIdCounter(AtomicU16::new(initial))
+    }
+
+    /// Returns the next message ID.
+    pub fn next_message_id(&self) -> CoapMessageId {
+        // fetch_add() wraps around on overflow.
+        CoapMessageId::from(self.0.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for CoapMessageIdCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Internal wrapper for the raw coap_address_t type, mainly used for conversion between types.
 pub(crate) struct CoapAddress(coap_address_t);
 
@@ -78,8 +120,6 @@ impl CoapAddress {
     }
 
     /// Converts this address into the corresponding raw [coap_address_t](libcoap_sys::coap_address_t)
-    // Kept for consistency
-    #[allow(dead_code)]
     pub(crate) fn into_raw_address(self) -> coap_address_t {
         self.0
     }
@@ -89,39 +129,65 @@ impl ToSocketAddrs for CoapAddress {
     type Iter = std::option::IntoIter<SocketAddr>;
 
     fn to_socket_addrs(&self) -> std::io::Result<Self::Iter> {
-        // SAFETY: That the underlying value of addr is a valid sockaddr is an invariant, the only
-        // way the value could be invalid is if as_mut_coap_address_t() (an unsafe function) is used
-        // incorrectly.
-        let socketaddr = match unsafe { self.0.addr.sa.as_ref().sa_family } as i32 {
-            AF_INET => {
-                // SAFETY: Validity of addr is an invariant, and we checked that the type of the
-                // underlying sockaddr is actually sockaddr_in.
-                let raw_addr = unsafe { self.0.addr.sin.as_ref() };
-                SocketAddrV4::new(
-                    Ipv4Addr::from(raw_addr.sin_addr.s_addr.to_ne_bytes()),
-                    u16::from_be(raw_addr.sin_port),
-                )
-                .into()
-            },
-            AF_INET6 => {
-                // SAFETY: Validity of addr is an invariant, and we checked that the type of the
-                // underlying sockaddr is actually sockaddr_in6.
-                let raw_addr = unsafe { self.0.addr.sin6.as_ref() };
-                SocketAddrV6::new(
-                    Ipv6Addr::from(raw_addr.sin6_addr.s6_addr),
-                    u16::from_be(raw_addr.sin6_port),
-                    raw_addr.sin6_flowinfo,
-                    raw_addr.sin6_scope_id,
-                )
-                .into()
-            },
-            // This should not happen as long as the invariants are kept.
-            _ => panic!("sa_family_t of underlying coap_address_t is invalid!"),
-        };
+        // That the underlying value of addr is a valid sockaddr is an invariant, the only way the
+        // value could be invalid is if as_mut_coap_address_t() (an unsafe function) is used
+        // incorrectly, so this should not fail as long as the invariants are kept.
+        let socketaddr =
+            coap_address_to_socket_addr(&self.0).expect("sa_family_t of underlying coap_address_t is invalid!");
         Ok(Some(socketaddr).into_iter())
     }
 }
 
+/// Converts the given raw [coap_address_t] into the corresponding [SocketAddr].
+///
+/// # Errors
+/// Returns [AddressConversionError::UnsupportedFamily] if the address family of `addr` is neither
+/// `AF_INET` nor `AF_INET6`.
+///
+/// # Examples
+/// ```
+/// use std::net::SocketAddr;
+///
+/// use libcoap_rs::types::{coap_address_to_socket_addr, socket_addr_to_coap_address};
+///
+/// let addr: SocketAddr = "127.0.0.1:5683".parse().unwrap();
+/// let raw_addr = socket_addr_to_coap_address(addr);
+/// assert_eq!(coap_address_to_socket_addr(&raw_addr), Ok(addr));
+/// ```
+pub fn coap_address_to_socket_addr(addr: &coap_address_t) -> Result<SocketAddr, AddressConversionError> {
+    // SAFETY: All variants of the sockaddr union are plain C structs for which any bit pattern is
+    // valid, and the address family is always located at the same position.
+    let family = unsafe { addr.addr.sa.as_ref().sa_family };
+    match family as i32 {
+        AF_INET => {
+            // SAFETY: We checked that the type of the underlying sockaddr is sockaddr_in.
+            let raw_addr = unsafe { addr.addr.sin.as_ref() };
+            Ok(SocketAddrV4::new(
+                Ipv4Addr::from(raw_addr.sin_addr.s_addr.to_ne_bytes()),
+                u16::from_be(raw_addr.sin_port),
+            )
+            .into())
+        },
+        AF_INET6 => {
+            // SAFETY: We checked that the type of the underlying sockaddr is sockaddr_in6.
+            let raw_addr = unsafe { addr.addr.sin6.as_ref() };
+            Ok(SocketAddrV6::new(
+                Ipv6Addr::from(raw_addr.sin6_addr.s6_addr),
+                u16::from_be(raw_addr.sin6_port),
+                raw_addr.sin6_flowinfo,
+                raw_addr.sin6_scope_id,
+            )
+            .into())
+        },
+        _ => Err(AddressConversionError::UnsupportedFamily(family as u16)),
+    }
+}
+
+/// Converts the given [SocketAddr] into the corresponding raw [coap_address_t].
+pub fn socket_addr_to_coap_address(addr: SocketAddr) -> coap_address_t {
+    CoapAddress::from(addr).into_raw_address()
+}
+
 impl From<SocketAddr> for CoapAddress {
     fn from(addr: SocketAddr) -> Self {
         match addr {
@@ -235,6 +301,16 @@ impl CoapUriScheme {
     pub fn from_raw_scheme(scheme: coap_uri_scheme_t) -> CoapUriScheme {
         FromPrimitive::from_u32(scheme as u32).expect("unknown scheme")
     }
+
+    /// Returns the port that is implied for URIs of this scheme if no port is given explicitly.
+    pub(crate) fn default_port(self) -> UriPort {
+        match self {
+            CoapUriScheme::Coap | CoapUriScheme::CoapTcp => 5683,
+            CoapUriScheme::Coaps | CoapUriScheme::CoapsTcp => 5684,
+            CoapUriScheme::Http | CoapUriScheme::CoapWs => 80,
+            CoapUriScheme::Https | CoapUriScheme::CoapsWs => 443,
+        }
+    }
 }
 
 impl FromStr for CoapUriScheme {
@@ -339,6 +415,32 @@ pub struct CoapUri {
 #[derive(Debug)]
 struct CoapUriInner(CString, PhantomPinned);
 
+/// Removes the percent-encoding (see
+/// [RFC 3986, Section 2.1](https://datatracker.ietf.org/doc/html/rfc3986#section-2.1)) from the
+/// given URI component, keeping `%` characters that are not followed by two hex digits.
+fn percent_decode(component: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(component.len());
+    let mut i = 0;
+    while i < component.len() {
+        let hex = component
+            .get(i + 1..i + 3)
+            .filter(|hex| component[i] == b'%' && hex.iter().all(u8::is_ascii_hexdigit));
+        match hex {
+            Some(hex) => {
+                // Two ASCII hex digits are always valid UTF-8 and fit into a u8.
+                let hex = std::str::from_utf8(hex).expect("hex digits must be valid UTF-8");
+                decoded.push(u8::from_str_radix(hex, 16).expect("hex digits must be a valid byte"));
+                i += 3;
+            },
+            None => {
+                decoded.push(component[i]);
+                i += 1;
+            },
+        }
+    }
+    decoded
+}
+
 impl CoapUri {
     /// Creates a new [CoapUri] for use as a request or location URI from its constituent parts.
     ///
@@ -566,6 +668,36 @@ impl CoapUri {
         Some(unsafe { std::slice::from_raw_parts(raw_str.s, raw_str.length) })
     }
 
+    /// Returns the segments of the URI path of this URI with their percent-encoding removed, i.e.,
+    /// the values of the "Uri-Path" options that represent this URI in a request.
+    ///
+    /// Percent-encoded slashes (`%2F`) are part of the segment they appear in instead of separating
+    /// two segments. Invalid UTF-8 sequences resulting from decoding are replaced with
+    /// [U+FFFD](std::char::REPLACEMENT_CHARACTER).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::UriParsingError;
+    /// use libcoap_rs::types::CoapUri;
+    ///
+    /// let uri = CoapUri::try_from_str("coap://example.com/files/a%2Fb/c%20d")?;
+    ///
+    /// assert_eq!(uri.path(), Some("files/a%2Fb/c%20d".as_bytes()));
+    /// assert_eq!(uri.decoded_path_segments(), vec!["files", "a/b", "c d"]);
+    ///
+    /// # Result::<(), UriParsingError>::Ok(())
+    /// ```
+    pub fn decoded_path_segments(&self) -> Vec<String> {
+        let path = self.path().unwrap_or_default();
+        let path = path.strip_prefix(b"/").unwrap_or(path);
+        if path.is_empty() {
+            return Vec::new();
+        }
+        path.split(|c| *c == b'/')
+            .map(|segment| String::from_utf8_lossy(&percent_decode(segment)).into_owned())
+            .collect()
+    }
+
     /// Returns the host part of this URI.
     pub fn query(&self) -> Option<&[u8]> {
         let raw_str = self.raw_uri.query;
@@ -578,6 +710,35 @@ impl CoapUri {
         Some(unsafe { std::slice::from_raw_parts(raw_str.s, raw_str.length) })
     }
 
+    /// Returns the components of the query of this URI with their percent-encoding removed, i.e.,
+    /// the values of the "Uri-Query" options that represent this URI in a request.
+    ///
+    /// Like for [CoapUri::decoded_path_segments()], percent-encoded ampersands (`%26`) are part of
+    /// the component they appear in, and invalid UTF-8 sequences are replaced with
+    /// [U+FFFD](std::char::REPLACEMENT_CHARACTER).
+    ///
+    /// # Examples
+    /// ```
+    /// use libcoap_rs::error::UriParsingError;
+    /// use libcoap_rs::types::CoapUri;
+    ///
+    /// let uri = CoapUri::try_from_str("coap://example.com/search?q=a%26b&lang=en")?;
+    ///
+    /// assert_eq!(uri.decoded_query_components(), vec!["q=a&b", "lang=en"]);
+    ///
+    /// # Result::<(), UriParsingError>::Ok(())
+    /// ```
+    pub fn decoded_query_components(&self) -> Vec<String> {
+        let query = self.query().unwrap_or_default();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        query
+            .split(|c| *c == b'&')
+            .map(|component| String::from_utf8_lossy(&percent_decode(component)).into_owned())
+            .collect()
+    }
+
     /// Returns whether this URI is a proxy URI.
     pub fn is_proxy(&self) -> bool {
         self.is_proxy
@@ -825,23 +986,86 @@ impl CoapUri {
     }
 }
 
+/// Normalizes the percent-encoding of a URI component for comparison purposes.
+///
+/// Percent-encoded octets that represent unreserved characters are decoded, while all other
+/// percent-encodings are converted to use uppercase hexadecimal digits (see
+/// [RFC 3986, section 6.2.2](https://datatracker.ietf.org/doc/html/rfc3986#section-6.2.2)).
+fn normalize_percent_encoding(component: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(component.len());
+    let mut i = 0;
+    while i < component.len() {
+        let decoded = (component[i] == b'%')
+            .then(|| component.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(c) if c.is_ascii_alphanumeric() || [b'-', b'.', b'_', b'~'].contains(&c) => {
+                normalized.push(c);
+                i += 3;
+            },
+            Some(_) => {
+                normalized.push(b'%');
+                normalized.extend(component[i + 1..i + 3].iter().map(u8::to_ascii_uppercase));
+                i += 3;
+            },
+            None => {
+                normalized.push(component[i]);
+                i += 1;
+            },
+        }
+    }
+    normalized
+}
+
+impl CoapUri {
+    /// Returns a normalized representation of this URI that is used for equality comparisons and
+    /// hashing.
+    ///
+    /// The host is compared case-insensitively, a missing port is replaced by the default port of
+    /// the URI scheme, and percent-encodings in the path and query are canonicalized.
+    fn normalized_parts(&self) -> (bool, Option<CoapUriScheme>, Option<Vec<u8>>, UriPort, Vec<u8>, Vec<u8>) {
+        let scheme = self.scheme();
+        let host = self.host().map(|v| v.to_ascii_lowercase());
+        let port = self
+            .port()
+            .unwrap_or_else(|| scheme.unwrap_or(CoapUriScheme::Coap).default_port());
+        let path = normalize_percent_encoding(self.path().unwrap_or_default());
+        let query = normalize_percent_encoding(self.query().unwrap_or_default());
+        (self.is_proxy, scheme, host, port, path, query)
+    }
+}
+
+/// Two [CoapUri]s are considered equal if they are equivalent after normalization, i.e., the host
+/// is compared case-insensitively, an omitted port is equal to the default port of the scheme, and
+/// percent-encodings are compared in their canonical form.
+///
+/// # Examples
+/// ```
+/// use libcoap_rs::error::UriParsingError;
+/// use libcoap_rs::types::CoapUri;
+///
+/// let uri_a: CoapUri = "coap://EXAMPLE.com/foo%2dbar".parse()?;
+/// let uri_b: CoapUri = "coap://example.com:5683/foo-bar".parse()?;
+/// assert_eq!(uri_a, uri_b);
+///
+/// # Result::<(), UriParsingError>::Ok(())
+/// ```
 impl PartialEq for CoapUri {
     fn eq(&self, other: &Self) -> bool {
-        self.raw_uri.port == other.raw_uri.port
-            && self.raw_uri.scheme == other.raw_uri.scheme
-            // SAFETY: After construction the fields of self.raw_uri always reference the
-            //         corresponding parts of the underlying string, which is pinned. Therefore, the
-            //         pointer and length are valid for the lifetime of this struct.
-            && unsafe {
-                coap_string_equal!(&self.raw_uri.host, &other.raw_uri.host)
-                    && coap_string_equal!(&self.raw_uri.path, &other.raw_uri.path)
-                    && coap_string_equal!(&self.raw_uri.query, &other.raw_uri.query)
-            }
+        self.normalized_parts() == other.normalized_parts()
     }
 }
 
 impl Eq for CoapUri {}
 
+impl Hash for CoapUri {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized_parts().hash(state)
+    }
+}
+
 impl Clone for CoapUri {
     fn clone(&self) -> Self {
         // SAFETY: raw_uri is a valid pointer to a coap_uri_t (by construction of this type and
@@ -867,16 +1091,75 @@ impl FromStr for CoapUri {
     }
 }
 
+/// Writes the given URI component, percent-encoding all bytes that are neither unreserved
+/// characters, sub-delimiters, `:`, `@`, valid percent-encodings, nor contained in `allowed` (see
+/// [RFC 3986, section 3.3](https://datatracker.ietf.org/doc/html/rfc3986#section-3.3)).
+fn write_percent_encoded(f: &mut Formatter<'_>, component: &[u8], allowed: &[u8]) -> std::fmt::Result {
+    for (i, c) in component.iter().enumerate() {
+        let is_percent_encoding = *c == b'%'
+            && component
+                .get(i + 1..i + 3)
+                .is_some_and(|v| v.iter().all(u8::is_ascii_hexdigit));
+        let is_allowed = c.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(c) || allowed.contains(c);
+        if is_allowed || is_percent_encoding {
+            write!(f, "{}", *c as char)?;
+        } else {
+            write!(f, "%{c:02X}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Formats the URI in its canonical form, i.e., `scheme://host:port/path?query`.
+///
+/// The host is converted to lowercase, the port is omitted if it is the default port of the
+/// scheme, and percent-encodings in the path and query are normalized (see [CoapUri]'s
+/// [PartialEq] implementation).
+/// Relative URIs are formatted as `/path?query`, an empty path is formatted as `/`.
+///
+/// The resulting string can be parsed into a [CoapUri] that is equal to the original one (except
+/// for proxy URIs, which have to be parsed using [CoapUri::try_from_str_proxy]).
+///
+/// # Examples
+/// ```
+/// use libcoap_rs::error::UriParsingError;
+/// use libcoap_rs::types::CoapUri;
+///
+/// let uri: CoapUri = "coap://EXAMPLE.com:5683/%7Esensors/temp.xml?a=1&b=2".parse()?;
+/// assert_eq!(uri.to_string(), "coap://example.com/~sensors/temp.xml?a=1&b=2");
+/// assert_eq!(uri.to_string().parse::<CoapUri>()?, uri);
+///
+/// # Result::<(), UriParsingError>::Ok(())
+/// ```
 impl Display for CoapUri {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        self.uri_str.fmt(f)
+        if let (Some(scheme), Some(host)) = (self.scheme(), self.host()) {
+            write!(f, "{scheme}://")?;
+            let host = String::from_utf8_lossy(host).to_ascii_lowercase();
+            if host.contains(':') {
+                // IPv6 addresses must be enclosed in brackets.
+                write!(f, "[{host}]")?;
+            } else {
+                write!(f, "{host}")?;
+            }
+            if let Some(port) = self.port().filter(|v| *v != scheme.default_port()) {
+                write!(f, ":{port}")?;
+            }
+        }
+        write!(f, "/")?;
+        write_percent_encoded(f, &normalize_percent_encoding(self.path().unwrap_or_default()), b"/")?;
+        if let Some(query) = self.query().filter(|v| !v.is_empty()) {
+            write!(f, "?")?;
+            write_percent_encoded(f, &normalize_percent_encoding(query), b"/?")?;
+        }
+        Ok(())
     }
 }
 
 /// Transport protocols that can be used with libcoap.
 #[repr(u32)]
 #[non_exhaustive]
-#[derive(Copy, Clone, FromPrimitive, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, FromPrimitive, PartialEq, Eq, Hash, Debug)]
 pub enum CoapProtocol {
     None = COAP_PROTO_NONE as u32,
     Udp = COAP_PROTO_UDP as u32,
@@ -913,6 +1196,62 @@ impl Display for CoapProtocol {
     }
 }
 
+/// Log levels that can be set for libcoap's internal logging.
+///
+/// Each level includes all levels above it, i.e., setting the log level to [CoapLogLevel::Warning]
+/// will also log [CoapLogLevel::Error] messages.
+///
+/// See the [libcoap documentation on logging](https://libcoap.net/doc/reference/develop/group__logging.html)
+/// for more information.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CoapLogLevel {
+    /// System is unusable.
+    Emergency,
+    /// Action must be taken immediately.
+    Alert,
+    /// Critical conditions.
+    Critical,
+    /// Error conditions.
+    Error,
+    /// Warning conditions.
+    Warning,
+    /// Normal, but significant conditions.
+    Notice,
+    /// Informational messages.
+    Info,
+    /// Debug messages.
+    Debug,
+    /// Debug level that additionally includes OSCORE-related debug messages.
+    Oscore,
+    /// Debug level that additionally includes debug messages of the DTLS library.
+    Dtls,
+}
+
+impl CoapLogLevel {
+    /// Returns the raw [coap_log_t] value corresponding to this log level.
+    pub fn to_raw_log_level(self) -> coap_log_t {
+        match self {
+            CoapLogLevel::Emergency => coap_log_t::COAP_LOG_EMERG,
+            CoapLogLevel::Alert => coap_log_t::COAP_LOG_ALERT,
+            CoapLogLevel::Critical => coap_log_t::COAP_LOG_CRIT,
+            CoapLogLevel::Error => coap_log_t::COAP_LOG_ERR,
+            CoapLogLevel::Warning => coap_log_t::COAP_LOG_WARN,
+            CoapLogLevel::Notice => coap_log_t::COAP_LOG_NOTICE,
+            CoapLogLevel::Info => coap_log_t::COAP_LOG_INFO,
+            CoapLogLevel::Debug => coap_log_t::COAP_LOG_DEBUG,
+            CoapLogLevel::Oscore => coap_log_t::COAP_LOG_OSCORE,
+            CoapLogLevel::Dtls => coap_log_t::COAP_LOG_DTLS_BASE,
+        }
+    }
+}
+
+#[doc(hidden)]
+impl From<CoapLogLevel> for coap_log_t {
+    fn from(level: CoapLogLevel) -> Self {
+        level.to_raw_log_level()
+    }
+}
+
 fn convert_to_fixed_size_slice(n: usize, val: &[u8]) -> Box<[u8]> {
     if val.len() > n {
         panic!("supplied slice too short");
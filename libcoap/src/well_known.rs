@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * well_known.rs - Resource discovery using /.well-known/core for the libcoap Rust Wrapper.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+//! Generation of the resource discovery resource at `/.well-known/core` (see
+//! [RFC 6690, Section 4](https://datatracker.ietf.org/doc/html/rfc6690#section-4)), which
+//! includes the attributes of resources added using [CoapContext::add_resource_with_attributes()].
+
+use libcoap_sys::coap_session_get_context;
+
+use crate::{
+    context::CoapContext,
+    message::{CoapMessageCommon, CoapRequest, CoapResponse},
+    protocol::{CoapContentFormat, CoapRequestCode, CoapResponseCode, ContentFormat},
+    resource::{CoapRequestHandler, CoapResource, ResourceDescription},
+    session::{CoapServerSession, CoapSessionCommon},
+};
+
+/// URI path of the resource discovery resource.
+pub(crate) const WELL_KNOWN_CORE_PATH: &str = ".well-known/core";
+
+/// Attributes that may contain multiple space-separated values, each of which is matched
+/// separately by query filters.
+const MULTI_VALUE_ATTRIBUTES: [&str; 3] = ["rt", "if", "ct"];
+
+/// A single target attribute of a link.
+struct LinkAttribute {
+    name: &'static str,
+    value: Option<String>,
+    quoted: bool,
+}
+
+impl LinkAttribute {
+    fn new(name: &'static str, value: Option<String>, quoted: bool) -> LinkAttribute {
+        LinkAttribute { name, value, quoted }
+    }
+
+    /// Appends this attribute (including the separating semicolon) to the given link.
+    fn write_to(&self, link: &mut String) {
+        link.push(';');
+        link.push_str(self.name);
+        match &self.value {
+            Some(value) if self.quoted => {
+                link.push_str("=\"");
+                link.push_str(&value.replace('\\', "\\\\").replace('"', "\\\""));
+                link.push('"');
+            },
+            Some(value) => {
+                link.push('=');
+                link.push_str(value);
+            },
+            None => {},
+        }
+    }
+}
+
+/// Converts the given resource description into the target attributes of its link.
+fn link_attributes(description: &ResourceDescription) -> Vec<LinkAttribute> {
+    let mut attributes = Vec::new();
+    if let Some(title) = &description.title {
+        attributes.push(LinkAttribute::new("title", Some(title.clone()), true));
+    }
+    if !description.resource_type.is_empty() {
+        attributes.push(LinkAttribute::new(
+            "rt",
+            Some(description.resource_type.join(" ")),
+            true,
+        ));
+    }
+    if !description.interface.is_empty() {
+        attributes.push(LinkAttribute::new("if", Some(description.interface.join(" ")), true));
+    }
+    if !description.content_formats.is_empty() {
+        let content_formats: Vec<String> = description.content_formats.iter().map(|v| v.to_string()).collect();
+        // Multiple content formats must be quoted, see RFC 7252, Section 7.2.1.
+        let quoted = content_formats.len() > 1;
+        attributes.push(LinkAttribute::new("ct", Some(content_formats.join(" ")), quoted));
+    }
+    if let Some(size_estimate) = description.size_estimate {
+        attributes.push(LinkAttribute::new("sz", Some(size_estimate.to_string()), false));
+    }
+    if description.observable {
+        attributes.push(LinkAttribute::new("obs", None, false));
+    }
+    attributes
+}
+
+/// Returns whether the link to `uri_path` with the given attributes matches the query filter
+/// `filter` (a single query parameter, see
+/// [RFC 6690, Section 4.1](https://datatracker.ietf.org/doc/html/rfc6690#section-4.1)).
+///
+/// Filters without a value are ignored.
+fn matches_filter(uri_path: &str, attributes: &[LinkAttribute], filter: &str) -> bool {
+    let Some((name, pattern)) = filter.split_once('=') else {
+        return true;
+    };
+    // A trailing asterisk matches all values starting with the remaining pattern.
+    let matches = |value: &str| match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == pattern,
+    };
+    if name == "href" {
+        return matches(&format!("/{uri_path}"));
+    }
+    attributes
+        .iter()
+        .filter(|v| v.name == name)
+        .filter_map(|v| v.value.as_deref())
+        .any(|value| {
+            if MULTI_VALUE_ATTRIBUTES.contains(&name) {
+                value.split(' ').any(&matches)
+            } else {
+                matches(value)
+            }
+        })
+}
+
+/// Generates the link-format document describing the given resources (along with their
+/// descriptions, if available) that match the given query filter.
+pub(crate) fn link_format<'a>(
+    resources: impl Iterator<Item = (&'a str, Option<&'a ResourceDescription>)>,
+    filter: Option<&str>,
+) -> String {
+    let mut links = Vec::new();
+    for (uri_path, description) in resources {
+        let attributes = description.map(link_attributes).unwrap_or_default();
+        if filter.is_some_and(|v| !matches_filter(uri_path, &attributes, v)) {
+            continue;
+        }
+        let mut link = format!("</{uri_path}>");
+        for attribute in &attributes {
+            attribute.write_to(&mut link);
+        }
+        links.push(link);
+    }
+    links.join(",")
+}
+
+/// Creates the resource discovery resource.
+pub(crate) fn well_known_core_resource() -> CoapResource<()> {
+    let resource = CoapResource::new(WELL_KNOWN_CORE_PATH, (), false);
+    resource.set_method_handler(
+        CoapRequestCode::Get,
+        // The user data must not be borrowed while the handler is running, as the handler lists
+        // the URI paths of all resources (including this one).
+        Some(CoapRequestHandler::new_resource_ref(handle_well_known_core_get)),
+    );
+    resource
+}
+
+fn handle_well_known_core_get(
+    _resource: &CoapResource<()>,
+    session: &mut CoapServerSession,
+    request: &CoapRequest,
+    mut response: CoapResponse,
+) {
+    // Only a single query filter is supported, see RFC 6690, Section 4.1.
+    let filter = request
+        .uri()
+        .query()
+        .and_then(|v| std::str::from_utf8(v).ok())
+        .and_then(|v| v.split('&').next());
+    // SAFETY: The session is valid while the handler is running and belongs to a context created
+    // by this library.
+    let context = unsafe { CoapContext::from_raw(coap_session_get_context(session.raw_session_mut())) };
+    let links = context.well_known_core(filter);
+    response.set_content_format(Some(CoapContentFormat::LinkFormat as ContentFormat));
+    response.set_data(Some(links.into_bytes()));
+    session.respond(response, CoapResponseCode::Content);
+}
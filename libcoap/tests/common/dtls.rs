@@ -5,10 +5,9 @@ use libcoap_rs::crypto::pki_rpk::{
 use libcoap_rs::crypto::ClientCryptoContext;
 use libcoap_rs::message::CoapMessageCommon;
 use libcoap_rs::protocol::{CoapMessageCode, CoapResponseCode};
-use libcoap_rs::session::{CoapClientSession, CoapSessionCommon};
+use libcoap_rs::session::CoapClientSession;
 use libcoap_rs::CoapContext;
 use std::path::PathBuf;
-use std::time::Duration;
 
 // Is used in some test cases, but not in others (causing a compiler warning)
 #[allow(unused)]
@@ -40,15 +39,7 @@ pub fn dtls_client_server_request_common<KTY: KeyType, FC, FS>(
     context.set_pki_root_ca_paths(Some("./resources/test-keys/ca/ca.crt.pem"), None::<PathBuf>);
     let session = CoapClientSession::connect_dtls(&mut context, server_address, client_crypto_ctx).unwrap();
 
-    let request = common::gen_test_request();
-    let req_handle = session.send_request(request).unwrap();
-    loop {
-        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
-        for response in session.poll_handle(&req_handle) {
-            assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
-            assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
-            server_handle.join().expect("Test server crashed with failure.");
-            return;
-        }
-    }
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
 }
@@ -20,7 +20,7 @@ use std::time::Duration;
 use libcoap_rs::{CoapContext, CoapRequestHandler, CoapResource};
 use libcoap_rs::message::{CoapMessageCommon, CoapRequest, CoapResponse};
 use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode};
-use libcoap_rs::session::CoapSessionCommon;
+use libcoap_rs::session::{CoapClientSession, CoapSessionCommon};
 use libcoap_sys::{coap_dtls_set_log_level, coap_log_t, coap_set_log_level};
 
 pub(crate) fn get_unused_server_addr() -> SocketAddr {
@@ -45,16 +45,28 @@ pub(crate) fn get_unused_server_addr() -> SocketAddr {
 /// As the context_configurator closure is responsible for binding to sockets, this can be used to
 /// spawn a test server and wait for it to be ready to accept requests before returning (avoiding
 /// test failure due to "Connection Refused" errors).
+// Is used in some test cases, but not in others (causing a compiler warning)
+#[allow(unused)]
 pub(crate) fn spawn_test_server<F: FnOnce(CoapContext<'static>) -> CoapContext<'static> + Send + 'static>(
     context_configurator: F,
+) -> JoinHandle<()> {
+    spawn_custom_test_server(move || context_configurator(CoapContext::new().unwrap()))
+}
+
+/// Spawns a test server in a new thread using the context created by context_constructor and waits
+/// for context_constructor to complete before returning.
+// Is used in some test cases, but not in others (causing a compiler warning)
+#[allow(unused)]
+pub(crate) fn spawn_custom_test_server<F: FnOnce() -> CoapContext<'static> + Send + 'static>(
+    context_constructor: F,
 ) -> JoinHandle<()> {
     let ready_condition = Arc::new((Mutex::new(false), Condvar::new()));
     let ready_condition2 = Arc::clone(&ready_condition);
 
     let server_handle = std::thread::spawn(move || {
         let (ready_var, ready_cond) = &*ready_condition2;
-        run_test_server(|context| {
-            let context = context_configurator(context);
+        run_test_server(|| {
+            let context = context_constructor();
             let mut ready_var = ready_var.lock().expect("ready condition mutex is poisoned");
             *ready_var = true;
             ready_cond.notify_all();
@@ -74,14 +86,15 @@ pub(crate) fn spawn_test_server<F: FnOnce(CoapContext<'static>) -> CoapContext<'
 }
 
 /// Configures and starts a test server in the current thread.
-pub(crate) fn run_test_server<F: FnOnce(CoapContext<'static>) -> CoapContext<'static>>(context_configurator: F) {
+///
+/// The server uses the context created by context_constructor.
+pub(crate) fn run_test_server<F: FnOnce() -> CoapContext<'static>>(context_constructor: F) {
     unsafe {
         libcoap_sys::coap_startup_with_feature_checks();
         coap_dtls_set_log_level(coap_log_t::COAP_LOG_DEBUG);
         coap_set_log_level(coap_log_t::COAP_LOG_DEBUG);
     }
-    let mut context = CoapContext::new().unwrap();
-    context = context_configurator(context);
+    let mut context = context_constructor();
     let request_completed = Rc::new(AtomicBool::new(false));
     let resource = CoapResource::new("test1", request_completed.clone(), false);
     resource.set_method_handler(
@@ -110,8 +123,31 @@ pub(crate) fn run_test_server<F: FnOnce(CoapContext<'static>) -> CoapContext<'st
     context.shutdown(Some(Duration::from_secs(0))).unwrap();
 }
 
+// Is used in some test cases, but not in others (causing a compiler warning)
+#[allow(unused)]
 pub(crate) fn gen_test_request() -> CoapRequest {
     let uri = "/test1".parse().expect("unable to parse request URI");
 
     CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap()
 }
+
+/// Sends the test server's regular test request (see [gen_test_request()]) using the given session,
+/// waits for the response and then for the test server to shut down.
+///
+/// Returns the response to the test request.
+// Is used in some test cases, but not in others (causing a compiler warning)
+#[allow(unused)]
+pub(crate) fn finish_test_server(
+    context: &mut CoapContext,
+    session: &CoapClientSession,
+    server_handle: JoinHandle<()>,
+) -> CoapResponse {
+    let req_handle = session.send_request(gen_test_request()).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            server_handle.join().expect("Test server crashed with failure.");
+            return response;
+        }
+    }
+}
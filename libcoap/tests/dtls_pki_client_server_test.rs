@@ -12,7 +12,12 @@
 use crate::common::dtls::dtls_client_server_request_common;
 use libcoap_rs::crypto::pki_rpk::{Asn1PrivateKeyType, DerFileKeyComponent, NonCertVerifying, PkiRpkContextBuilder};
 use libcoap_rs::crypto::pki_rpk::{Pki, PkiKeyDef};
+use libcoap_rs::message::{CoapMessageCommon, CoapRequest, CoapResponse};
+use libcoap_rs::protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode};
+use libcoap_rs::session::{CoapClientSession, CoapServerSession, CoapSessionCommon};
+use libcoap_rs::{CoapContext, CoapRequestHandler, CoapResource};
 use std::path::PathBuf;
+use std::time::Duration;
 
 mod common;
 
@@ -118,3 +123,114 @@ pub fn dtls_pki_asn1_memory_client_server_request() {
     };
     dtls_client_server_request_common(client_key, server_key, ctx_configurator, ctx_configurator)
 }
+
+#[test]
+pub fn dtls_pki_peer_certificate_der() {
+    const DER_SERVER_PUBLIC_CERT: &[u8] = include_bytes!("../resources/test-keys/server/server.crt.der");
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let key_storage = manifest_dir.join("./resources/test-keys");
+    let client_key = PkiKeyDef::with_pem_files(
+        Some(key_storage.join("./ca/ca.crt.pem")),
+        key_storage.join("./client/client.crt.pem"),
+        key_storage.join("./client/client.key.pem"),
+    );
+    let server_key = PkiKeyDef::with_pem_files(
+        Some(key_storage.join("./ca/ca.crt.pem")),
+        key_storage.join("./server/server.crt.pem"),
+        key_storage.join("./server/server.key.pem"),
+    );
+
+    let server_address = common::get_unused_server_addr();
+    let server_handle = common::spawn_test_server(move |mut context: CoapContext| {
+        let server_crypto_ctx = PkiRpkContextBuilder::<'static, Pki, NonCertVerifying>::new(server_key)
+            .verify_peer_cert()
+            .check_common_ca(true)
+            .build();
+        context.set_pki_rpk_context(server_crypto_ctx).unwrap();
+        context.add_endpoint_dtls(server_address).unwrap();
+        context
+            .set_pki_root_ca_paths(Some("./resources/test-keys/ca/ca.crt.pem"), None::<PathBuf>)
+            .unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    context
+        .set_pki_root_ca_paths(Some("./resources/test-keys/ca/ca.crt.pem"), None::<PathBuf>)
+        .unwrap();
+    let client_crypto_ctx = PkiRpkContextBuilder::<'static, Pki, NonCertVerifying>::new(client_key)
+        .verify_peer_cert()
+        .check_common_ca(true)
+        .build();
+    let session = CoapClientSession::connect_dtls(&mut context, server_address, client_crypto_ctx).unwrap();
+    assert_eq!(session.peer_certificate_der(), None);
+
+    common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(session.peer_certificate_der().as_deref(), Some(DER_SERVER_PUBLIC_CERT));
+}
+
+#[test]
+pub fn dtls_pki_peer_common_name() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let key_storage = manifest_dir.join("./resources/test-keys");
+    let client_key = PkiKeyDef::with_pem_files(
+        Some(key_storage.join("./ca/ca.crt.pem")),
+        key_storage.join("./client/client.crt.pem"),
+        key_storage.join("./client/client.key.pem"),
+    );
+    let server_key = PkiKeyDef::with_pem_files(
+        Some(key_storage.join("./ca/ca.crt.pem")),
+        key_storage.join("./server/server.crt.pem"),
+        key_storage.join("./server/server.key.pem"),
+    );
+
+    let server_address = common::get_unused_server_addr();
+    let server_handle = common::spawn_test_server(move |mut context: CoapContext| {
+        let server_crypto_ctx = PkiRpkContextBuilder::<'static, Pki, NonCertVerifying>::new(server_key)
+            .verify_peer_cert()
+            .check_common_ca(true)
+            .build();
+        context.set_pki_rpk_context(server_crypto_ctx).unwrap();
+        context.add_endpoint_dtls(server_address).unwrap();
+        context
+            .set_pki_root_ca_paths(Some("./resources/test-keys/ca/ca.crt.pem"), None::<PathBuf>)
+            .unwrap();
+        let resource = CoapResource::new("identity", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    // Respond with the Common Name of the client certificate.
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    rsp.set_data(sess.dtls_peer_cn().map(String::into_bytes));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    context
+        .set_pki_root_ca_paths(Some("./resources/test-keys/ca/ca.crt.pem"), None::<PathBuf>)
+        .unwrap();
+    let client_crypto_ctx = PkiRpkContextBuilder::<'static, Pki, NonCertVerifying>::new(client_key)
+        .verify_peer_cert()
+        .check_common_ca(true)
+        .build();
+    let session = CoapClientSession::connect_dtls(&mut context, server_address, client_crypto_ctx).unwrap();
+
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, "/identity".parse().unwrap()).unwrap();
+    let req_handle = session.send_request(request).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+            assert_eq!(response.data(), Some("client.example.com".as_bytes()));
+            break;
+        }
+    }
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
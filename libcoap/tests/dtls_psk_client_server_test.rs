@@ -8,16 +8,22 @@
  */
 
 #![cfg(feature = "dtls-psk")]
-use std::time::Duration;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use libcoap_rs::crypto::psk::PskKey;
-use libcoap_rs::crypto::psk::{ClientPskContextBuilder, ServerPskContextBuilder};
-use libcoap_rs::session::CoapClientSession;
+use libcoap_rs::crypto::psk::{
+    ClientPskContextBuilder, CoapPskDatabase, ServerPskContextBuilder, ServerPskIdentityKeyProvider,
+};
+use libcoap_rs::error::PskKeyProviderError;
+use libcoap_rs::session::{CoapClientSession, CoapServerSession, CoapSession, CoapSessionState};
 use libcoap_rs::{
-    message::CoapMessageCommon,
-    protocol::{CoapMessageCode, CoapResponseCode},
+    message::{CoapMessageCommon, CoapRequest, CoapResponse},
+    protocol::{CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode},
     session::CoapSessionCommon,
-    CoapContext,
+    CoapContext, CoapEvent, CoapRequestHandler, CoapResource,
 };
 
 mod common;
@@ -38,15 +44,438 @@ pub fn dtls_psk_client_server_request() {
     let mut context = CoapContext::new().unwrap();
     let session = CoapClientSession::connect_dtls(&mut context, server_address, client_psk_context).unwrap();
 
-    let request = common::gen_test_request();
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
+}
+
+#[test]
+pub fn dtls_psk_connected_event() {
+    let server_address = common::get_unused_server_addr();
+    let dummy_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+    let client_psk_context = ClientPskContextBuilder::new(dummy_key.clone()).build();
+    let dtls_connected = Arc::new(AtomicBool::new(false));
+    let server_dtls_connected = Arc::clone(&dtls_connected);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        let server_psk_context = ServerPskContextBuilder::new(dummy_key.clone()).build();
+        context.set_psk_context(server_psk_context).unwrap();
+        context.set_event_callback(move |event, session| {
+            if event == CoapEvent::DtlsConnected {
+                assert!(matches!(session, CoapSession::Server(_)));
+                server_dtls_connected.store(true, Ordering::Relaxed);
+            }
+        });
+        context.add_endpoint_dtls(server_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_dtls(&mut context, server_address, client_psk_context).unwrap();
+
+    common::finish_test_server(&mut context, &session, server_handle);
+    assert!(dtls_connected.load(Ordering::Relaxed));
+}
+
+#[test]
+pub fn dtls_psk_convenience_constructors() {
+    let server_address = common::get_unused_server_addr();
+    let dummy_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+    let client_psk_context = ClientPskContextBuilder::new(dummy_key.clone()).build();
+
+    let server_handle = common::spawn_custom_test_server(move || {
+        let server_psk_context = ServerPskContextBuilder::new(dummy_key.clone()).build();
+        CoapContext::new_dtls_server(server_address, server_psk_context).unwrap()
+    });
+
+    let (mut context, session) = CoapContext::new_dtls_client(server_address, client_psk_context).unwrap();
+
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
+}
+
+#[test]
+pub fn dtls_psk_hello_session_not_announced() {
+    let server_address = common::get_unused_server_addr();
+    let dummy_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+    let client_psk_context = ClientPskContextBuilder::new(dummy_key.clone()).build();
+    let server_events = Arc::new(Mutex::new(Vec::new()));
+    let server_events_handler = Arc::clone(&server_events);
+
+    // Run server and client in the same thread so that the server's sessions can be inspected.
+    let mut server_context = CoapContext::new().unwrap();
+    let server_psk_context = ServerPskContextBuilder::new(dummy_key).build();
+    server_context.set_psk_context(server_psk_context).unwrap();
+    server_context.set_event_callback(move |event, session| {
+        if let CoapSession::Server(session) = session {
+            server_events_handler.lock().unwrap().push((event, session.is_hello()));
+        }
+    });
+    server_context.add_endpoint_dtls(server_address).unwrap();
+    // Returns whether the server has a HELLO session for the given client port.
+    let has_hello_session = |server_context: &CoapContext, port: u16| {
+        let found = AtomicBool::new(false);
+        server_context.send_broadcast(
+            CoapResponse::new(CoapMessageType::Non, CoapResponseCode::Content).unwrap(),
+            |session| {
+                if session.is_hello() && session.addr_remote().port() == port {
+                    found.store(true, Ordering::Relaxed);
+                }
+                false
+            },
+        );
+        found.load(Ordering::Relaxed)
+    };
+
+    // Start a DTLS connection attempt that never completes the cookie exchange (a ClientHello that
+    // is never followed up on), which causes the server to create a HELLO session.
+    let hello_socket = UdpSocket::bind(SocketAddr::new(server_address.ip(), 0)).unwrap();
+    let hello_port = hello_socket.local_addr().unwrap().port();
+    let mut client_hello = vec![0x16, 0xFE, 0xFD, 0, 0, 0, 0, 0, 0, 0, 0, 0, 12, 0x01];
+    client_hello.extend_from_slice(&[0u8; 11]);
+    hello_socket.send_to(&client_hello, server_address).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !has_hello_session(&server_context, hello_port) {
+        assert!(Instant::now() < deadline, "timed out waiting for HELLO session");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+    }
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_dtls(&mut context, server_address, client_psk_context).unwrap();
+    let req_handle = session.send_request(common::gen_test_request()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while session.poll_handle(&req_handle).next().is_none() {
+        assert!(Instant::now() < deadline, "timed out waiting for response");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+        context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+    }
+    // The HELLO session still exists, as its peer never completed the cookie exchange.
+    assert!(has_hello_session(&server_context, hello_port));
+
+    let server_events = server_events.lock().unwrap();
+    // Only the session of the actual client should have been announced, and only once it was no
+    // longer a HELLO session.
+    assert!(server_events.iter().all(|(_, is_hello)| !is_hello));
+    assert_eq!(
+        server_events
+            .iter()
+            .filter(|(event, _)| *event == CoapEvent::ServerSessionNew)
+            .count(),
+        1
+    );
+    assert!(server_events.contains(&(CoapEvent::DtlsConnected, false)));
+}
+
+/// Key provider that derives the keys for identities starting with `device-` from a master key and
+/// rejects all other identities.
+#[derive(Debug)]
+struct DerivingKeyProvider;
+
+impl DerivingKeyProvider {
+    fn derive_key(identity: &[u8]) -> Vec<u8> {
+        b"dtls_master_key_"
+            .iter()
+            .zip(identity.iter().cycle())
+            .map(|(k, i)| k ^ i)
+            .collect()
+    }
+}
+
+impl<'a> ServerPskIdentityKeyProvider<'a> for DerivingKeyProvider {
+    fn key_for_identity(
+        &self,
+        identity: &[u8],
+        _session: &CoapServerSession<'_>,
+    ) -> Result<PskKey<'a>, PskKeyProviderError> {
+        if !identity.starts_with(b"device-") {
+            return Err(PskKeyProviderError::UnknownIdentity);
+        }
+        Ok(PskKey::new(Some(identity), Self::derive_key(identity)))
+    }
+}
+
+#[test]
+pub fn dtls_psk_unknown_identity_rejected() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        let default_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+        let server_psk_context = ServerPskContextBuilder::new(default_key)
+            .id_key_provider(DerivingKeyProvider)
+            .build();
+        context.set_psk_context(server_psk_context).unwrap();
+        context.add_endpoint_dtls(server_address).unwrap();
+        context
+    });
+
+    // A client using an unknown identity must observe a handshake failure.
+    let handshake_failed = Arc::new(AtomicBool::new(false));
+    let handshake_failed_handler = Arc::clone(&handshake_failed);
+    let unknown_key = PskKey::new(Some("intruder"), "dtls_test_key___");
+    let mut context = CoapContext::new().unwrap();
+    context.set_event_callback(move |event, session| {
+        if matches!(session, CoapSession::Client(_)) && matches!(event, CoapEvent::DtlsError | CoapEvent::DtlsClosed) {
+            handshake_failed_handler.store(true, Ordering::Relaxed);
+        }
+    });
+    let session = CoapClientSession::connect_dtls(
+        &mut context,
+        server_address,
+        ClientPskContextBuilder::new(unknown_key).build(),
+    )
+    .unwrap();
+    let req_handle = session.send_request(common::gen_test_request()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !handshake_failed.load(Ordering::Relaxed) && !matches!(session.state(), CoapSessionState::None) {
+        assert!(
+            Instant::now() < deadline,
+            "DTLS handshake with unknown identity did not fail"
+        );
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+        assert!(session.poll_handle(&req_handle).next().is_none());
+    }
+    assert!(!matches!(session.state(), CoapSessionState::Established));
+    std::mem::drop(session);
+    std::mem::drop(context);
+
+    // A client using a derived key for a known identity must still be able to connect.
+    let device_key = PskKey::new(Some("device-1"), DerivingKeyProvider::derive_key(b"device-1"));
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_dtls(
+        &mut context,
+        server_address,
+        ClientPskContextBuilder::new(device_key).build(),
+    )
+    .unwrap();
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+}
+
+#[test]
+pub fn dtls_psk_peer_identity() {
+    let server_address = common::get_unused_server_addr();
+    let dummy_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+    let client_psk_context = ClientPskContextBuilder::new(dummy_key.clone()).build();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        let server_psk_context = ServerPskContextBuilder::new(dummy_key.clone()).build();
+        context.set_psk_context(server_psk_context).unwrap();
+        context.add_endpoint_dtls(server_address).unwrap();
+        let resource = CoapResource::new("identity", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    // Respond with the identity the client authenticated with.
+                    assert_eq!(sess.psk_identity(), sess.dtls_peer_identity());
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    rsp.set_data(sess.dtls_peer_identity());
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_dtls(&mut context, server_address, client_psk_context).unwrap();
+
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, "/identity".parse().unwrap()).unwrap();
     let req_handle = session.send_request(request).unwrap();
     loop {
         assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
-        for response in session.poll_handle(&req_handle) {
-            assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
-            assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
-            server_handle.join().expect("Test server crashed with failure.");
-            return;
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            assert_eq!(response.data(), Some("dtls_test_id".as_bytes()));
+            break;
         }
     }
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn dtls_psk_database_updated_at_runtime() {
+    let server_address = common::get_unused_server_addr();
+    let database = CoapPskDatabase::new();
+    database.insert("device-initial", "dtls_test_key__1");
+
+    let server_database = database.clone();
+    let server_handle = common::spawn_test_server(move |mut context| {
+        let default_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+        let server_psk_context = ServerPskContextBuilder::new(default_key)
+            .id_key_provider(server_database)
+            .build();
+        context.set_psk_context(server_psk_context).unwrap();
+        context.add_endpoint_dtls(server_address).unwrap();
+        context
+    });
+
+    // Concurrently modify and read the database while the server is running.
+    let workers: Vec<_> = (0..4)
+        .map(|worker| {
+            let database = database.clone();
+            std::thread::spawn(move || {
+                for i in 0..200 {
+                    let identity = format!("device-{worker}-{i}");
+                    database.insert(identity.as_str(), "dtls_test_key__2");
+                    assert!(database.contains_identity(identity.as_bytes()));
+                    assert!(database.contains_identity(b"device-initial"));
+                    assert!(database.len() >= 2);
+                    database.remove(identity.as_bytes());
+                    assert!(!database.contains_identity(identity.as_bytes()));
+                }
+            })
+        })
+        .collect();
+
+    // Keys added after the server context was created can be used by clients.
+    database.insert("device-runtime", "dtls_test_key__3");
+    let runtime_key = PskKey::new(Some("device-runtime"), "dtls_test_key__3");
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_dtls(
+        &mut context,
+        server_address,
+        ClientPskContextBuilder::new(runtime_key).build(),
+    )
+    .unwrap();
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+
+    for worker in workers {
+        worker.join().expect("PSK database worker thread panicked");
+    }
+    assert_eq!(database.len(), 2);
+    assert!(database.contains_identity(b"device-runtime"));
+    database.remove(b"device-runtime");
+    database.remove(b"device-initial");
+    assert!(database.is_empty());
+}
+
+/// Sends a GET request for `path` and returns the payload of the response.
+#[cfg(dtls_cid_support)]
+fn request_payload(context: &mut CoapContext, session: &CoapClientSession, path: &str) -> String {
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, path.parse().unwrap()).unwrap();
+    let req_handle = session.send_request(request).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            return String::from_utf8(response.data().unwrap().to_vec()).unwrap();
+        }
+    }
+}
+
+#[cfg(dtls_cid_support)]
+#[test]
+pub fn dtls_psk_connection_id_address_migration() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::atomic::AtomicUsize;
+
+    // Datagrams from a new address can only be assigned to the existing session if the DTLS
+    // library supports Connection IDs.
+    if unsafe { libcoap_sys::coap_dtls_cid_is_supported() } == 0 {
+        return;
+    }
+    let server_address = common::get_unused_server_addr();
+    let dummy_key = PskKey::new(Some("dtls_test_id"), "dtls_test_key___");
+    let client_psk_context = ClientPskContextBuilder::new(dummy_key.clone()).use_cid(true).build();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        let server_psk_context = ServerPskContextBuilder::new(dummy_key.clone()).build();
+        context.set_psk_context(server_psk_context).unwrap();
+        context.add_endpoint_dtls(server_address).unwrap();
+        let address_changes = Rc::new(Cell::new(0));
+        let resource = CoapResource::new("migrate", Rc::clone(&address_changes), false);
+        context.set_event_callback(move |event, _session| {
+            if event == CoapEvent::ServerSessionAddressChanged {
+                address_changes.set(address_changes.get() + 1);
+            }
+        });
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |address_changes: &mut Rc<Cell<usize>>,
+                 session: &mut CoapServerSession,
+                 _req: &CoapRequest,
+                 mut rsp: CoapResponse| {
+                    let payload = format!(
+                        "{} {} {}",
+                        session.address_migrated(),
+                        address_changes.get(),
+                        session.addr_remote()
+                    );
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    rsp.set_data(Some(payload.into_bytes()));
+                    session.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    // Relay all datagrams between the client and the server, so that the address the server sees
+    // for the client can be changed while the DTLS session stays the same (e.g., as if the client
+    // was behind a NAT that assigned it a new port).
+    let relay = UdpSocket::bind(SocketAddr::new(server_address.ip(), 0)).unwrap();
+    let relay_address = relay.local_addr().unwrap();
+    let upstream = [
+        UdpSocket::bind(SocketAddr::new(server_address.ip(), 0)).unwrap(),
+        UdpSocket::bind(SocketAddr::new(server_address.ip(), 0)).unwrap(),
+    ];
+    let upstream_addresses = [upstream[0].local_addr().unwrap(), upstream[1].local_addr().unwrap()];
+    let active_upstream = Arc::new(AtomicUsize::new(0));
+    let relay_stopped = Arc::new(AtomicBool::new(false));
+    let relay_handle = {
+        let active_upstream = Arc::clone(&active_upstream);
+        let relay_stopped = Arc::clone(&relay_stopped);
+        std::thread::spawn(move || {
+            let mut buf = [0; 2048];
+            let mut client_address = None;
+            for socket in upstream.iter().chain([&relay]) {
+                socket.set_read_timeout(Some(Duration::from_millis(1))).unwrap();
+            }
+            while !relay_stopped.load(Ordering::Relaxed) {
+                if let Ok((len, address)) = relay.recv_from(&mut buf) {
+                    client_address = Some(address);
+                    upstream[active_upstream.load(Ordering::Relaxed)]
+                        .send_to(&buf[..len], server_address)
+                        .unwrap();
+                }
+                for socket in &upstream {
+                    if let (Ok(len), Some(address)) = (socket.recv(&mut buf), client_address) {
+                        relay.send_to(&buf[..len], address).unwrap();
+                    }
+                }
+            }
+        })
+    };
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_dtls(&mut context, relay_address, client_psk_context).unwrap();
+    assert_eq!(
+        request_payload(&mut context, &session, "/migrate"),
+        format!("false 0 {}", upstream_addresses[0])
+    );
+
+    // The server assigns the datagrams from the new address to the existing session and reports
+    // the address change before the request is handled.
+    active_upstream.store(1, Ordering::Relaxed);
+    assert_eq!(
+        request_payload(&mut context, &session, "/migrate"),
+        format!("true 1 {}", upstream_addresses[1])
+    );
+
+    request_payload(&mut context, &session, "/test1");
+    server_handle.join().expect("Test server crashed with failure.");
+    relay_stopped.store(true, Ordering::Relaxed);
+    relay_handle.join().expect("relay thread panicked");
 }
@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * http_test.rs - Tests for conversions between CoAP and HTTP messages.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+#![cfg(feature = "http")]
+use http::{Method, StatusCode};
+use libcoap_rs::error::ProxyError;
+use libcoap_rs::message::{CoapMessageCommon, CoapRequest, CoapResponse};
+use libcoap_rs::protocol::{
+    CoapContentFormat, CoapMatch, CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode,
+};
+use libcoap_rs::types::CoapUri;
+
+#[test]
+pub fn coap_response_to_http_response() {
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Created).unwrap();
+    response
+        .set_location(Some("/sensors/42?v=1".parse::<CoapUri>().unwrap()))
+        .unwrap();
+    response.set_content_format(Some(CoapContentFormat::TextPlain as u16));
+    let http_response = response.into_http_response().unwrap();
+    assert_eq!(http_response.status(), StatusCode::CREATED);
+    assert_eq!(http_response.headers()["location"], "/sensors/42?v=1");
+    assert_eq!(http_response.headers()["content-type"], "text/plain; charset=utf-8");
+    assert!(http_response.headers().get("cache-control").is_none());
+    assert!(http_response.body().is_empty());
+
+    // Unknown content formats are still passed on to the HTTP client.
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response.set_content_format(Some(65000));
+    response.set_data(Some(vec![1, 2, 3]));
+    let http_response = response.into_http_response().unwrap();
+    assert_eq!(
+        http_response.headers()["content-type"],
+        "application/coap-payload; cf=65000"
+    );
+    assert_eq!(http_response.body(), &vec![1, 2, 3]);
+
+    for (code, status) in [
+        (CoapResponseCode::Valid, StatusCode::NOT_MODIFIED),
+        (CoapResponseCode::Unauthorized, StatusCode::FORBIDDEN),
+        (CoapResponseCode::NotAllowed, StatusCode::BAD_REQUEST),
+        (
+            CoapResponseCode::UnsupportedContentFormat,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ),
+        (CoapResponseCode::ProxyingNotSupported, StatusCode::BAD_GATEWAY),
+    ] {
+        let response = CoapResponse::new(CoapMessageType::Ack, code).unwrap();
+        assert_eq!(response.into_http_response().unwrap().status(), status);
+    }
+
+    let response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Continue).unwrap();
+    assert!(matches!(
+        response.into_http_response(),
+        Err(ProxyError::UnsupportedResponseCode(CoapResponseCode::Continue))
+    ));
+
+    // The message code can be changed to a non-response code using the underlying message.
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response
+        .as_message_mut()
+        .set_code(CoapMessageCode::Request(CoapRequestCode::Get));
+    assert!(matches!(
+        response.into_http_response(),
+        Err(ProxyError::InvalidMessageCode(CoapMessageCode::Request(
+            CoapRequestCode::Get
+        )))
+    ));
+}
+
+#[test]
+pub fn http_request_to_coap_request() {
+    let http_request = http::Request::builder()
+        .method(Method::PUT)
+        .uri("http://proxy.example.com/sensors/42?unit=celsius")
+        .header("Content-Type", "application/json")
+        .header("Accept", "image/png, application/cbor;q=0.9, application/json;q=0.5")
+        .header("If-Match", "\"1337\", \"abcd\"")
+        .body(b"{\"value\": 21.5}".to_vec())
+        .unwrap();
+    let request = CoapRequest::from_http_request(http_request).unwrap();
+    assert_eq!(request.code(), CoapMessageCode::Request(CoapRequestCode::Put));
+    assert_eq!(request.uri().path(), Some("sensors/42".as_bytes()));
+    assert_eq!(request.uri().query(), Some("unit=celsius".as_bytes()));
+    assert_eq!(request.content_format(), Some(CoapContentFormat::Json as u16));
+    assert_eq!(request.accept(), Some(CoapContentFormat::Cbor as u16));
+    assert_eq!(
+        request.if_match(),
+        Some(&vec![
+            CoapMatch::ETag(vec![0x13, 0x37].into_boxed_slice()),
+            CoapMatch::ETag(vec![0xab, 0xcd].into_boxed_slice())
+        ])
+    );
+    assert_eq!(request.data(), Some(b"{\"value\": 21.5}".as_slice()));
+
+    // ETags sent to HTTP clients can be used for cache validation.
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response.set_etag(Some(vec![0x00, 0xff, 0x42].into_boxed_slice()));
+    let http_response = response.into_http_response().unwrap();
+    let http_request = http::Request::builder()
+        .uri("/sensors/42")
+        .header("If-None-Match", http_response.headers()["etag"].clone())
+        .body(Vec::new())
+        .unwrap();
+    let request = CoapRequest::from_http_request(http_request).unwrap();
+    assert_eq!(request.code(), CoapMessageCode::Request(CoapRequestCode::Get));
+    assert_eq!(request.etag(), Some(&vec![vec![0x00, 0xff, 0x42].into_boxed_slice()]));
+    assert!(!request.if_none_match());
+    assert_eq!(request.data(), None);
+
+    let http_request = http::Request::builder()
+        .method(Method::PUT)
+        .uri("/sensors/43")
+        .header("If-None-Match", "*")
+        .header("Content-Type", "Text/Plain")
+        .body(b"21.5".to_vec())
+        .unwrap();
+    let request = CoapRequest::from_http_request(http_request).unwrap();
+    assert!(request.if_none_match());
+    assert_eq!(request.content_format(), Some(CoapContentFormat::TextPlain as u16));
+
+    let http_request = http::Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/sensors")
+        .body(Vec::new())
+        .unwrap();
+    assert!(matches!(
+        CoapRequest::from_http_request(http_request),
+        Err(ProxyError::UnsupportedMethod(Method::OPTIONS))
+    ));
+
+    let http_request = http::Request::builder()
+        .method(Method::POST)
+        .uri("/sensors")
+        .header("Content-Type", "image/png")
+        .body(vec![0x89, 0x50])
+        .unwrap();
+    assert!(matches!(
+        CoapRequest::from_http_request(http_request),
+        Err(ProxyError::UnsupportedMediaType(_))
+    ));
+}
+
+#[test]
+pub fn http_request_with_target_to_coap_request() {
+    for (method, code, body) in [
+        (Method::GET, CoapRequestCode::Get, Vec::new()),
+        (Method::POST, CoapRequestCode::Post, b"21.5".to_vec()),
+        (Method::PUT, CoapRequestCode::Put, b"22.0".to_vec()),
+        (Method::DELETE, CoapRequestCode::Delete, Vec::new()),
+    ] {
+        let http_request = http::Request::builder()
+            .method(method)
+            .uri("http://proxy.example.com/coap/sensor.example.com/temperature")
+            .header("Content-Type", "text/plain")
+            .body(body.clone())
+            .unwrap();
+        let target_uri = "coap://sensor.example.com/temperature?unit=celsius"
+            .parse::<CoapUri>()
+            .unwrap();
+        let request = CoapRequest::from_http_request_with_target(http_request, target_uri).unwrap();
+        assert_eq!(request.code(), CoapMessageCode::Request(code));
+        assert_eq!(request.uri().path(), Some("temperature".as_bytes()));
+        assert_eq!(request.uri().query(), Some("unit=celsius".as_bytes()));
+        assert_eq!(request.content_format(), Some(CoapContentFormat::TextPlain as u16));
+        assert_eq!(request.data(), (!body.is_empty()).then_some(body.as_slice()));
+    }
+
+    for method in [Method::HEAD, Method::OPTIONS, Method::TRACE] {
+        let http_request = http::Request::builder()
+            .method(method.clone())
+            .uri("/temperature")
+            .body(Vec::new())
+            .unwrap();
+        let target_uri = "coap://sensor.example.com/temperature".parse::<CoapUri>().unwrap();
+        assert!(matches!(
+            CoapRequest::from_http_request_with_target(http_request, target_uri),
+            Err(ProxyError::UnsupportedMethod(m)) if m == method
+        ));
+    }
+}
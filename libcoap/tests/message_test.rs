@@ -0,0 +1,1431 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * message_test.rs - Tests for message construction and validation.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use libcoap_rs::error::{
+    HopLimitError, MessageCodeError, MessageConversionError, MessageTypeError, ObserveError, OptionValueError,
+    PathMatchError, SessionError, UriParsingError,
+};
+use libcoap_rs::message::{
+    CoapMessage, CoapMessageCommon, CoapOption, CoapRequest, CoapResponse, TruncationResult, DEFAULT_MAX_OPTIONS,
+};
+use libcoap_rs::protocol::{
+    CoapContentFormat, CoapMatch, CoapMessageCode, CoapMessageType, CoapOptionType, CoapRequestCode, CoapResponseCode,
+    CoapToken, Observe, ObserveAction,
+};
+use libcoap_rs::session::{CoapClientSession, CoapSessionCommon};
+use libcoap_rs::types::{CoapMessageIdCounter, CoapUri};
+use libcoap_rs::CoapContext;
+use libcoap_sys::{coap_delete_pdu, coap_pdu_code_t, coap_pdu_init, coap_pdu_parse, coap_pdu_type_t, coap_proto_t};
+
+mod common;
+
+#[test]
+pub fn repeated_non_repeatable_option_rejected_on_send() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let mut request = common::gen_test_request();
+    request.add_option(CoapOption::ContentFormat(CoapContentFormat::TextPlain as u16));
+    request.add_option(CoapOption::ContentFormat(CoapContentFormat::Json as u16));
+
+    assert!(matches!(
+        session.send_request(request),
+        Err(SessionError::MessageConversion(
+            MessageConversionError::NonRepeatableOptionRepeated(CoapOptionType::ContentFormat)
+        ))
+    ));
+}
+
+#[test]
+pub fn observe_registration_requests() {
+    let mut request = common::gen_test_request();
+    assert!(!request.is_observe_registration());
+    assert!(!request.is_observe_deregistration());
+
+    request.as_observe_registration().unwrap();
+    assert!(request.is_observe_registration());
+    assert!(!request.is_observe_deregistration());
+
+    request.as_observe_deregistration().unwrap();
+    assert!(!request.is_observe_registration());
+    assert!(request.is_observe_deregistration());
+
+    let uri = "/test1".parse().unwrap();
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Post, uri).unwrap();
+    assert!(matches!(
+        request.as_observe_registration(),
+        Err(MessageConversionError::InvalidOptionForMessageType(
+            CoapOptionType::Observe
+        ))
+    ));
+    request.set_observe(Some(ObserveAction::Register));
+    assert!(!request.is_observe_registration());
+}
+
+#[test]
+pub fn observe_value_validation() {
+    assert_eq!(Observe::sequence(0xFF_FFFF), Ok(Observe::MAX));
+    assert_eq!(Observe::sequence(0x100_0000), Err(ObserveError::OutOfRange));
+    assert_eq!(Observe::from(ObserveAction::Register), Observe::REGISTER);
+    assert_eq!(
+        ObserveAction::try_from(Observe::DEREGISTER),
+        Ok(ObserveAction::Deregister)
+    );
+    assert_eq!(
+        ObserveAction::try_from(Observe::sequence(2).unwrap()),
+        Err(ObserveError::InvalidRequestValue(2))
+    );
+
+    let mut response = CoapResponse::new(CoapMessageType::Non, CoapResponseCode::Content).unwrap();
+    response.set_observe(Some(Observe::MAX));
+    assert_eq!(response.observe(), Some(Observe::MAX));
+    response.set_observe_sequence(Observe::MAX.value() + 1);
+    assert_eq!(response.observe(), Some(Observe::sequence(0).unwrap()));
+    assert_eq!(
+        CoapOption::Observe(Observe::MAX).into_value_bytes().as_deref(),
+        Ok([0xFF, 0xFF, 0xFF].as_slice())
+    );
+
+    let uri = "/test1".parse().unwrap();
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.set_observe(Some(ObserveAction::Deregister));
+    assert_eq!(request.observe(), Some(ObserveAction::Deregister));
+    assert!(request.is_observe_deregistration());
+    request.set_observe(None);
+    assert_eq!(request.observe(), None);
+
+    // Requests received with other values are rejected.
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, common::get_unused_server_addr()).unwrap();
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapRequestCode::Get.into());
+    message.add_option(CoapOption::Observe(Observe::sequence(5).unwrap()));
+    assert!(matches!(
+        CoapRequest::from_message(message, &session),
+        Err(MessageConversionError::InvalidOptionValue(
+            Some(CoapOptionType::Observe),
+            OptionValueError::IllegalValue
+        ))
+    ));
+}
+
+#[test]
+pub fn other_option_validation() {
+    assert_eq!(
+        CoapOption::other(CoapOptionType::ContentFormat as u16, vec![0u8]),
+        Err(OptionValueError::KnownOptionNumber(CoapOptionType::ContentFormat))
+    );
+    assert_eq!(
+        CoapOption::other(65000, vec![0u8; 65535 + 270]),
+        Err(OptionValueError::TooLong)
+    );
+
+    let option = CoapOption::other(65000, vec![1u8, 2, 3]).unwrap();
+    assert_eq!(option, CoapOption::Other(65000, vec![1u8, 2, 3].into_boxed_slice()));
+    assert_eq!(option.number(), 65000);
+}
+
+#[test]
+pub fn message_bytes_round_trip() {
+    let mut message = CoapMessage::new(
+        CoapMessageType::Ack,
+        CoapMessageCode::Response(CoapResponseCode::Content),
+    );
+    message.set_mid(Some(0x4711));
+    message.set_token(Some(vec![1u8, 2, 3, 4]));
+    message.add_option(CoapOption::Other(2000, vec![0u8; 300].into_boxed_slice()));
+    message.add_option(CoapOption::ContentFormat(CoapContentFormat::TextPlain as u16));
+    message.add_option(CoapOption::LocationPath("a".repeat(20)));
+    message.set_data(Some("Hello World!".as_bytes()));
+
+    let bytes = message.to_bytes().unwrap();
+    assert_eq!(bytes[..4], [0x64, 0x45, 0x47, 0x11]);
+    assert_eq!(bytes[bytes.len() - 13..], *b"\xFFHello World!");
+
+    let parsed = CoapMessage::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed.type_(), message.type_());
+    assert_eq!(parsed.code(), message.code());
+    assert_eq!(parsed.mid(), message.mid());
+    assert_eq!(parsed.token(), message.token());
+    assert_eq!(parsed.data(), message.data());
+    // Options are sorted by their number when serializing.
+    let mut options: Vec<CoapOption> = message.options_iter().cloned().collect();
+    options.sort_by_key(CoapOption::number);
+    assert_eq!(parsed.options_iter().cloned().collect::<Vec<_>>(), options);
+    assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+    assert_eq!(
+        CoapMessage::from_bytes(&[0x41, 0x01, 0x12]),
+        Err(MessageConversionError::MalformedMessage)
+    );
+    message.set_token(Some(vec![0u8; 9]));
+    assert_eq!(message.to_bytes(), Err(MessageConversionError::TokenTooLong));
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+pub fn message_bytes_zero_copy() {
+    let mut message = CoapMessage::new(CoapMessageType::Non, CoapMessageCode::Request(CoapRequestCode::Post));
+    message.set_mid(Some(1));
+    message.set_data(Some("Hello World!".as_bytes()));
+    let buffer = bytes::Bytes::try_from(message).unwrap();
+
+    let parsed = CoapMessage::try_from(buffer.clone()).unwrap();
+    let data = parsed.data_as_bytes().unwrap();
+    assert_eq!(data, "Hello World!".as_bytes());
+    // The message body references the received buffer instead of a copy of it.
+    assert_eq!(data.as_ptr(), buffer[buffer.len() - data.len()..].as_ptr());
+    assert_eq!(parsed.data().unwrap().as_ptr(), data.as_ptr());
+}
+
+#[test]
+pub fn empty_ack_and_rst_construction() {
+    let mut request = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    request.set_mid(Some(0x1234));
+    request.set_token(Some(vec![0xABu8]));
+
+    let ack = CoapMessage::new_ack(&request).unwrap();
+    assert_eq!(ack.type_(), CoapMessageType::Ack);
+    assert_eq!(ack.code(), CoapMessageCode::Empty);
+    assert_eq!(ack.mid(), Some(0x1234));
+    assert_eq!(ack.token().map(CoapToken::as_bytes), Some([].as_slice()));
+    assert_eq!(ack.to_bytes().unwrap(), [0x60, 0x00, 0x12, 0x34]);
+
+    let rst = CoapMessage::new_rst(&request).unwrap();
+    assert_eq!(rst.type_(), CoapMessageType::Rst);
+    assert_eq!(rst.code(), CoapMessageCode::Empty);
+    assert_eq!(rst.mid(), Some(0x1234));
+
+    request.set_type_(CoapMessageType::Non);
+    assert_eq!(
+        CoapMessage::new_ack(&request),
+        Err(MessageTypeError::InvalidForReplyTo(CoapMessageType::Non))
+    );
+    assert!(CoapMessage::new_rst(&request).is_ok());
+    assert_eq!(
+        CoapMessage::new_rst(&ack),
+        Err(MessageTypeError::InvalidForReplyTo(CoapMessageType::Ack))
+    );
+}
+
+#[test]
+pub fn option_value_validation_bounds() {
+    let validate = |option: CoapOption| CoapOptionType::validate_value(&option);
+
+    assert_eq!(validate(CoapOption::UriPort(0)), Err(OptionValueError::IllegalValue));
+    assert_eq!(validate(CoapOption::UriPort(1)), Ok(()));
+    assert_eq!(validate(CoapOption::UriPort(u16::MAX)), Ok(()));
+
+    assert_eq!(validate(CoapOption::HopLimit(0)), Err(OptionValueError::IllegalValue));
+    assert_eq!(validate(CoapOption::HopLimit(1)), Ok(()));
+    assert_eq!(validate(CoapOption::HopLimit(255)), Ok(()));
+    assert_eq!(validate(CoapOption::HopLimit(256)), Err(OptionValueError::IllegalValue));
+
+    assert_eq!(validate(CoapOption::UriPath("a".repeat(255))), Ok(()));
+    assert_eq!(
+        validate(CoapOption::UriPath("a".repeat(256))),
+        Err(OptionValueError::TooLong)
+    );
+    assert_eq!(validate(CoapOption::UriQuery("a".repeat(255))), Ok(()));
+    assert_eq!(
+        validate(CoapOption::UriQuery("a".repeat(256))),
+        Err(OptionValueError::TooLong)
+    );
+
+    assert_eq!(validate(CoapOption::UriHost("example.com".to_string())), Ok(()));
+    assert_eq!(validate(CoapOption::UriHost("192.0.2.1".to_string())), Ok(()));
+    assert_eq!(validate(CoapOption::UriHost("[2001:db8::1]".to_string())), Ok(()));
+    assert_eq!(validate(CoapOption::UriHost("2001:db8::1".to_string())), Ok(()));
+    assert_eq!(validate(CoapOption::UriHost("a".repeat(63))), Ok(()));
+    assert_eq!(
+        validate(CoapOption::UriHost("a".repeat(64))),
+        Err(OptionValueError::IllegalValue)
+    );
+    assert_eq!(
+        validate(CoapOption::UriHost("-example.com".to_string())),
+        Err(OptionValueError::IllegalValue)
+    );
+    assert_eq!(
+        validate(CoapOption::UriHost("[2001:db8::g]".to_string())),
+        Err(OptionValueError::IllegalValue)
+    );
+    assert_eq!(
+        validate(CoapOption::UriHost("exam_ple.com".to_string())),
+        Err(OptionValueError::InvalidCharacter)
+    );
+
+    let mut request = common::gen_test_request();
+    request.add_option(CoapOption::HopLimit(0));
+    let server_address = common::get_unused_server_addr();
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    assert!(matches!(
+        session.send_request(request),
+        Err(SessionError::MessageConversion(
+            MessageConversionError::InvalidOptionValue(Some(CoapOptionType::HopLimit), OptionValueError::IllegalValue)
+        ))
+    ));
+}
+
+#[test]
+pub fn option_value_bytes_borrowing() {
+    let etag = CoapOption::ETag(vec![0x01u8, 0x02, 0x03].into_boxed_slice());
+    let CoapOption::ETag(etag_value) = &etag else {
+        unreachable!()
+    };
+    // Borrowing the value of an opaque option must not allocate a new buffer.
+    match etag.value_bytes() {
+        Cow::Borrowed(value) => assert_eq!(value.as_ptr(), etag_value.as_ptr()),
+        Cow::Owned(_) => panic!("value of opaque option was copied"),
+    }
+
+    let path = CoapOption::UriPath("test1".to_string());
+    assert!(matches!(path.value_bytes(), Cow::Borrowed(b"test1")));
+    assert!(matches!(CoapOption::IfNoneMatch.value_bytes(), Cow::Borrowed([])));
+
+    let max_age = CoapOption::MaxAge(0x012345);
+    assert!(matches!(max_age.value_bytes(), Cow::Owned(_)));
+    assert_eq!(max_age.value_bytes().as_ref(), [0x01, 0x23, 0x45]);
+
+    for option in [
+        etag,
+        path,
+        max_age,
+        CoapOption::UriPort(5683),
+        CoapOption::NoResponse(2),
+    ] {
+        assert_eq!(*option.value_bytes(), *option.clone().into_value_bytes().unwrap());
+    }
+}
+
+#[test]
+pub fn response_total_size() {
+    const BODY_SIZE: usize = 100 * 1024;
+
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response.set_data(Some(vec![0u8; BODY_SIZE]));
+    assert_eq!(response.total_size(), None);
+    // Size2 is not added automatically, even if the body requires a blockwise transfer.
+    assert!(!response
+        .clone()
+        .into_message()
+        .options_iter()
+        .any(|v| matches!(v, CoapOption::Size2(_))));
+    response.set_include_total_size(true);
+    let message = response.into_message();
+    assert_eq!(
+        message
+            .options_iter()
+            .filter(|v| **v == CoapOption::Size2(BODY_SIZE as u32))
+            .count(),
+        1
+    );
+
+    // A client receiving the first block can use Size2 to determine the transfer progress.
+    let response = CoapResponse::from_message(message).unwrap();
+    let received_bytes = 1024;
+    let progress = received_bytes * 100 / response.total_size().unwrap();
+    assert_eq!(progress, 1);
+
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response.set_data(Some(vec![0u8; 16]));
+    assert!(!response
+        .clone()
+        .into_message()
+        .options_iter()
+        .any(|v| matches!(v, CoapOption::Size2(_))));
+    response.set_total_size(BODY_SIZE).unwrap();
+    assert_eq!(response.total_size(), Some(BODY_SIZE));
+    assert!(response
+        .into_message()
+        .options_iter()
+        .any(|v| *v == CoapOption::Size2(BODY_SIZE as u32)));
+
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    assert_eq!(response.set_total_size(u32::MAX as usize), Ok(()));
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(
+        response.set_total_size(u32::MAX as usize + 1),
+        Err(OptionValueError::TooLong)
+    );
+}
+
+#[test]
+pub fn create_only_put_request() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let uri = "/test1".parse().unwrap();
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Put, uri).unwrap();
+    request.set_data(Some("created".as_bytes()));
+    request.set_if_none_match(true);
+    assert!(request.if_none_match());
+
+    let count_if_none_match = |message: &CoapMessage| {
+        message
+            .options_iter()
+            .filter(|v| matches!(v, CoapOption::IfNoneMatch))
+            .count()
+    };
+    let message = request.into_message();
+    assert_eq!(count_if_none_match(&message), 1);
+
+    // Parsing and re-encoding the request must not duplicate the option.
+    let request = CoapRequest::from_message(message, &session).unwrap();
+    assert!(request.if_none_match());
+    let mut request = CoapRequest::from_message(request.into_message(), &session).unwrap();
+    request.set_if_none_match(false);
+    assert_eq!(count_if_none_match(&request.into_message()), 0);
+}
+
+#[test]
+pub fn raw_option_bytes_access() {
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    message.add_option(CoapOption::Other(65000, vec![1, 2, 3].into()));
+    message.add_option(CoapOption::UriPath("sensors".to_string()));
+    message.add_option(CoapOption::Other(65000, vec![4, 5].into()));
+    message.add_option(CoapOption::UriPort(5683));
+
+    assert_eq!(message.raw_option_bytes(65000).as_deref(), Some([1, 2, 3].as_slice()));
+    assert_eq!(
+        message.raw_option_bytes_all(65000).collect::<Vec<_>>(),
+        vec![[1, 2, 3].as_slice(), [4, 5].as_slice()]
+    );
+    assert_eq!(
+        message.raw_option_bytes(CoapOptionType::UriPath as u16).as_deref(),
+        Some("sensors".as_bytes())
+    );
+    // Integer options are encoded on demand (5683 = 0x1633).
+    assert_eq!(
+        message.raw_option_bytes(CoapOptionType::UriPort as u16).as_deref(),
+        Some([0x16, 0x33].as_slice())
+    );
+    assert_eq!(message.raw_option_bytes(65001), None);
+    assert_eq!(message.raw_option_bytes_all(65001).count(), 0);
+}
+
+#[test]
+pub fn response_payload_as_str() {
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    assert_eq!(response.payload(), None);
+    assert_eq!(response.payload_as_str(), Ok(None));
+
+    response.set_data(Some("{\"temperature\": 22.5}".as_bytes()));
+    assert_eq!(response.payload(), Some("{\"temperature\": 22.5}".as_bytes()));
+    assert_eq!(response.payload_as_str(), Ok(Some("{\"temperature\": 22.5}")));
+
+    // Parsing the response from a message must preserve the body.
+    let response = CoapResponse::from_message(response.into_message()).unwrap();
+    assert_eq!(response.payload_as_str(), Ok(Some("{\"temperature\": 22.5}")));
+
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response.set_data(Some(vec![0x48, 0x69, 0xFF, 0xFE]));
+    assert_eq!(response.payload(), Some([0x48, 0x69, 0xFF, 0xFE].as_slice()));
+    let error = response.payload_as_str().unwrap_err();
+    assert_eq!(error.valid_up_to(), 2);
+}
+
+#[test]
+pub fn response_block1_continue() {
+    // Block number 5, more blocks follow, block size 256 bytes (SZX 4).
+    const BLOCK1: u32 = (5 << 4) | 0x08 | 4;
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Changed).unwrap();
+    response.add_option(CoapOption::Block1(0x08));
+    response.set_block1_continue(BLOCK1).unwrap();
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Continue));
+    let block1_options: Vec<&CoapOption> = response
+        .options_iter()
+        .filter(|option| matches!(option, CoapOption::Block1(_)))
+        .collect();
+    assert_eq!(block1_options, vec![&CoapOption::Block1(BLOCK1)]);
+
+    // The option must survive encoding the response.
+    let response = CoapResponse::from_message(response.into_message()).unwrap();
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Continue));
+    assert!(response
+        .options_iter()
+        .any(|option| *option == CoapOption::Block1(BLOCK1)));
+
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Changed).unwrap();
+    // The last block must be answered with the actual response.
+    assert_eq!(
+        response.set_block1_continue((5 << 4) | 4),
+        Err(OptionValueError::IllegalValue)
+    );
+    // BERT blocks are not supported.
+    assert_eq!(
+        response.set_block1_continue((5 << 4) | 0x08 | 7),
+        Err(OptionValueError::IllegalValue)
+    );
+    assert_eq!(
+        response.set_block1_continue((1 << 24) | 0x08 | 4),
+        Err(OptionValueError::TooLong)
+    );
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Changed));
+    assert!(response.options_iter().next().is_none());
+}
+
+#[test]
+pub fn reset_and_empty_ack_detection() {
+    let mut request = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    request.set_mid(Some(7));
+    assert!(!request.is_reset());
+    assert!(!request.is_empty_ack());
+
+    let rst = CoapMessage::new_rst(&request).unwrap();
+    assert!(rst.is_reset());
+    assert!(!rst.is_empty_ack());
+    let ack = CoapMessage::new_ack(&request).unwrap();
+    assert!(ack.is_empty_ack());
+    assert!(!ack.is_reset());
+
+    // Piggybacked responses are not empty acknowledgements.
+    let response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    assert!(!response.into_message().is_empty_ack());
+}
+
+#[test]
+pub fn request_content_negotiation() {
+    const OFFERED: [u16; 2] = [CoapContentFormat::Cbor as u16, CoapContentFormat::Json as u16];
+
+    // Without an Accept option, any content format is acceptable.
+    let mut request = common::gen_test_request();
+    assert!(request.accepted_content_formats().is_empty());
+    assert_eq!(
+        request.best_content_format(&OFFERED),
+        Some(CoapContentFormat::Cbor as u16)
+    );
+    assert_eq!(request.best_content_format(&[]), None);
+
+    request.set_accept(Some(CoapContentFormat::Json as u16));
+    assert_eq!(request.accepted_content_formats(), vec![CoapContentFormat::Json as u16]);
+    assert_eq!(
+        request.best_content_format(&OFFERED),
+        Some(CoapContentFormat::Json as u16)
+    );
+    assert_eq!(request.best_content_format(&OFFERED[..1]), None);
+
+    // Additional Accept options are considered in the order they were added.
+    request.set_accept(Some(CoapContentFormat::TextPlain as u16));
+    request.add_option(CoapOption::Accept(CoapContentFormat::Json as u16));
+    request.add_option(CoapOption::Accept(CoapContentFormat::Cbor as u16));
+    assert_eq!(
+        request.accepted_content_formats(),
+        vec![
+            CoapContentFormat::TextPlain as u16,
+            CoapContentFormat::Json as u16,
+            CoapContentFormat::Cbor as u16
+        ]
+    );
+    assert_eq!(
+        request.best_content_format(&OFFERED),
+        Some(CoapContentFormat::Json as u16)
+    );
+    assert_eq!(
+        request.best_content_format(&[CoapContentFormat::ApplicationXml as u16]),
+        None
+    );
+}
+
+#[test]
+pub fn message_id_counter_generates_unique_ids() {
+    const THREADS: usize = 4;
+    const IDS_PER_THREAD: usize = 2500;
+    // Start close to the upper limit to also cover the wrap-around.
+    let counter = Arc::new(CoapMessageIdCounter::with_initial_message_id(u16::MAX - 1000));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let counter = Arc::clone(&counter);
+            std::thread::spawn(move || {
+                (0..IDS_PER_THREAD)
+                    .map(|_| counter.next_message_id())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let mut ids = HashSet::new();
+    for handle in handles {
+        for id in handle.join().unwrap() {
+            assert!((0..=i32::from(u16::MAX)).contains(&id));
+            assert!(ids.insert(id), "message ID {id} was generated twice");
+        }
+    }
+    assert_eq!(ids.len(), THREADS * IDS_PER_THREAD);
+}
+
+#[test]
+pub fn response_to_message() {
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response.set_mid(Some(0x1234));
+    response.set_token(Some(vec![0xAB]));
+    response.set_content_format(Some(CoapContentFormat::Json as u16));
+    response.set_max_age(Some(30));
+    response.set_data(Some(b"{}".to_vec()));
+
+    let message = response.to_message();
+    // The response is still usable afterwards.
+    assert_eq!(response.content_format(), Some(CoapContentFormat::Json as u16));
+    let expected = response.into_message().to_bytes().unwrap();
+    assert_eq!(message.to_bytes().unwrap(), expected);
+}
+
+#[test]
+pub fn raw_pdu_lossy_parsing() {
+    // CON GET request with message ID 0x1234, token 0xAB, a Uri-Path option containing invalid
+    // UTF-8 and a valid Uri-Query option "a=b".
+    let bytes = [0x41, 0x01, 0x12, 0x34, 0xAB, 0xB2, 0xFF, 0xFE, 0x43, b'a', b'=', b'b'];
+    assert!(matches!(
+        CoapMessage::from_bytes(&bytes),
+        Err(MessageConversionError::InvalidOptionValue(
+            Some(CoapOptionType::UriPath),
+            _
+        ))
+    ));
+
+    // SAFETY: The PDU pointer is checked for validity before use and deleted afterwards.
+    let message = unsafe {
+        let pdu = coap_pdu_init(
+            coap_pdu_type_t::COAP_MESSAGE_CON,
+            coap_pdu_code_t::COAP_EMPTY_CODE,
+            0,
+            bytes.len(),
+        );
+        assert!(!pdu.is_null());
+        assert_ne!(
+            coap_pdu_parse(coap_proto_t::COAP_PROTO_UDP, bytes.as_ptr(), bytes.len(), pdu),
+            0
+        );
+        assert!(CoapMessage::from_raw_pdu(pdu).is_err());
+        let message = CoapMessage::from_raw_pdu_lossy(pdu);
+        coap_delete_pdu(pdu);
+        message
+    };
+    assert_eq!(message.code(), CoapMessageCode::Request(CoapRequestCode::Get));
+    assert_eq!(message.mid(), Some(0x1234));
+    let options: Vec<&CoapOption> = message.options_iter().collect();
+    assert_eq!(
+        options,
+        vec![
+            &CoapOption::Other(CoapOptionType::UriPath as u16, Box::new([0xFF, 0xFE])),
+            &CoapOption::UriQuery("a=b".to_string()),
+        ]
+    );
+    assert!(message.has_lossy_options());
+    assert_eq!(message.lossy_option_numbers(), vec![CoapOptionType::UriPath as u16]);
+
+    let valid = CoapMessage::from_bytes(&[0x41, 0x01, 0x12, 0x34, 0xAB, 0xB4, b't', b'e', b's', b't']).unwrap();
+    assert!(!valid.has_lossy_options());
+    assert!(valid.lossy_option_numbers().is_empty());
+}
+
+#[test]
+pub fn request_path_template_matching() {
+    let request =
+        |path: &str| CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, path.parse().unwrap()).unwrap();
+
+    // Exact matches of templates without parameters.
+    let sensors = request("/sensors/temperature");
+    assert!(sensors.extract_path_params("/sensors/temperature").unwrap().is_empty());
+    assert!(sensors.matches_template("sensors/temperature"));
+
+    // Templates containing multiple parameters.
+    let readings = request("/sensors/42/readings/humidity");
+    let params = readings.extract_path_params("/sensors/{id}/readings/{type}").unwrap();
+    assert_eq!(params.len(), 2);
+    assert_eq!(params["id"], "42");
+    assert_eq!(params["type"], "humidity");
+    assert!(readings.matches_template("/{a}/{b}/{c}/{d}"));
+
+    // Mismatching segment counts and literals.
+    assert_eq!(
+        readings.extract_path_params("/sensors/{id}"),
+        Err(PathMatchError::NoMatch)
+    );
+    assert_eq!(
+        readings.extract_path_params("/actuators/{id}/readings/{type}"),
+        Err(PathMatchError::NoMatch)
+    );
+    assert!(!sensors.matches_template("/sensors/temperature/{unit}"));
+    assert!(!request("/").matches_template("/sensors"));
+    assert!(request("/").matches_template("/"));
+
+    // Malformed templates are rejected regardless of the requested path.
+    for template in ["/sensors/{}", "/sensors/{id", "/sensors/id}", "/{id}/{id}", "/x{id}"] {
+        assert!(matches!(
+            readings.extract_path_params(template),
+            Err(PathMatchError::InvalidTemplate(_))
+        ));
+        assert!(!readings.matches_template(template));
+    }
+}
+
+#[test]
+pub fn percent_encoded_path_segments() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let uri = "/files/a%2Fb?name=x%26y".parse().unwrap();
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    assert_eq!(request.uri().decoded_path_segments(), vec!["files", "a/b"]);
+    assert_eq!(request.extract_path_params("/files/{name}").unwrap()["name"], "a/b");
+
+    // On the wire, the encoded slash is part of a single Uri-Path option (and the encoded
+    // ampersand part of a single Uri-Query option).
+    let message = request.into_message();
+    let options: Vec<_> = message
+        .options_iter()
+        .filter(|v| matches!(v, CoapOption::UriPath(_) | CoapOption::UriQuery(_)))
+        .cloned()
+        .collect();
+    assert_eq!(
+        options,
+        vec![
+            CoapOption::UriPath("files".to_string()),
+            CoapOption::UriPath("a/b".to_string()),
+            CoapOption::UriQuery("name=x&y".to_string()),
+        ]
+    );
+
+    // Parsing and re-encoding the request must preserve the option values (i.e., the values are
+    // neither split nor decoded a second time).
+    let request = CoapRequest::from_message(message, &session).unwrap();
+    assert_eq!(request.uri().decoded_path_segments(), vec!["files", "a/b"]);
+    let reencoded: Vec<_> = request
+        .into_message()
+        .options_iter()
+        .filter(|v| matches!(v, CoapOption::UriPath(_) | CoapOption::UriQuery(_)))
+        .cloned()
+        .collect();
+    assert_eq!(reencoded, options);
+}
+
+#[test]
+pub fn response_etag_generation() {
+    let response = |payload: &str| {
+        let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+        response.set_data(Some(payload.as_bytes()));
+        response
+    };
+
+    // Identical payloads result in identical ETags, different payloads in different ones.
+    let mut first = response("21.5 Cel");
+    let mut second = response("21.5 Cel");
+    let mut third = response("22.0 Cel");
+    assert!(!first.etag_matches_payload());
+    first.compute_and_set_etag();
+    second.compute_and_set_etag();
+    third.compute_and_set_etag();
+    assert!((4..=8).contains(&first.etag().unwrap().len()));
+    assert_eq!(first.etag(), second.etag());
+    assert_ne!(first.etag(), third.etag());
+    assert!(first.etag_matches_payload());
+
+    // Changing the payload invalidates the generated ETag.
+    first.set_data(Some("23.0 Cel".as_bytes()));
+    assert!(!first.etag_matches_payload());
+
+    // Automatically generated ETags are based on the final payload.
+    let mut auto = response("placeholder");
+    auto.set_auto_etag(true);
+    auto.set_data(Some("22.0 Cel".as_bytes()));
+    let auto = CoapResponse::from_message(auto.into_message()).unwrap();
+    assert_eq!(auto.etag(), third.etag());
+    assert!(auto.etag_matches_payload());
+}
+
+#[test]
+pub fn option_count_limit() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    message.set_mid(Some(1));
+    assert_eq!(message.max_options(), DEFAULT_MAX_OPTIONS);
+    for _ in 0..DEFAULT_MAX_OPTIONS {
+        message.add_option(CoapOption::UriQuery("a".to_string()));
+    }
+    assert!(message.to_bytes().is_ok());
+
+    // One option past the limit is rejected both when serializing and sending the message.
+    message.add_option(CoapOption::UriQuery("a".to_string()));
+    assert_eq!(
+        message.to_bytes(),
+        Err(MessageConversionError::TooManyOptions(DEFAULT_MAX_OPTIONS + 1))
+    );
+    assert!(matches!(
+        session.send(message.clone()),
+        Err(SessionError::MessageConversion(MessageConversionError::TooManyOptions(
+            _
+        )))
+    ));
+
+    // The limit is configurable.
+    message.set_max_options(DEFAULT_MAX_OPTIONS + 1);
+    assert!(message.to_bytes().is_ok());
+    message.set_max_options(2);
+    assert_eq!(
+        message.to_bytes(),
+        Err(MessageConversionError::TooManyOptions(DEFAULT_MAX_OPTIONS + 1))
+    );
+}
+
+#[test]
+pub fn option_try_from_bytes() {
+    let valid: &[(CoapOptionType, &[u8], CoapOption)] = &[
+        (CoapOptionType::IfMatch, &[], CoapOption::IfMatch(CoapMatch::Empty)),
+        (
+            CoapOptionType::IfMatch,
+            &[0x01, 0x02],
+            CoapOption::IfMatch(CoapMatch::ETag(Box::new([0x01, 0x02]))),
+        ),
+        (
+            CoapOptionType::UriHost,
+            b"example.com",
+            CoapOption::UriHost("example.com".to_string()),
+        ),
+        (CoapOptionType::ETag, &[0xAB], CoapOption::ETag(Box::new([0xAB]))),
+        (CoapOptionType::IfNoneMatch, &[], CoapOption::IfNoneMatch),
+        (CoapOptionType::UriPort, &[0x16, 0x33], CoapOption::UriPort(5683)),
+        (
+            CoapOptionType::LocationPath,
+            b"loc",
+            CoapOption::LocationPath("loc".to_string()),
+        ),
+        (
+            CoapOptionType::UriPath,
+            b"test",
+            CoapOption::UriPath("test".to_string()),
+        ),
+        (CoapOptionType::ContentFormat, &[0x32], CoapOption::ContentFormat(50)),
+        (CoapOptionType::MaxAge, &[0x01, 0x00], CoapOption::MaxAge(256)),
+        (
+            CoapOptionType::UriQuery,
+            b"a=b",
+            CoapOption::UriQuery("a=b".to_string()),
+        ),
+        (CoapOptionType::Accept, &[], CoapOption::Accept(0)),
+        (
+            CoapOptionType::LocationQuery,
+            b"c=d",
+            CoapOption::LocationQuery("c=d".to_string()),
+        ),
+        (
+            CoapOptionType::ProxyUri,
+            b"coap://example.com",
+            CoapOption::ProxyUri("coap://example.com".to_string()),
+        ),
+        (
+            CoapOptionType::ProxyScheme,
+            b"coap",
+            CoapOption::ProxyScheme("coap".to_string()),
+        ),
+        (CoapOptionType::Size1, &[0x04, 0x00], CoapOption::Size1(1024)),
+        (CoapOptionType::Size2, &[0x04, 0x00], CoapOption::Size2(1024)),
+        (CoapOptionType::Block1, &[0x16], CoapOption::Block1(0x16)),
+        (CoapOptionType::Block2, &[0x01, 0x16], CoapOption::Block2(0x116)),
+        (CoapOptionType::HopLimit, &[0x10], CoapOption::HopLimit(16)),
+        (CoapOptionType::NoResponse, &[0x02], CoapOption::NoResponse(2)),
+        (
+            CoapOptionType::Observe,
+            &[0x01],
+            CoapOption::Observe(Observe::DEREGISTER),
+        ),
+        (CoapOptionType::Oscore, &[0x09], CoapOption::Oscore(Box::new([0x09]))),
+        (
+            CoapOptionType::Echo,
+            &[0x01, 0x02],
+            CoapOption::Echo(Box::new([0x01, 0x02])),
+        ),
+        (CoapOptionType::RTag, &[0x05], CoapOption::RTag(Box::new([0x05]))),
+        (CoapOptionType::QBlock1, &[0x0E], CoapOption::QBlock1(0x0E)),
+        (CoapOptionType::QBlock2, &[0x0E], CoapOption::QBlock2(0x0E)),
+    ];
+    for (opt_type, bytes, expected) in valid.iter().cloned() {
+        assert_eq!(CoapOption::try_from((opt_type, bytes)), Ok(expected.clone()));
+        assert_eq!(CoapOption::try_from((opt_type as u16, bytes)), Ok(expected));
+
+        // Values that are too short or too long for the option type are rejected.
+        if opt_type.min_len() > 0 {
+            let too_short = vec![b'a'; opt_type.min_len() - 1];
+            assert_eq!(
+                CoapOption::try_from((opt_type, too_short.as_slice())),
+                Err(OptionValueError::TooShort)
+            );
+        }
+        let too_long = vec![b'a'; opt_type.max_len() + 1];
+        assert_eq!(
+            CoapOption::try_from((opt_type, too_long.as_slice())),
+            Err(OptionValueError::TooLong)
+        );
+        assert_eq!(
+            CoapOption::try_from((opt_type as u16, too_long.as_slice())),
+            Err(OptionValueError::TooLong)
+        );
+    }
+
+    // String options must contain valid UTF-8.
+    assert!(matches!(
+        CoapOption::try_from((CoapOptionType::UriPath, [0xFF, 0xFE].as_slice())),
+        Err(OptionValueError::StringConversion(_))
+    ));
+    // Unknown option numbers are returned as is.
+    assert_eq!(
+        CoapOption::try_from((65000u16, b"custom".as_slice())),
+        Ok(CoapOption::Other(65000, Box::new(*b"custom")))
+    );
+}
+
+#[test]
+pub fn request_method_accessors() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let methods = [
+        CoapRequestCode::Get,
+        CoapRequestCode::Post,
+        CoapRequestCode::Put,
+        CoapRequestCode::Delete,
+        CoapRequestCode::Fetch,
+        CoapRequestCode::Patch,
+        CoapRequestCode::IPatch,
+    ];
+    for method in methods {
+        let mut request = CoapRequest::new(CoapMessageType::Con, method, "/test".parse().unwrap()).unwrap();
+        assert_eq!(request.method(), Some(method));
+        assert_eq!(request.code(), CoapMessageCode::Request(method));
+
+        // The method is preserved when the request is converted into a message and back.
+        let request_roundtrip = CoapRequest::from_message(request.clone().into_message(), &session).unwrap();
+        assert_eq!(request_roundtrip.method(), Some(method));
+
+        request.set_method(CoapRequestCode::Get);
+        assert_eq!(request.method(), Some(CoapRequestCode::Get));
+        request.set_method(method);
+        assert_eq!(request.code(), CoapMessageCode::Request(method));
+
+        // The message code can be changed to a non-request code using the underlying message.
+        request
+            .as_message_mut()
+            .set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+        assert_eq!(request.method(), None);
+    }
+
+    // Messages with a non-request code cannot be parsed into requests.
+    let response = CoapMessage::new(
+        CoapMessageType::Ack,
+        CoapMessageCode::Response(CoapResponseCode::Content),
+    );
+    assert_eq!(
+        CoapRequest::from_message(response, &session).err(),
+        Some(MessageConversionError::InvalidMessageCode(
+            MessageCodeError::NotARequestCode
+        ))
+    );
+}
+
+#[test]
+pub fn estimated_pdu_size_at_mtu_boundary() {
+    const MAX_PDU_SIZE: usize = 1024;
+
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Post));
+    message.set_mid(Some(1));
+    message.set_token(Some(vec![0xAB; 8]));
+    // Option delta 11 fits into the option header, length 20 requires one extended byte.
+    message.add_option(CoapOption::UriPath("a".repeat(20)));
+    // Header (4) + token (8) + option (1 + 1 + 20) + payload marker (1) = 35 bytes of overhead.
+    message.set_data(Some(vec![0; MAX_PDU_SIZE - 35]));
+    assert_eq!(message.estimated_pdu_size(), MAX_PDU_SIZE);
+    assert_eq!(message.to_bytes().unwrap().len(), MAX_PDU_SIZE);
+    assert!(message.fits_in_pdu(MAX_PDU_SIZE));
+
+    // One more byte of payload no longer fits.
+    message.set_data(Some(vec![0; MAX_PDU_SIZE - 34]));
+    assert_eq!(message.estimated_pdu_size(), MAX_PDU_SIZE + 1);
+    assert!(!message.fits_in_pdu(MAX_PDU_SIZE));
+
+    // Options with larger deltas require extended option header bytes.
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    message.set_mid(Some(1));
+    message.add_option(CoapOption::Size2(1024));
+    assert_eq!(message.estimated_pdu_size(), message.to_bytes().unwrap().len());
+
+    // The estimate for requests includes the options generated from their URI.
+    let mut request = CoapRequest::new(
+        CoapMessageType::Con,
+        CoapRequestCode::Put,
+        "/sensors/temperature?unit=celsius".parse().unwrap(),
+    )
+    .unwrap();
+    request.set_mid(Some(1));
+    request.set_token(Some(vec![0x01, 0x02]));
+    request.set_content_format(Some(CoapContentFormat::TextPlain as u16));
+    request.set_data(Some(b"21.5".as_slice()));
+    let encoded_len = request.clone().into_message().to_bytes().unwrap().len();
+    assert_eq!(request.estimated_pdu_size(), encoded_len);
+    assert!(request.fits_in_pdu(encoded_len));
+    assert!(!request.fits_in_pdu(encoded_len - 1));
+}
+
+#[test]
+pub fn truncate_message_to_fit() {
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Post));
+    message.set_mid(Some(1));
+    message.set_token(Some(vec![0x01, 0x02, 0x03, 0x04]));
+    message.add_option(CoapOption::UriPath("sensors".to_string()));
+    message.add_option(CoapOption::ContentFormat(CoapContentFormat::Json as u16));
+    message.add_option(CoapOption::Size1(100_000));
+    message.add_option(CoapOption::Other(2048, vec![0; 40].into_boxed_slice()));
+    message.set_data(Some(b"21.5".as_slice()));
+    // Header (4) + token (4) + Uri-Path (8) + Content-Format (2) + Size1 (5) + unknown elective
+    // option (44) + payload marker and payload (5).
+    assert_eq!(message.estimated_pdu_size(), 72);
+    let max_size = message.estimated_pdu_size() - 50;
+
+    // Messages that already fit are not changed.
+    let mut fitting = message.clone();
+    assert_eq!(fitting.truncate_to_fit(72), TruncationResult::Fits { dropped: 0 });
+    assert_eq!(fitting.options_iter().count(), 4);
+
+    // If the message cannot fit even without its elective options, it is left unchanged.
+    let mut too_large = message.clone();
+    assert_eq!(
+        too_large.truncate_to_fit(20),
+        TruncationResult::ImpossibleWithCriticalOptions
+    );
+    assert_eq!(too_large.estimated_pdu_size(), 72);
+
+    // All three elective options have to be dropped, the critical Uri-Path option is kept.
+    assert_eq!(message.truncate_to_fit(max_size), TruncationResult::Fits { dropped: 3 });
+    assert_eq!(message.estimated_pdu_size(), 21);
+    assert_eq!(message.to_bytes().unwrap().len(), 21);
+    let options: Vec<CoapOption> = message.options_iter().cloned().collect();
+    assert_eq!(options, vec![CoapOption::UriPath("sensors".to_string())]);
+    assert_eq!(message.token().map(CoapToken::as_bytes), Some([0x01, 0x02, 0x03, 0x04].as_slice()));
+    assert_eq!(message.data(), Some(b"21.5".as_slice()));
+
+    // Unknown critical options are never dropped.
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Get));
+    message.set_mid(Some(1));
+    message.add_option(CoapOption::Other(2049, vec![0; 40].into_boxed_slice()));
+    message.add_option(CoapOption::Size2(0));
+    assert_eq!(
+        message.truncate_to_fit(message.estimated_pdu_size() - 1),
+        TruncationResult::Fits { dropped: 1 }
+    );
+    assert!(matches!(
+        message.options_iter().collect::<Vec<_>>().as_slice(),
+        [CoapOption::Other(2049, _)]
+    ));
+}
+
+#[test]
+pub fn options_map_round_trip() {
+    let code = CoapMessageCode::Response(CoapResponseCode::Content);
+    let mut message = CoapMessage::new(CoapMessageType::Ack, code);
+    message.add_option(CoapOption::ETag(vec![0x01, 0x02].into_boxed_slice()));
+    message.add_option(CoapOption::ContentFormat(CoapContentFormat::Json as u16));
+    message.add_option(CoapOption::LocationPath("sensors".to_string()));
+    message.add_option(CoapOption::LocationPath("42".to_string()));
+    message.add_option(CoapOption::MaxAge(0));
+    message.add_option(CoapOption::Other(65000, vec![0xAB].into_boxed_slice()));
+    message.add_option(CoapOption::ETag(vec![0x03].into_boxed_slice()));
+    message.set_mid(Some(1));
+
+    let map = message.options_as_map().unwrap();
+    assert_eq!(
+        map.keys().copied().collect::<Vec<_>>(),
+        vec![
+            CoapOptionType::ETag as u16,
+            CoapOptionType::LocationPath as u16,
+            CoapOptionType::ContentFormat as u16,
+            CoapOptionType::MaxAge as u16,
+            65000
+        ]
+    );
+    assert_eq!(
+        map[&(CoapOptionType::ETag as u16)],
+        vec![vec![0x01, 0x02].into_boxed_slice(), vec![0x03].into_boxed_slice()]
+    );
+    // Zero values are encoded as empty option values.
+    assert_eq!(map[&(CoapOptionType::MaxAge as u16)], vec![Box::<[u8]>::default()]);
+
+    let mut rebuilt = CoapMessage::from_options_map(CoapMessageType::Ack, code, map.clone()).unwrap();
+    assert_eq!(rebuilt.options_as_map(), Ok(map));
+    assert_eq!(rebuilt.type_(), CoapMessageType::Ack);
+    assert_eq!(rebuilt.code(), code);
+    assert_eq!(rebuilt.mid(), None);
+    rebuilt.set_mid(Some(1));
+    assert_eq!(rebuilt.to_bytes().unwrap(), message.to_bytes().unwrap());
+
+    // Invalid values for known option numbers are rejected.
+    let mut invalid = BTreeMap::new();
+    invalid.insert(
+        CoapOptionType::UriPort as u16,
+        vec![vec![0x01, 0x02, 0x03].into_boxed_slice()],
+    );
+    assert_eq!(
+        CoapMessage::from_options_map(CoapMessageType::Con, CoapRequestCode::Get.into(), invalid).unwrap_err(),
+        OptionValueError::TooLong
+    );
+
+    // Options whose values cannot be encoded are reported instead of being omitted.
+    message.add_option(CoapOption::HopLimit(0));
+    assert_eq!(message.options_as_map(), Err(OptionValueError::IllegalValue));
+}
+
+#[test]
+pub fn request_method_constructors() {
+    let uri = "coap://example.com:5684/sensors/temp?unit=c&precision=2";
+    let expected_uri_options = [
+        CoapOption::UriHost("example.com".to_string()),
+        CoapOption::UriPort(5684),
+        CoapOption::UriPath("sensors".to_string()),
+        CoapOption::UriPath("temp".to_string()),
+        CoapOption::UriQuery("unit=c".to_string()),
+        CoapOption::UriQuery("precision=2".to_string()),
+    ];
+
+    let requests = [
+        (CoapRequest::get(uri).unwrap(), CoapRequestCode::Get, None),
+        (
+            CoapRequest::post(uri, b"21.5".to_vec()).unwrap(),
+            CoapRequestCode::Post,
+            Some(b"21.5".as_slice()),
+        ),
+        (
+            CoapRequest::put(uri, b"22.0".to_vec()).unwrap(),
+            CoapRequestCode::Put,
+            Some(b"22.0".as_slice()),
+        ),
+        (CoapRequest::delete(uri).unwrap(), CoapRequestCode::Delete, None),
+        (CoapRequest::fetch(uri).unwrap(), CoapRequestCode::Fetch, None),
+    ];
+    for (request, method, payload) in requests {
+        assert_eq!(request.type_(), CoapMessageType::Con);
+        assert_eq!(request.method(), Some(method));
+        assert_eq!(request.data(), payload);
+
+        let message = request.into_message();
+        let uri_options: Vec<&CoapOption> = message
+            .options_iter()
+            .filter(|option| {
+                matches!(
+                    option,
+                    CoapOption::UriHost(_) | CoapOption::UriPort(_) | CoapOption::UriPath(_) | CoapOption::UriQuery(_)
+                )
+            })
+            .collect();
+        assert_eq!(uri_options, expected_uri_options.iter().collect::<Vec<_>>());
+    }
+
+    assert!(matches!(
+        CoapRequest::get("ftp://example.com/sensors"),
+        Err(UriParsingError::NotACoapScheme(_))
+    ));
+    assert!(CoapRequest::post("ftp://example.com/sensors", b"data".to_vec()).is_err());
+}
+
+#[test]
+pub fn request_size_hint() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let mut request = CoapRequest::get("/sensors/temp").unwrap();
+    assert!(!request.size_hint_requested());
+    assert!(!request
+        .clone()
+        .into_message()
+        .options_iter()
+        .any(|v| matches!(v, CoapOption::Size2(_))));
+
+    request.request_size_hint();
+    assert!(request.size_hint_requested());
+    let message = request.into_message();
+    assert_eq!(
+        message.options_iter().filter(|v| **v == CoapOption::Size2(0)).count(),
+        1
+    );
+    assert!(CoapRequest::from_message(message, &session)
+        .unwrap()
+        .size_hint_requested());
+
+    // In requests, Size2 may only be used to ask for the total size.
+    let mut message = CoapRequest::get("/sensors/temp").unwrap().into_message();
+    message.add_option(CoapOption::Size2(1024));
+    assert!(matches!(
+        CoapRequest::from_message(message, &session),
+        Err(MessageConversionError::InvalidOptionValue(
+            Some(CoapOptionType::Size2),
+            OptionValueError::IllegalValue
+        ))
+    ));
+}
+
+#[test]
+pub fn duplicate_non_repeatable_options() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let mut message = CoapRequest::get("coap://example.com/sensors").unwrap().into_message();
+    assert!(!message.has_any_duplicates());
+    assert!(message.find_duplicate_non_repeatable_options().is_empty());
+
+    // Repeatable options may occur multiple times.
+    message.add_option(CoapOption::UriPath("temp".into()));
+    assert!(!message.has_any_duplicates());
+
+    message.add_option(CoapOption::Accept(CoapContentFormat::Json as u16));
+    message.add_option(CoapOption::Accept(CoapContentFormat::Cbor as u16));
+    message.add_option(CoapOption::UriHost("example.org".into()));
+    message.add_option(CoapOption::ContentFormat(CoapContentFormat::TextPlain as u16));
+    message.add_option(CoapOption::ContentFormat(CoapContentFormat::Json as u16));
+    message.add_option(CoapOption::Accept(CoapContentFormat::TextPlain as u16));
+    assert!(message.has_any_duplicates());
+    assert_eq!(
+        message.find_duplicate_non_repeatable_options(),
+        vec![
+            CoapOptionType::Accept,
+            CoapOptionType::UriHost,
+            CoapOptionType::ContentFormat
+        ]
+    );
+
+    // By default, parsing fails on the first repeated option it encounters.
+    assert!(matches!(
+        CoapRequest::from_message(message.clone(), &session),
+        Err(MessageConversionError::NonRepeatableOptionRepeated(_))
+    ));
+
+    let message = message.with_strict_parsing(true);
+    assert!(message.strict_parsing());
+    assert_eq!(
+        CoapRequest::from_message(message, &session).unwrap_err(),
+        MessageConversionError::NonRepeatableOptionsRepeated(vec![
+            CoapOptionType::Accept,
+            CoapOptionType::UriHost,
+            CoapOptionType::ContentFormat
+        ])
+    );
+}
+
+#[test]
+pub fn response_location_helpers() {
+    let location: CoapUri = "/sensors/42?v=1&unit=c".parse().unwrap();
+    let response = CoapResponse::created_at(location, CoapMessageType::Ack).unwrap();
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Created));
+
+    let message = response.into_message();
+    let location_path: Vec<_> = message
+        .options_iter()
+        .filter_map(|v| match v {
+            CoapOption::LocationPath(v) => Some(v.as_str()),
+            _ => None,
+        })
+        .collect();
+    let location_query: Vec<_> = message
+        .options_iter()
+        .filter_map(|v| match v {
+            CoapOption::LocationQuery(v) => Some(v.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(location_path, vec!["sensors", "42"]);
+    assert_eq!(location_query, vec!["v=1", "unit=c"]);
+    assert!(!message
+        .options_iter()
+        .any(|v| matches!(v, CoapOption::UriPath(_) | CoapOption::UriQuery(_))));
+
+    let parsed = CoapResponse::from_message(message).unwrap();
+    assert_eq!(parsed.location().and_then(|v| v.path()), Some("sensors/42".as_bytes()));
+    assert_eq!(parsed.location().and_then(|v| v.query()), Some("v=1&unit=c".as_bytes()));
+
+    // Locations must be relative URIs.
+    assert_eq!(
+        CoapResponse::created_at("coap://example.com/sensors".parse().unwrap(), CoapMessageType::Ack).unwrap_err(),
+        MessageConversionError::InvalidOptionValue(Some(CoapOptionType::LocationPath), OptionValueError::IllegalValue)
+    );
+    assert_eq!(
+        CoapResponse::created_at("/sensors".parse().unwrap(), CoapMessageType::Rst).unwrap_err(),
+        MessageConversionError::InvalidMessageType(MessageTypeError::InvalidForMessageCode(CoapMessageType::Rst))
+    );
+}
+
+#[test]
+pub fn response_valid_for_etag() {
+    let etag: Box<[u8]> = vec![0x01, 0x02, 0x03].into_boxed_slice();
+    let response = CoapResponse::new_valid(etag.clone());
+    assert!(response.is_not_modified());
+    assert_eq!(response.type_(), CoapMessageType::Ack);
+    assert_eq!(response.etag(), Some(&etag));
+    assert_eq!(response.data(), None);
+
+    // Turning an existing response into a 2.03 Valid response removes its representation.
+    let mut response = CoapResponse::new(CoapMessageType::Non, CoapResponseCode::Content).unwrap();
+    response.set_data(Some(b"21.5".as_slice()));
+    response.set_content_format(Some(CoapContentFormat::TextPlain as u16));
+    response.set_auto_etag(true);
+    response.set_max_age(Some(30));
+    response.set_valid(etag.clone());
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Valid));
+    assert_eq!(response.type_(), CoapMessageType::Non);
+
+    let message = response.into_message();
+    assert_eq!(message.data(), None);
+    let options: Vec<CoapOption> = message.options_iter().cloned().collect();
+    assert_eq!(options, vec![CoapOption::MaxAge(30), CoapOption::ETag(etag)]);
+}
+
+#[test]
+pub fn request_normalized_query() {
+    let request = CoapRequest::get("/sensors?a=1").unwrap();
+    assert_eq!(
+        request.normalized_query(),
+        vec![("a".to_string(), Some("1".to_string()))]
+    );
+
+    let request = CoapRequest::get("/sensors?b").unwrap();
+    assert_eq!(request.normalized_query(), vec![("b".to_string(), None)]);
+
+    // Repeated keys are preserved and sorted by their value.
+    let request = CoapRequest::get("/sensors?a=2&b&a=1").unwrap();
+    assert_eq!(
+        request.normalized_query(),
+        vec![
+            ("a".to_string(), Some("1".to_string())),
+            ("a".to_string(), Some("2".to_string())),
+            ("b".to_string(), None),
+        ]
+    );
+    assert_eq!(
+        request.normalized_query_options(),
+        vec![
+            CoapOption::UriQuery("a=1".to_string()),
+            CoapOption::UriQuery("a=2".to_string()),
+            CoapOption::UriQuery("b".to_string()),
+        ]
+    );
+
+    // Only the first equals sign separates the key from the value.
+    let request = CoapRequest::get("/sensors?filter=t=20").unwrap();
+    assert_eq!(
+        request.normalized_query(),
+        vec![("filter".to_string(), Some("t=20".to_string()))]
+    );
+
+    assert!(CoapRequest::get("/sensors").unwrap().normalized_query().is_empty());
+}
+
+#[test]
+pub fn request_hop_limit_decrement() {
+    let mut request = CoapRequest::get("/sensors").unwrap();
+    // Requests without a Hop-Limit are not subject to loop detection.
+    assert_eq!(request.decrement_hop_limit(), Ok(()));
+    assert_eq!(request.hop_limit(), None);
+
+    request.set_hop_limit(Some(2));
+    assert_eq!(request.decrement_hop_limit(), Ok(()));
+    assert_eq!(request.hop_limit(), Some(1));
+    assert_eq!(request.decrement_hop_limit(), Err(HopLimitError::Exhausted));
+    assert_eq!(request.hop_limit(), Some(1));
+
+    assert_eq!(
+        CoapOption::HopLimit(0).into_value_bytes(),
+        Err(OptionValueError::IllegalValue)
+    );
+    assert_eq!(
+        CoapOption::HopLimit(256).into_value_bytes(),
+        Err(OptionValueError::IllegalValue)
+    );
+    assert_eq!(
+        CoapOption::HopLimit(255).into_value_bytes().as_deref(),
+        Ok([0xFFu8].as_slice())
+    );
+}
+
+#[test]
+pub fn request_clone_for_retry() {
+    let mut request = CoapRequest::post("coap://example.com/sensors?unit=celsius", b"21.5".to_vec()).unwrap();
+    request.set_content_format(Some(CoapContentFormat::TextPlain as u16));
+    request.set_accept(Some(CoapContentFormat::Json as u16));
+    request.set_mid(Some(4711));
+    request.set_token(Some(vec![0x13, 0x37]));
+
+    let retry = request.clone_for_retry();
+    assert_eq!(retry.mid(), None);
+    assert_eq!(retry.token(), None);
+    assert_eq!(retry.code(), request.code());
+    assert_eq!(retry.type_(), request.type_());
+    assert_eq!(retry.uri(), request.uri());
+    assert_eq!(retry.data(), request.data());
+
+    let original_options: Vec<CoapOption> = request.into_message().options_iter().cloned().collect();
+    let retry_options: Vec<CoapOption> = retry.into_message().options_iter().cloned().collect();
+    assert_eq!(retry_options, original_options);
+}
+
+#[test]
+pub fn message_type_code_combinations() {
+    let get = CoapMessageCode::Request(CoapRequestCode::Get);
+    let content = CoapMessageCode::Response(CoapResponseCode::Content);
+
+    let valid = [
+        (CoapMessageType::Con, CoapMessageCode::Empty),
+        (CoapMessageType::Ack, CoapMessageCode::Empty),
+        (CoapMessageType::Rst, CoapMessageCode::Empty),
+        (CoapMessageType::Con, get),
+        (CoapMessageType::Non, get),
+        (CoapMessageType::Con, content),
+        (CoapMessageType::Non, content),
+        (CoapMessageType::Ack, content),
+    ];
+    for (type_, code) in valid {
+        assert_eq!(
+            CoapMessage::new(type_, code).validate_type_code(),
+            Ok(()),
+            "{type_:?} {code:?}"
+        );
+    }
+
+    let invalid = [
+        (CoapMessageType::Non, CoapMessageCode::Empty),
+        (CoapMessageType::Ack, get),
+        (CoapMessageType::Rst, get),
+        (CoapMessageType::Rst, content),
+    ];
+    for (type_, code) in invalid {
+        assert_eq!(
+            CoapMessage::new(type_, code).validate_type_code(),
+            Err(MessageConversionError::InvalidTypeCodeCombination(type_, code))
+        );
+    }
+
+    let mut reset = CoapMessage::new(CoapMessageType::Rst, CoapMessageCode::Empty);
+    reset.set_data(Some(b"payload".to_vec()));
+    assert_eq!(
+        reset.validate_type_code(),
+        Err(MessageConversionError::DataInEmptyMessage)
+    );
+}
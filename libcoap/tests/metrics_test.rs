@@ -0,0 +1,106 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * metrics_test.rs - Tests for the metrics recorded by contexts and sessions.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+#![cfg(feature = "metrics")]
+use std::time::Duration;
+
+use libcoap_rs::{
+    message::{CoapMessageCommon, CoapResponse},
+    metrics::{MESSAGES_RECEIVED, MESSAGES_SENT, REQUESTS_DURATION_MS, SESSIONS_ACTIVE},
+    protocol::{CoapMessageCode, CoapRequestCode, CoapResponseCode},
+    session::{CoapClientSession, CoapSessionCommon},
+    CoapContext, CoapRequestHandler, CoapResource,
+};
+use metrics_util::{
+    debugging::{DebugValue, DebuggingRecorder},
+    CompositeKey,
+};
+
+mod common;
+
+/// Returns the value of the metric with the given name and protocol label from the snapshot.
+fn metric_value<'a>(snapshot: &'a [(CompositeKey, DebugValue)], name: &str, protocol: &str) -> &'a DebugValue {
+    snapshot
+        .iter()
+        .find(|(key, _)| {
+            key.key().name() == name
+                && key
+                    .key()
+                    .labels()
+                    .any(|label| label.key() == "protocol" && label.value() == protocol)
+        })
+        .map(|(_, value)| value)
+        .unwrap_or_else(|| panic!("metric {name} was not recorded"))
+}
+
+#[test]
+pub fn metrics_count_requests() {
+    const REQUEST_COUNT: usize = 3;
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    let snapshot = metrics::with_local_recorder(&recorder, || {
+        let server_address = common::get_unused_server_addr();
+        // Use a single context for both the server and the client, so that all metrics are
+        // recorded on this thread.
+        let mut context = CoapContext::new().unwrap();
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("test1", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_: &mut (), sess, _req, mut rsp: CoapResponse| {
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+        for _ in 0..REQUEST_COUNT {
+            let req_handle = session.send_request(common::gen_test_request()).unwrap();
+            loop {
+                let timeout = Duration::from_secs(10);
+                assert!(context.do_io(Some(timeout)).expect("error during IO") <= timeout);
+                if let Some(response) = session.poll_handle(&req_handle).next() {
+                    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+                    break;
+                }
+            }
+        }
+        // Take the snapshot while the sessions still exist.
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key, value))
+            .collect::<Vec<_>>()
+    });
+
+    // Each request and each response is counted once (on the client and server side,
+    // respectively).
+    assert_eq!(
+        metric_value(&snapshot, MESSAGES_SENT, "udp"),
+        &DebugValue::Counter(2 * REQUEST_COUNT as u64)
+    );
+    assert_eq!(
+        metric_value(&snapshot, MESSAGES_RECEIVED, "udp"),
+        &DebugValue::Counter(2 * REQUEST_COUNT as u64)
+    );
+    match metric_value(&snapshot, REQUESTS_DURATION_MS, "udp") {
+        DebugValue::Histogram(durations) => assert_eq!(durations.len(), REQUEST_COUNT),
+        value => panic!("unexpected value for request durations: {value:?}"),
+    }
+    // The client session and the server session.
+    match metric_value(&snapshot, SESSIONS_ACTIVE, "udp") {
+        DebugValue::Gauge(sessions) => assert_eq!(sessions.0, 2.0),
+        value => panic!("unexpected value for active sessions: {value:?}"),
+    }
+}
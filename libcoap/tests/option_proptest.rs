@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * option_proptest.rs - Property-based tests for the encoding of CoAP options.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+#![cfg(feature = "proptest")]
+use libcoap_rs::message::{CoapMessage, CoapMessageCommon, CoapOption};
+use libcoap_rs::protocol::{CoapMatch, CoapMessageCode, CoapMessageType, CoapRequestCode};
+use proptest::arbitrary::any;
+use proptest::collection::vec;
+use proptest::test_runner::{Config, TestRunner};
+
+/// Encodes the given option and decodes it again.
+///
+/// Decoding uses the same conversion that is applied to the options of received messages.
+fn round_trip(option: &CoapOption) -> CoapOption {
+    let bytes = option
+        .clone()
+        .into_value_bytes()
+        .expect("unable to encode option value");
+    CoapOption::try_from((option.number(), bytes.as_ref())).expect("unable to decode option value")
+}
+
+fn runner() -> TestRunner {
+    TestRunner::new(Config {
+        cases: 1000,
+        ..Config::default()
+    })
+}
+
+#[test]
+pub fn option_round_trip() {
+    runner()
+        .run(&any::<CoapOption>(), |option| {
+            assert_eq!(round_trip(&option), option);
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[test]
+pub fn option_round_trip_edge_cases() {
+    for option in [
+        CoapOption::IfMatch(CoapMatch::Empty),
+        CoapOption::UriPath(String::new()),
+        CoapOption::MaxAge(0),
+        CoapOption::MaxAge(u32::MAX),
+        CoapOption::ETag(vec![0x42].into_boxed_slice()),
+        CoapOption::ETag(vec![0xff; 8].into_boxed_slice()),
+    ] {
+        assert_eq!(round_trip(&option), option);
+    }
+}
+
+#[test]
+pub fn block_option_round_trip() {
+    for szx in 0..=7 {
+        for more in [false, true] {
+            for num in [0, 1, 15, 16, 255, 4096, 0xF_FFFF] {
+                let value = (num << 4) | (u32::from(more) << 3) | szx;
+                for option in [
+                    CoapOption::Block1(value),
+                    CoapOption::Block2(value),
+                    CoapOption::QBlock1(value),
+                    CoapOption::QBlock2(value),
+                ] {
+                    let decoded = round_trip(&option);
+                    let (CoapOption::Block1(decoded_value)
+                    | CoapOption::Block2(decoded_value)
+                    | CoapOption::QBlock1(decoded_value)
+                    | CoapOption::QBlock2(decoded_value)) = decoded
+                    else {
+                        panic!("decoded option has a different type: {decoded:?}");
+                    };
+                    assert_eq!(decoded_value >> 4, num);
+                    assert_eq!(decoded_value & 0x08 != 0, more);
+                    assert_eq!(decoded_value & 0x07, szx);
+                    assert_eq!(decoded, option);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+pub fn options_map_round_trip() {
+    let code = CoapMessageCode::Request(CoapRequestCode::Post);
+    runner()
+        .run(&vec(any::<CoapOption>(), 0..16), |options| {
+            let mut message = CoapMessage::new(CoapMessageType::Con, code);
+            for option in &options {
+                message.add_option(option.clone());
+            }
+            let map = message.options_as_map().expect("unable to encode options");
+            let rebuilt = CoapMessage::from_options_map(CoapMessageType::Con, code, map.clone())
+                .expect("unable to rebuild message from options map");
+            assert_eq!(rebuilt.options_as_map(), Ok(map));
+
+            // Options are grouped by their number, but keep their order within each group.
+            let mut expected = options;
+            expected.sort_by_key(CoapOption::number);
+            assert_eq!(rebuilt.options_iter().cloned().collect::<Vec<_>>(), expected);
+            Ok(())
+        })
+        .unwrap();
+}
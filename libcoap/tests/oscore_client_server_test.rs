@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * oscore_client_server_test.rs - Tests for OSCORE-protected clients+servers.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+// OSCORE requires libcoap to be built with a crypto library.
+#![cfg(all(
+    feature = "oscore",
+    any(
+        feature = "dtls_openssl",
+        feature = "dtls_gnutls",
+        feature = "dtls_mbedtls",
+        feature = "dtls_tinydtls"
+    )
+))]
+
+use libcoap_rs::error::SessionCreationError;
+use libcoap_rs::oscore::{OscoreAeadAlgorithm, OscoreContext};
+use libcoap_rs::session::CoapClientSession;
+use libcoap_rs::{
+    message::CoapMessageCommon,
+    protocol::{CoapMessageCode, CoapResponseCode},
+    CoapContext,
+};
+
+mod common;
+
+const MASTER_SECRET: &[u8] = b"\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f\x10";
+const MASTER_SALT: &[u8] = b"\x9e\x7c\xa9\x22\x23\x78\x63\x40";
+
+#[test]
+pub fn oscore_client_server_request() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        let oscore = OscoreContext::new(MASTER_SECRET, b"\x02", b"\x01", 0).master_salt(MASTER_SALT);
+        context.enable_oscore_server(oscore).unwrap();
+        context.add_endpoint_udp(server_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let oscore = OscoreContext::new(MASTER_SECRET, b"\x01", b"\x02", 0)
+        .master_salt(MASTER_SALT)
+        .aead_algorithm(OscoreAeadAlgorithm::AesCcm16_64_128);
+    let session = CoapClientSession::connect_udp_oscore(&mut context, server_address, oscore).unwrap();
+
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
+}
+
+#[test]
+pub fn oscore_sender_id_too_long_is_rejected() {
+    let server_address = common::get_unused_server_addr();
+    let mut context = CoapContext::new().unwrap();
+    // The sender ID must not be longer than the AEAD nonce length minus 6 bytes (7 bytes here).
+    let oscore = OscoreContext::new(MASTER_SECRET, &[0; 8], b"\x01", 0);
+    assert_eq!(
+        CoapClientSession::connect_udp_oscore(&mut context, server_address, oscore).unwrap_err(),
+        SessionCreationError::InvalidOscoreContext
+    );
+}
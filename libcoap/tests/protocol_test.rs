@@ -0,0 +1,248 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * protocol_test.rs - Tests for CoAP protocol value types.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+use std::collections::HashSet;
+
+use libcoap_rs::error::{CodeParseError, MessageTypeParseError, TokenError};
+use libcoap_rs::protocol::{
+    is_critical_option, is_valid_option_number, CoapContentFormat, CoapMessageCode, CoapMessageType, CoapOptionType,
+    CoapRequestCode, CoapResponseCode, CoapToken, COAP_OPTION_NUMBERS_SORTED,
+};
+
+const RESPONSE_CODES: [(CoapResponseCode, &str, &str); 27] = [
+    (CoapResponseCode::Created, "2.01", "Created"),
+    (CoapResponseCode::Deleted, "2.02", "Deleted"),
+    (CoapResponseCode::Valid, "2.03", "Valid"),
+    (CoapResponseCode::Changed, "2.04", "Changed"),
+    (CoapResponseCode::Content, "2.05", "Content"),
+    (CoapResponseCode::Continue, "2.31", "Continue"),
+    (CoapResponseCode::BadRequest, "4.00", "Bad Request"),
+    (CoapResponseCode::Unauthorized, "4.01", "Unauthorized"),
+    (CoapResponseCode::BadOption, "4.02", "Bad Option"),
+    (CoapResponseCode::Forbidden, "4.03", "Forbidden"),
+    (CoapResponseCode::NotFound, "4.04", "Not Found"),
+    (CoapResponseCode::NotAllowed, "4.05", "Method Not Allowed"),
+    (CoapResponseCode::NotAcceptable, "4.06", "Not Acceptable"),
+    (CoapResponseCode::Incomplete, "4.08", "Request Entity Incomplete"),
+    (CoapResponseCode::Conflict, "4.09", "Conflict"),
+    (CoapResponseCode::PreconditionFailed, "4.12", "Precondition Failed"),
+    (CoapResponseCode::RequestTooLarge, "4.13", "Request Entity Too Large"),
+    (
+        CoapResponseCode::UnsupportedContentFormat,
+        "4.15",
+        "Unsupported Content-Format",
+    ),
+    (CoapResponseCode::Unprocessable, "4.22", "Unprocessable Entity"),
+    (CoapResponseCode::TooManyRequests, "4.29", "Too Many Requests"),
+    (CoapResponseCode::InternalError, "5.00", "Internal Server Error"),
+    (CoapResponseCode::NotImplemented, "5.01", "Not Implemented"),
+    (CoapResponseCode::BadGateway, "5.02", "Bad Gateway"),
+    (CoapResponseCode::ServiceUnavailable, "5.03", "Service Unavailable"),
+    (CoapResponseCode::GatewayTimeout, "5.04", "Gateway Timeout"),
+    (CoapResponseCode::ProxyingNotSupported, "5.05", "Proxying Not Supported"),
+    (CoapResponseCode::HopLimitReached, "5.08", "Hop Limit Reached"),
+];
+
+#[test]
+pub fn response_code_dotted_notation() {
+    for (code, dotted, phrase) in RESPONSE_CODES {
+        assert_eq!(code.to_string(), dotted);
+        assert_eq!(code.display_dotted(), dotted);
+        assert_eq!(code.reason_phrase(), phrase);
+        assert_eq!(CoapResponseCode::from_dotted(dotted), Ok(code));
+        assert_eq!(dotted.parse::<CoapResponseCode>(), Ok(code));
+    }
+
+    assert_eq!(CoapResponseCode::from_dotted("2.07"), Err(CodeParseError::UnknownCode));
+    assert_eq!(CoapResponseCode::from_dotted("0.01"), Err(CodeParseError::UnknownCode));
+    assert_eq!(CoapResponseCode::from_dotted("205"), Err(CodeParseError::InvalidFormat));
+    assert_eq!(CoapResponseCode::from_dotted("2.5"), Err(CodeParseError::InvalidFormat));
+    assert_eq!(
+        CoapResponseCode::from_dotted("8.00"),
+        Err(CodeParseError::InvalidFormat)
+    );
+    assert_eq!(
+        CoapResponseCode::from_dotted("2.32"),
+        Err(CodeParseError::InvalidFormat)
+    );
+}
+
+#[test]
+pub fn request_code_dotted_notation() {
+    let request_codes = [
+        (CoapRequestCode::Get, "0.01"),
+        (CoapRequestCode::Post, "0.02"),
+        (CoapRequestCode::Put, "0.03"),
+        (CoapRequestCode::Delete, "0.04"),
+        (CoapRequestCode::Fetch, "0.05"),
+        (CoapRequestCode::Patch, "0.06"),
+        (CoapRequestCode::IPatch, "0.07"),
+    ];
+    for (code, dotted) in request_codes {
+        assert_eq!(code.to_string(), dotted);
+        assert_eq!(dotted.parse::<CoapRequestCode>(), Ok(code));
+    }
+
+    assert_eq!("2.05".parse::<CoapRequestCode>(), Err(CodeParseError::UnknownCode));
+    assert_eq!("GETS".parse::<CoapRequestCode>(), Err(CodeParseError::InvalidFormat));
+}
+
+#[test]
+pub fn request_code_method_names() {
+    let methods = [
+        (CoapRequestCode::Get, "GET"),
+        (CoapRequestCode::Post, "POST"),
+        (CoapRequestCode::Put, "PUT"),
+        (CoapRequestCode::Delete, "DELETE"),
+        (CoapRequestCode::Fetch, "FETCH"),
+        (CoapRequestCode::Patch, "PATCH"),
+        (CoapRequestCode::IPatch, "iPATCH"),
+    ];
+    for (code, name) in methods {
+        assert_eq!(code.method_str(), name);
+        assert_eq!(CoapMessageCode::Request(code).to_method_str(), Some(name));
+        assert_eq!(name.parse::<CoapRequestCode>(), Ok(code));
+        assert_eq!(CoapMessageCode::Request(code).is_idempotent(), code.is_idempotent());
+    }
+
+    assert_eq!("get".parse::<CoapRequestCode>(), Err(CodeParseError::InvalidFormat));
+    assert_eq!("LIST".parse::<CoapRequestCode>(), Err(CodeParseError::InvalidFormat));
+    assert_eq!(
+        CoapMessageCode::Response(CoapResponseCode::Content).to_method_str(),
+        None
+    );
+    assert_eq!(CoapMessageCode::Empty.to_method_str(), None);
+    assert!(!CoapMessageCode::Response(CoapResponseCode::Content).is_idempotent());
+}
+
+#[test]
+pub fn request_code_method_properties() {
+    // Method, safe, idempotent (RFC 7252, Section 5.8 and RFC 8132).
+    let methods = [
+        (CoapRequestCode::Get, true, true),
+        (CoapRequestCode::Post, false, false),
+        (CoapRequestCode::Put, false, true),
+        (CoapRequestCode::Delete, false, true),
+        (CoapRequestCode::Fetch, true, true),
+        (CoapRequestCode::Patch, false, false),
+        (CoapRequestCode::IPatch, false, true),
+    ];
+    for (code, safe, idempotent) in methods {
+        assert_eq!(code.is_safe(), safe, "{code}");
+        assert_eq!(code.is_idempotent(), idempotent, "{code}");
+    }
+}
+
+#[test]
+pub fn content_format_media_type_parameters() {
+    let text_plain = CoapContentFormat::from(0);
+    assert_eq!(text_plain, CoapContentFormat::TextPlain);
+    assert!(text_plain.is_text());
+    assert_eq!(text_plain.charset(), Some("utf-8"));
+    assert_eq!(text_plain.structured_suffix(), None);
+
+    let senml_cbor = CoapContentFormat::from(112);
+    assert_eq!(senml_cbor, CoapContentFormat::SenMlCbor);
+    assert!(!senml_cbor.is_text());
+    assert_eq!(senml_cbor.charset(), None);
+    assert_eq!(senml_cbor.structured_suffix(), Some("cbor"));
+
+    let octet_stream = CoapContentFormat::from(42);
+    assert_eq!(octet_stream, CoapContentFormat::OctetStream);
+    assert!(!octet_stream.is_text());
+    assert_eq!(octet_stream.charset(), None);
+    assert_eq!(octet_stream.structured_suffix(), None);
+
+    assert_eq!(CoapContentFormat::SenMlJson.structured_suffix(), Some("json"));
+    assert!(CoapContentFormat::SenMlJson.is_text());
+    assert!(!CoapContentFormat::Other.is_text());
+}
+
+#[test]
+pub fn option_numbers_are_sorted() {
+    assert!(COAP_OPTION_NUMBERS_SORTED.windows(2).all(|v| v[0] < v[1]));
+    // Every listed number must belong to a known option type, and vice versa.
+    for number in COAP_OPTION_NUMBERS_SORTED {
+        assert!(CoapOptionType::try_from(number).is_ok());
+    }
+    for number in 0..=u16::MAX {
+        assert_eq!(is_valid_option_number(number), CoapOptionType::try_from(number).is_ok());
+    }
+    // The option types are declared in the order of their option numbers.
+    assert_eq!(COAP_OPTION_NUMBERS_SORTED[0], CoapOptionType::IfMatch as u16);
+    assert_eq!(COAP_OPTION_NUMBERS_SORTED.last(), Some(&(CoapOptionType::RTag as u16)));
+
+    assert!(is_critical_option(CoapOptionType::IfMatch as u16));
+    assert!(is_critical_option(CoapOptionType::Block1 as u16));
+    assert!(!is_critical_option(CoapOptionType::ETag as u16));
+    assert!(!is_critical_option(CoapOptionType::Size1 as u16));
+}
+
+#[test]
+pub fn random_tokens_are_unique() {
+    let tokens: HashSet<CoapToken> = (0..100).map(|_| CoapToken::random(8).unwrap()).collect();
+    assert_eq!(tokens.len(), 100);
+    assert!(tokens.iter().all(|v| v.len() == 8));
+
+    assert!(CoapToken::random(0).unwrap().is_empty());
+}
+
+#[test]
+pub fn token_length_is_enforced() {
+    assert!(matches!(CoapToken::random(9), Err(TokenError::TooLong(9))));
+    assert!(matches!(CoapToken::from_bytes(&[0; 9]), Err(TokenError::TooLong(9))));
+    assert!(matches!(
+        CoapToken::try_from([0u8; 12].as_slice()),
+        Err(TokenError::TooLong(12))
+    ));
+
+    let token = CoapToken::from_bytes(&[0x01, 0x23, 0xab, 0xcd, 0x00, 0x00, 0x00, 0xff]).unwrap();
+    assert_eq!(token.as_bytes(), &[0x01, 0x23, 0xab, 0xcd, 0x00, 0x00, 0x00, 0xff]);
+    assert_eq!(token.to_string(), "0123abcd000000ff");
+    assert_eq!(CoapToken::from_bytes(&[]).unwrap().to_string(), "");
+}
+
+#[test]
+pub fn message_type_string_round_trip() {
+    let types = [
+        (CoapMessageType::Con, "CON"),
+        (CoapMessageType::Non, "NON"),
+        (CoapMessageType::Ack, "ACK"),
+        (CoapMessageType::Rst, "RST"),
+    ];
+    for (type_, abbreviation) in types {
+        assert_eq!(type_.to_string(), abbreviation);
+        assert_eq!(abbreviation.parse::<CoapMessageType>(), Ok(type_));
+        assert_eq!(abbreviation.to_lowercase().parse::<CoapMessageType>(), Ok(type_));
+    }
+    assert_eq!("Con".parse::<CoapMessageType>(), Ok(CoapMessageType::Con));
+
+    assert_eq!("".parse::<CoapMessageType>(), Err(MessageTypeParseError::UnknownType));
+    assert_eq!(
+        "CONFIRMABLE".parse::<CoapMessageType>(),
+        Err(MessageTypeParseError::UnknownType)
+    );
+}
+
+#[test]
+pub fn request_code_raw_conversion() {
+    let request_codes = [
+        CoapRequestCode::Get,
+        CoapRequestCode::Post,
+        CoapRequestCode::Put,
+        CoapRequestCode::Delete,
+        CoapRequestCode::Fetch,
+        CoapRequestCode::Patch,
+        CoapRequestCode::IPatch,
+    ];
+    for code in request_codes {
+        assert_eq!(CoapRequestCode::from(code.to_raw_request()), code);
+        assert_eq!(CoapRequestCode::try_from(code.to_raw_pdu_code()).ok(), Some(code));
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * pubsub_test.rs - Tests for the publish-subscribe broker.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+#![cfg(feature = "pubsub")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use libcoap_rs::session::{CoapClientSession, CoapRequestHandle};
+use libcoap_rs::{
+    message::{CoapMessageCommon, CoapRequest, CoapResponse},
+    protocol::{CoapContentFormat, CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode, Observe},
+    pubsub::CoapPubSubBroker,
+    session::CoapSessionCommon,
+    CoapContext,
+};
+
+mod common;
+
+fn gen_pubsub_request(code: CoapRequestCode, path: &str) -> CoapRequest {
+    let uri = path.parse().expect("unable to parse request URI");
+    CoapRequest::new(CoapMessageType::Con, code, uri).unwrap()
+}
+
+fn await_response(context: &mut CoapContext, session: &CoapClientSession, handle: &CoapRequestHandle) -> CoapResponse {
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(handle).next() {
+            return response;
+        }
+    }
+}
+
+#[test]
+pub fn pubsub_topic_lifecycle() {
+    let server_address = common::get_unused_server_addr();
+    let stop_server = Arc::new(AtomicBool::new(false));
+    let stop_server2 = Arc::clone(&stop_server);
+    let (ready_sender, ready_receiver) = mpsc::channel();
+
+    let server_handle = std::thread::spawn(move || {
+        let mut context = CoapContext::new().unwrap();
+        context.add_endpoint_udp(server_address).unwrap();
+        let mut broker = CoapPubSubBroker::new(context);
+        broker
+            .create_topic("temperature", CoapContentFormat::TextPlain as u16)
+            .unwrap();
+        ready_sender.send(()).unwrap();
+        while !stop_server2.load(Ordering::Relaxed) {
+            broker.do_io(Some(Duration::from_millis(100))).unwrap();
+        }
+        assert!(broker.topic("temperature").is_some());
+        assert!(broker.topic("humidity").is_none());
+    });
+    ready_receiver.recv().unwrap();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    // Create a new topic.
+    let mut request = gen_pubsub_request(CoapRequestCode::Post, "/ps");
+    request.set_data(Some("<humidity>;ct=0".as_bytes()));
+    let req_handle = session.send_request(request).unwrap();
+    let response = await_response(&mut context, &session, &req_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Created));
+    assert_eq!(
+        response.location().and_then(|v| v.path()),
+        Some("ps/humidity".as_bytes())
+    );
+
+    // Discover the available topics.
+    let req_handle = session
+        .send_request(gen_pubsub_request(CoapRequestCode::Get, "/ps"))
+        .unwrap();
+    let response = await_response(&mut context, &session, &req_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    let topics = std::str::from_utf8(response.data().unwrap()).unwrap();
+    assert!(topics.contains("</ps/humidity>"));
+    assert!(topics.contains("</ps/temperature>"));
+
+    // Subscribe to the new topic, which does not have a value yet.
+    let mut request = gen_pubsub_request(CoapRequestCode::Get, "/ps/humidity");
+    request.set_token(Some(vec![0x13, 0x37]));
+    request.as_observe_registration().unwrap();
+    let subscription_handle = session.send_request(request).unwrap();
+    let response = await_response(&mut context, &session, &subscription_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.observe().map(Observe::value), Some(0));
+    assert_eq!(response.data(), None);
+
+    // Publish a value and receive the notification.
+    let mut request = gen_pubsub_request(CoapRequestCode::Put, "/ps/humidity");
+    request.set_data(Some("42".as_bytes()));
+    let req_handle = session.send_request(request).unwrap();
+    let response = await_response(&mut context, &session, &req_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Changed));
+    let notification = await_response(&mut context, &session, &subscription_handle);
+    assert_eq!(notification.observe().map(Observe::value), Some(1));
+    assert_eq!(notification.data(), Some("42".as_bytes()));
+
+    // Unsubscribe from the topic.
+    let mut request = gen_pubsub_request(CoapRequestCode::Get, "/ps/humidity");
+    request.set_token(Some(vec![0x13, 0x37]));
+    request.as_observe_deregistration().unwrap();
+    let req_handle = session.send_request(request).unwrap();
+    let response = await_response(&mut context, &session, &req_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data(), Some("42".as_bytes()));
+
+    // Publishing another value must no longer result in a notification.
+    let mut request = gen_pubsub_request(CoapRequestCode::Put, "/ps/humidity");
+    request.set_data(Some("43".as_bytes()));
+    let req_handle = session.send_request(request).unwrap();
+    let response = await_response(&mut context, &session, &req_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Changed));
+    let deadline = Instant::now() + Duration::from_millis(500);
+    while Instant::now() < deadline {
+        context.do_io(Some(Duration::from_millis(50))).expect("error during IO");
+        assert!(session.poll_handle(&subscription_handle).next().is_none());
+    }
+
+    // Delete the topic again.
+    let req_handle = session
+        .send_request(gen_pubsub_request(CoapRequestCode::Delete, "/ps/humidity"))
+        .unwrap();
+    let response = await_response(&mut context, &session, &req_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Deleted));
+    let req_handle = session
+        .send_request(gen_pubsub_request(CoapRequestCode::Get, "/ps/humidity"))
+        .unwrap();
+    let response = await_response(&mut context, &session, &req_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::NotFound));
+
+    stop_server.store(true, Ordering::Relaxed);
+    server_handle.join().unwrap();
+}
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * senml_test.rs - Tests for the SenML payload helpers.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+#![cfg(feature = "senml")]
+use libcoap_rs::{
+    error::SenmlError,
+    message::{CoapMessageCommon, CoapRequest, CoapResponse},
+    protocol::{CoapContentFormat, CoapMessageType, CoapRequestCode, CoapResponseCode, ContentFormat},
+    senml::{self, SenmlContentFormat, SenmlRecord},
+};
+
+fn record(name: &str, unit: Option<&str>, value: Option<f64>, time: f64) -> SenmlRecord {
+    SenmlRecord {
+        name: name.to_string(),
+        unit: unit.map(str::to_string),
+        value,
+        time,
+    }
+}
+
+#[test]
+pub fn senml_json_pack_is_resolved() {
+    let pack = br#"[
+        {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "n": "voltage", "u": "V", "v": 120.1},
+        {"n": "current", "t": -5, "u": "A", "v": 1.2},
+        {"bn": "urn:dev:ow:10e2073a01080064:", "n": "current", "u": "A", "v": 1.3, "t": 2},
+        {"n": "status", "bt": 1320067500}
+    ]"#;
+    let records = senml::from_json(pack).unwrap();
+    assert_eq!(
+        records,
+        vec![
+            record(
+                "urn:dev:ow:10e2073a01080063:voltage",
+                Some("V"),
+                Some(120.1),
+                1320067464.0
+            ),
+            record(
+                "urn:dev:ow:10e2073a01080063:current",
+                Some("A"),
+                Some(1.2),
+                1320067459.0
+            ),
+            record(
+                "urn:dev:ow:10e2073a01080064:current",
+                Some("A"),
+                Some(1.3),
+                1320067466.0
+            ),
+            record("urn:dev:ow:10e2073a01080064:status", None, None, 1320067500.0),
+        ]
+    );
+
+    // Records without a name (and without a base name) are invalid.
+    assert_eq!(senml::from_json(br#"[{"v": 1.0}]"#), Err(SenmlError::MissingName));
+    // Fields that must be understood are rejected.
+    assert!(matches!(
+        senml::from_json(br#"[{"n": "a", "v": 1.0, "foo_": 1}]"#),
+        Err(SenmlError::Malformed(_))
+    ));
+}
+
+#[test]
+pub fn senml_cbor_pack_is_resolved() {
+    // [{-2: "dev:", -3: 100, 0: "temp", 1: "Cel", 2: 23}, {0: "hum", 2: 40.5, 6: 10}]
+    let pack = [
+        0x82, 0xA5, 0x21, 0x64, b'd', b'e', b'v', b':', 0x22, 0x18, 0x64, 0x00, 0x64, b't', b'e', b'm', b'p', 0x01,
+        0x63, b'C', b'e', b'l', 0x02, 0x17, 0xA3, 0x00, 0x63, b'h', b'u', b'm', 0x02, 0xFB, 0x40, 0x44, 0x40, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x06, 0x0A,
+    ];
+    let records = senml::from_cbor(&pack).unwrap();
+    assert_eq!(
+        records,
+        vec![
+            record("dev:temp", Some("Cel"), Some(23.0), 100.0),
+            record("dev:hum", None, Some(40.5), 110.0),
+        ]
+    );
+    // Serializing and parsing the records again yields the same records.
+    assert_eq!(senml::from_cbor(&senml::to_cbor(&records)).unwrap(), records);
+}
+
+#[test]
+pub fn senml_message_payloads() {
+    let records = vec![
+        record("dev:temp", Some("Cel"), Some(21.5), 1700000000.0),
+        record("dev:hum", Some("%RH"), Some(40.0), 0.0),
+    ];
+
+    let uri = "/sensors".parse().unwrap();
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Post, uri).unwrap();
+    request.set_senml(&records, SenmlContentFormat::Json);
+    assert_eq!(
+        request.content_format(),
+        Some(CoapContentFormat::SenMlJson as ContentFormat)
+    );
+    assert_eq!(request.senml().unwrap(), records);
+
+    let mut response = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    response.set_senml(&records, SenmlContentFormat::Cbor);
+    assert_eq!(
+        response.content_format(),
+        Some(CoapContentFormat::SenMlCbor as ContentFormat)
+    );
+    assert_eq!(response.senml().unwrap(), records);
+
+    // Payloads in other content formats are not parsed.
+    let json = Some(CoapContentFormat::Json as ContentFormat);
+    response.set_content_format(json);
+    response.set_data(Some(senml::to_json(&records)));
+    assert_eq!(response.senml(), Err(SenmlError::UnsupportedContentFormat(json)));
+}
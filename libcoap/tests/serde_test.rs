@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * serde_test.rs - Tests for (de)serializing CoAP messages using serde.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+#![cfg(feature = "serde")]
+use libcoap_rs::message::{CoapMessage, CoapMessageCommon, CoapOption};
+use libcoap_rs::protocol::{CoapMatch, CoapMessageCode, CoapMessageType, CoapRequestCode};
+
+#[test]
+pub fn message_json_round_trip() {
+    let mut message = CoapMessage::new(CoapMessageType::Con, CoapMessageCode::Request(CoapRequestCode::Put));
+    message.set_mid(Some(0x1234));
+    message.set_token(Some(vec![0xde, 0xad, 0xbe, 0xef]));
+    message.add_option(CoapOption::UriPath("sensors".to_string()));
+    message.add_option(CoapOption::UriPath("temperature".to_string()));
+    message.add_option(CoapOption::IfMatch(CoapMatch::ETag(
+        vec![0x01, 0xff].into_boxed_slice(),
+    )));
+    message.add_option(CoapOption::ContentFormat(0));
+    message.add_option(CoapOption::other(65000, b"custom".as_slice()).unwrap());
+    message.set_data(Some("21.5".as_bytes()));
+
+    let json = serde_json::to_string(&message).unwrap();
+    // Byte fields are represented as hexadecimal strings.
+    assert!(json.contains("\"deadbeef\""));
+    assert!(json.contains("\"01ff\""));
+    assert!(json.contains("\"32312e35\""));
+    assert!(json.contains("{\"UriPath\":\"sensors\"}"));
+
+    let deserialized: CoapMessage = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, message);
+
+    // Messages without token and body can be (de)serialized as well.
+    let empty = CoapMessage::new(CoapMessageType::Rst, CoapMessageCode::Empty);
+    let deserialized: CoapMessage = serde_json::from_str(&serde_json::to_string(&empty).unwrap()).unwrap();
+    assert_eq!(deserialized, empty);
+}
+
+#[test]
+pub fn message_invalid_hex_rejected() {
+    let message = CoapMessage::new(CoapMessageType::Non, CoapMessageCode::Empty);
+    let json = serde_json::to_string(&message).unwrap();
+    assert!(json.contains("\"token\":null"));
+    let json = json.replace("\"token\":null", "\"token\":\"abc\"");
+    assert!(serde_json::from_str::<CoapMessage>(&json).is_err());
+    let json = json.replace("\"token\":\"abc\"", "\"token\":\"zz\"");
+    assert!(serde_json::from_str::<CoapMessage>(&json).is_err());
+}
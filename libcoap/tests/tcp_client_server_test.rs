@@ -12,10 +12,8 @@ use libcoap_rs::session::CoapClientSession;
 use libcoap_rs::{
     message::CoapMessageCommon,
     protocol::{CoapMessageCode, CoapResponseCode},
-    session::CoapSessionCommon,
     CoapContext,
 };
-use std::time::Duration;
 
 mod common;
 
@@ -31,15 +29,7 @@ pub fn basic_client_server_request() {
     let mut context = CoapContext::new().unwrap();
     let session = CoapClientSession::connect_tcp(&mut context, server_address).unwrap();
 
-    let request = common::gen_test_request();
-    let req_handle = session.send_request(request).unwrap();
-    loop {
-        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
-        for response in session.poll_handle(&req_handle) {
-            assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
-            assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
-            server_handle.join().unwrap();
-            return;
-        }
-    }
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
 }
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * types_test.rs - Tests for conversions between libcoap and Rust types.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+use libcoap_rs::error::AddressConversionError;
+use libcoap_rs::types::{coap_address_to_socket_addr, socket_addr_to_coap_address};
+use libcoap_sys::coap_address_t;
+
+#[test]
+pub fn socket_addr_coap_address_round_trip() {
+    let addresses = [
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 5683)),
+        SocketAddr::from((Ipv6Addr::LOCALHOST, 5684)),
+        SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 5685, 0x12345, 1)),
+    ];
+    for addr in addresses {
+        let raw_addr = socket_addr_to_coap_address(addr);
+        assert_eq!(coap_address_to_socket_addr(&raw_addr), Ok(addr));
+    }
+}
+
+#[test]
+pub fn coap_address_with_unsupported_family() {
+    // SAFETY: coap_address_t is a plain C struct, so an all-zero value is valid (with the address
+    // family AF_UNSPEC).
+    let raw_addr: coap_address_t = unsafe { std::mem::zeroed() };
+    assert_eq!(
+        coap_address_to_socket_addr(&raw_addr),
+        Err(AddressConversionError::UnsupportedFamily(0))
+    );
+}
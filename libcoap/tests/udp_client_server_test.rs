@@ -7,14 +7,26 @@
  * See the README as well as the LICENSE file for more information.
  */
 
-use libcoap_rs::session::CoapClientSession;
+use libcoap_rs::error::{DownloadError, FileResourceError, OutstandingSessionsError, RequestError, SessionError};
+use libcoap_rs::session::{CoapClientSession, CoapNackReason, CoapRequestHandle, CoapServerSession};
 use libcoap_rs::{
-    message::CoapMessageCommon,
-    protocol::{CoapMessageCode, CoapResponseCode},
+    message::{CoapMessage, CoapMessageCommon, CoapOption, CoapRequest, CoapResponse},
+    protocol::{
+        CoapContentFormat, CoapMessageCode, CoapMessageType, CoapRequestCode, CoapResponseCode, CoapToken, ETag,
+        Observe,
+    },
+    rate_limit::{RateLimitConfig, RateLimitKey},
     session::CoapSessionCommon,
-    CoapContext,
+    types::{CoapMessageId, CoapProtocol},
+    AccessDecision, CoapContext, CoapContextBuilder, CoapEvent, CoapRequestHandler, CoapResource,
+    ObserveConfirmationPolicy, ResourceDescription, ResourceHandle,
 };
-use std::time::Duration;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 mod common;
 
@@ -30,15 +42,2318 @@ pub fn basic_client_server_request() {
     let mut context = CoapContext::new().unwrap();
     let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
 
-    let request = common::gen_test_request();
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
+}
+
+#[test]
+pub fn server_session_close() {
+    let server_address = common::get_unused_server_addr();
+    let session_closed = Arc::new(AtomicBool::new(false));
+    let server_session_closed = Arc::clone(&session_closed);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("close", server_session_closed, false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |closed: &mut Arc<AtomicBool>, sess: &mut CoapServerSession, _req: &CoapRequest, rsp: CoapResponse| {
+                    sess.close();
+                    assert!(sess.is_closed());
+                    assert!(matches!(sess.send(rsp), Err(SessionError::Closed)));
+                    closed.store(true, Ordering::Relaxed);
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = "/close".parse().expect("unable to parse request URI");
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    let req_handle = session.send_request(request).unwrap();
+
+    // Wait for the server to close the session, then ensure that no response arrives afterwards.
+    let mut closed_at = None;
+    while closed_at.map_or(true, |v: Instant| v.elapsed() < Duration::from_secs(1)) {
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+        assert_eq!(session.poll_handle(&req_handle).count(), 0);
+        if closed_at.is_none() && session_closed.load(Ordering::Relaxed) {
+            closed_at = Some(Instant::now());
+        }
+    }
+
+    // Use a fresh session to complete the test server's regular test request.
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn client_download() {
+    /// Block size used by the server (corresponding to SZX = 6).
+    const BLOCK_SIZE: usize = 1024;
+
+    let server_address = common::get_unused_server_addr();
+    let body: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let served_blocks = Arc::new(AtomicUsize::new(0));
+    let server_body = body.clone();
+    let server_served_blocks = Arc::clone(&served_blocks);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("firmware", (server_body, server_served_blocks), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |(body, served_blocks): &mut (Vec<u8>, Arc<AtomicUsize>),
+                 sess: &mut CoapServerSession,
+                 req: &CoapRequest,
+                 mut rsp: CoapResponse| {
+                    let num = req
+                        .options_iter()
+                        .find_map(|option| match option {
+                            CoapOption::Block2(value) => Some(value >> 4),
+                            _ => None,
+                        })
+                        .unwrap_or(0);
+                    let start = num as usize * BLOCK_SIZE;
+                    let end = (start + BLOCK_SIZE).min(body.len());
+                    let more = end < body.len();
+                    rsp.add_option(CoapOption::Block2((num << 4) | ((more as u32) << 3) | 6));
+                    rsp.add_option(CoapOption::Size2(body.len() as u32));
+                    rsp.set_data(Some(body[start..end].to_vec()));
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                    served_blocks.fetch_add(1, Ordering::Relaxed);
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let mut progress = Vec::new();
+    let uri = "/firmware".parse().expect("unable to parse request URI");
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    let downloaded = session
+        .download(
+            &mut context,
+            request,
+            Some(Duration::from_secs(60)),
+            None,
+            |received, total| progress.push((received, total)),
+        )
+        .expect("error during download");
+    assert_eq!(downloaded, body);
+    assert_eq!(served_blocks.load(Ordering::Relaxed), body.len().div_ceil(BLOCK_SIZE));
+    assert!(!progress.is_empty());
+    assert!(progress.windows(2).all(|v| v[0].0 < v[1].0));
+    assert_eq!(progress.last().unwrap().0, body.len());
+
+    // Complete the test server's regular test request.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn client_download_cancelled() {
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let cancel = AtomicBool::new(true);
+    let result = session.download(&mut context, common::gen_test_request(), None, Some(&cancel), |_, _| {});
+    assert!(matches!(result, Err(DownloadError::Cancelled)));
+}
+
+#[test]
+pub fn client_download_fails_for_unreachable_peer() {
+    // This socket never answers, so the request is never acknowledged.
+    let silent_socket = UdpSocket::bind("localhost:0").unwrap();
+    let server_address = silent_socket.local_addr().unwrap();
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    session.set_ack_timeout(1, 0);
+    session.set_ack_random_factor(1, 0);
+    session.set_max_retransmit(1);
+
+    // Without a timeout or cancellation flag, the download must still end once libcoap gives up
+    // retransmitting the request.
+    let result = session.download(&mut context, common::gen_test_request(), None, None, |_, _| {});
+    assert!(matches!(
+        result,
+        Err(DownloadError::Request(RequestError::RetransmitExhausted))
+    ));
+}
+
+#[test]
+pub fn duplicate_request_handled_once() {
+    let server_address = common::get_unused_server_addr();
+    let handler_calls = Arc::new(AtomicUsize::new(0));
+    let server_handler_calls = Arc::clone(&handler_calls);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        assert!(context.deduplication());
+        let resource = CoapResource::new("dedup", server_handler_calls, false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |calls: &mut Arc<AtomicUsize>,
+                 sess: &mut CoapServerSession,
+                 _req: &CoapRequest,
+                 mut rsp: CoapResponse| {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = "/dedup".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.set_mid(Some(session.next_message_id()));
+    request.set_token(Some(vec![0x13u8, 0x37]));
+
+    // Send the same request (same message ID and token) twice.
+    let req_handle = session.send_request(request.clone()).unwrap();
+    session.send_request(request).unwrap();
+    let started_at = Instant::now();
+    let mut received_response = false;
+    while started_at.elapsed() < Duration::from_secs(1) {
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+        received_response |= session.poll_handle(&req_handle).count() > 0;
+    }
+    assert!(received_response);
+    assert_eq!(handler_calls.load(Ordering::Relaxed), 1);
+
+    // Complete the test server's regular test request.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn session_limit_refuses_sessions() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context.set_max_sessions(1);
+        assert_eq!(context.max_sessions(), Some(1));
+        let resource = CoapResource::new("limit", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let sessions = [
+        CoapClientSession::connect_udp(&mut context, server_address).unwrap(),
+        CoapClientSession::connect_udp(&mut context, server_address).unwrap(),
+    ];
+    let mut responses = Vec::new();
+    for session in &sessions {
+        let uri = "/limit".parse().expect("unable to parse request URI");
+        let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+        let req_handle = session.send_request(request).unwrap();
+        let response = loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                break response;
+            }
+        };
+        responses.push(response);
+    }
+    assert_eq!(
+        responses[0].code(),
+        CoapMessageCode::Response(CoapResponseCode::Content)
+    );
+    assert_eq!(
+        responses[1].code(),
+        CoapMessageCode::Response(CoapResponseCode::ServiceUnavailable)
+    );
+    assert!(responses[1].max_age().is_some());
+
+    // Complete the test server's regular test request using the accepted session.
+    common::finish_test_server(&mut context, &sessions[0], server_handle);
+}
+
+#[test]
+pub fn observe_notifications_have_increasing_sequence_numbers() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("observe", 0usize, false);
+        resource.set_get_observable(true);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new_resource_ref(
+                |res: &CoapResource<usize>, sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                    let mut calls = res.user_data_mut();
+                    *calls += 1;
+                    let notify = *calls < 3;
+                    drop(calls);
+                    if notify {
+                        assert!(res.notify_observers());
+                    }
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = "/observe".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.as_observe_registration().unwrap();
+    let req_handle = session.send_request(request).unwrap();
+
+    let mut observe_values = Vec::new();
+    while observe_values.len() < 3 {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        observe_values.extend(
+            session
+                .poll_handle(&req_handle)
+                .map(|v| v.observe().map(Observe::value)),
+        );
+    }
+    assert_eq!(observe_values, [Some(0), Some(1), Some(2)]);
+
+    // Complete the test server's regular test request.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn cancelled_requests_are_no_longer_tracked() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let uri = "/unknown".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.set_token(Some(vec![0x01, 0x02, 0x03]));
+    session.send_request(request).unwrap();
+    assert!(session.cancel_by_token(&[0x01, 0x02, 0x03]));
+    assert!(!session.is_waiting_for_token(&CoapToken::try_from([0x01, 0x02, 0x03].as_slice()).unwrap()));
+    assert!(!session.cancel_by_token(&[0x01, 0x02, 0x03]));
+
+    let mut request = common::gen_test_request();
+    request.set_mid(Some(0x1337));
+    session.send_request(request).unwrap();
+    assert!(session.cancel_request(0x1337));
+    assert!(!session.cancel_request(0x1337));
+
+    // The server still handles the request, but its response is no longer stored.
+    while !server_handle.is_finished() {
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+    }
+    server_handle.join().unwrap();
+}
+
+#[test]
+pub fn cancelled_queued_requests_are_not_transmitted() {
+    let server_socket = UdpSocket::bind("localhost:0").unwrap();
+    server_socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_socket.local_addr().unwrap()).unwrap();
+
+    let mut cancelled_request = common::gen_test_request();
+    cancelled_request.set_token(Some(vec![0x01]));
+    let mut request = common::gen_test_request();
+    request.set_token(Some(vec![0x02]));
+    session.try_send(cancelled_request).unwrap();
+    session.try_send(request).unwrap();
+    assert!(session.cancel_by_token(&[0x01]));
+    assert_eq!(session.send_queue_len(), 1);
+    context
+        .do_io(Some(Duration::from_millis(100)))
+        .expect("error during IO");
+
+    let mut buf = [0; 1500];
+    let (len, client_address) = server_socket.recv_from(&mut buf).unwrap();
+    // Only the request that was not cancelled is transmitted (the token follows the four byte
+    // header, see RFC 7252, Section 3).
+    assert!(len >= 5);
+    assert_eq!(buf[0] & 0x0f, 1);
+    assert_eq!(buf[4], 0x02);
+    // Acknowledge the request, so that it is not retransmitted either.
+    server_socket
+        .send_to(&[0x60, 0x00, buf[2], buf[3]], client_address)
+        .unwrap();
+    context
+        .do_io(Some(Duration::from_millis(100)))
+        .expect("error during IO");
+    assert!(server_socket.recv_from(&mut buf).is_err());
+}
+
+#[test]
+pub fn handler_requests_have_reception_time() {
+    let server_address = common::get_unused_server_addr();
+    let has_timestamps = Arc::new(AtomicBool::new(false));
+    let server_has_timestamps = Arc::clone(&has_timestamps);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("latency", server_has_timestamps, false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |flag: &mut Arc<AtomicBool>, sess: &mut CoapServerSession, req: &CoapRequest, mut rsp: CoapResponse| {
+                    let received_at = req.as_message().received_at();
+                    flag.store(
+                        received_at.is_some() && rsp.request_received_at() == received_at,
+                        Ordering::Relaxed,
+                    );
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = "/latency".parse().expect("unable to parse request URI");
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    let req_handle = session.send_request(request).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if session.poll_handle(&req_handle).next().is_some() {
+            break;
+        }
+    }
+    assert!(has_timestamps.load(Ordering::Relaxed));
+
+    // Complete the test server's regular test request.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn ping_is_answered_with_pong() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let received_pong = Arc::new(AtomicBool::new(false));
+    let handler_received_pong = Arc::clone(&received_pong);
+    let ping_id = session.send_ping().unwrap();
+    session.set_ping_handler(move |id| {
+        assert_eq!(id, ping_id);
+        handler_received_pong.store(true, Ordering::Relaxed);
+    });
+    while !received_pong.load(Ordering::Relaxed) {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+    }
+
+    // Finish the test server.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn context_dropped_before_session() {
+    let server_address = common::get_unused_server_addr();
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let other_session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    assert_eq!(context.outstanding_sessions(), 2);
+    std::mem::drop(other_session);
+    assert_eq!(context.outstanding_sessions(), 1);
+
+    // Closing the context while the remaining session is still alive must report the outstanding
+    // session, while still allowing the session to be dropped afterwards.
+    assert_eq!(context.close(), Err(OutstandingSessionsError::SessionsInUse(1)));
+    std::mem::drop(session);
+
+    let context = CoapContext::new().unwrap();
+    assert_eq!(context.close(), Ok(()));
+}
+
+#[test]
+pub fn size_hint_elicits_total_size() {
+    let server_address = common::get_unused_server_addr();
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let mut request = common::gen_test_request();
+    request.request_size_hint();
+    let req_handle = session.send_request(request).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            // The body fits into a single message, so Size2 is only included because it was asked
+            // for.
+            assert_eq!(response.total_size(), Some("Hello World!".len()));
+            break;
+        }
+    }
+    server_handle.join().unwrap();
+}
+
+#[test]
+pub fn session_poisoned_after_context_dropped() {
+    let server_address = common::get_unused_server_addr();
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    assert!(!session.is_poisoned());
+
+    // The session that outlived the context must be marked as unusable instead of silently
+    // referring to a context that no longer exists.
+    std::mem::drop(context);
+    assert!(session.is_poisoned());
+    assert!(matches!(
+        session.send(common::gen_test_request()),
+        Err(SessionError::ContextDropped)
+    ));
+    assert!(matches!(
+        session.send_request(common::gen_test_request()),
+        Err(SessionError::ContextDropped)
+    ));
+    assert!(matches!(session.send_ping(), Err(SessionError::ContextDropped)));
+
+    // Poisoned sessions can still be dropped safely.
+    std::mem::drop(session);
+}
+
+/// Block size used by [block2_handler()] (corresponding to SZX = 2).
+const TEST_BLOCK_SIZE: usize = 64;
+
+/// Request handler that manually serves the given body using Block2 transfers.
+fn block2_handler(body: &mut Vec<u8>, sess: &mut CoapServerSession, req: &CoapRequest, mut rsp: CoapResponse) {
+    let num = req
+        .options_iter()
+        .find_map(|option| match option {
+            CoapOption::Block2(value) => Some(value >> 4),
+            _ => None,
+        })
+        .unwrap_or(0);
+    let start = num as usize * TEST_BLOCK_SIZE;
+    let end = (start + TEST_BLOCK_SIZE).min(body.len());
+    let more = end < body.len();
+    rsp.add_option(CoapOption::Block2((num << 4) | ((more as u32) << 3) | 2));
+    rsp.set_data(Some(body[start..end].to_vec()));
+    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+    sess.send(rsp).unwrap();
+}
+
+#[test]
+pub fn interleaved_blockwise_downloads() {
+    let server_address = common::get_unused_server_addr();
+    let body_a: Vec<u8> = (0..300).map(|i| b'a' + (i % 26) as u8).collect();
+    let body_b: Vec<u8> = (0..500).map(|i| b'A' + (i % 26) as u8).collect();
+    let server_body_a = body_a.clone();
+    let server_body_b = body_b.clone();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        for (path, body) in [("large_a", server_body_a), ("large_b", server_body_b)] {
+            let resource = CoapResource::new(path, body, false);
+            resource.set_method_handler(CoapRequestCode::Get, Some(CoapRequestHandler::new(block2_handler)));
+            context.add_resource(resource);
+        }
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    // Start both downloads before performing any IO so that the block-wise transfers overlap.
+    let gen_request = |path: &str| {
+        let uri = path.parse().expect("unable to parse request URI");
+        CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap()
+    };
+    let handle_a = session.send_request(gen_request("/large_a")).unwrap();
+    let handle_b = session.send_request(gen_request("/large_b")).unwrap();
+    let mut response_a = None;
+    let mut response_b = None;
+    while response_a.is_none() || response_b.is_none() {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        response_a = response_a.or_else(|| session.poll_handle(&handle_a).next());
+        response_b = response_b.or_else(|| session.poll_handle(&handle_b).next());
+    }
+    assert_eq!(response_a.unwrap().data(), Some(body_a.as_slice()));
+    assert_eq!(response_b.unwrap().data(), Some(body_b.as_slice()));
+
+    // Finish the test server.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn serve_resource_directory() {
+    let server_address = common::get_unused_server_addr();
+    let dir = std::env::temp_dir().join(format!("libcoap-rs-files-{}", server_address.port()));
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    let large_file: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+    std::fs::write(dir.join("hello.txt"), "Hello World!").unwrap();
+    std::fs::write(dir.join("sub").join("large.bin"), &large_file).unwrap();
+
+    let mut context = CoapContext::new().unwrap();
+    assert!(matches!(
+        context.add_file_resource("files/../secret", dir.join("hello.txt")),
+        Err(FileResourceError::PathTraversal)
+    ));
+    assert!(matches!(
+        context.add_resource_directory("files", dir.join("sub").join("..")),
+        Err(FileResourceError::PathTraversal)
+    ));
+    assert!(matches!(
+        context.add_file_resource("files", dir.clone()),
+        Err(FileResourceError::InvalidFileType)
+    ));
+    std::mem::drop(context);
+
+    let server_dir = dir.clone();
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let handles = context.add_resource_directory("files", server_dir).unwrap();
+        assert_eq!(
+            handles.iter().map(ResourceHandle::uri_path).collect::<HashSet<_>>(),
+            HashSet::from(["files/hello.txt", "files/sub/large.bin"])
+        );
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let mut get_file = |path: &str| {
+        let uri = path.parse().expect("unable to parse request URI");
+        let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+        let req_handle = session.send_request(request).unwrap();
+        loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                return response;
+            }
+        }
+    };
+
+    let response = get_file("/files/hello.txt");
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data(), Some("Hello World!".as_bytes()));
+    assert_eq!(response.content_format(), Some(CoapContentFormat::TextPlain as u16));
+    assert_eq!(response.max_age(), Some(60));
+    assert!(response.etag().is_some());
+
+    // Larger files are transferred block-wise.
+    let response = get_file("/files/sub/large.bin");
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data(), Some(large_file.as_slice()));
+    assert_eq!(response.content_format(), Some(CoapContentFormat::OctetStream as u16));
+
+    let response = get_file("/files/missing.txt");
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::NotFound));
+
+    // Finish the test server.
+    let response = get_file("/test1");
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    server_handle.join().unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// Representation of a resource used to test conditional requests.
+#[derive(Debug)]
+struct VersionedValue {
+    value: Vec<u8>,
+    version: u8,
+}
+
+impl VersionedValue {
+    fn etag(&self) -> ETag {
+        vec![self.version].into_boxed_slice()
+    }
+}
+
+fn versioned_get_handler(
+    data: &mut VersionedValue,
+    session: &mut CoapServerSession,
+    request: &CoapRequest,
+    mut response: CoapResponse,
+) {
+    match request.accept() {
+        None | Some(0) => {},
+        Some(_) => {
+            response.set_code(CoapResponseCode::NotAcceptable);
+            session.send(response).unwrap();
+            return;
+        },
+    }
+    let etag = data.etag();
+    if request.etag().is_some_and(|etags| etags.contains(&etag)) {
+        response.set_valid(etag);
+    } else {
+        response.set_code(CoapResponseCode::Content);
+        response.set_content_format(Some(CoapContentFormat::TextPlain as u16));
+        response.set_data(Some(data.value.clone()));
+        response.set_etag(Some(etag));
+    }
+    session.send(response).unwrap();
+}
+
+fn versioned_put_handler(
+    data: &mut VersionedValue,
+    session: &mut CoapServerSession,
+    request: &CoapRequest,
+    mut response: CoapResponse,
+) {
+    data.value = request.data().unwrap_or_default().to_vec();
+    data.version += 1;
+    response.set_code(CoapResponseCode::Changed);
+    session.send(response).unwrap();
+}
+
+#[test]
+pub fn conditional_get_revalidation() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let value = VersionedValue {
+            value: b"21.5".to_vec(),
+            version: 1,
+        };
+        let resource = CoapResource::new("temperature", value, false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(versioned_get_handler)),
+        );
+        resource.set_method_handler(
+            CoapRequestCode::Put,
+            Some(CoapRequestHandler::new(versioned_put_handler)),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = || "/temperature".parse().unwrap();
+    let timeout = Some(Duration::from_secs(10));
+
+    // Fetch the initial representation.
+    let response = session
+        .get_with_accept(&mut context, uri(), CoapContentFormat::TextPlain as u16, timeout)
+        .unwrap();
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert!(!response.is_not_modified());
+    assert_eq!(response.data(), Some(b"21.5".as_slice()));
+    let cached_etag = response.etag().unwrap().clone();
+
+    // Unsupported content formats are rejected.
+    let response = session
+        .get_with_accept(&mut context, uri(), CoapContentFormat::Cbor as u16, timeout)
+        .unwrap();
+    assert_eq!(
+        response.code(),
+        CoapMessageCode::Response(CoapResponseCode::NotAcceptable)
+    );
+
+    // As long as the value does not change, the cached representation remains valid.
+    let response = session
+        .get_with_etag(&mut context, uri(), &cached_etag, timeout)
+        .unwrap();
+    assert!(response.is_not_modified());
+    assert_eq!(response.etag(), Some(&cached_etag));
+    assert_eq!(response.data(), None);
+    assert_eq!(response.content_format(), None);
+
+    // Change the value, which invalidates the cached representation.
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Put, uri()).unwrap();
+    request.set_data(Some(b"22.0".as_slice()));
+    let req_handle = session.send_request(request).unwrap();
+    loop {
+        assert!(context.do_io(timeout).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Changed));
+            break;
+        }
+    }
+
+    let response = session
+        .get_with_etag(&mut context, uri(), &cached_etag, timeout)
+        .unwrap();
+    assert!(!response.is_not_modified());
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    assert_eq!(response.data(), Some(b"22.0".as_slice()));
+    let new_etag = response.etag().unwrap().clone();
+    assert_ne!(new_etag, cached_etag);
+    let response = session.get_with_etag(&mut context, uri(), &new_etag, timeout).unwrap();
+    assert!(response.is_not_modified());
+
+    // Finish the test server.
+    let response = session
+        .get_with_accept(&mut context, "/test1".parse().unwrap(), 0, timeout)
+        .unwrap();
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+    server_handle.join().unwrap();
+}
+
+#[test]
+pub fn dual_stack_endpoint_accepts_ipv4_clients() {
+    let port = common::get_unused_server_addr().port();
+    let dual_stack_address = SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(dual_stack_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let ipv4_address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port);
+    let session = CoapClientSession::connect_udp(&mut context, ipv4_address).unwrap();
+    let response = common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+}
+
+#[test]
+pub fn rate_limit_rejects_excess_requests() {
+    const BURST_SIZE: u32 = 5;
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context.set_rate_limit(RateLimitConfig {
+            max_requests_per_second: 1,
+            burst_size: BURST_SIZE,
+            // Use a separate limit per session, so that the test request sent using its own session
+            // below is not affected by the requests sent before.
+            by: RateLimitKey::SourceSession,
+        });
+        let resource = CoapResource::new("limited", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let start = Instant::now();
+    let mut accepted = 0;
+    let mut rejected = 0;
+    for _ in 0..100 {
+        let uri = "/limited".parse().expect("unable to parse request URI");
+        let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+        let req_handle = session.send_request(request).unwrap();
+        let response = loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                break response;
+            }
+        };
+        match response.code() {
+            CoapMessageCode::Response(CoapResponseCode::Content) => accepted += 1,
+            CoapMessageCode::Response(CoapResponseCode::TooManyRequests) => {
+                assert!(response.max_age().is_some_and(|v| v >= 1));
+                rejected += 1;
+            },
+            code => panic!("unexpected response code {code:?}"),
+        }
+    }
+    // The bucket is refilled with one token per second while the requests are sent.
+    let max_accepted = u64::from(BURST_SIZE) + start.elapsed().as_secs() + 1;
+    assert!(accepted >= BURST_SIZE.into() && accepted <= max_accepted);
+    assert_eq!(accepted + rejected, 100);
+
+    // Complete the test server's regular test request using a session that is not rate limited yet.
+    let test_session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let response = common::finish_test_server(&mut context, &test_session, server_handle);
+    assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+}
+
+// Linux allows binding to any address of the 127.0.0.0/8 loopback network.
+#[cfg(target_os = "linux")]
+#[test]
+pub fn rate_limit_stats_of_idle_addresses_are_pruned() {
+    const SOURCE_COUNT: u8 = 100;
+    let server_address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), common::get_unused_server_addr().port());
+
+    // Run the server in the same thread so that its statistics can be inspected.
+    let mut server_context = CoapContext::new().unwrap();
+    server_context.add_endpoint_udp(server_address).unwrap();
+    server_context.set_rate_limit(RateLimitConfig {
+        // Buckets are refilled (and therefore idle) almost immediately after each request.
+        max_requests_per_second: 1_000_000,
+        burst_size: 1,
+        by: RateLimitKey::SourceIp,
+    });
+    let resource = CoapResource::new("limited", (), false);
+    resource.set_method_handler(
+        CoapRequestCode::Get,
+        Some(CoapRequestHandler::new(
+            |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                sess.send(rsp).unwrap();
+            },
+        )),
+    );
+    server_context.add_resource(resource);
+
+    // CON GET request with message ID 0x1337, token 0x42 and Uri-Path "limited".
+    let mut request = vec![0x41, 0x01, 0x13, 0x37, 0x42, 0xb7];
+    request.extend_from_slice(b"limited");
+    let mut buf = [0; 1500];
+    for source in 0..SOURCE_COUNT {
+        let client_socket = UdpSocket::bind((Ipv4Addr::new(127, 0, 1, source), 0)).unwrap();
+        client_socket.set_nonblocking(true).unwrap();
+        client_socket.send_to(&request, server_address).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client_socket.recv_from(&mut buf).is_err() {
+            assert!(Instant::now() < deadline, "timeout while waiting for response");
+            server_context
+                .do_io(Some(Duration::from_millis(10)))
+                .expect("error during IO");
+        }
+        // Each address gets its own bucket, so no request is rejected.
+        assert_eq!(buf[1], 0x45);
+    }
+
+    // The statistics of addresses whose buckets were removed are removed as well.
+    let stats = server_context.rate_limit_stats();
+    assert!(stats.len() < usize::from(SOURCE_COUNT));
+    let last_source = IpAddr::V4(Ipv4Addr::new(127, 0, 1, SOURCE_COUNT - 1));
+    assert_eq!(stats[&last_source].accepted, 1);
+    assert!(stats.values().all(|v| v.rejected == 0));
+}
+
+#[test]
+pub fn reset_handler_reports_rejected_requests() {
+    const REQUEST_MID: CoapMessageId = 0x4242;
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("reject", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, req: &CoapRequest, _rsp: CoapResponse| {
+                    let rst = CoapMessage::new_rst(req.as_message()).unwrap();
+                    assert!(rst.is_reset());
+                    sess.send(rst).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let reset_mids = Arc::new(Mutex::new(Vec::new()));
+    let handler_reset_mids = Arc::clone(&reset_mids);
+    session.set_reset_handler(move |mid| handler_reset_mids.lock().unwrap().push(mid));
+
+    let uri = "/reject".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.set_mid(Some(REQUEST_MID));
+    let req_handle = session.send_request(request).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while reset_mids.lock().unwrap().is_empty() {
+        assert!(Instant::now() < deadline, "timeout while waiting for reset message");
+        context.do_io(Some(Duration::from_secs(1))).expect("error during IO");
+    }
+    assert_eq!(*reset_mids.lock().unwrap(), vec![REQUEST_MID]);
+    assert!(session.poll_handle(&req_handle).next().is_none());
+    // The rejected request is still pending until it is cancelled.
+    assert!(session.cancel_request(REQUEST_MID));
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn probing_rate_limits_non_confirmable_messages() {
+    // Each message consists of the 4 byte header, the payload marker and 95 bytes of payload.
+    const MESSAGE_LEN: u32 = 100;
+    const PROBING_RATE: u32 = 10 * MESSAGE_LEN;
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    session.set_probing_rate(PROBING_RATE);
+    assert_eq!(session.probing_rate(), PROBING_RATE);
+
+    let window = Duration::from_secs(1);
+    let start = Instant::now();
+    let mut sent = 0;
+    while start.elapsed() < window {
+        let mut message = CoapMessage::new(CoapMessageType::Non, CoapMessageCode::Request(CoapRequestCode::Post));
+        message.set_mid(Some(sent));
+        message.set_token(Some(Vec::new()));
+        message.set_data(Some(vec![0u8; 95]));
+        match session.send(message) {
+            Ok(_) => sent += 1,
+            Err(SessionError::ProbingRateExceeded(retry_after)) => {
+                assert!(retry_after <= Duration::from_millis(100));
+                context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+            },
+            Err(e) => panic!("unexpected error while sending message: {e}"),
+        }
+    }
+    // The first message is sent immediately, each following one only after the transmission time
+    // of the previous one has passed.
+    let max_sent = 1 + (start.elapsed().as_secs_f64() * f64::from(PROBING_RATE / MESSAGE_LEN)) as CoapMessageId;
+    assert!(sent <= max_sent, "sent {sent} messages, expected at most {max_sent}");
+    assert!(sent >= 5, "sent only {sent} messages");
+
+    // Confirmable messages are not subject to the probing rate.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn max_body_size_rejects_oversized_requests() {
+    const MAX_BODY_SIZE: usize = 16;
+    let server_address = common::get_unused_server_addr();
+    let handler_calls = Arc::new(AtomicUsize::new(0));
+    let server_handler_calls = Arc::clone(&handler_calls);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context.set_max_body_size(MAX_BODY_SIZE);
+        let resource = CoapResource::new("upload", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Put,
+            Some(CoapRequestHandler::new(
+                move |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    server_handler_calls.fetch_add(1, Ordering::SeqCst);
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Changed));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    // The first request indicates a total body size above the limit, the second one has a body
+    // exceeding the limit, the third one is too large for a single datagram (and therefore sent
+    // using Block1 transfers) and the last one is within the limit.
+    let requests = [
+        (Some(1_000_000), 4, CoapResponseCode::RequestTooLarge),
+        (None, MAX_BODY_SIZE + 1, CoapResponseCode::RequestTooLarge),
+        (None, 4 * session.max_pdu_size(), CoapResponseCode::RequestTooLarge),
+        (None, MAX_BODY_SIZE, CoapResponseCode::Changed),
+    ];
+    for (size1, body_size, expected_code) in requests {
+        let uri = "/upload".parse().expect("unable to parse request URI");
+        let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Put, uri).unwrap();
+        if let Some(size1) = size1 {
+            request.add_option(CoapOption::Size1(size1));
+        }
+        request.set_data(Some(vec![0u8; body_size]));
+        let req_handle = session.send_request(request).unwrap();
+        let response = loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                break response;
+            }
+        };
+        assert_eq!(response.code(), CoapMessageCode::Response(expected_code));
+        // Rejections indicate the maximum body size (see RFC 7959, Section 4).
+        let size1 = response.options_iter().find_map(|option| match option {
+            CoapOption::Size1(size) => Some(*size as usize),
+            _ => None,
+        });
+        assert_eq!(
+            size1,
+            (expected_code == CoapResponseCode::RequestTooLarge).then_some(MAX_BODY_SIZE)
+        );
+    }
+    assert_eq!(handler_calls.load(Ordering::SeqCst), 1);
+
+    // The first block of a block-wise transfer whose Size1 option exceeds the limit is rejected
+    // right away, i.e., before the server allocates memory for reassembling the body.
+    // CON PUT request with message ID 0x1234, no token, Uri-Path "upload", Block1 (NUM 0, M 1,
+    // SZX 0) and Size1 1000000, followed by the first 16 byte block.
+    let client_socket = UdpSocket::bind("localhost:0").unwrap();
+    client_socket.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut request = vec![0x40, 0x03, 0x12, 0x34, 0xb6];
+    request.extend_from_slice(b"upload");
+    request.extend_from_slice(&[0xd1, 0x03, 0x08, 0xd3, 0x14, 0x0f, 0x42, 0x40, 0xff]);
+    request.extend_from_slice(&[0u8; 16]);
+    client_socket.send_to(&request, server_address).unwrap();
+    let mut buf = [0; 1500];
+    let len = client_socket
+        .recv_from(&mut buf)
+        .expect("timeout while waiting for response")
+        .0;
+    // ACK 4.13 Request Entity Too Large instead of 2.31 Continue.
+    assert!(len >= 4);
+    assert_eq!(buf[..4], [0x60, 0x8d, 0x12, 0x34]);
+    assert_eq!(handler_calls.load(Ordering::SeqCst), 1);
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn resource_attributes_are_advertised() {
+    let server_address = common::get_unused_server_addr();
+    // Use a single context for both the server and the client, so that the attributes can be
+    // updated while the server is running.
+    let mut context = CoapContext::new().unwrap();
+    context.add_endpoint_udp(server_address).unwrap();
+    let handle = context.add_resource_with_attributes(
+        CoapResource::new("sensors/temp", (), false),
+        ResourceDescription {
+            title: Some("Temperature".to_string()),
+            resource_type: vec!["temperature-c".to_string()],
+            interface: vec!["sensor".to_string()],
+            content_formats: vec![CoapContentFormat::TextPlain as u16],
+            observable: true,
+            size_estimate: Some(8),
+        },
+    );
+    context.add_resource(CoapResource::new("config", (), false));
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let discover = |context: &mut CoapContext, uri: &str| {
+        let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri.parse().unwrap()).unwrap();
+        let req_handle = session.send_request(request).unwrap();
+        loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+                assert_eq!(response.content_format(), Some(CoapContentFormat::LinkFormat as u16));
+                return String::from_utf8(response.data().unwrap().to_vec()).unwrap();
+            }
+        }
+    };
+
+    assert_eq!(
+        discover(&mut context, "/.well-known/core"),
+        "</sensors/temp>;title=\"Temperature\";rt=\"temperature-c\";if=\"sensor\";ct=0;sz=8;obs,</config>"
+    );
+    assert_eq!(
+        discover(&mut context, "/.well-known/core?rt=temp*"),
+        "</sensors/temp>;title=\"Temperature\";rt=\"temperature-c\";if=\"sensor\";ct=0;sz=8;obs"
+    );
+    assert_eq!(discover(&mut context, "/.well-known/core?href=/config"), "</config>");
+
+    handle.update_attributes(
+        &mut context,
+        ResourceDescription {
+            content_formats: vec![CoapContentFormat::TextPlain as u16, CoapContentFormat::Cbor as u16],
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        discover(&mut context, "/.well-known/core?ct=60"),
+        "</sensors/temp>;ct=\"0 60\""
+    );
+}
+
+#[test]
+pub fn registered_well_known_core_lists_all_resources() {
+    let server_address = common::get_unused_server_addr();
+    let mut context = CoapContext::new().unwrap();
+    context.add_endpoint_udp(server_address).unwrap();
+    context.register_well_known_core();
+    context.add_resource(CoapResource::new("alpha", (), false));
+    context.add_resource(CoapResource::new("beta", (), false));
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let discover = |context: &mut CoapContext, uri: &str| {
+        let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri.parse().unwrap()).unwrap();
+        let req_handle = session.send_request(request).unwrap();
+        loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+                return String::from_utf8(response.data().unwrap().to_vec()).unwrap();
+            }
+        }
+    };
+
+    assert_eq!(discover(&mut context, "/.well-known/core"), "</alpha>,</beta>");
+    assert_eq!(discover(&mut context, "/.well-known/core?href=/beta"), "</beta>");
+    assert_eq!(discover(&mut context, "/.well-known/core?rt=temperature"), "");
+}
+
+#[test]
+pub fn send_assigns_message_ids() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    // A non-confirmable message without a message ID is assigned one, while acknowledgements have
+    // to refer to an existing message ID.
+    let mut message = CoapMessage::new(CoapMessageType::Non, CoapMessageCode::Request(CoapRequestCode::Get));
+    message.set_token(Some(Vec::new()));
+    assert!(session.send(message).is_ok());
+    let message = CoapMessage::new(CoapMessageType::Ack, CoapMessageCode::Empty);
+    assert!(session.send(message).is_err());
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn request_echo_is_forwarded() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("echo", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, req: &CoapRequest, mut rsp: CoapResponse| {
+                    // Reflect the received Echo option value in the response body.
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    rsp.set_data(req.echo().map(|v| v.to_vec()));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let echo: Box<[u8]> = Box::new([0x4a, 0x7f, 0x01, 0x99]);
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, "/echo".parse().unwrap()).unwrap();
+    request.set_echo(Some(echo.clone()));
+    assert_eq!(request.echo(), Some(&echo));
+    let req_handle = session.send_request(request).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            assert_eq!(response.data(), Some(echo.as_ref()));
+            break;
+        }
+    }
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn endpoints_report_assigned_port() {
+    let mut context = CoapContext::new().unwrap();
+    context.add_endpoint_udp("127.0.0.1:0".parse().unwrap()).unwrap();
+    let endpoints: Vec<_> = context.endpoints().collect();
+    assert_eq!(endpoints.len(), 1);
+    assert_eq!(endpoints[0].protocol(), CoapProtocol::Udp);
+    let server_address = endpoints[0].local_addr();
+    assert_eq!(server_address.ip(), Ipv4Addr::LOCALHOST);
+    assert_ne!(server_address.port(), 0);
+
+    // The reported address must be the one the endpoint is actually reachable at (requests for
+    // unknown resources are answered with 4.04 Not Found).
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let req_handle = session.send_request(common::gen_test_request()).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::NotFound));
+            break;
+        }
+    }
+}
+
+#[test]
+pub fn separate_response_is_acknowledged() {
+    // Responses to non-confirmable requests cannot be turned into separate responses.
+    let mut response = CoapResponse::new(CoapMessageType::Non, CoapResponseCode::Content).unwrap();
+    assert!(response.set_separate().is_err());
+
+    let server_address = common::get_unused_server_addr();
+    let retransmissions = Arc::new(AtomicUsize::new(0));
+    let server_retransmissions = Arc::clone(&retransmissions);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context.set_event_callback(move |event, _session| {
+            if matches!(event, CoapEvent::MsgRetransmitted) {
+                server_retransmissions.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let resource = CoapResource::new("separate", (), false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    assert!(rsp.is_piggybacked());
+                    rsp.set_separate().unwrap();
+                    assert!(!rsp.is_piggybacked());
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    rsp.set_data(Some("separate".as_bytes()));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, "/separate".parse().unwrap()).unwrap();
     let req_handle = session.send_request(request).unwrap();
     loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            assert_eq!(response.type_(), CoapMessageType::Con);
+            assert_eq!(response.data(), Some("separate".as_bytes()));
+            break;
+        }
+    }
+
+    // Keep processing messages for longer than the initial retransmission timeout of the server,
+    // which would retransmit the separate response if the client had not acknowledged it.
+    let deadline = Instant::now() + Duration::from_secs(4);
+    while Instant::now() < deadline {
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+        assert!(session.poll_handle(&req_handle).next().is_none());
+    }
+
+    common::finish_test_server(&mut context, &session, server_handle);
+    assert_eq!(retransmissions.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+pub fn context_builder_applies_configuration() {
+    let server_address = common::get_unused_server_addr();
+    let resource = CoapResource::new("built", (), false);
+    resource.set_method_handler(
+        CoapRequestCode::Get,
+        Some(CoapRequestHandler::new(
+            |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                sess.send(rsp).unwrap();
+            },
+        )),
+    );
+    let mut context = CoapContextBuilder::new()
+        .bind(server_address)
+        .add_resource_with_attributes(
+            resource,
+            ResourceDescription {
+                title: Some("Built".to_string()),
+                ..Default::default()
+            },
+        )
+        .max_sessions(4)
+        .build()
+        .unwrap();
+    assert_eq!(context.max_sessions(), Some(4));
+    assert_eq!(context.endpoints().count(), 1);
+
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    for path in ["/built", "/.well-known/core"] {
+        let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, path.parse().unwrap()).unwrap();
+        let req_handle = session.send_request(request).unwrap();
+        loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
+                if path == "/.well-known/core" {
+                    let links = std::str::from_utf8(response.data().unwrap()).unwrap();
+                    assert!(links.contains("</built>;title=\"Built\""));
+                }
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+pub fn pmtu_discovery_reduces_max_pdu_size() {
+    let server_socket = UdpSocket::bind("localhost:0").unwrap();
+    server_socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let server_address = server_socket.local_addr().unwrap();
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    assert_eq!(session.pmtu_discovered(), None);
+    let initial_max_pdu_size = session.max_pdu_size();
+
+    // The IP and UDP headers are not part of the maximum PDU size.
+    let header_size = if server_address.is_ipv4() { 28 } else { 48 };
+    session.on_pmtu_discovery(576);
+    assert_eq!(session.pmtu_discovered(), Some(576));
+    assert!(session.max_pdu_size() < initial_max_pdu_size);
+    assert_eq!(session.max_pdu_size(), 576 - header_size);
+
+    // Request bodies that do not fit into a single datagram anymore are sent block-wise.
+    let uri = "/upload".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Put, uri).unwrap();
+    request.set_data(Some(vec![0x42; 2000]));
+    session.send_request(request).unwrap();
+    context
+        .do_io(Some(Duration::from_millis(100)))
+        .expect("error during IO");
+    let mut buf = [0; 4096];
+    let (len, _) = server_socket.recv_from(&mut buf).unwrap();
+    assert!(len <= 576 - header_size);
+    // The first block is a PUT request as well (code 0.03).
+    assert_eq!(buf[1], 0x03);
+}
+
+#[test]
+pub fn observers_are_tracked_and_notifications_rate_limited() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("observe", 0usize, false);
+        resource.set_get_observable(true);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new_resource_ref(
+                |res: &CoapResource<usize>, sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                    let calls = {
+                        let mut calls = res.user_data_mut();
+                        *calls += 1;
+                        *calls
+                    };
+                    if calls == 1 {
+                        // Observers are only tracked once the response to their registration
+                        // has been sent.
+                        assert!(res.observers().is_empty());
+                        assert!(res.notify_observers());
+                        return;
+                    }
+                    let observer = res.observers().first().cloned().unwrap();
+                    assert_eq!(res.observers().len(), 1);
+                    assert!(observer.session().unwrap() == *sess);
+                    assert_eq!(observer.transport_type(), CoapMessageType::Con);
+                    assert_eq!(observer.notification_count(), calls as u32 - 2);
+                    let rate = Duration::from_millis(500);
+                    if calls == 2 {
+                        // The observer has just been notified, so several rapid publishes result in
+                        // a single trailing notification once the rate limit expires.
+                        for _ in 0..5 {
+                            assert!(!res.notify_observers_with_rate_limit(rate));
+                        }
+                    } else {
+                        assert!(!res.notify_observers_with_rate_limit(rate));
+                        // Cancelling the observation replaces the next notification with an
+                        // error response.
+                        observer.cancel();
+                        assert!(res.notify_observers());
+                    }
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = "/observe".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.as_observe_registration().unwrap();
+    let req_handle = session.send_request(request).unwrap();
+
+    let mut responses = Vec::new();
+    let mut received_at = Vec::new();
+    while responses.len() < 4 {
         assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
         for response in session.poll_handle(&req_handle) {
-            assert_eq!(response.code(), CoapMessageCode::Response(CoapResponseCode::Content));
-            assert_eq!(response.data().unwrap().as_ref(), "Hello World!".as_bytes());
-            server_handle.join().unwrap();
-            return;
+            responses.push((response.code(), response.observe().map(Observe::value)));
+            received_at.push(Instant::now());
+        }
+    }
+    // The trailing notification is only sent once the rate limit has expired.
+    assert!(received_at[2] - received_at[1] >= Duration::from_millis(250));
+    assert_eq!(
+        responses,
+        [
+            (CoapMessageCode::Response(CoapResponseCode::Content), Some(0)),
+            (CoapMessageCode::Response(CoapResponseCode::Content), Some(1)),
+            (CoapMessageCode::Response(CoapResponseCode::Content), Some(2)),
+            (CoapMessageCode::Response(CoapResponseCode::ServiceUnavailable), None),
+        ]
+    );
+
+    // Complete the test server's regular test request.
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn rate_limited_notifications_are_throttled_per_observer() {
+    let server_address = common::get_unused_server_addr();
+    let rate = Duration::from_secs(1);
+
+    // Run server and client in the same thread so that changes can be triggered step by step.
+    let mut server_context = CoapContext::new().unwrap();
+    server_context.add_endpoint_udp(server_address).unwrap();
+    let resource = CoapResource::new("counter", 0u32, false);
+    resource.set_get_observable(true);
+    resource.set_method_handler(
+        CoapRequestCode::Get,
+        Some(CoapRequestHandler::new(
+            |value: &mut u32, sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                rsp.set_data(Some(value.to_string().into_bytes()));
+                sess.send(rsp).unwrap();
+            },
+        )),
+    );
+    resource.set_method_handler(
+        CoapRequestCode::Put,
+        Some(CoapRequestHandler::new_resource_ref(
+            move |res: &CoapResource<u32>, sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                *res.user_data_mut() += 1;
+                let notified = res.notify_observers_with_rate_limit(rate);
+                rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Changed));
+                rsp.set_data(Some(notified.to_string().into_bytes()));
+                sess.send(rsp).unwrap();
+            },
+        )),
+    );
+    server_context.add_resource(resource);
+
+    let mut context = CoapContext::new().unwrap();
+    let sessions = [
+        CoapClientSession::connect_udp(&mut context, server_address).unwrap(),
+        CoapClientSession::connect_udp(&mut context, server_address).unwrap(),
+    ];
+    // Waits for the next response to the given request and returns its payload.
+    fn next_payload(
+        server_context: &mut CoapContext,
+        context: &mut CoapContext,
+        session: &CoapClientSession,
+        handle: &CoapRequestHandle,
+    ) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            assert!(Instant::now() < deadline, "timed out waiting for response");
+            server_context
+                .do_io(Some(Duration::from_millis(10)))
+                .expect("error during IO");
+            context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+            if let Some(response) = session.poll_handle(handle).next() {
+                return String::from_utf8(response.data().unwrap_or_default().to_vec()).unwrap();
+            }
+        }
+    }
+    let observe = |session: &CoapClientSession| {
+        let uri = "/counter".parse().expect("unable to parse request URI");
+        let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+        request.as_observe_registration().unwrap();
+        session.send_request(request).unwrap()
+    };
+    let put = |session: &CoapClientSession| {
+        let uri = "/counter".parse().expect("unable to parse request URI");
+        let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Put, uri).unwrap();
+        session.send_request(request).unwrap()
+    };
+
+    // The first observer is notified about the first change immediately.
+    let first_observation = observe(&sessions[0]);
+    assert_eq!(
+        next_payload(&mut server_context, &mut context, &sessions[0], &first_observation),
+        "0"
+    );
+    let update = put(&sessions[0]);
+    assert_eq!(
+        next_payload(&mut server_context, &mut context, &sessions[0], &update),
+        "true"
+    );
+    assert_eq!(
+        next_payload(&mut server_context, &mut context, &sessions[0], &first_observation),
+        "1"
+    );
+    let first_notified_at = Instant::now();
+
+    // The second observer has not been notified yet, so it is not affected by the rate limit of
+    // the first one.
+    let second_observation = observe(&sessions[1]);
+    assert_eq!(
+        next_payload(&mut server_context, &mut context, &sessions[1], &second_observation),
+        "1"
+    );
+    let update = put(&sessions[1]);
+    assert_eq!(
+        next_payload(&mut server_context, &mut context, &sessions[1], &update),
+        "true"
+    );
+    assert_eq!(
+        next_payload(&mut server_context, &mut context, &sessions[1], &second_observation),
+        "2"
+    );
+    assert_eq!(sessions[0].poll_handle(&first_observation).count(), 0);
+
+    // The first observer receives the latest state once its rate limit has expired.
+    assert_eq!(
+        next_payload(&mut server_context, &mut context, &sessions[0], &first_observation),
+        "2"
+    );
+    assert!(first_notified_at.elapsed() >= rate / 2);
+}
+
+#[test]
+pub fn fetch_and_patch_requests_are_dispatched() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("methods", (), false);
+        let handler = |code: CoapResponseCode| {
+            move |_data: &mut (), sess: &mut CoapServerSession, req: &CoapRequest, mut rsp: CoapResponse| {
+                let mut body = req.method().unwrap().to_string().into_bytes();
+                body.extend_from_slice(req.data().unwrap_or_default());
+                rsp.set_code(CoapMessageCode::Response(code));
+                rsp.set_data(Some(body));
+                sess.send(rsp).unwrap();
+            }
+        };
+        resource.set_method_handler(
+            CoapRequestCode::Fetch,
+            Some(CoapRequestHandler::new(handler(CoapResponseCode::Content))),
+        );
+        resource.set_method_handler(
+            CoapRequestCode::Patch,
+            Some(CoapRequestHandler::new(handler(CoapResponseCode::Changed))),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let requests = [
+        (CoapRequestCode::Fetch, "query", CoapResponseCode::Content, "0.05query"),
+        (CoapRequestCode::Patch, "patch", CoapResponseCode::Changed, "0.06patch"),
+    ];
+    for (method, payload, expected_code, expected_body) in requests {
+        let uri = "/methods".parse().expect("unable to parse request URI");
+        let mut request = CoapRequest::new(CoapMessageType::Con, method, uri).unwrap();
+        assert_eq!(request.method(), Some(method));
+        request.set_data(Some(payload.as_bytes()));
+        let req_handle = session.send_request(request).unwrap();
+        loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                assert_eq!(response.code(), CoapMessageCode::Response(expected_code));
+                assert_eq!(response.data(), Some(expected_body.as_bytes()));
+                break;
+            }
+        }
+    }
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn unreachable_peer_exhausts_retransmissions() {
+    // This socket never answers, so requests are never acknowledged (while no ICMP errors are
+    // reported either, see icmp_error_fails_request_fast()).
+    let silent_socket = UdpSocket::bind("localhost:0").unwrap();
+    let server_address = silent_socket.local_addr().unwrap();
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    session.set_ack_timeout(1, 0);
+    session.set_ack_random_factor(1, 0);
+    session.set_max_retransmit(1);
+    let uri = || "/test1".parse().unwrap();
+
+    // The application-level deadline elapses while retransmissions are still pending.
+    let result = session.get_with_accept(
+        &mut context,
+        uri(),
+        CoapContentFormat::TextPlain as u16,
+        Some(Duration::from_millis(200)),
+    );
+    assert!(matches!(result, Err(RequestError::Timeout)));
+
+    // Without a deadline, the request fails once libcoap gives up retransmitting it.
+    let start_time = Instant::now();
+    let result = session.get_with_accept(&mut context, uri(), CoapContentFormat::TextPlain as u16, None);
+    assert!(matches!(result, Err(RequestError::RetransmitExhausted)));
+    // The request is retransmitted once after ACK_TIMEOUT and given up after another
+    // 2 * ACK_TIMEOUT, so it must not have failed before the first retransmission.
+    assert!(start_time.elapsed() >= Duration::from_secs(1));
+
+    // Requests sent using send_request() report the failure through their handle and are no
+    // longer tracked afterwards.
+    let req_handle = session.send_request(common::gen_test_request()).unwrap();
+    let error = loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(error) = session.take_request_error(&req_handle) {
+            break error;
+        }
+    };
+    assert!(matches!(error, RequestError::RetransmitExhausted));
+    assert!(!session.is_waiting_for_token(req_handle.token()));
+    assert_eq!(session.poll_handle(&req_handle).count(), 0);
+    // Failed requests are no longer pending, but cancelling them discards their failure state.
+    assert!(!session.cancel_by_token(req_handle.token().as_bytes()));
+}
+
+#[test]
+pub fn unreachable_peer_triggers_nack_handler() {
+    // Nothing is listening on this address, so messages are never acknowledged.
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    session.set_ack_timeout(1, 0);
+    session.set_ack_random_factor(1, 0);
+    session.set_max_retransmit(1);
+    let nacks = Arc::new(Mutex::new(Vec::new()));
+    let nacks_clone = Arc::clone(&nacks);
+    session.set_nack_handler(move |mid, reason| nacks_clone.lock().unwrap().push((mid, reason)));
+
+    let mid = session.send(common::gen_test_request()).unwrap();
+    // Depending on the platform, ICMP errors may be reported before libcoap finally gives up
+    // retransmitting the message.
+    while !nacks.lock().unwrap().contains(&(mid, CoapNackReason::TooManyRetries)) {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+    }
+    assert!(nacks.lock().unwrap().iter().all(|(nack_mid, reason)| *nack_mid == mid
+        && matches!(reason, CoapNackReason::TooManyRetries | CoapNackReason::IcmpIssue)));
+}
+
+// Linux reports ICMP "port unreachable" errors on connected UDP sockets.
+#[cfg(target_os = "linux")]
+#[test]
+pub fn icmp_error_fails_request_fast() {
+    // Nothing is listening on this address, so the local host answers with an ICMP error.
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let nacks = Arc::new(Mutex::new(Vec::new()));
+    let nacks_clone = Arc::clone(&nacks);
+    session.set_nack_handler(move |_mid, reason| nacks_clone.lock().unwrap().push(reason));
+
+    let start_time = Instant::now();
+    let result = session.get_with_accept(
+        &mut context,
+        "/test1".parse().unwrap(),
+        CoapContentFormat::TextPlain as u16,
+        Some(Duration::from_secs(30)),
+    );
+    assert!(matches!(result, Err(RequestError::PeerUnreachable)));
+    // The request must fail before the first retransmission (after the default ACK_TIMEOUT of two
+    // seconds).
+    assert!(start_time.elapsed() < Duration::from_secs(2));
+    assert!(nacks.lock().unwrap().contains(&CoapNackReason::IcmpIssue));
+}
+
+// Linux reports ICMP "port unreachable" errors on connected UDP sockets.
+#[cfg(target_os = "linux")]
+#[test]
+pub fn icmp_error_is_reported_for_request_handle() {
+    // Nothing is listening on this address, so the local host answers with an ICMP error.
+    let server_address = common::get_unused_server_addr();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+
+    let req_handle = session.send_request(common::gen_test_request()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    let error = loop {
+        assert!(Instant::now() < deadline, "timeout while waiting for ICMP error");
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+        assert_eq!(session.poll_handle(&req_handle).count(), 0);
+        if let Some(error) = session.take_request_error(&req_handle) {
+            break error;
+        }
+    };
+    assert!(matches!(error, RequestError::PeerUnreachable));
+    // ICMP "Port Unreachable" messages do not lower the path MTU.
+    assert_eq!(session.pmtu_discovered(), None);
+    // The failed request is no longer tracked, so retransmissions can not be answered anymore.
+    assert!(!session.is_waiting_for_token(req_handle.token()));
+    assert!(session.take_request_error(&req_handle).is_none());
+    assert_eq!(session.poll_handle(&req_handle).count(), 0);
+}
+
+#[test]
+pub fn multicast_servers_respond_to_group_requests() {
+    // "All CoAP Nodes" group (RFC 7252, Section 12.8).
+    let group = IpAddr::V4(Ipv4Addr::new(224, 0, 1, 187));
+    let port = common::get_unused_server_addr().port();
+
+    // Both servers listen on the same port, so that both of them receive the group request.
+    let server_handles: Vec<_> = (0..2)
+        .map(|_| {
+            common::spawn_custom_test_server(move || {
+                let context =
+                    CoapContext::new_multicast_server(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port), &[group])
+                        .unwrap();
+                // The unspecified address also receives the group requests, so no additional
+                // endpoints are necessary.
+                assert_eq!(context.endpoints().count(), 1);
+                context
+            })
+        })
+        .collect();
+
+    // Multicast requests must be non-confirmable (RFC 7252, Section 8.1), but the servers must not
+    // acknowledge confirmable ones either. As libcoap clients do not send confirmable multicast
+    // requests, the request is sent using a plain socket.
+    let client_socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+    client_socket.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    // CON GET request with message ID 0x1337, token 0x42 and Uri-Path "test1".
+    let mut request = vec![0x41, 0x01, 0x13, 0x37, 0x42, 0xb5];
+    request.extend_from_slice(b"test1");
+    client_socket.send_to(&request, SocketAddr::new(group, port)).unwrap();
+
+    let mut responses = Vec::new();
+    let mut buf = [0; 1500];
+    while responses.len() < 2 {
+        let (len, _) = client_socket
+            .recv_from(&mut buf)
+            .expect("timeout while waiting for responses");
+        responses.push(buf[..len].to_vec());
+    }
+    for response in responses {
+        // NON 2.05 Content response with token 0x42 (see RFC 7252, Section 3).
+        assert_eq!(response[0], 0x51);
+        assert_eq!(response[1], 0x45);
+        assert_eq!(response[4], 0x42);
+        assert!(response.ends_with(b"\xffHello World!"));
+    }
+
+    // Each server only stops after it has answered the group request.
+    for server_handle in server_handles {
+        server_handle.join().unwrap();
+    }
+}
+
+#[test]
+pub fn send_batch_transmits_all_requests() {
+    const BATCH_SIZE: usize = 10;
+    let server_address = common::get_unused_server_addr();
+    let handler_calls = Arc::new(AtomicUsize::new(0));
+    let server_handler_calls = Arc::clone(&handler_calls);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("batch", server_handler_calls, false);
+        resource.set_method_handler(
+            CoapRequestCode::Post,
+            Some(CoapRequestHandler::new(
+                |cnt: &mut Arc<AtomicUsize>, _sess: &mut CoapServerSession, _req: &CoapRequest, _rsp: CoapResponse| {
+                    cnt.fetch_add(1, Ordering::Relaxed);
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let mut session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    // Do not delay any of the non-confirmable requests.
+    session.set_probing_rate(u32::MAX);
+    let requests = (0..BATCH_SIZE)
+        .map(|i| {
+            let uri = "/batch".parse().expect("unable to parse request URI");
+            let mut request = CoapRequest::new(CoapMessageType::Non, CoapRequestCode::Post, uri).unwrap();
+            request.set_data(Some(vec![i as u8]));
+            request
+        })
+        .collect();
+    let message_ids = session.send_batch(requests).expect("unable to send batch");
+    assert_eq!(message_ids.len(), BATCH_SIZE);
+    assert_eq!(message_ids.iter().collect::<HashSet<_>>().len(), BATCH_SIZE);
+
+    let started_at = Instant::now();
+    while handler_calls.load(Ordering::Relaxed) < BATCH_SIZE && started_at.elapsed() < Duration::from_secs(5) {
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+    }
+    assert_eq!(handler_calls.load(Ordering::Relaxed), BATCH_SIZE);
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn access_control_denies_requests() {
+    let server_address = common::get_unused_server_addr();
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        let resource = CoapResource::new("readonly", (), false);
+        let handler = |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+            rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+            sess.send(rsp).unwrap();
+        };
+        resource.set_method_handler(CoapRequestCode::Get, Some(CoapRequestHandler::new(handler)));
+        resource.set_method_handler(CoapRequestCode::Delete, Some(CoapRequestHandler::new(handler)));
+        context.add_resource(resource);
+        context.set_access_control(|_session, request| match request.method() {
+            Some(CoapRequestCode::Delete) => AccessDecision::Deny(CoapResponseCode::Unauthorized),
+            _ => AccessDecision::Allow,
+        });
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let requests = [
+        (CoapRequestCode::Get, CoapResponseCode::Content),
+        (CoapRequestCode::Delete, CoapResponseCode::Unauthorized),
+        // Requests for methods without a handler are rejected by libcoap itself.
+        (CoapRequestCode::Put, CoapResponseCode::NotAllowed),
+    ];
+    for (method, expected_code) in requests {
+        let uri = "/readonly".parse().expect("unable to parse request URI");
+        let request = CoapRequest::new(CoapMessageType::Con, method, uri).unwrap();
+        let req_handle = session.send_request(request).unwrap();
+        loop {
+            assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+            if let Some(response) = session.poll_handle(&req_handle).next() {
+                assert_eq!(response.code(), CoapMessageCode::Response(expected_code));
+                break;
+            }
+        }
+    }
+
+    common::finish_test_server(&mut context, &session, server_handle);
+}
+
+#[test]
+pub fn resources_report_observer_counts() {
+    let server_address = common::get_unused_server_addr();
+
+    // Run server and client in the same thread so that the server context can be inspected.
+    let mut server_context = CoapContext::new().unwrap();
+    server_context.add_endpoint_udp(server_address).unwrap();
+    for (path, observable) in [("sensor", true), ("actuator", true), ("config", false)] {
+        let resource = CoapResource::new(path, (), false);
+        resource.set_get_observable(observable);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        server_context.add_resource(resource);
+    }
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = "/sensor".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.as_observe_registration().unwrap();
+    let req_handle = session.send_request(request).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        assert!(Instant::now() < deadline, "timed out waiting for observe registration");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+        context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+        if session.poll_handle(&req_handle).next().is_some() {
+            break;
+        }
+    }
+
+    let mut resources: Vec<_> = server_context
+        .resources()
+        .map(|v| (v.path, v.observable, v.observer_count))
+        .collect();
+    resources.sort();
+    assert_eq!(
+        resources,
+        [
+            ("actuator".to_string(), true, 0),
+            ("config".to_string(), false, 0),
+            ("sensor".to_string(), true, 1),
+        ]
+    );
+}
+
+#[test]
+pub fn try_send_applies_backpressure() {
+    let server_address = common::get_unused_server_addr();
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    session.set_send_queue_capacity(3);
+
+    let mut handles = Vec::new();
+    for _ in 0..3 {
+        handles.push(session.try_send(common::gen_test_request()).unwrap());
+    }
+    assert_eq!(session.send_queue_len(), 3);
+    assert!(matches!(
+        session.try_send(common::gen_test_request()),
+        Err(SessionError::WouldBlock)
+    ));
+    // Handles of queued requests can already be polled.
+    assert_eq!(session.poll_handle(&handles[0]).count(), 0);
+
+    // Processing IO hands queued requests over to libcoap (only one of them, as NSTART defaults
+    // to one), making room for new requests.
+    context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+    assert_eq!(session.send_queue_len(), 2);
+    assert!(session.try_send(common::gen_test_request()).is_ok());
+}
+
+#[test]
+pub fn try_send_respects_nstart() {
+    let server_socket = UdpSocket::bind("localhost:0").unwrap();
+    server_socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_socket.local_addr().unwrap()).unwrap();
+    let handles: Vec<_> = (0..2)
+        .map(|_| session.try_send(common::gen_test_request()).unwrap())
+        .collect();
+
+    let mut buf = [0; 1500];
+    for (index, handle) in handles.iter().enumerate() {
+        context
+            .do_io(Some(Duration::from_millis(100)))
+            .expect("error during IO");
+        // Only one request may be outstanding at a time, as NSTART defaults to one.
+        assert_eq!(session.send_queue_len(), handles.len() - index - 1);
+        let (len, client_address) = server_socket.recv_from(&mut buf).unwrap();
+        assert!(server_socket.recv_from(&mut [0; 1500]).is_err());
+        // Answer the request with a piggybacked 2.05 Content response echoing its message ID and
+        // token (see RFC 7252, Section 3).
+        let token_len = usize::from(buf[0] & 0x0f);
+        assert_eq!(&buf[4..4 + token_len], handle.token().as_bytes());
+        let mut response = vec![0x60 | (buf[0] & 0x0f), 0x45, buf[2], buf[3]];
+        response.extend_from_slice(&buf[4..4 + token_len]);
+        assert!(len >= response.len());
+        server_socket.send_to(&response, client_address).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while session.poll_handle(handle).next().is_none() {
+            assert!(Instant::now() < deadline, "timeout while waiting for response");
+            context
+                .do_io(Some(Duration::from_millis(100)))
+                .expect("error during IO");
+        }
+    }
+    assert_eq!(session.send_queue_len(), 0);
+}
+
+#[test]
+pub fn send_broadcast_only_reaches_filtered_sessions() {
+    let server_address = common::get_unused_server_addr();
+
+    // Run server and client in the same thread so that the server can push messages outside of
+    // request handlers.
+    let mut server_context = CoapContext::new().unwrap();
+    server_context.add_endpoint_udp(server_address).unwrap();
+    let resource = CoapResource::new("push", (), false);
+    resource.set_method_handler(
+        CoapRequestCode::Get,
+        Some(CoapRequestHandler::new(
+            |_data: &mut (), sess: &mut CoapServerSession, _req: &CoapRequest, mut rsp: CoapResponse| {
+                rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                sess.send(rsp).unwrap();
+            },
+        )),
+    );
+    server_context.add_resource(resource);
+
+    let mut context = CoapContext::new().unwrap();
+    let sessions = [
+        CoapClientSession::connect_udp(&mut context, server_address).unwrap(),
+        CoapClientSession::connect_udp(&mut context, server_address).unwrap(),
+    ];
+    let handles: Vec<_> = sessions
+        .iter()
+        .map(|session| {
+            let uri = "/push".parse().expect("unable to parse request URI");
+            let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+            request.set_token(Some(vec![0x42]));
+            session.send_request(request).unwrap()
+        })
+        .collect();
+    let mut received = [0, 0];
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while received != [1, 1] {
+        assert!(Instant::now() < deadline, "timed out waiting for initial responses");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+        context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+        for (i, session) in sessions.iter().enumerate() {
+            received[i] += session.poll_handle(&handles[i]).count();
+        }
+    }
+
+    let selected_port = sessions[0].addr_local().port();
+    let mut notification = CoapResponse::new(CoapMessageType::Ack, CoapResponseCode::Content).unwrap();
+    notification.set_token(Some(vec![0x42]));
+    notification.set_data(Some("pushed".as_bytes()));
+    assert_eq!(
+        server_context.send_broadcast(notification, |session| session.addr_remote().port() == selected_port),
+        1
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let pushed = loop {
+        assert!(Instant::now() < deadline, "timed out waiting for pushed response");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+        context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+        assert_eq!(sessions[1].poll_handle(&handles[1]).count(), 0);
+        if let Some(response) = sessions[0].poll_handle(&handles[0]).next() {
+            break response;
+        }
+    };
+    // Pushed responses can not be piggybacked and are sent as confirmable messages instead.
+    assert_eq!(pushed.type_(), CoapMessageType::Con);
+    assert_eq!(pushed.data().unwrap().as_ref(), "pushed".as_bytes());
+
+    // Push a response to the other session only.
+    let other_port = sessions[1].addr_local().port();
+    let other_session = RefCell::new(None);
+    server_context.send_broadcast(
+        CoapResponse::new(CoapMessageType::Non, CoapResponseCode::Content).unwrap(),
+        |session| {
+            if session.addr_remote().port() == other_port {
+                other_session.replace(Some(session.clone()));
+            }
+            false
+        },
+    );
+    let mut other_session = other_session
+        .into_inner()
+        .expect("server session for second client not found");
+    let mut notification = CoapResponse::new(CoapMessageType::Non, CoapResponseCode::Content).unwrap();
+    notification.set_token(Some(vec![0x42]));
+    notification.set_data(Some("sent to".as_bytes()));
+    server_context.send_to(&mut other_session, notification).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let pushed = loop {
+        assert!(Instant::now() < deadline, "timed out waiting for pushed response");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+        context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+        assert_eq!(sessions[0].poll_handle(&handles[0]).count(), 0);
+        if let Some(response) = sessions[1].poll_handle(&handles[1]).next() {
+            break response;
+        }
+    };
+    assert_eq!(pushed.type_(), CoapMessageType::Non);
+    assert_eq!(pushed.data().unwrap().as_ref(), "sent to".as_bytes());
+}
+
+#[test]
+pub fn observe_confirmation_policy_escalates_and_prunes() {
+    let server_address = common::get_unused_server_addr();
+
+    // Run server and client in the same thread so that the server's observers can be inspected.
+    let mut server_context = CoapContext::new().unwrap();
+    server_context.add_endpoint_udp(server_address).unwrap();
+    let resource = CoapResource::new("observe", (), false);
+    resource.set_get_observable(true);
+    resource.set_observe_confirmation_policy(Some(ObserveConfirmationPolicy {
+        every_nth: Some(3),
+        max_interval: None,
+    }));
+    resource.set_method_handler(
+        CoapRequestCode::Get,
+        Some(CoapRequestHandler::new_resource_ref(
+            |res: &CoapResource<()>, sess: &mut CoapServerSession, req: &CoapRequest, mut rsp: CoapResponse| {
+                rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                sess.send(rsp).unwrap();
+                if req.observe().is_none() {
+                    // Make the next notification confirmable, so that its rejection is reported.
+                    res.set_observe_confirmation_policy(Some(ObserveConfirmationPolicy {
+                        every_nth: Some(1),
+                        max_interval: None,
+                    }));
+                    assert!(res.notify_observers());
+                } else if res.observers().first().map_or(true, |v| v.notification_count() < 2) {
+                    assert!(res.notify_observers());
+                }
+            },
+        )),
+    );
+    server_context.add_resource(resource);
+
+    let mut context = CoapContext::new().unwrap();
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let uri = "/observe".parse().expect("unable to parse request URI");
+    let mut request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, uri).unwrap();
+    request.as_observe_registration().unwrap();
+    let req_handle = session.send_request(request).unwrap();
+
+    let mut types = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while types.len() < 4 {
+        assert!(Instant::now() < deadline, "timed out waiting for notifications");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+        context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+        types.extend(session.poll_handle(&req_handle).map(|v| v.type_()));
+    }
+    assert_eq!(
+        types,
+        [
+            CoapMessageType::Ack,
+            CoapMessageType::Non,
+            CoapMessageType::Non,
+            CoapMessageType::Con
+        ]
+    );
+    let observer_count = |context: &CoapContext| context.resources().map(|v| v.observer_count).sum::<usize>();
+    assert_eq!(observer_count(&server_context), 1);
+
+    // The client is no longer interested and rejects the next (confirmable) notification.
+    session.remove_handle(req_handle);
+    let req_handle = session.send_request(CoapRequest::get("/observe").unwrap()).unwrap();
+    let mut response_received = false;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !response_received || observer_count(&server_context) > 0 {
+        assert!(Instant::now() < deadline, "timed out waiting for observer to be pruned");
+        server_context
+            .do_io(Some(Duration::from_millis(10)))
+            .expect("error during IO");
+        context.do_io(Some(Duration::from_millis(10))).expect("error during IO");
+        response_received |= session.poll_handle(&req_handle).next().is_some();
+    }
+}
+
+#[test]
+pub fn strict_parsing_applies_to_received_messages() {
+    let server_address = common::get_unused_server_addr();
+    let handler_calls = Arc::new(AtomicUsize::new(0));
+    let handler_calls2 = Arc::clone(&handler_calls);
+
+    let server_handle = common::spawn_test_server(move |mut context| {
+        context.add_endpoint_udp(server_address).unwrap();
+        context.set_strict_parsing(true);
+        let resource = CoapResource::new("strict", handler_calls2, false);
+        resource.set_method_handler(
+            CoapRequestCode::Get,
+            Some(CoapRequestHandler::new(
+                |calls: &mut Arc<AtomicUsize>,
+                 sess: &mut CoapServerSession,
+                 req: &CoapRequest,
+                 mut rsp: CoapResponse| {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    rsp.set_code(CoapMessageCode::Response(CoapResponseCode::Content));
+                    rsp.set_data(Some([u8::from(req.as_message().strict_parsing())]));
+                    sess.send(rsp).unwrap();
+                },
+            )),
+        );
+        context.add_resource(resource);
+        context
+    });
+
+    let mut context = CoapContext::new().unwrap();
+    context.set_strict_parsing(true);
+    let session = CoapClientSession::connect_udp(&mut context, server_address).unwrap();
+    let request = CoapRequest::new(CoapMessageType::Con, CoapRequestCode::Get, "/strict".parse().unwrap()).unwrap();
+    let req_handle = session.send_request(request).unwrap();
+    loop {
+        assert!(context.do_io(Some(Duration::from_secs(10))).expect("error during IO") <= Duration::from_secs(10));
+        if let Some(response) = session.poll_handle(&req_handle).next() {
+            // Both the request received by the server and the response received by the client
+            // are parsed with strict parsing enabled.
+            assert_eq!(response.data(), Some([1].as_slice()));
+            assert!(response.as_message().strict_parsing());
+            break;
         }
     }
+
+    // CON GET request with message ID 0x1337, no token, Uri-Path "strict" and two Accept options.
+    let client_socket = UdpSocket::bind("localhost:0").unwrap();
+    client_socket.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut request = vec![0x40, 0x01, 0x13, 0x37, 0xb6];
+    request.extend_from_slice(b"strict");
+    request.extend_from_slice(&[0x61, 0x00, 0x01, 0x32]);
+    client_socket.send_to(&request, server_address).unwrap();
+    let mut buf = [0; 1500];
+    let len = client_socket
+        .recv_from(&mut buf)
+        .expect("timeout while waiting for response")
+        .0;
+    // The malformed request is rejected without calling the handler.
+    assert!(len >= 4);
+    assert_eq!(buf[2..4], [0x13, 0x37]);
+    assert_eq!(handler_calls.load(Ordering::Relaxed), 1);
+
+    common::finish_test_server(&mut context, &session, server_handle);
 }
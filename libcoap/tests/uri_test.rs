@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: BSD-2-Clause
+/*
+ * uri_test.rs - Tests for CoAP URI handling.
+ * This file is part of the libcoap-rs crate, see the README and LICENSE files for
+ * more information and terms of use.
+ * Copyright © 2021-2024 The NAMIB Project Developers, all rights reserved.
+ * See the README as well as the LICENSE file for more information.
+ */
+
+use std::collections::HashSet;
+
+use libcoap_rs::types::CoapUri;
+
+#[test]
+pub fn equivalent_uris_compare_equal() {
+    let uri_a: CoapUri = "coap://H/p".parse().unwrap();
+    let uri_b: CoapUri = "coap://h:5683/p".parse().unwrap();
+    assert_eq!(uri_a, uri_b);
+
+    let mut set = HashSet::new();
+    set.insert(uri_a);
+    assert!(!set.insert(uri_b));
+
+    let uri_a: CoapUri = "coaps://example.com/a%2fb?q=%7e".parse().unwrap();
+    let uri_b: CoapUri = "coaps://example.com:5684/a%2Fb?q=~".parse().unwrap();
+    assert_eq!(uri_a, uri_b);
+
+    let uri_a: CoapUri = "coap://example.com/p".parse().unwrap();
+    let uri_b: CoapUri = "coap://example.com:5684/p".parse().unwrap();
+    assert_ne!(uri_a, uri_b);
+}
+
+#[test]
+pub fn uris_display_in_canonical_form() {
+    let examples = [
+        // Equivalent URIs from RFC 7252, Section 6.3.
+        (
+            "coap://example.com:5683/~sensors/temp.xml",
+            "coap://example.com/~sensors/temp.xml",
+        ),
+        (
+            "coap://EXAMPLE.com/%7Esensors/temp.xml",
+            "coap://example.com/~sensors/temp.xml",
+        ),
+        (
+            "coap://example.com/%7esensors/temp.xml",
+            "coap://example.com/~sensors/temp.xml",
+        ),
+        // URIs exercising the decomposition rules of RFC 7252, Section 6.4.
+        (
+            "coap://example.net/.well-known/core",
+            "coap://example.net/.well-known/core",
+        ),
+        ("coap://example.net", "coap://example.net/"),
+        ("coap://[2001:db8::2:1]/", "coap://[2001:db8::2:1]/"),
+        (
+            "coap://198.51.100.1:61616//%2F//?%2F%2F&?%26",
+            "coap://198.51.100.1:61616//%2F//?%2F%2F&?%26",
+        ),
+        ("coaps://example.org/temp?a=1&b=2", "coaps://example.org/temp?a=1&b=2"),
+        ("coaps://example.org:5683/temp", "coaps://example.org:5683/temp"),
+        ("/foo/bar?answer=42", "/foo/bar?answer=42"),
+    ];
+    for (uri_str, canonical) in examples {
+        let uri: CoapUri = uri_str.parse().unwrap();
+        assert_eq!(uri.to_string(), canonical, "unexpected canonical form of {}", uri_str);
+        assert_eq!(uri.to_string().parse::<CoapUri>().unwrap(), uri);
+    }
+}